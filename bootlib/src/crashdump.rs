@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Format of the crash dumps written by the SVSM on a panic, shared with the
+//! host tool which extracts them.
+//!
+//! A dump starts with a [`CrashDumpHeader`], followed by an ELF core file at
+//! [`CRASH_DUMP_DATA_OFFSET`]. The header is written after the core file is
+//! complete, so a valid header always describes a complete dump.
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// fw_cfg file which configures the crash dump target
+pub const CRASH_DUMP_CONFIG_FILE: &str = "opt/svsm/crashdump";
+
+/// Dump into a region of guest memory
+pub const CRASH_DUMP_TARGET_MEMORY: u32 = 1;
+/// Dump to a virtio-blk device
+pub const CRASH_DUMP_TARGET_VIRTIO_BLK: u32 = 2;
+
+/// Contents of the [`CRASH_DUMP_CONFIG_FILE`] fw_cfg file
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct CrashDumpConfig {
+    /// One of the `CRASH_DUMP_TARGET_*` values
+    pub target: u32,
+    pub reserved: u32,
+    /// Guest physical address of the memory region, or MMIO address of the
+    /// virtio-blk device
+    pub address: u64,
+    /// Size of the memory region. For block devices the dump is limited to
+    /// this size when it is not zero.
+    pub size: u64,
+}
+
+pub const CRASH_DUMP_MAGIC: [u8; 8] = *b"SVSMCORE";
+pub const CRASH_DUMP_VERSION: u32 = 1;
+
+/// Offset of the ELF core file from the start of the dump
+pub const CRASH_DUMP_DATA_OFFSET: usize = 4096;
+
+/// The dump target was too small and the core file is truncated
+pub const CRASH_DUMP_FLAG_TRUNCATED: u32 = 1 << 0;
+
+/// Header at the start of a crash dump
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct CrashDumpHeader {
+    pub magic: [u8; 8],
+    pub version: u32,
+    /// `CRASH_DUMP_FLAG_*` values
+    pub flags: u32,
+    /// Size of the ELF core file
+    pub size: u64,
+    /// [`crash_dump_checksum()`] over the ELF core file
+    pub checksum: u64,
+}
+
+/// Initial value for [`crash_dump_checksum()`]
+pub const CRASH_DUMP_CHECKSUM_INIT: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Updates the 64-bit FNV-1a checksum `state` with `data`. The checksum only
+/// detects incomplete or corrupted dumps, it does not protect against
+/// modifications.
+pub fn crash_dump_checksum(state: u64, data: &[u8]) -> u64 {
+    data.iter().fold(state, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Types of the notes in the ELF core file with the "SVSM" owner name
+pub mod note {
+    /// The panic message as UTF-8 text
+    pub const NT_SVSM_PANIC: u32 = 1;
+    /// An array of [`CrashDumpCpu`](super::CrashDumpCpu) entries
+    pub const NT_SVSM_CPUS: u32 = 2;
+    /// A sequence of [`CrashDumpTask`](super::CrashDumpTask) entries, each
+    /// followed by `name_len` bytes of the task name
+    pub const NT_SVSM_TASKS: u32 = 3;
+    /// The messages from the kernel log buffer as UTF-8 text, oldest first
+    pub const NT_SVSM_LOG: u32 = 4;
+}
+
+/// Per-CPU entry of the `NT_SVSM_CPUS` note
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct CrashDumpCpu {
+    pub cpu_index: u32,
+    pub apic_id: u32,
+    /// 1 if the CPU has been started
+    pub online: u32,
+    /// 1 if this is the CPU which wrote the dump
+    pub crashed: u32,
+}
+
+/// Per-task entry of the `NT_SVSM_TASKS` note
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct CrashDumpTask {
+    pub task_id: u32,
+    pub process_id: u32,
+    /// Index of the CPU the task is assigned to
+    pub cpu_index: u32,
+    /// 1 if the task is runnable, 0 if it is blocked, 2 if it has terminated
+    pub state: u32,
+    /// 1 for user-mode tasks
+    pub user: u32,
+    /// Length of the task name following the entry
+    pub name_len: u32,
+}
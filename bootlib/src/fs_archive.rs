@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Definitions of the signed filesystem archive format shared between the
+//! build tools, which sign the archive, and the SVSM, which verifies the
+//! signature at boot.
+//!
+//! A signed filesystem image starts with a [`FsSignatureHeader`], padded to
+//! at least [`FS_SIGNATURE_HEADER_SIZE`] bytes, followed by the archive
+//! itself. The archive is placed such that the contents of its files start
+//! on a page boundary, so that the SVSM can map them directly. The
+//! signature is an ECDSA signature over [`FsSignedData`], which binds the
+//! version of the image to the SHA-384 digest of the archive, made with a
+//! NIST P-384 key. The public part of the key and the minimum accepted
+//! version are passed to the SVSM in the measured IGVM parameter block, so
+//! the archive itself does not need to be part of the launch measurement.
+//! Instead, the SVSM measures the signed data at runtime.
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Magic value at the start of a signed filesystem image.
+pub const FS_SIGNATURE_MAGIC: [u8; 8] = *b"SVSMFSIG";
+
+/// Minimum offset of the archive from the start of the image. The header
+/// occupies at least a full page.
+pub const FS_SIGNATURE_HEADER_SIZE: usize = 4096;
+
+/// Size of a SEC1-encoded uncompressed NIST P-384 public key.
+pub const P384_PUBLIC_KEY_SIZE: usize = 97;
+
+/// Size of an ECDSA NIST P-384 signature, consisting of the big-endian `r`
+/// and `s` values.
+pub const P384_SIGNATURE_SIZE: usize = 96;
+
+/// Size of the SHA-384 digest of the archive.
+pub const FS_DIGEST_SIZE: usize = 48;
+
+/// The header of a signed filesystem image.
+#[repr(C, packed)]
+#[derive(IntoBytes, FromBytes, Immutable, KnownLayout, Clone, Copy, Debug)]
+pub struct FsSignatureHeader {
+    /// Must be [`FS_SIGNATURE_MAGIC`].
+    pub magic: [u8; 8],
+
+    /// The version of the image, which increases with every release of an
+    /// image. The SVSM refuses images with a version below the minimum
+    /// version in the IGVM parameter block.
+    pub version: u64,
+
+    /// The size of the archive following the header in bytes.
+    pub archive_size: u64,
+
+    /// The offset of the archive from the start of the image, which is at
+    /// least [`FS_SIGNATURE_HEADER_SIZE`].
+    pub archive_offset: u64,
+
+    /// The signature over the [`FsSignedData`] of the archive.
+    pub signature: [u8; P384_SIGNATURE_SIZE],
+}
+
+/// The data covered by the signature of a filesystem image.
+#[repr(C, packed)]
+#[derive(IntoBytes, FromBytes, Immutable, KnownLayout, Clone, Copy, Debug)]
+pub struct FsSignedData {
+    /// The version of the image from the [`FsSignatureHeader`].
+    pub version: u64,
+
+    /// The SHA-384 digest of the archive.
+    pub digest: [u8; FS_DIGEST_SIZE],
+}
+
+/// The public key used to verify the filesystem archive signature, as
+/// SEC1-encoded uncompressed NIST P-384 point. A key of all zeros means
+/// that the archive is not required to be signed.
+#[repr(transparent)]
+#[derive(IntoBytes, FromBytes, Immutable, KnownLayout, Clone, Copy, Debug)]
+pub struct FsPublicKey(pub [u8; P384_PUBLIC_KEY_SIZE]);
+
+impl FsPublicKey {
+    /// Returns `true` if a public key is configured.
+    pub fn is_present(&self) -> bool {
+        self.0.iter().any(|b| *b != 0)
+    }
+}
+
+impl Default for FsPublicKey {
+    fn default() -> Self {
+        Self([0; P384_PUBLIC_KEY_SIZE])
+    }
+}
@@ -7,6 +7,7 @@
 //! This crate provides definitions of IGVM parameters to be parsed by
 //! COCONUT-SVSM to determine its configuration.
 
+use crate::fs_archive::FsPublicKey;
 use zerocopy::{Immutable, IntoBytes};
 
 /// The IGVM parameter page is an unmeasured page containing individual
@@ -55,7 +56,14 @@ pub struct IgvmParamBlockFwInfo {
     pub in_low_memory: u8,
 
     #[doc(hidden)]
-    pub _reserved: [u8; 7],
+    pub _reserved: [u8; 3],
+
+    /// The guest physical address of the Linux boot parameters (zero page)
+    /// if the firmware is a Linux kernel that is booted directly, or zero
+    /// otherwise. The SVSM fills the E820 table in the boot parameters with
+    /// the guest memory map and validates all guest memory outside of the
+    /// firmware range.
+    pub linux_boot_params: u32,
 
     /// The guest physical address at which the firmware expects to find the
     /// secrets page.
@@ -158,6 +166,27 @@ pub struct IgvmParamBlock {
 
     /// The value of vTOM used by the guest, or zero if not used.
     pub vtom: u64,
+
+    /// The public key which must have signed the filesystem archive. If a
+    /// key is present, the SVSM refuses to boot with an archive that does
+    /// not carry a valid signature.
+    pub fs_public_key: FsPublicKey,
+
+    /// The minimum version of a signed filesystem image. Older images are
+    /// refused, so that a signed image cannot be rolled back to a release
+    /// with known issues.
+    pub fs_min_version: u64,
+
+    /// The minimum generation of the persistent vTPM NV state. The vTPM
+    /// refuses to start with older state, so that the host cannot roll the
+    /// NV state back to before the generation this image was built for.
+    pub vtpm_nv_min_generation: u64,
+
+    /// The guest physical address of the ECAM region of a PCI host bridge
+    /// reserved for the SVSM, or zero if there is none. The SVSM only drives
+    /// PCI functions on bus 0 of this host bridge, which must not be
+    /// described to the guest.
+    pub svsm_pci_ecam: u64,
 }
 
 /// The IGVM context page is a measured page that is used to specify the start
@@ -10,7 +10,10 @@
 
 #![no_std]
 
+pub mod crashdump;
 pub mod firmware;
+pub mod fs_archive;
 pub mod igvm_params;
 pub mod kernel_launch;
+pub mod linux_boot;
 pub mod platform;
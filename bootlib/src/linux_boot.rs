@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Definitions of the Linux x86 boot protocol shared between the IGVM
+//! builder, which lays out a directly booted Linux kernel, and the SVSM,
+//! which completes the boot parameters before launching it.
+
+use zerocopy::{Immutable, IntoBytes};
+
+/// Offset of the number of E820 entries in the boot parameters.
+pub const BOOT_PARAMS_E820_ENTRIES: usize = 0x1e8;
+
+/// Offset of the E820 table in the boot parameters.
+pub const BOOT_PARAMS_E820_TABLE: usize = 0x2d0;
+
+/// Maximum number of entries in the E820 table of the boot parameters.
+pub const E820_MAX_ENTRIES: usize = 128;
+
+/// E820 type of usable RAM.
+pub const E820_TYPE_RAM: u32 = 1;
+
+/// E820 type of reserved memory.
+pub const E820_TYPE_RESERVED: u32 = 2;
+
+/// An entry of the E820 table in the boot parameters.
+#[repr(C, packed)]
+#[derive(IntoBytes, Immutable, Clone, Copy, Debug, Default)]
+pub struct BootE820Entry {
+    /// The base physical address of the memory range.
+    pub addr: u64,
+
+    /// The size of the memory range in bytes.
+    pub size: u64,
+
+    /// The type of the memory range.
+    pub entry_type: u32,
+}
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+pub struct CmdOptions {
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Extract the ELF core file from a crash dump target.
+    Extract {
+        /// The block device image or guest memory dump containing the crash
+        /// dump
+        #[arg()]
+        input: String,
+
+        /// The filename of the ELF core file to write
+        #[arg(short, long)]
+        output: String,
+
+        /// Offset of the crash dump within the input. By default the input
+        /// is searched for a crash dump at page-aligned offsets.
+        #[arg(long, value_parser = parse_u64)]
+        offset: Option<u64>,
+    },
+    /// Generate the fw_cfg file which configures the crash dump target.
+    ///
+    /// Pass the file to QEMU with
+    /// -fw_cfg name=opt/svsm/crashdump,file=<output>
+    Config {
+        /// The filename of the configuration file to write
+        #[arg(short, long)]
+        output: String,
+
+        /// Guest physical address of a memory region to dump into. The
+        /// region must be page aligned and must not be used by the guest.
+        #[arg(long, value_parser = parse_u64, conflicts_with = "virtio_blk", required_unless_present = "virtio_blk")]
+        memory: Option<u64>,
+
+        /// MMIO address of the virtio-blk device to dump to
+        #[arg(long, value_parser = parse_u64)]
+        virtio_blk: Option<u64>,
+
+        /// Size of the memory region, or the maximum size of the dump on
+        /// the block device. Zero uses the whole block device.
+        #[arg(long, value_parser = parse_u64, default_value_t = 0)]
+        size: u64,
+    },
+}
+
+fn parse_u64(value: &str) -> Result<u64, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| e.to_string())
+}
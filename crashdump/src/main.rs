@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+#![forbid(unsafe_code)]
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use bootlib::crashdump::{
+    crash_dump_checksum, CrashDumpConfig, CrashDumpHeader, CRASH_DUMP_CHECKSUM_INIT,
+    CRASH_DUMP_DATA_OFFSET, CRASH_DUMP_FLAG_TRUNCATED, CRASH_DUMP_MAGIC, CRASH_DUMP_TARGET_MEMORY,
+    CRASH_DUMP_TARGET_VIRTIO_BLK, CRASH_DUMP_VERSION,
+};
+use clap::Parser;
+use cmd_options::{CmdOptions, Commands};
+use zerocopy::{FromBytes, IntoBytes};
+
+mod cmd_options;
+
+const PAGE_SIZE: u64 = 4096;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = CmdOptions::parse();
+
+    match options.command {
+        Commands::Extract {
+            input,
+            output,
+            offset,
+        } => extract_command(&input, &output, offset),
+        Commands::Config {
+            output,
+            memory,
+            virtio_blk,
+            size,
+        } => config_command(&output, memory, virtio_blk, size),
+    }
+}
+
+fn read_header<R: Read + Seek>(file: &mut R, offset: u64) -> io::Result<Option<CrashDumpHeader>> {
+    let mut buf = [0u8; size_of::<CrashDumpHeader>()];
+    file.seek(SeekFrom::Start(offset))?;
+    match file.read_exact(&mut buf) {
+        Ok(()) => Ok(CrashDumpHeader::read_from_bytes(&buf)
+            .ok()
+            .filter(|header| header.magic == CRASH_DUMP_MAGIC)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn find_header<R: Read + Seek>(
+    file: &mut R,
+    len: u64,
+    offset: Option<u64>,
+) -> Result<(u64, CrashDumpHeader), String> {
+    let offsets = match offset {
+        Some(offset) => offset..offset + 1,
+        None => 0..len,
+    };
+    for offset in offsets.step_by(PAGE_SIZE as usize) {
+        if let Some(header) = read_header(file, offset).map_err(|e| e.to_string())? {
+            return Ok((offset, header));
+        }
+    }
+    Err(String::from("No crash dump found in the input file"))
+}
+
+/// Reads the ELF core file of the crash dump in the first `len` bytes of
+/// `file`. The dump is searched for at page-aligned offsets unless `offset`
+/// is given.
+fn read_core<R: Read + Seek>(
+    file: &mut R,
+    len: u64,
+    offset: Option<u64>,
+) -> Result<(u64, CrashDumpHeader, Vec<u8>), String> {
+    let (offset, header) = find_header(file, len, offset)?;
+    if header.version != CRASH_DUMP_VERSION {
+        return Err(format!("Unsupported crash dump version {}", header.version));
+    }
+    let core_offset = offset + CRASH_DUMP_DATA_OFFSET as u64;
+    if header.size > len.saturating_sub(core_offset) {
+        return Err(String::from("The crash dump exceeds the input file"));
+    }
+
+    let mut core = vec![0u8; header.size as usize];
+    file.seek(SeekFrom::Start(core_offset))
+        .and_then(|_| file.read_exact(&mut core))
+        .map_err(|e| format!("Failed to read the ELF core file: {e}"))?;
+    if crash_dump_checksum(CRASH_DUMP_CHECKSUM_INIT, &core) != header.checksum {
+        return Err(String::from("Crash dump checksum mismatch"));
+    }
+    Ok((offset, header, core))
+}
+
+fn extract_command(input: &str, output: &str, offset: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let mut file = File::open(input).inspect_err(|_| {
+        eprintln!("Failed to open crash dump file {input}");
+    })?;
+    let len = file.metadata()?.len();
+    let (offset, header, core) = read_core(&mut file, len, offset)?;
+    if header.flags & CRASH_DUMP_FLAG_TRUNCATED != 0 {
+        eprintln!("Warning: the crash dump target was too small, the core file is truncated");
+    }
+
+    fs::write(output, &core).inspect_err(|_| {
+        eprintln!("Failed to write ELF core file {output}");
+    })?;
+    println!(
+        "Extracted {} bytes of crash dump at offset {offset:#x} to {output}",
+        core.len()
+    );
+    Ok(())
+}
+
+fn dump_config(
+    memory: Option<u64>,
+    virtio_blk: Option<u64>,
+    size: u64,
+) -> Result<CrashDumpConfig, String> {
+    let (target, address) = match (memory, virtio_blk) {
+        (Some(address), None) => {
+            if address % PAGE_SIZE != 0 || size % PAGE_SIZE != 0 {
+                return Err(String::from("The memory region must be page aligned"));
+            }
+            if size <= CRASH_DUMP_DATA_OFFSET as u64 {
+                return Err(String::from(
+                    "The memory region is too small for a crash dump",
+                ));
+            }
+            (CRASH_DUMP_TARGET_MEMORY, address)
+        }
+        (None, Some(address)) => (CRASH_DUMP_TARGET_VIRTIO_BLK, address),
+        _ => {
+            return Err(String::from(
+                "Exactly one crash dump target must be specified",
+            ))
+        }
+    };
+
+    Ok(CrashDumpConfig {
+        target,
+        reserved: 0,
+        address,
+        size,
+    })
+}
+
+fn config_command(
+    output: &str,
+    memory: Option<u64>,
+    virtio_blk: Option<u64>,
+    size: u64,
+) -> Result<(), Box<dyn Error>> {
+    let config = dump_config(memory, virtio_blk, size)?;
+    let mut file = File::create(output).inspect_err(|_| {
+        eprintln!("Failed to create output file {output}");
+    })?;
+    file.write_all(config.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Returns an image with a crash dump of `core` at `offset`.
+    fn dump_image(offset: usize, core: &[u8], flags: u32) -> Vec<u8> {
+        let header = CrashDumpHeader {
+            magic: CRASH_DUMP_MAGIC,
+            version: CRASH_DUMP_VERSION,
+            flags,
+            size: core.len() as u64,
+            checksum: crash_dump_checksum(CRASH_DUMP_CHECKSUM_INIT, core),
+        };
+        let mut image = vec![0u8; offset + CRASH_DUMP_DATA_OFFSET + core.len()];
+        image[offset..offset + size_of::<CrashDumpHeader>()].copy_from_slice(header.as_bytes());
+        image[offset + CRASH_DUMP_DATA_OFFSET..].copy_from_slice(core);
+        image
+    }
+
+    fn read_image(image: &[u8], offset: Option<u64>) -> Result<(u64, Vec<u8>), String> {
+        read_core(&mut Cursor::new(image), image.len() as u64, offset)
+            .map(|(offset, _, core)| (offset, core))
+    }
+
+    #[test]
+    fn test_read_core() {
+        let core = b"\x7fELF core".repeat(1000);
+        let image = dump_image(3 * PAGE_SIZE as usize, &core, 0);
+        assert_eq!(read_image(&image, None), Ok((3 * PAGE_SIZE, core.clone())));
+        assert_eq!(
+            read_image(&image, Some(3 * PAGE_SIZE)),
+            Ok((3 * PAGE_SIZE, core))
+        );
+        assert!(read_image(&image, Some(PAGE_SIZE)).is_err());
+    }
+
+    #[test]
+    fn test_read_core_truncated_flag() {
+        let image = dump_image(0, b"core", CRASH_DUMP_FLAG_TRUNCATED);
+        let (_, header, core) =
+            read_core(&mut Cursor::new(&image), image.len() as u64, None).unwrap();
+        assert_eq!(header.flags, CRASH_DUMP_FLAG_TRUNCATED);
+        assert_eq!(core, b"core");
+    }
+
+    #[test]
+    fn test_read_core_invalid() {
+        assert!(read_image(&[0u8; 2 * PAGE_SIZE as usize], None).is_err());
+
+        let mut image = dump_image(0, b"core", 0);
+        *image.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            read_image(&image, None),
+            Err(String::from("Crash dump checksum mismatch"))
+        );
+
+        // The header claims more data than the image contains.
+        let image = dump_image(0, b"core", 0);
+        assert!(read_image(&image[..image.len() - 1], None).is_err());
+
+        let mut image = dump_image(0, b"core", 0);
+        image[8] = CRASH_DUMP_VERSION as u8 + 1;
+        assert!(read_image(&image, None).is_err());
+    }
+
+    #[test]
+    fn test_dump_config() {
+        let config = dump_config(Some(0x1000_0000), None, 0x10_0000).unwrap();
+        assert_eq!(config.target, CRASH_DUMP_TARGET_MEMORY);
+        assert_eq!(config.address, 0x1000_0000);
+        assert_eq!(config.size, 0x10_0000);
+
+        let config = dump_config(None, Some(0xfef0_0000), 0).unwrap();
+        assert_eq!(config.target, CRASH_DUMP_TARGET_VIRTIO_BLK);
+        assert_eq!(config.address, 0xfef0_0000);
+
+        assert!(dump_config(Some(0x1000_0800), None, 0x10_0000).is_err());
+        assert!(dump_config(Some(0x1000_0000), None, 0x1800).is_err());
+        assert!(dump_config(Some(0x1000_0000), None, PAGE_SIZE).is_err());
+        assert!(dump_config(Some(0x1000_0000), Some(0xfef0_0000), 0x10_0000).is_err());
+        assert!(dump_config(None, None, 0).is_err());
+    }
+}
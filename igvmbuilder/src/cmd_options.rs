@@ -5,6 +5,16 @@
 // Author: Roy Hopkins <roy.hopkins@suse.com>
 
 use clap::{Parser, ValueEnum};
+use std::num::ParseIntError;
+
+/// Parses an address given as decimal or, with a `0x` prefix, hexadecimal
+/// number.
+fn parse_address(value: &str) -> Result<u64, ParseIntError> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+}
 
 #[derive(Parser, Debug)]
 pub struct CmdOptions {
@@ -24,10 +34,49 @@ pub struct CmdOptions {
     #[arg(long)]
     pub filesystem: Option<String>,
 
+    /// Optional PEM file with the NIST P-384 public key the filesystem image
+    /// must be signed with. The filesystem image is not measured when a key
+    /// is given.
+    #[arg(long)]
+    pub fs_public_key: Option<String>,
+
+    /// Size in bytes to reserve for the filesystem image. This keeps the
+    /// launch measurement independent of the size of the signed image.
+    #[arg(long, requires = "fs_public_key")]
+    pub filesystem_size: Option<u64>,
+
+    /// Minimum version of the signed filesystem image. The SVSM refuses to
+    /// boot with an older image.
+    #[arg(long, requires = "fs_public_key")]
+    pub fs_min_version: Option<u64>,
+
+    /// Minimum generation of the persistent vTPM NV state. The vTPM refuses
+    /// to start with older NV state.
+    #[arg(long)]
+    pub vtpm_nv_min_generation: Option<u64>,
+
+    /// Guest physical address of the ECAM region of a PCI host bridge which
+    /// is reserved for the SVSM and hidden from the guest. The SVSM drives
+    /// the VirtIO functions on bus 0 of this host bridge.
+    #[arg(long, value_parser = parse_address)]
+    pub svsm_pci_ecam: Option<u64>,
+
     /// Optional firmware file, e.g. OVMF.fd
     #[arg(short, long)]
     pub firmware: Option<String>,
 
+    /// Optional Linux kernel bzImage to boot directly instead of a firmware
+    #[arg(long, conflicts_with = "firmware")]
+    pub linux: Option<String>,
+
+    /// Optional initrd for the Linux kernel
+    #[arg(long, requires = "linux")]
+    pub initrd: Option<String>,
+
+    /// Optional command line for the Linux kernel
+    #[arg(long, requires = "linux")]
+    pub cmdline: Option<String>,
+
     /// Output filename for the generated IGVM file
     #[arg(short, long)]
     pub output: String,
@@ -11,6 +11,7 @@ use igvm::IgvmDirectiveHeader;
 
 use crate::cmd_options::CmdOptions;
 use crate::igvm_firmware::IgvmFirmware;
+use crate::linux_firmware::LinuxFirmware;
 use crate::ovmf_firmware::OvmfFirmware;
 
 pub trait Firmware {
@@ -25,7 +26,14 @@ pub fn parse_firmware(
     parameter_count: u32,
     compatibility_mask: u32,
 ) -> Result<Box<dyn Firmware>, Box<dyn Error>> {
-    if let Some(filename) = &options.firmware {
+    if let Some(filename) = &options.linux {
+        LinuxFirmware::parse(
+            filename,
+            options.initrd.as_ref(),
+            options.cmdline.as_ref(),
+            compatibility_mask,
+        )
+    } else if let Some(filename) = &options.firmware {
         match options.hypervisor {
             crate::cmd_options::Hypervisor::Qemu => {
                 OvmfFirmware::parse(filename, parameter_count, compatibility_mask)
@@ -91,6 +91,7 @@ impl GpaMap {
         //   0x8nnnnn-0x8nnnnn: IGVM parameter block
         //   0x8nnnnn-0x8nnnnn: general and memory map parameter pages
         //   0xFFnn0000-0xFFFFFFFF: [TDX stage 1 +] OVMF firmware (QEMU only, if specified)
+        //   0x6000000-0x6nnnnnn: Linux boot data, kernel and initrd (if specified)
 
         let stage1_image = if let Some(stage1) = &options.tdx_stage1 {
             if COMPATIBILITY_MASK.contains(TDP_COMPATIBILITY_MASK) {
@@ -113,6 +114,13 @@ impl GpaMap {
         } else {
             0
         };
+        let kernel_fs_len = match options.filesystem_size {
+            Some(size) if (size as usize) < kernel_fs_len => {
+                return Err("Filesystem image is larger than the reserved size".into());
+            }
+            Some(size) => size as usize,
+            None => kernel_fs_len,
+        };
 
         let stage2_image = GpaRange::new(STAGE2_START.into(), stage2_len as u64)?;
 
@@ -157,6 +165,20 @@ impl GpaMap {
             GpaRange::new(0, 0)?
         };
 
+        // Make sure that the firmware does not overlap with the SVSM boot
+        // data or the kernel region.
+        if let Some(firmware) = firmware {
+            let fw_info = firmware.get_fw_info();
+            let fw_start = u64::from(fw_info.start);
+            let fw_end = fw_start + u64::from(fw_info.size);
+            let boot_data_end = memory_map.get_end().max(guest_context.get_end());
+            if (fw_start < boot_data_end && fw_end > u64::from(STAGE2_BASE))
+                || (fw_start < kernel.get_end() && fw_end > kernel.get_start())
+            {
+                return Err("Firmware overlaps with the SVSM".into());
+            }
+        }
+
         let vmsa = match options.hypervisor {
             Hypervisor::Qemu | Hypervisor::Vanadium => {
                 // VMSA address is currently hardcoded in kvm
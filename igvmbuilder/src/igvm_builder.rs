@@ -6,10 +6,11 @@
 
 use std::cmp::Ordering;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::mem::size_of;
 
+use bootlib::fs_archive::FsPublicKey;
 use bootlib::igvm_params::{IgvmGuestContext, IgvmParamBlock, IgvmParamBlockFwInfo};
 use bootlib::platform::SvsmPlatformType;
 use clap::Parser;
@@ -21,6 +22,9 @@ use igvm_defs::{
     IgvmPageDataFlags, IgvmPageDataType, IgvmPlatformType, IGVM_VHS_PARAMETER,
     IGVM_VHS_PARAMETER_INSERT, IGVM_VHS_SUPPORTED_PLATFORM, PAGE_SIZE_4K,
 };
+use p384::elliptic_curve::sec1::ToEncodedPoint;
+use p384::pkcs8::DecodePublicKey;
+use p384::PublicKey;
 use zerocopy::IntoBytes;
 
 use crate::cmd_options::{CmdOptions, Hypervisor};
@@ -90,13 +94,14 @@ impl IgvmBuilder {
             return Err("No platform specified".into());
         }
 
-        let firmware = match options.firmware {
-            Some(_) => Some(parse_firmware(
+        let firmware = if options.firmware.is_some() || options.linux.is_some() {
+            Some(parse_firmware(
                 &options,
                 IGVM_PARAMETER_COUNT,
                 COMPATIBILITY_MASK.get(),
-            )?),
-            None => None,
+            )?)
+        } else {
+            None
         };
         let gpa_map = GpaMap::new(&options, &firmware)?;
         Ok(Self {
@@ -249,6 +254,10 @@ impl IgvmBuilder {
             vtom,
             use_alternate_injection: u8::from(self.options.alt_injection),
             is_qemu,
+            fs_public_key: self.fs_public_key()?,
+            fs_min_version: self.options.fs_min_version.unwrap_or(0),
+            vtpm_nv_min_generation: self.options.vtpm_nv_min_generation.unwrap_or(0),
+            svsm_pci_ecam: self.options.svsm_pci_ecam.unwrap_or(0),
             ..Default::default()
         })
     }
@@ -446,13 +455,7 @@ impl IgvmBuilder {
         self.add_param_block(param_block);
 
         // Add optional filesystem image
-        if let Some(fs) = &self.options.filesystem {
-            self.add_data_pages_from_file(
-                &fs.clone(),
-                self.gpa_map.kernel_fs.get_start(),
-                COMPATIBILITY_MASK.get(),
-            )?;
-        }
+        self.add_filesystem_pages()?;
 
         // Add the kernel elf binary
         self.add_data_pages_from_file(
@@ -600,6 +603,55 @@ impl IgvmBuilder {
         Ok(())
     }
 
+    fn add_filesystem_pages(&mut self) -> Result<(), Box<dyn Error>> {
+        let data = match &self.options.filesystem {
+            Some(path) => fs::read(path).inspect_err(|_| {
+                eprintln!("Could not open input file {}", path);
+            })?,
+            None => Vec::new(),
+        };
+
+        // A signed filesystem image is verified by the SVSM at boot, so it
+        // is not included in the launch measurement. This allows replacing
+        // it without changing the measurement.
+        let flags = IgvmPageDataFlags::new().with_unmeasured(self.options.fs_public_key.is_some());
+
+        let page_size = PAGE_SIZE_4K as usize;
+        let range = &self.gpa_map.kernel_fs;
+        for (index, gpa) in (range.get_start()..range.get_end())
+            .step_by(page_size)
+            .enumerate()
+        {
+            let mut page = data
+                .chunks(page_size)
+                .nth(index)
+                .unwrap_or_default()
+                .to_vec();
+            page.resize(page_size, 0);
+            self.directives.push(IgvmDirectiveHeader::PageData {
+                gpa,
+                compatibility_mask: COMPATIBILITY_MASK.get(),
+                flags,
+                data_type: IgvmPageDataType::NORMAL,
+                data: page,
+            });
+        }
+        Ok(())
+    }
+
+    fn fs_public_key(&self) -> Result<FsPublicKey, Box<dyn Error>> {
+        let Some(path) = &self.options.fs_public_key else {
+            return Ok(FsPublicKey::default());
+        };
+        let pem = fs::read_to_string(path).inspect_err(|_| {
+            eprintln!("Could not open public key file {}", path);
+        })?;
+        let key = PublicKey::from_public_key_pem(&pem)?;
+        Ok(FsPublicKey(
+            key.to_encoded_point(false).as_bytes().try_into()?,
+        ))
+    }
+
     fn add_param_block(&mut self, param_block: &IgvmParamBlock) {
         let mut data = param_block.as_bytes().to_vec();
         data.resize(PAGE_SIZE_4K as usize, 0);
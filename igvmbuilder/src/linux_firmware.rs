@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use std::error::Error;
+use std::fs;
+
+use bootlib::igvm_params::{IgvmGuestContext, IgvmParamBlockFwInfo};
+use igvm::IgvmDirectiveHeader;
+use igvm_defs::{IgvmPageDataFlags, IgvmPageDataType, PAGE_SIZE_4K};
+
+use crate::firmware::Firmware;
+use crate::igvm_builder::SNP_COMPATIBILITY_MASK;
+
+// Guest physical address at which the Linux boot data is placed. This is
+// above the SVSM boot data and the Hyper-V SVSM kernel region.
+const LINUX_BASE: u64 = 0x6000000;
+
+// Layout of the pages at the start of the Linux boot data.
+const BOOT_PARAMS_OFFSET: u64 = 0;
+const GDT_OFFSET: u64 = 0x1000;
+const CC_BLOB_OFFSET: u64 = 0x1100;
+const SECRETS_OFFSET: u64 = 0x2000;
+const CAA_OFFSET: u64 = 0x3000;
+const CPUID_OFFSET: u64 = 0x4000;
+const CMDLINE_OFFSET: u64 = 0x5000;
+
+// Offsets into the boot parameters (zero page) and the bzImage setup header,
+// as defined by the Linux x86 boot protocol.
+const BP_CC_BLOB_ADDRESS: usize = 0x13c;
+const BP_SETUP_SECTS: usize = 0x1f1;
+const BP_JUMP: usize = 0x200;
+const BP_HEADER: usize = 0x202;
+const BP_VERSION: usize = 0x206;
+const BP_TYPE_OF_LOADER: usize = 0x210;
+const BP_LOADFLAGS: usize = 0x211;
+const BP_CODE32_START: usize = 0x214;
+const BP_RAMDISK_IMAGE: usize = 0x218;
+const BP_RAMDISK_SIZE: usize = 0x21c;
+const BP_CMD_LINE_PTR: usize = 0x228;
+const BP_INITRD_ADDR_MAX: usize = 0x22c;
+const BP_KERNEL_ALIGNMENT: usize = 0x230;
+const BP_RELOCATABLE_KERNEL: usize = 0x234;
+const BP_CMDLINE_SIZE: usize = 0x238;
+const BP_INIT_SIZE: usize = 0x260;
+
+const SETUP_HEADER_MAGIC: u32 = 0x53726448; // "HdrS"
+const MIN_BOOT_PROTOCOL: u16 = 0x020a;
+const LOADFLAGS_LOADED_HIGH: u8 = 0x01;
+const TYPE_OF_LOADER_UNDEFINED: u8 = 0xff;
+
+// Signature and size of struct cc_blob_sev_info, which tells the kernel
+// where to find the secrets and CPUID pages.
+const CC_BLOB_SEV_HDR_MAGIC: u32 = 0x45444d41; // "AMDE"
+const CC_BLOB_SIZE: usize = 40;
+
+// Selectors of the flat code and data segments expected by the 32-bit boot
+// protocol.
+const BOOT_CS: u16 = 0x10;
+const BOOT_DS: u16 = 0x18;
+const GDT_CODE32: u64 = 0x00cf9a000000ffff;
+const GDT_DATA32: u64 = 0x00cf92000000ffff;
+
+// CR0 with protection enabled and paging disabled.
+const CR0_PE_ET: u64 = 0x11;
+
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, Box<dyn Error>> {
+    data.get(offset)
+        .copied()
+        .ok_or("Linux kernel image is too small".into())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Box<dyn Error>> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or("Linux kernel image is too small")?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or("Linux kernel image is too small")?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn gpa_u32(gpa: u64) -> Result<u32, Box<dyn Error>> {
+    u32::try_from(gpa).map_err(|_| "Linux boot data must be located below 4GB".into())
+}
+
+/// A Linux kernel that is booted directly through the 32-bit entry point of
+/// the x86 boot protocol, with an optional initrd and command line. The
+/// boot parameters, command line, kernel and initrd are placed at
+/// `LINUX_BASE` and are part of the measured IGVM image. The SVSM completes
+/// the E820 table in the boot parameters before launching the kernel.
+pub struct LinuxFirmware {
+    fw_info: IgvmParamBlockFwInfo,
+    guest_context: IgvmGuestContext,
+    directives: Vec<IgvmDirectiveHeader>,
+    compatibility_mask: u32,
+}
+
+impl LinuxFirmware {
+    pub fn parse(
+        filename: &String,
+        initrd: Option<&String>,
+        cmdline: Option<&String>,
+        compatibility_mask: u32,
+    ) -> Result<Box<dyn Firmware>, Box<dyn Error>> {
+        // The kernel learns about the SVSM from the secrets page, which is
+        // only provided on SEV-SNP.
+        let compatibility_mask = compatibility_mask & SNP_COMPATIBILITY_MASK;
+        if compatibility_mask == 0 {
+            return Err("Linux direct boot is only supported on SEV-SNP".into());
+        }
+
+        let kernel = fs::read(filename).inspect_err(|_| {
+            eprintln!("Failed to open Linux kernel file {}", filename);
+        })?;
+        let initrd = match initrd {
+            Some(filename) => fs::read(filename).inspect_err(|_| {
+                eprintln!("Failed to open initrd file {}", filename);
+            })?,
+            None => Vec::new(),
+        };
+
+        if read_u32(&kernel, BP_HEADER)? != SETUP_HEADER_MAGIC {
+            return Err("Linux kernel is not a bzImage".into());
+        }
+        if read_u16(&kernel, BP_VERSION)? < MIN_BOOT_PROTOCOL {
+            return Err("Linux kernel boot protocol version is too old".into());
+        }
+        if read_u8(&kernel, BP_LOADFLAGS)? & LOADFLAGS_LOADED_HIGH == 0 {
+            return Err("Linux kernel cannot be loaded at 1MB or above".into());
+        }
+        if read_u8(&kernel, BP_RELOCATABLE_KERNEL)? == 0 {
+            return Err("Linux kernel is not relocatable".into());
+        }
+
+        let setup_sects = match read_u8(&kernel, BP_SETUP_SECTS)? {
+            0 => 4,
+            sects => sects as usize,
+        };
+        let setup_size = (setup_sects + 1) * 512;
+        let header_end = BP_HEADER + read_u8(&kernel, BP_JUMP + 1)? as usize;
+        let image = kernel
+            .get(setup_size..)
+            .ok_or("Linux kernel image is too small")?;
+        let kernel_alignment = u64::from(read_u32(&kernel, BP_KERNEL_ALIGNMENT)?);
+        let init_size = u64::from(read_u32(&kernel, BP_INIT_SIZE)?);
+        if !kernel_alignment.is_power_of_two() {
+            return Err("Linux kernel alignment is invalid".into());
+        }
+
+        // The command line is NUL-terminated.
+        let mut cmdline = cmdline.map(|s| s.as_bytes().to_vec()).unwrap_or_default();
+        if cmdline.len() > read_u32(&kernel, BP_CMDLINE_SIZE)? as usize {
+            return Err("Linux kernel command line is too long".into());
+        }
+        cmdline.push(0);
+
+        // Lay out the boot data. The decompressor runs in place and uses
+        // init_size bytes from the load address, so the initrd is placed
+        // beyond that.
+        let boot_params_gpa = LINUX_BASE + BOOT_PARAMS_OFFSET;
+        let gdt_gpa = LINUX_BASE + GDT_OFFSET;
+        let cc_blob_gpa = LINUX_BASE + CC_BLOB_OFFSET;
+        let secrets_gpa = LINUX_BASE + SECRETS_OFFSET;
+        let caa_gpa = LINUX_BASE + CAA_OFFSET;
+        let cpuid_gpa = LINUX_BASE + CPUID_OFFSET;
+        let cmdline_gpa = LINUX_BASE + CMDLINE_OFFSET;
+        let cmdline_end = (cmdline_gpa + cmdline.len() as u64).next_multiple_of(PAGE_SIZE_4K);
+        let kernel_gpa = cmdline_end.next_multiple_of(kernel_alignment);
+        let kernel_end = (kernel_gpa + image.len() as u64).next_multiple_of(PAGE_SIZE_4K);
+        let init_end = kernel_end.max((kernel_gpa + init_size).next_multiple_of(PAGE_SIZE_4K));
+        let initrd_gpa = init_end;
+        let initrd_end = (initrd_gpa + initrd.len() as u64).next_multiple_of(PAGE_SIZE_4K);
+
+        if !initrd.is_empty()
+            && initrd_gpa + initrd.len() as u64 - 1
+                > u64::from(read_u32(&kernel, BP_INITRD_ADDR_MAX)?)
+        {
+            return Err("Initrd exceeds the maximum address supported by the kernel".into());
+        }
+
+        // Build the boot parameters from the setup header of the kernel.
+        let mut boot_params = vec![0u8; PAGE_SIZE_4K as usize];
+        let header = kernel
+            .get(BP_SETUP_SECTS..header_end)
+            .ok_or("Linux kernel image is too small")?;
+        boot_params[BP_SETUP_SECTS..header_end].copy_from_slice(header);
+        boot_params[BP_TYPE_OF_LOADER] = TYPE_OF_LOADER_UNDEFINED;
+        write_u32(&mut boot_params, BP_CODE32_START, gpa_u32(kernel_gpa)?);
+        write_u32(&mut boot_params, BP_CMD_LINE_PTR, gpa_u32(cmdline_gpa)?);
+        if !initrd.is_empty() {
+            write_u32(&mut boot_params, BP_RAMDISK_IMAGE, gpa_u32(initrd_gpa)?);
+            write_u32(
+                &mut boot_params,
+                BP_RAMDISK_SIZE,
+                gpa_u32(initrd.len() as u64)?,
+            );
+        }
+        write_u32(&mut boot_params, BP_CC_BLOB_ADDRESS, gpa_u32(cc_blob_gpa)?);
+
+        // The GDT and the confidential computing blob share a page.
+        let mut gdt_page = vec![0u8; PAGE_SIZE_4K as usize];
+        let gdt = [0u64, 0u64, GDT_CODE32, GDT_DATA32];
+        for (i, entry) in gdt.iter().enumerate() {
+            gdt_page[i * 8..(i + 1) * 8].copy_from_slice(&entry.to_le_bytes());
+        }
+        let mut cc_blob = Vec::with_capacity(CC_BLOB_SIZE);
+        cc_blob.extend_from_slice(&CC_BLOB_SEV_HDR_MAGIC.to_le_bytes());
+        cc_blob.extend_from_slice(&0u16.to_le_bytes()); // version
+        cc_blob.extend_from_slice(&0u16.to_le_bytes()); // reserved
+        cc_blob.extend_from_slice(&secrets_gpa.to_le_bytes());
+        cc_blob.extend_from_slice(&(PAGE_SIZE_4K as u32).to_le_bytes());
+        cc_blob.extend_from_slice(&0u32.to_le_bytes()); // reserved
+        cc_blob.extend_from_slice(&cpuid_gpa.to_le_bytes());
+        cc_blob.extend_from_slice(&(PAGE_SIZE_4K as u32).to_le_bytes());
+        cc_blob.extend_from_slice(&0u32.to_le_bytes()); // reserved
+        let cc_blob_offset = (CC_BLOB_OFFSET - GDT_OFFSET) as usize;
+        gdt_page[cc_blob_offset..cc_blob_offset + CC_BLOB_SIZE].copy_from_slice(&cc_blob);
+
+        let mut linux_fw = Self {
+            fw_info: IgvmParamBlockFwInfo {
+                start: gpa_u32(LINUX_BASE)?,
+                size: gpa_u32(initrd_end - LINUX_BASE)?,
+                secrets_page: gpa_u32(secrets_gpa)?,
+                caa_page: gpa_u32(caa_gpa)?,
+                cpuid_page: gpa_u32(cpuid_gpa)?,
+                linux_boot_params: gpa_u32(boot_params_gpa)?,
+                ..Default::default()
+            },
+            guest_context: IgvmGuestContext {
+                cr0: CR0_PE_ET,
+                gdt_base: gdt_gpa,
+                gdt_limit: (gdt.len() * 8 - 1) as u32,
+                code_selector: BOOT_CS,
+                data_selector: BOOT_DS,
+                rip: kernel_gpa,
+                rsi: boot_params_gpa,
+                ..Default::default()
+            },
+            directives: Vec::new(),
+            compatibility_mask,
+        };
+
+        linux_fw.add_pages(boot_params_gpa, &boot_params);
+        linux_fw.add_pages(gdt_gpa, &gdt_page);
+        linux_fw.add_pages(cmdline_gpa, &cmdline);
+        linux_fw.add_pages(kernel_gpa, image);
+        linux_fw.add_pages(initrd_gpa, &initrd);
+
+        // The gaps in the firmware range are not part of the image and are
+        // validated by the SVSM.
+        linux_fw.add_prevalidated(cmdline_end, kernel_gpa)?;
+        linux_fw.add_prevalidated(kernel_end, init_end)?;
+
+        Ok(Box::new(linux_fw))
+    }
+
+    fn add_pages(&mut self, gpa: u64, data: &[u8]) {
+        for (i, page_data) in data.chunks(PAGE_SIZE_4K as usize).enumerate() {
+            self.directives.push(IgvmDirectiveHeader::PageData {
+                gpa: gpa + i as u64 * PAGE_SIZE_4K,
+                compatibility_mask: self.compatibility_mask,
+                flags: IgvmPageDataFlags::new(),
+                data_type: IgvmPageDataType::NORMAL,
+                data: page_data.to_vec(),
+            });
+        }
+    }
+
+    fn add_prevalidated(&mut self, start: u64, end: u64) -> Result<(), Box<dyn Error>> {
+        if start == end {
+            return Ok(());
+        }
+        let index = self.fw_info.prevalidated_count as usize;
+        self.fw_info.prevalidated[index].base = gpa_u32(start)?;
+        self.fw_info.prevalidated[index].size = gpa_u32(end - start)?;
+        self.fw_info.prevalidated_count += 1;
+        Ok(())
+    }
+}
+
+impl Firmware for LinuxFirmware {
+    fn directives(&self) -> &Vec<IgvmDirectiveHeader> {
+        &self.directives
+    }
+
+    fn get_guest_context(&self) -> Option<IgvmGuestContext> {
+        Some(self.guest_context)
+    }
+
+    fn get_vtom(&self) -> u64 {
+        0
+    }
+
+    fn get_fw_info(&self) -> IgvmParamBlockFwInfo {
+        self.fw_info
+    }
+}
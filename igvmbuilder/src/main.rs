@@ -15,6 +15,7 @@ mod firmware;
 mod gpa_map;
 mod igvm_builder;
 mod igvm_firmware;
+mod linux_firmware;
 mod ovmf_firmware;
 mod paging;
 mod platform;
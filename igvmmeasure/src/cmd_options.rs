@@ -27,7 +27,8 @@ pub struct CmdOptions {
     /// Determine how to pages that contain only zeroes in the IGVM file.
     ///
     /// When true, zero pages are measured using the native zero page type
-    /// if the underlying platform supports it.
+    /// if the underlying platform supports it. TDX has no native zero page
+    /// type, so this option has no effect on TDX measurements.
     ///
     /// When false, the page is measured as a normal page containing all zeros.
     #[arg(short, long)]
@@ -91,4 +92,6 @@ pub enum Platform {
     SevEs,
     /// Calculate the launch measurement for SEV-SNP
     SevSnp,
+    /// Calculate the MRTD measurement for TDX
+    Tdx,
 }
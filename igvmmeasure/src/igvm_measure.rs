@@ -13,6 +13,7 @@ use sha2::{Digest, Sha256};
 use zerocopy07::AsBytes;
 
 use crate::page_info::PageInfo;
+use crate::tdx_measure::TdxMrtd;
 
 #[derive(Copy, Clone)]
 pub enum IgvmMeasureError {
@@ -128,6 +129,7 @@ impl std::fmt::Display for SnpPageType {
 struct IgvmMeasureContext {
     digest_snp: [u8; 48],
     digest_es: Sha256,
+    mrtd: TdxMrtd,
 }
 
 impl Default for IgvmMeasureContext {
@@ -135,6 +137,7 @@ impl Default for IgvmMeasureContext {
         Self {
             digest_snp: [0u8; 48],
             digest_es: Sha256::default(),
+            mrtd: TdxMrtd::default(),
         }
     }
 }
@@ -235,7 +238,10 @@ impl IgvmMeasure {
                     vp_index: _vp_index,
                     vmsa,
                 } => {
-                    self.measure_vmsa(&mut ctx, *gpa, *compatibility_mask, vmsa)?;
+                    // The initial VP state is not part of the TDX measurement
+                    if self.platform != IgvmPlatformType::TDX {
+                        self.measure_vmsa(&mut ctx, *gpa, *compatibility_mask, vmsa)?;
+                    }
                 }
                 IgvmDirectiveHeader::SnpIdBlock {
                     compatibility_mask,
@@ -253,6 +259,8 @@ impl IgvmMeasure {
 
         if (self.platform == IgvmPlatformType::SEV_ES) || (self.platform == IgvmPlatformType::SEV) {
             self.digest = ctx.digest_es.finalize_reset().to_vec();
+        } else if self.platform == IgvmPlatformType::TDX {
+            self.digest = ctx.mrtd.finalize().to_vec();
         } else {
             self.digest = ctx.digest_snp.to_vec();
         }
@@ -360,12 +368,37 @@ impl IgvmMeasure {
             if let Some(page_info) = page_info {
                 ctx.digest_snp = page_info.update_hash();
             }
+        } else if self.platform == IgvmPlatformType::TDX {
+            self.measure_page_tdx(ctx, gpa, flags, data_type, data);
         } else {
             self.log_page(SnpPageType::Normal, gpa, PAGE_SIZE_4K);
             ctx.digest_es.update(data);
         }
     }
 
+    fn measure_page_tdx(
+        &mut self,
+        ctx: &mut IgvmMeasureContext,
+        gpa: u64,
+        flags: &IgvmPageDataFlags,
+        data_type: IgvmPageDataType,
+        data: &[u8],
+    ) {
+        // Every page is added with TDH.MEM.PAGE.ADD, but only the contents of
+        // measured pages are extended into MRTD. TDX has no native zero page
+        // type, so zero pages are measured as normal pages containing zeros.
+        ctx.mrtd.page_add(gpa);
+        if flags.unmeasured() || (data_type != IgvmPageDataType::NORMAL) {
+            self.log_page(SnpPageType::Unmeasured, gpa, PAGE_SIZE_4K);
+        } else if data.is_empty() {
+            self.log_page(SnpPageType::Zero, gpa, PAGE_SIZE_4K);
+            ctx.mrtd.mr_extend(gpa, &[0u8; PAGE_SIZE_4K as usize]);
+        } else {
+            self.log_page(SnpPageType::Normal, gpa, data.len() as u64);
+            ctx.mrtd.mr_extend(gpa, data);
+        }
+    }
+
     fn check_vmsa(&self, gpa: u64, vmsa: &SevVmsa) -> Result<(), IgvmMeasureError> {
         if self.check_kvm {
             if self.vmsa_count > 0 {
@@ -23,6 +23,7 @@ mod cmd_options;
 mod id_block;
 mod igvm_measure;
 mod page_info;
+mod tdx_measure;
 mod utils;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -36,6 +37,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         Platform::Sev => IgvmPlatformType::SEV,
         Platform::SevEs => IgvmPlatformType::SEV_ES,
         Platform::SevSnp => IgvmPlatformType::SEV_SNP,
+        Platform::Tdx => IgvmPlatformType::TDX,
     };
     let compatibility_mask = get_compatibility_mask(&igvm, platform).ok_or(String::from(
         "IGVM file is not compatible with the specified platform.",
@@ -77,10 +79,14 @@ fn measure_command(
     measure: &IgvmMeasure,
 ) -> Result<(), Box<dyn Error>> {
     if !bare {
+        let digest_name = match options.platform {
+            Platform::Tdx => "MRTD",
+            _ => "Launch Digest",
+        };
         println!(
             "\n==============================================================================================================="
         );
-        print!("igvmmeasure '{}'\nLaunch Digest: ", options.input);
+        print!("igvmmeasure '{}'\n{}: ", options.input, digest_name);
     }
 
     measure
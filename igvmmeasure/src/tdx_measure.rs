@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use sha2::{Digest, Sha384};
+
+/// Size of the buffer the TDX module hashes for every measurement operation
+const TDX_MEASUREMENT_BUFFER_SIZE: usize = 128;
+
+/// Size of the chunks that are measured by a single TDH.MR.EXTEND call
+pub const TDX_MR_EXTEND_CHUNK_SIZE: usize = 256;
+
+/// Size of the pages added to the TD
+const TDX_PAGE_SIZE: usize = 4096;
+
+/// Replays the measurement of the initial TD contents into MRTD in the way
+/// the TDX module performs it.
+#[derive(Debug, Default)]
+pub struct TdxMrtd {
+    sha384: Sha384,
+}
+
+impl TdxMrtd {
+    /// Builds the buffer the TDX module hashes for an operation: the ASCII
+    /// name of the operation, followed by the GPA at offset 16. The remainder
+    /// of the buffer is zero.
+    fn operation_buffer(operation: &[u8], gpa: u64) -> [u8; TDX_MEASUREMENT_BUFFER_SIZE] {
+        let mut buffer = [0u8; TDX_MEASUREMENT_BUFFER_SIZE];
+        buffer[..operation.len()].copy_from_slice(operation);
+        buffer[16..24].copy_from_slice(&gpa.to_le_bytes());
+        buffer
+    }
+
+    /// Measures the addition of a 4K page to the TD with TDH.MEM.PAGE.ADD.
+    pub fn page_add(&mut self, gpa: u64) {
+        self.sha384
+            .update(Self::operation_buffer(b"MEM.PAGE.ADD", gpa));
+    }
+
+    /// Measures the contents of a 4K page with one TDH.MR.EXTEND call for
+    /// every 256-byte chunk of the page. `data` must cover the full page.
+    pub fn mr_extend(&mut self, gpa: u64, data: &[u8]) {
+        assert_eq!(data.len(), TDX_PAGE_SIZE);
+
+        for (index, chunk) in data.chunks(TDX_MR_EXTEND_CHUNK_SIZE).enumerate() {
+            let chunk_gpa = gpa + (index * TDX_MR_EXTEND_CHUNK_SIZE) as u64;
+            self.sha384
+                .update(Self::operation_buffer(b"MR.EXTEND", chunk_gpa));
+            self.sha384.update(chunk);
+        }
+    }
+
+    /// Calculates the final MRTD value as done by TDH.MR.FINALIZE.
+    pub fn finalize(&mut self) -> [u8; 48] {
+        self.sha384.finalize_reset().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mrtd() {
+        // Reference value calculated independently from the measurement
+        // operations defined in the TDX module ABI specification.
+        const EXPECTED: [u8; 48] = [
+            0xf5, 0x3f, 0x02, 0x7a, 0x87, 0x6b, 0xa6, 0x5d, 0xa3, 0x6c, 0xe4, 0x35, 0x3c, 0x2c,
+            0x5e, 0x13, 0x36, 0x1e, 0x5c, 0x6d, 0x1c, 0x01, 0x7f, 0x07, 0x67, 0x22, 0xb6, 0x57,
+            0x13, 0xd6, 0x06, 0xca, 0x89, 0x87, 0xe7, 0x64, 0x8b, 0xf5, 0xfa, 0x14, 0x73, 0x3e,
+            0x87, 0x98, 0x52, 0xdb, 0xe3, 0x01,
+        ];
+
+        let mut page = [0u8; TDX_PAGE_SIZE];
+        page[..400].copy_from_slice(&b"SVSM".repeat(100));
+
+        let mut mrtd = TdxMrtd::default();
+        // A partially filled page, an unmeasured page and a zero page
+        mrtd.page_add(0xffff_f000);
+        mrtd.mr_extend(0xffff_f000, &page);
+        mrtd.page_add(0xffff_e000);
+        mrtd.page_add(0x10_0000);
+        mrtd.mr_extend(0x10_0000, &[0u8; TDX_PAGE_SIZE]);
+        assert_eq!(mrtd.finalize(), EXPECTED);
+    }
+}
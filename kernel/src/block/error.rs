@@ -8,4 +8,10 @@
 pub enum BlockDeviceError {
     /// Generic error for all read and write operations on a block device.
     Failed,
+    /// The device is too small for the requested data.
+    NoSpace,
+    /// Data stored on the device failed the integrity check.
+    Corrupted,
+    /// Data stored on the device is older than expected.
+    Rollback,
 }
@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Encrypted and integrity-protected storage of a data blob on a block
+//! device.
+//!
+//! The device is split into two slots which both can hold a complete copy
+//! of the data. Every slot starts with a [`SealedHeader`], followed by the
+//! data encrypted with AES-256-GCM. The header is used as additional
+//! authenticated data, so it can not be modified without being noticed.
+//!
+//! Each update increments a generation counter and writes the data to slot
+//! `generation % 2`. An interrupted write therefore never destroys the last
+//! good copy. Loading picks the authentic copy with the highest generation
+//! and detects rollbacks when the generations of the two slots do not
+//! belong together, or when the data is older than a generation the caller
+//! knows about.
+
+extern crate alloc;
+
+use super::api::BlockDriver;
+use super::BlockDeviceError;
+use crate::crypto::aead::{Aes256Gcm, Aes256GcmTrait, AUTHTAG_SIZE, IV_SIZE, KEY_SIZE};
+use crate::crypto::rng::fill_random;
+use crate::error::SvsmError;
+use crate::types::PAGE_SIZE;
+use crate::utils::align_up;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+const SEALED_MAGIC: [u8; 8] = *b"SVSMSEAL";
+const SEALED_VERSION: u32 = 1;
+const SEALED_SLOTS: usize = 2;
+
+/// Header at the beginning of every slot.
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct SealedHeader {
+    magic: [u8; 8],
+    version: u32,
+    /// Size of the unencrypted data
+    size: u32,
+    /// Incremented on every update of the data
+    generation: u64,
+    iv: [u8; IV_SIZE],
+    reserved: [u8; 4],
+}
+
+const HEADER_SIZE: usize = size_of::<SealedHeader>();
+
+/// Data blob stored encrypted on a block device.
+pub struct SealedStore {
+    dev: Box<dyn BlockDriver + Send>,
+    key: [u8; KEY_SIZE],
+    /// Maximum size of the data
+    capacity: usize,
+    slot_size: usize,
+    /// Generation of the most recent data on the device
+    generation: u64,
+}
+
+impl core::fmt::Debug for SealedStore {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SealedStore")
+            .field("capacity", &self.capacity)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl SealedStore {
+    /// Creates a store for data of up to `capacity` bytes on a block device.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The block device holding the data.
+    /// * `key` - The AES-256-GCM key used to seal the data. It should be
+    ///   derived for this single purpose.
+    /// * `capacity` - Maximum size of the data.
+    ///
+    /// # Returns
+    ///
+    /// The new [`SealedStore`], or [`BlockDeviceError::NoSpace`] when the
+    /// device is too small.
+    pub fn new(
+        dev: Box<dyn BlockDriver + Send>,
+        key: &[u8; KEY_SIZE],
+        capacity: usize,
+    ) -> Result<Self, SvsmError> {
+        let slot_size = align_up(HEADER_SIZE + capacity + AUTHTAG_SIZE, PAGE_SIZE);
+        if capacity > u32::MAX as usize || dev.size() < slot_size * SEALED_SLOTS {
+            return Err(BlockDeviceError::NoSpace.into());
+        }
+
+        Ok(Self {
+            dev,
+            key: *key,
+            capacity,
+            slot_size,
+            generation: 0,
+        })
+    }
+
+    /// Returns the generation of the most recent data loaded or stored.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn slot_block(&self, slot: usize) -> usize {
+        (slot * self.slot_size) >> self.dev.block_size_log2()
+    }
+
+    /// Reads and decrypts the data in a slot. Returns `None` when the slot
+    /// was never written.
+    fn read_slot(&self, slot: usize) -> Result<Option<(u64, Vec<u8>)>, SvsmError> {
+        let mut buf = vec![0u8; self.slot_size];
+        self.dev.read_blocks(self.slot_block(slot), &mut buf)?;
+
+        let (header, payload) =
+            SealedHeader::read_from_prefix(&buf).map_err(|_| BlockDeviceError::Corrupted)?;
+        if header.magic != SEALED_MAGIC {
+            return Ok(None);
+        }
+
+        let size = header.size as usize;
+        if header.version != SEALED_VERSION
+            || size > self.capacity
+            || header.generation as usize % SEALED_SLOTS != slot
+        {
+            return Err(BlockDeviceError::Corrupted.into());
+        }
+
+        let mut data = vec![0u8; size];
+        Aes256Gcm::decrypt(
+            &header.iv,
+            &self.key,
+            header.as_bytes(),
+            &payload[..size + AUTHTAG_SIZE],
+            &mut data,
+        )
+        .map_err(|_| BlockDeviceError::Corrupted)?;
+
+        Ok(Some((header.generation, data)))
+    }
+
+    /// Loads the most recent data from the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_generation` - Lowest acceptable generation of the data, or 0
+    ///   when the caller has no trusted record of the last generation.
+    ///
+    /// # Returns
+    ///
+    /// The data, or `None` when the device holds no data yet. Fails with
+    /// [`BlockDeviceError::Corrupted`] when no slot passes the integrity
+    /// check, and with [`BlockDeviceError::Rollback`] when an older version
+    /// of the data was restored on the device.
+    pub fn load(&mut self, min_generation: u64) -> Result<Option<Vec<u8>>, SvsmError> {
+        let mut slots: Vec<(u64, Vec<u8>)> = Vec::new();
+        let mut corrupted = false;
+
+        for slot in 0..SEALED_SLOTS {
+            match self.read_slot(slot) {
+                Ok(Some(entry)) => slots.push(entry),
+                Ok(None) => {}
+                // An interrupted update leaves a slot which fails the
+                // integrity check. This is fine as long as the other slot
+                // is intact.
+                Err(SvsmError::Block(BlockDeviceError::Corrupted)) => corrupted = true,
+                Err(e) => return Err(e),
+            }
+        }
+
+        slots.sort_by_key(|(generation, _)| *generation);
+        let Some((generation, data)) = slots.pop() else {
+            if corrupted {
+                return Err(BlockDeviceError::Corrupted.into());
+            }
+            if min_generation > 0 {
+                return Err(BlockDeviceError::Rollback.into());
+            }
+            self.generation = 0;
+            return Ok(None);
+        };
+
+        // Both slots are written alternately, so intact slots always hold
+        // consecutive generations.
+        if slots.first().is_some_and(|(g, _)| g + 1 != generation) || generation < min_generation {
+            return Err(BlockDeviceError::Rollback.into());
+        }
+
+        self.generation = generation;
+        Ok(Some(data))
+    }
+
+    /// Encrypts and stores new data on the device. The slot holding the
+    /// most recent data is left untouched.
+    ///
+    /// # Returns
+    ///
+    /// `()` on success, [`SvsmError`] on failure.
+    pub fn store(&mut self, data: &[u8]) -> Result<(), SvsmError> {
+        if data.len() > self.capacity {
+            return Err(BlockDeviceError::NoSpace.into());
+        }
+
+        let generation = self.generation.checked_add(1).ok_or(SvsmError::Crypto)?;
+        let mut header = SealedHeader {
+            magic: SEALED_MAGIC,
+            version: SEALED_VERSION,
+            size: data.len() as u32,
+            generation,
+            iv: [0; IV_SIZE],
+            reserved: [0; 4],
+        };
+        fill_random(&mut header.iv)?;
+
+        let mut buf = vec![0u8; self.slot_size];
+        buf[..HEADER_SIZE].copy_from_slice(header.as_bytes());
+        Aes256Gcm::encrypt(
+            &header.iv,
+            &self.key,
+            header.as_bytes(),
+            data,
+            &mut buf[HEADER_SIZE..],
+        )
+        .map_err(|_| SvsmError::Crypto)?;
+
+        let slot = generation as usize % SEALED_SLOTS;
+        self.dev.write_blocks(self.slot_block(slot), &buf)?;
+        self.dev.flush()?;
+
+        self.generation = generation;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locking::SpinLock;
+    use alloc::sync::Arc;
+
+    const BLOCK_SIZE_LOG2: u8 = 9;
+
+    #[derive(Debug)]
+    struct RamDisk(Arc<SpinLock<Vec<u8>>>);
+
+    impl BlockDriver for RamDisk {
+        fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> Result<(), SvsmError> {
+            let start = block_id << BLOCK_SIZE_LOG2;
+            buf.copy_from_slice(&self.0.lock()[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_blocks(&self, block_id: usize, buf: &[u8]) -> Result<(), SvsmError> {
+            let start = block_id << BLOCK_SIZE_LOG2;
+            self.0.lock()[start..start + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn block_size_log2(&self) -> u8 {
+            BLOCK_SIZE_LOG2
+        }
+
+        fn size(&self) -> usize {
+            self.0.lock().len()
+        }
+
+        fn flush(&self) -> Result<(), SvsmError> {
+            Ok(())
+        }
+    }
+
+    const KEY: [u8; KEY_SIZE] = [0x42; KEY_SIZE];
+    const CAPACITY: usize = 1000;
+
+    fn new_disk() -> Arc<SpinLock<Vec<u8>>> {
+        Arc::new(SpinLock::new(vec![0u8; 2 * PAGE_SIZE]))
+    }
+
+    fn open(disk: &Arc<SpinLock<Vec<u8>>>, key: &[u8; KEY_SIZE]) -> SealedStore {
+        SealedStore::new(Box::new(RamDisk(disk.clone())), key, CAPACITY).unwrap()
+    }
+
+    #[test]
+    fn test_sealed_store_roundtrip() {
+        let disk = new_disk();
+        let mut store = open(&disk, &KEY);
+        assert_eq!(store.load(0).unwrap(), None);
+
+        store.store(b"first").unwrap();
+        store.store(b"second").unwrap();
+        assert_eq!(store.generation(), 2);
+        assert!(!disk.lock().windows(6).any(|w| w == b"second"));
+
+        let mut store = open(&disk, &KEY);
+        assert_eq!(store.load(0).unwrap().as_deref(), Some(&b"second"[..]));
+        assert_eq!(store.generation(), 2);
+
+        let mut store = open(&disk, &[0x43; KEY_SIZE]);
+        assert!(matches!(
+            store.load(0),
+            Err(SvsmError::Block(BlockDeviceError::Corrupted))
+        ));
+
+        assert!(matches!(
+            SealedStore::new(Box::new(RamDisk(disk)), &KEY, 2 * PAGE_SIZE),
+            Err(SvsmError::Block(BlockDeviceError::NoSpace))
+        ));
+    }
+
+    #[test]
+    fn test_sealed_store_torn_write() {
+        let disk = new_disk();
+        let mut store = open(&disk, &KEY);
+        store.store(b"first").unwrap();
+        store.store(b"second").unwrap();
+
+        // Corrupt the slot with generation 2
+        disk.lock()[HEADER_SIZE] ^= 1;
+        let mut store = open(&disk, &KEY);
+        assert_eq!(store.load(0).unwrap().as_deref(), Some(&b"first"[..]));
+
+        // The next update overwrites the corrupted slot
+        store.store(b"third").unwrap();
+        let mut store = open(&disk, &KEY);
+        assert_eq!(store.load(0).unwrap().as_deref(), Some(&b"third"[..]));
+    }
+
+    #[test]
+    fn test_sealed_store_rollback() {
+        let disk = new_disk();
+        let mut store = open(&disk, &KEY);
+        store.store(b"first").unwrap();
+        let old = disk.lock().clone();
+        store.store(b"second").unwrap();
+        store.store(b"third").unwrap();
+        store.store(b"fourth").unwrap();
+
+        // Restore generation 1 next to generation 4
+        disk.lock()[PAGE_SIZE..].copy_from_slice(&old[PAGE_SIZE..]);
+        let mut store = open(&disk, &KEY);
+        assert!(matches!(
+            store.load(0),
+            Err(SvsmError::Block(BlockDeviceError::Rollback))
+        ));
+
+        // Restore the complete device at generation 1
+        disk.lock().copy_from_slice(&old);
+        let mut store = open(&disk, &KEY);
+        assert_eq!(store.load(1).unwrap().as_deref(), Some(&b"first"[..]));
+        assert!(matches!(
+            store.load(4),
+            Err(SvsmError::Block(BlockDeviceError::Rollback))
+        ));
+    }
+}
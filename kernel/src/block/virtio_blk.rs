@@ -6,11 +6,11 @@
 // Author: Oliver Steffen <osteffen@redhat.com>
 
 use super::api::BlockDriver;
-use crate::address::PhysAddr;
 use crate::block::BlockDeviceError;
 use crate::error::SvsmError;
 use crate::types::PAGE_SIZE;
 use crate::virtio::devices::VirtIOBlkDevice;
+use crate::virtio::VirtioDeviceAddress;
 use virtio_drivers::device::blk::SECTOR_SIZE;
 extern crate alloc;
 use alloc::boxed::Box;
@@ -23,13 +23,14 @@ impl core::fmt::Debug for VirtIOBlkDriver {
 }
 
 impl VirtIOBlkDriver {
-    pub fn new(mmio_base: PhysAddr) -> Result<Self, SvsmError> {
-        Ok(VirtIOBlkDriver(VirtIOBlkDevice::new(mmio_base)?))
+    pub fn new(address: VirtioDeviceAddress) -> Result<Self, SvsmError> {
+        Ok(VirtIOBlkDriver(VirtIOBlkDevice::new(address)?))
     }
 }
 
 impl BlockDriver for VirtIOBlkDriver {
     fn read_blocks(&self, block_id: usize, buf: &mut [u8]) -> Result<(), SvsmError> {
+        self.0.check()?;
         self.0.device.locked_do(|dev| {
             buf.chunks_mut(PAGE_SIZE)
                 .zip((block_id..).step_by(PAGE_SIZE / SECTOR_SIZE))
@@ -41,6 +42,7 @@ impl BlockDriver for VirtIOBlkDriver {
     }
 
     fn write_blocks(&self, block_id: usize, buf: &[u8]) -> Result<(), SvsmError> {
+        self.0.check()?;
         self.0.device.locked_do(|dev| {
             buf.chunks(PAGE_SIZE)
                 .zip((block_id..).step_by(PAGE_SIZE / SECTOR_SIZE))
@@ -60,6 +62,7 @@ impl BlockDriver for VirtIOBlkDriver {
     }
 
     fn flush(&self) -> Result<(), SvsmError> {
+        self.0.check()?;
         self.0
             .device
             .lock()
@@ -70,25 +73,17 @@ impl BlockDriver for VirtIOBlkDriver {
 
 #[cfg(all(test, test_in_svsm))]
 mod tests {
-    use crate::{
-        address::PhysAddr, fw_cfg::FwCfg, platform::SVSM_PLATFORM, testutils::is_qemu_test_env,
-    };
+    use crate::{testutils::is_qemu_test_env, virtio::virtio_devices};
     use core::cmp::min;
     extern crate alloc;
     use super::*;
 
-    /// Find the first virtio-blk device in the hardware-info list
+    /// Find the first virtio-blk device
     fn get_blk_device() -> VirtIOBlkDriver {
-        let cfg = FwCfg::new(SVSM_PLATFORM.get_io_port());
-
-        let dev = cfg
-            .get_virtio_mmio_addresses()
-            .unwrap_or_default()
-            .iter()
-            .find_map(|a| VirtIOBlkDriver::new(PhysAddr::from(*a)).ok())
-            .expect("No virtio-blk device found");
-
-        dev
+        virtio_devices()
+            .into_iter()
+            .find_map(|a| VirtIOBlkDriver::new(a).ok())
+            .expect("No virtio-blk device found")
     }
 
     /// Get the sha256 sum of the disk image from the host (see `scripts/test-in-svsm.sh`)
@@ -18,6 +18,7 @@ use crate::platform::{parse_fw_meta_data, SevFWMetaData, SvsmPlatform};
 use crate::serial::SERIAL_PORT;
 use crate::utils::MemoryRegion;
 use alloc::vec::Vec;
+use bootlib::fs_archive::FsPublicKey;
 use cpuarch::vmsa::VMSA;
 
 fn check_ovmf_regions(
@@ -171,7 +172,8 @@ impl<'a> SvsmConfig<'a> {
         match &self.igvm_params {
             Some(igvm_params) => {
                 let flash_regions = igvm_params.get_fw_regions();
-                if !igvm_params.fw_in_low_memory() {
+                // Only OVMF images are placed into the flash range.
+                if !igvm_params.fw_in_low_memory() && igvm_params.linux_boot_params().is_none() {
                     check_ovmf_regions(&flash_regions, kernel_region);
                 }
                 flash_regions
@@ -196,6 +198,13 @@ impl<'a> SvsmConfig<'a> {
         }
     }
 
+    pub fn linux_boot_params(&self) -> Option<PhysAddr> {
+        match &self.igvm_params {
+            Some(igvm_params) => igvm_params.linux_boot_params(),
+            None => None,
+        }
+    }
+
     pub fn invalidate_boot_data(&self) -> bool {
         // Boot data should be invalidated if and only if IGVM parameters were
         // present.
@@ -222,4 +231,32 @@ impl<'a> SvsmConfig<'a> {
             None => true,
         }
     }
+
+    pub fn fs_public_key(&self) -> Option<FsPublicKey> {
+        match &self.igvm_params {
+            Some(igvm_params) => igvm_params.fs_public_key(),
+            None => None,
+        }
+    }
+
+    pub fn fs_min_version(&self) -> u64 {
+        match &self.igvm_params {
+            Some(igvm_params) => igvm_params.fs_min_version(),
+            None => 0,
+        }
+    }
+
+    pub fn vtpm_nv_min_generation(&self) -> u64 {
+        match &self.igvm_params {
+            Some(igvm_params) => igvm_params.vtpm_nv_min_generation(),
+            None => 0,
+        }
+    }
+
+    pub fn svsm_pci_ecam(&self) -> Option<PhysAddr> {
+        match &self.igvm_params {
+            Some(igvm_params) => igvm_params.svsm_pci_ecam(),
+            None => None,
+        }
+    }
 }
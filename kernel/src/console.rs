@@ -7,6 +7,7 @@
 use crate::error::SvsmError;
 use crate::io::IOPort;
 use crate::locking::SpinLock;
+use crate::log_buffer::log_buffer_record;
 use crate::serial::{SerialPort, Terminal, DEFAULT_SERIAL_PORT};
 use crate::utils::immut_after_init::{ImmutAfterInitCell, ImmutAfterInitResult};
 use core::fmt;
@@ -130,6 +131,8 @@ impl log::Log for ConsoleLogger {
                 ));
             }
         };
+
+        log_buffer_record(record);
     }
 
     fn flush(&self) {}
@@ -6,8 +6,9 @@
 
 use crate::address::VirtAddr;
 use crate::cpu::idt::common::INT_INJ_VECTOR;
+use crate::cpu::msr::rdtsc;
 use crate::cpu::percpu::{current_ghcb, this_cpu, PerCpuShared, PERCPU_AREAS};
-use crate::cpu::x86::apic_post_irq;
+use crate::cpu::x86::{apic_calibrate_tsc, apic_cycles_to_tsc, apic_post_irq, tsc_to_apic_cycles};
 use crate::error::ApicError::Emulation;
 use crate::error::SvsmError;
 use crate::mm::GuestPtr;
@@ -20,18 +21,85 @@ use bitfield_struct::bitfield;
 use core::sync::atomic::Ordering;
 
 const APIC_REGISTER_APIC_ID: u64 = 0x802;
+const APIC_REGISTER_VERSION: u64 = 0x803;
 const APIC_REGISTER_TPR: u64 = 0x808;
 const APIC_REGISTER_PPR: u64 = 0x80A;
 const APIC_REGISTER_EOI: u64 = 0x80B;
+const APIC_REGISTER_LDR: u64 = 0x80D;
+const APIC_REGISTER_SVR: u64 = 0x80F;
 const APIC_REGISTER_ISR_0: u64 = 0x810;
 const APIC_REGISTER_ISR_7: u64 = 0x817;
 const APIC_REGISTER_TMR_0: u64 = 0x818;
 const APIC_REGISTER_TMR_7: u64 = 0x81F;
 const APIC_REGISTER_IRR_0: u64 = 0x820;
 const APIC_REGISTER_IRR_7: u64 = 0x827;
+const APIC_REGISTER_ESR: u64 = 0x828;
 const APIC_REGISTER_ICR: u64 = 0x830;
+const APIC_REGISTER_LVT_TIMER: u64 = 0x832;
+const APIC_REGISTER_LVT_ERROR: u64 = 0x837;
+const APIC_REGISTER_TIMER_ICR: u64 = 0x838;
+const APIC_REGISTER_TIMER_CCR: u64 = 0x839;
+const APIC_REGISTER_TIMER_DCR: u64 = 0x83E;
 const APIC_REGISTER_SELF_IPI: u64 = 0x83F;
 
+// The TSC deadline MSR is not an APIC register, but it is accessed through
+// the same requests because it belongs to the APIC timer.
+const MSR_TSC_DEADLINE: u64 = 0x6E0;
+
+// Version 0x14 with six LVT entries, from the timer to the error LVT.
+const APIC_VERSION: u64 = 0x0005_0014;
+
+const APIC_SVR_WRITE_MASK: u32 = 0x1FF;
+const APIC_SVR_ENABLE: u32 = 1 << 8;
+
+const APIC_ESR_SEND_ILLEGAL_VECTOR: u32 = 1 << 5;
+const APIC_ESR_RECEIVE_ILLEGAL_VECTOR: u32 = 1 << 6;
+
+// Indexes into the LVT array, in the order of the LVT registers.
+const APIC_LVT_TIMER: usize = 0;
+const APIC_LVT_ERROR: usize = 5;
+const APIC_LVT_COUNT: usize = 6;
+
+const APIC_LVT_VECTOR_MASK: u32 = 0xFF;
+const APIC_LVT_MASKED: u32 = 1 << 16;
+const APIC_LVT_TIMER_MODE_SHIFT: u32 = 17;
+
+// Writable bits of the timer, thermal, performance counter, LINT0, LINT1
+// and error LVT registers.
+const APIC_LVT_WRITE_MASKS: [u32; APIC_LVT_COUNT] = [
+    0x0007_00FF,
+    0x0001_07FF,
+    0x0001_07FF,
+    0x0001_A7FF,
+    0x0001_A7FF,
+    0x0001_00FF,
+];
+
+const APIC_TIMER_DCR_MASK: u32 = 0xB;
+
+/// Vectors below 16 are reserved and cannot be delivered.
+const APIC_MIN_VECTOR: u8 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ApicTimerMode {
+    OneShot = 0,
+    Periodic = 1,
+    TscDeadline = 2,
+}
+
+impl ApicTimerMode {
+    /// Extracts the timer mode from an LVT timer register value, or `None`
+    /// for the reserved mode.
+    fn from_lvt(lvt: u32) -> Option<Self> {
+        match (lvt >> APIC_LVT_TIMER_MODE_SHIFT) & 3 {
+            0 => Some(Self::OneShot),
+            1 => Some(Self::Periodic),
+            2 => Some(Self::TscDeadline),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum IcrDestFmt {
     Dest = 0,
@@ -115,6 +183,17 @@ pub struct LocalApic {
     interrupt_queued: bool,
     lazy_eoi_pending: bool,
     nmi_pending: bool,
+    svr: u32,
+    esr: u32,
+    pending_esr: u32,
+    icr: u64,
+    lvt: [u32; APIC_LVT_COUNT],
+    timer_dcr: u32,
+    timer_icr: u32,
+    /// Timer period in TSC ticks, used in periodic mode
+    timer_period: u64,
+    /// TSC value at which the timer expires, or `None` if it is not armed
+    timer_deadline: Option<u64>,
 }
 
 impl LocalApic {
@@ -131,6 +210,15 @@ impl LocalApic {
             interrupt_queued: false,
             lazy_eoi_pending: false,
             nmi_pending: false,
+            svr: 0xFF,
+            esr: 0,
+            pending_esr: 0,
+            icr: 0,
+            lvt: [APIC_LVT_MASKED; APIC_LVT_COUNT],
+            timer_dcr: 0,
+            timer_icr: 0,
+            timer_period: 0,
+            timer_deadline: None,
         }
     }
 
@@ -292,6 +380,9 @@ impl LocalApic {
         // consumed.
         self.consume_host_interrupts();
 
+        // Post the timer interrupt if the timer expired.
+        self.check_timer();
+
         // Consume any pending IPIs.
         if cpu_shared.ipi_pending() {
             self.consume_pending_ipis(cpu_shared);
@@ -441,6 +532,164 @@ impl LocalApic {
         self.update_required = true;
     }
 
+    /// Posts an interrupt generated by the local APIC itself, e.g. from the
+    /// timer, recording an error if the vector cannot be delivered.
+    fn post_local_interrupt(&mut self, vector: u8) {
+        if vector < APIC_MIN_VECTOR {
+            self.signal_error(APIC_ESR_RECEIVE_ILLEGAL_VECTOR);
+        } else {
+            self.post_interrupt(vector, false);
+        }
+    }
+
+    /// Records an error in the ESR and posts the error interrupt if the
+    /// error LVT is not masked.
+    fn signal_error(&mut self, error: u32) {
+        self.pending_esr |= error;
+
+        let lvt = self.lvt[APIC_LVT_ERROR];
+        let vector = (lvt & APIC_LVT_VECTOR_MASK) as u8;
+        // An illegal error vector would only raise another error.
+        if lvt & APIC_LVT_MASKED == 0 && vector >= APIC_MIN_VECTOR {
+            self.post_interrupt(vector, false);
+        }
+    }
+
+    fn timer_mode(&self) -> ApicTimerMode {
+        // The reserved mode is never written to the LVT.
+        ApicTimerMode::from_lvt(self.lvt[APIC_LVT_TIMER]).unwrap()
+    }
+
+    /// Returns the divisor of the APIC bus frequency configured for the
+    /// timer.
+    fn timer_divisor(&self) -> u64 {
+        let value = ((self.timer_dcr & 8) >> 1) | (self.timer_dcr & 3);
+        if value == 7 {
+            1
+        } else {
+            2 << value
+        }
+    }
+
+    /// Checks whether the timer expired and posts the timer interrupt if so.
+    /// In periodic mode the timer is rearmed for the next period, otherwise
+    /// it is disarmed.
+    fn check_timer(&mut self) {
+        let Some(deadline) = self.timer_deadline else {
+            return;
+        };
+        let now = rdtsc();
+        if now < deadline {
+            return;
+        }
+
+        let lvt = self.lvt[APIC_LVT_TIMER];
+        if lvt & APIC_LVT_MASKED == 0 {
+            self.post_local_interrupt((lvt & APIC_LVT_VECTOR_MASK) as u8);
+        }
+
+        self.timer_deadline = match self.timer_mode() {
+            ApicTimerMode::Periodic => {
+                // Expiries missed in between are coalesced into one
+                // interrupt.
+                let late = (now - deadline) % self.timer_period;
+                Some(now + self.timer_period - late)
+            }
+            _ => None,
+        };
+    }
+
+    /// Returns the TSC value at which the timer interrupt needs to be
+    /// posted, or `None` if the timer is not armed or its LVT is masked.
+    pub fn timer_deadline(&self) -> Option<u64> {
+        self.timer_deadline
+            .filter(|_| self.lvt[APIC_LVT_TIMER] & APIC_LVT_MASKED == 0)
+    }
+
+    fn timer_current_count(&mut self) -> u64 {
+        self.check_timer();
+        if self.timer_mode() == ApicTimerMode::TscDeadline {
+            return 0;
+        }
+        let Some(deadline) = self.timer_deadline else {
+            return 0;
+        };
+
+        let cycles = tsc_to_apic_cycles(deadline.saturating_sub(rdtsc())).unwrap_or(0);
+        (cycles / self.timer_divisor()).min(self.timer_icr.into())
+    }
+
+    fn write_timer_icr(&mut self, value: u64) -> Result<(), SvsmError> {
+        let count = u32::try_from(value).map_err(|_| Emulation)?;
+        // The initial count is ignored in TSC-deadline mode.
+        if self.timer_mode() == ApicTimerMode::TscDeadline {
+            return Ok(());
+        }
+
+        self.timer_icr = count;
+        self.timer_deadline = None;
+        if count != 0 {
+            // The timer counts in APIC bus cycles, which are converted into
+            // TSC ticks measured against the APIC timer of the SVSM. The
+            // timer can only be emulated when the SVSM uses its own timer.
+            apic_calibrate_tsc().ok_or(Emulation)?;
+            let cycles = u64::from(count) * self.timer_divisor();
+            self.timer_period = apic_cycles_to_tsc(cycles).ok_or(Emulation)?.max(1);
+            self.timer_deadline = Some(rdtsc().saturating_add(self.timer_period));
+        }
+        Ok(())
+    }
+
+    fn write_tsc_deadline(&mut self, value: u64) {
+        // The TSC deadline is ignored unless the timer is in TSC-deadline
+        // mode. A value of zero disarms the timer.
+        if self.timer_mode() == ApicTimerMode::TscDeadline {
+            self.timer_deadline = (value != 0).then_some(value);
+        }
+    }
+
+    fn write_lvt(&mut self, index: usize, value: u64) -> Result<(), SvsmError> {
+        let mut lvt = u32::try_from(value).map_err(|_| Emulation)? & APIC_LVT_WRITE_MASKS[index];
+        // All LVT entries stay masked while the APIC is software-disabled.
+        if self.svr & APIC_SVR_ENABLE == 0 {
+            lvt |= APIC_LVT_MASKED;
+        }
+
+        if index == APIC_LVT_TIMER {
+            let mode = ApicTimerMode::from_lvt(lvt).ok_or(Emulation)?;
+            // Switching from or to TSC-deadline mode disarms the timer.
+            let old_mode = self.timer_mode();
+            if (mode == ApicTimerMode::TscDeadline) != (old_mode == ApicTimerMode::TscDeadline) {
+                self.timer_icr = 0;
+                self.timer_deadline = None;
+            }
+        }
+
+        self.lvt[index] = lvt;
+        Ok(())
+    }
+
+    fn write_svr(&mut self, value: u64) -> Result<(), SvsmError> {
+        self.svr = u32::try_from(value).map_err(|_| Emulation)? & APIC_SVR_WRITE_MASK;
+        // Software-disabling the APIC masks all LVT entries.
+        if self.svr & APIC_SVR_ENABLE == 0 {
+            for lvt in self.lvt.iter_mut() {
+                *lvt |= APIC_LVT_MASKED;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_esr(&mut self, value: u64) -> Result<(), SvsmError> {
+        // In x2APIC mode the ESR must be written with zero, which latches
+        // the errors recorded since the last write.
+        if value != 0 {
+            return Err(SvsmError::Apic(Emulation));
+        }
+        self.esr = core::mem::take(&mut self.pending_esr);
+        Ok(())
+    }
+
     fn post_icr_interrupt(&mut self, icr: ApicIcr) {
         if icr.message_type() == IcrMessageType::Nmi {
             self.nmi_pending = true;
@@ -593,8 +842,31 @@ impl LocalApic {
         // read.
         self.check_delivered_interrupts(cpu_state, caa_addr);
 
+        // Make an expired timer visible in the IRR.
+        self.check_timer();
+
         match register {
             APIC_REGISTER_APIC_ID => Ok(u64::from(cpu_shared.apic_id())),
+            APIC_REGISTER_VERSION => Ok(APIC_VERSION),
+            APIC_REGISTER_LDR => {
+                // The logical x2APIC ID is derived from the APIC ID.
+                let apic_id = cpu_shared.apic_id();
+                Ok(u64::from(((apic_id >> 4) << 16) | (1 << (apic_id & 0xF))))
+            }
+            APIC_REGISTER_SVR => Ok(self.svr.into()),
+            APIC_REGISTER_ESR => Ok(self.esr.into()),
+            APIC_REGISTER_ICR => Ok(self.icr),
+            APIC_REGISTER_LVT_TIMER..=APIC_REGISTER_LVT_ERROR => {
+                let index = usize::try_from(register - APIC_REGISTER_LVT_TIMER).unwrap();
+                Ok(self.lvt[index].into())
+            }
+            APIC_REGISTER_TIMER_ICR => Ok(self.timer_icr.into()),
+            APIC_REGISTER_TIMER_CCR => Ok(self.timer_current_count()),
+            APIC_REGISTER_TIMER_DCR => Ok(self.timer_dcr.into()),
+            MSR_TSC_DEADLINE => Ok(match self.timer_mode() {
+                ApicTimerMode::TscDeadline => self.timer_deadline.unwrap_or(0),
+                _ => 0,
+            }),
             APIC_REGISTER_IRR_0..=APIC_REGISTER_IRR_7 => {
                 let offset = register - APIC_REGISTER_IRR_0;
                 let index: usize = offset.try_into().unwrap();
@@ -632,6 +904,12 @@ impl LocalApic {
             return Err(SvsmError::Apic(Emulation));
         }
 
+        self.icr = value;
+        if icr.message_type() == IcrMessageType::Fixed && icr.vector() < APIC_MIN_VECTOR {
+            self.signal_error(APIC_ESR_SEND_ILLEGAL_VECTOR);
+            return Ok(());
+        }
+
         self.send_ipi(icr);
 
         Ok(())
@@ -664,7 +942,27 @@ impl LocalApic {
             APIC_REGISTER_ICR => self.handle_icr_write(value),
             APIC_REGISTER_SELF_IPI => {
                 let vector = u8::try_from(value).map_err(|_| Emulation)?;
-                self.post_interrupt(vector, false);
+                if vector < APIC_MIN_VECTOR {
+                    self.signal_error(APIC_ESR_SEND_ILLEGAL_VECTOR);
+                } else {
+                    self.post_interrupt(vector, false);
+                }
+                Ok(())
+            }
+            APIC_REGISTER_SVR => self.write_svr(value),
+            APIC_REGISTER_ESR => self.write_esr(value),
+            APIC_REGISTER_LVT_TIMER..=APIC_REGISTER_LVT_ERROR => {
+                let index = usize::try_from(register - APIC_REGISTER_LVT_TIMER).unwrap();
+                self.write_lvt(index, value)
+            }
+            APIC_REGISTER_TIMER_ICR => self.write_timer_icr(value),
+            APIC_REGISTER_TIMER_DCR => {
+                let dcr = u32::try_from(value).map_err(|_| Emulation)?;
+                self.timer_dcr = dcr & APIC_TIMER_DCR_MASK;
+                Ok(())
+            }
+            MSR_TSC_DEADLINE => {
+                self.write_tsc_deadline(value);
                 Ok(())
             }
             _ => Err(SvsmError::Apic(Emulation)),
@@ -837,11 +1135,27 @@ impl LocalApic {
         }
     }
 
+    /// Checks whether APIC emulation can be handed off to the host. The
+    /// state of the emulated timer cannot be transferred to the host APIC,
+    /// so the handoff fails while the timer is armed. An expired timer is
+    /// posted to the IRR first so that its interrupt is handed off.
+    pub fn check_handoff(&mut self) -> Result<(), SvsmError> {
+        self.check_timer();
+        if self.timer_deadline.is_some() {
+            return Err(SvsmError::Apic(Emulation));
+        }
+        Ok(())
+    }
+
     pub fn disable_apic_emulation<T: GuestCpuState>(
         &mut self,
         cpu_state: &mut T,
         caa_addr: Option<VirtAddr>,
-    ) {
+    ) -> Result<(), SvsmError> {
+        // Refuse the handoff before any state is changed if the guest still
+        // relies on the emulated timer.
+        self.check_handoff()?;
+
         // Ensure that any previous interrupt delivery is complete.
         self.check_delivered_interrupts(cpu_state, caa_addr);
 
@@ -867,5 +1181,181 @@ impl LocalApic {
                 cpu_state.interrupts_enabled(),
             )
             .expect("Failed to disable alterate injection");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMER_VECTOR: u32 = 0x40;
+    const ERROR_VECTOR: u32 = 0x41;
+
+    fn enabled_apic() -> LocalApic {
+        let mut apic = LocalApic::new();
+        apic.write_svr(0x1FF).unwrap();
+        apic
+    }
+
+    fn timer_lvt(mode: ApicTimerMode) -> u64 {
+        u64::from(TIMER_VECTOR | ((mode as u32) << APIC_LVT_TIMER_MODE_SHIFT))
+    }
+
+    #[test]
+    fn test_apic_timer_divisor() {
+        let mut apic = LocalApic::new();
+        for (dcr, divisor) in [
+            (0x0, 2),
+            (0x1, 4),
+            (0x2, 8),
+            (0x3, 16),
+            (0x8, 32),
+            (0x9, 64),
+            (0xA, 128),
+            (0xB, 1),
+        ] {
+            apic.timer_dcr = dcr;
+            assert_eq!(apic.timer_divisor(), divisor);
+        }
+    }
+
+    #[test]
+    fn test_apic_lvt_write_masks() {
+        let mut apic = enabled_apic();
+        for (index, mask) in APIC_LVT_WRITE_MASKS.iter().enumerate() {
+            // Use a mode field that is valid for the timer LVT.
+            let value = !(3u32 << APIC_LVT_TIMER_MODE_SHIFT);
+            apic.write_lvt(index, value.into()).unwrap();
+            assert_eq!(apic.lvt[index], value & mask);
+        }
+
+        // Values beyond 32 bits and the reserved timer mode are rejected.
+        assert!(apic.write_lvt(APIC_LVT_ERROR, 1 << 32).is_err());
+        assert!(apic
+            .write_lvt(APIC_LVT_TIMER, 3 << APIC_LVT_TIMER_MODE_SHIFT)
+            .is_err());
+    }
+
+    #[test]
+    fn test_apic_lvt_masked_when_disabled() {
+        let mut apic = LocalApic::new();
+        apic.write_lvt(APIC_LVT_ERROR, ERROR_VECTOR.into()).unwrap();
+        assert_eq!(apic.lvt[APIC_LVT_ERROR], ERROR_VECTOR | APIC_LVT_MASKED);
+
+        apic.write_svr(0x1FF).unwrap();
+        apic.write_lvt(APIC_LVT_ERROR, ERROR_VECTOR.into()).unwrap();
+        assert_eq!(apic.lvt[APIC_LVT_ERROR], ERROR_VECTOR);
+
+        // Software-disabling the APIC masks all entries again.
+        apic.write_svr(0xFF).unwrap();
+        assert_eq!(apic.lvt[APIC_LVT_ERROR], ERROR_VECTOR | APIC_LVT_MASKED);
+    }
+
+    #[test]
+    fn test_apic_esr_latch() {
+        let mut apic = enabled_apic();
+        apic.write_lvt(APIC_LVT_ERROR, ERROR_VECTOR.into()).unwrap();
+
+        // A timer with an illegal vector records an error, which only
+        // becomes visible in the ESR after it is written.
+        apic.post_local_interrupt(APIC_MIN_VECTOR - 1);
+        assert_eq!(apic.esr, 0);
+        assert!(LocalApic::test_vector_register(
+            &apic.irr,
+            ERROR_VECTOR as u8
+        ));
+
+        apic.write_esr(0).unwrap();
+        assert_eq!(apic.esr, APIC_ESR_RECEIVE_ILLEGAL_VECTOR);
+
+        // The next write clears the errors latched before.
+        apic.write_esr(0).unwrap();
+        assert_eq!(apic.esr, 0);
+
+        // Only zero can be written to the ESR.
+        assert!(apic.write_esr(1).is_err());
+    }
+
+    #[test]
+    fn test_apic_periodic_rearm() {
+        let mut apic = enabled_apic();
+        apic.write_lvt(APIC_LVT_TIMER, timer_lvt(ApicTimerMode::Periodic))
+            .unwrap();
+
+        // Arm the timer with a deadline that expired several periods ago.
+        let period = 1_000_000;
+        apic.timer_period = period;
+        apic.timer_deadline = Some(rdtsc() - 3 * period - 1);
+
+        apic.check_timer();
+        let now = rdtsc();
+        assert!(LocalApic::test_vector_register(
+            &apic.irr,
+            TIMER_VECTOR as u8
+        ));
+        let deadline = apic.timer_deadline().unwrap();
+        assert!(deadline > now - period);
+        assert!(deadline <= now + period);
+
+        // A one-shot timer is disarmed after it expired.
+        apic.write_lvt(APIC_LVT_TIMER, timer_lvt(ApicTimerMode::OneShot))
+            .unwrap();
+        apic.timer_deadline = Some(rdtsc() - 1);
+        apic.check_timer();
+        assert_eq!(apic.timer_deadline(), None);
+    }
+
+    #[test]
+    fn test_apic_tsc_deadline_switch() {
+        let mut apic = enabled_apic();
+
+        // The TSC deadline is ignored outside of TSC-deadline mode.
+        apic.write_tsc_deadline(u64::MAX);
+        assert_eq!(apic.timer_deadline(), None);
+
+        // Switching to TSC-deadline mode disarms a running timer.
+        apic.timer_icr = 100;
+        apic.timer_deadline = Some(u64::MAX);
+        apic.write_lvt(APIC_LVT_TIMER, timer_lvt(ApicTimerMode::TscDeadline))
+            .unwrap();
+        assert_eq!(apic.timer_icr, 0);
+        assert_eq!(apic.timer_deadline(), None);
+
+        // The initial count is ignored in TSC-deadline mode.
+        apic.write_timer_icr(100).unwrap();
+        assert_eq!(apic.timer_icr, 0);
+
+        apic.write_tsc_deadline(u64::MAX);
+        assert_eq!(apic.timer_deadline(), Some(u64::MAX));
+        apic.write_tsc_deadline(0);
+        assert_eq!(apic.timer_deadline(), None);
+
+        // Switching back disarms the timer as well.
+        apic.write_tsc_deadline(u64::MAX);
+        apic.write_lvt(APIC_LVT_TIMER, timer_lvt(ApicTimerMode::Periodic))
+            .unwrap();
+        assert_eq!(apic.timer_deadline(), None);
+    }
+
+    #[test]
+    fn test_apic_handoff_with_timer() {
+        let mut apic = enabled_apic();
+        apic.write_lvt(APIC_LVT_TIMER, timer_lvt(ApicTimerMode::TscDeadline))
+            .unwrap();
+        assert!(apic.check_handoff().is_ok());
+
+        // An armed timer prevents the handoff.
+        apic.write_tsc_deadline(u64::MAX);
+        assert!(apic.check_handoff().is_err());
+
+        // An expired timer is posted and does not prevent the handoff.
+        apic.write_tsc_deadline(1);
+        assert!(apic.check_handoff().is_ok());
+        assert!(LocalApic::test_vector_register(
+            &apic.irr,
+            TIMER_VECTOR as u8
+        ));
     }
 }
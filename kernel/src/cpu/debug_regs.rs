@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Hardware breakpoints and watchpoints through the debug registers DR0-DR3,
+//! DR6 and DR7.
+//!
+//! The breakpoint configuration is global and programmed on all CPUs. A
+//! change is pushed to the other CPUs with an IPI where possible. CPUs which
+//! cannot be reached by an IPI pick up the configuration at their next task
+//! switch.
+
+use super::ipi::{ipi_available, send_multicast_ipi, IpiMessage, IpiTarget};
+use super::irq_state::raw_get_tpr;
+use super::percpu::this_cpu;
+use crate::address::{Address, VirtAddr};
+use crate::error::SvsmError;
+use crate::locking::SpinLockIrqSafe;
+use crate::types::TPR_SYNCH;
+use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of hardware breakpoints provided by the debug registers
+pub const HW_BREAKPOINT_COUNT: usize = 4;
+
+/// Value of DR6 with no debug condition reported
+const DR6_DEFAULT: u64 = 0xffff_0ff0;
+/// Value of DR7 with all breakpoints disabled
+const DR7_DEFAULT: u64 = 0x400;
+
+/// Condition which triggers a hardware breakpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugBreakpointKind {
+    /// Instruction execution
+    Execute,
+    /// Data writes
+    Write,
+    /// Data reads or writes. The debug registers cannot trap on reads only.
+    Access,
+}
+
+/// A hardware breakpoint or watchpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugBreakpoint {
+    addr: VirtAddr,
+    kind: DebugBreakpointKind,
+    len: usize,
+}
+
+impl DebugBreakpoint {
+    /// Creates a new hardware breakpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to watch, aligned to `len`.
+    /// * `kind` - The condition which triggers the breakpoint.
+    /// * `len` - Number of bytes to watch: 1, 2, 4 or 8. Must be 1 for
+    ///   [`DebugBreakpointKind::Execute`].
+    ///
+    /// # Returns
+    ///
+    /// The breakpoint, or [`SvsmError::NotSupported`] when the debug
+    /// registers cannot express it.
+    pub fn new(addr: VirtAddr, kind: DebugBreakpointKind, len: usize) -> Result<Self, SvsmError> {
+        let valid_len = match kind {
+            DebugBreakpointKind::Execute => len == 1,
+            _ => matches!(len, 1 | 2 | 4 | 8),
+        };
+        if !valid_len || !addr.is_aligned(len) {
+            return Err(SvsmError::NotSupported);
+        }
+        Ok(Self { addr, kind, len })
+    }
+
+    pub fn addr(&self) -> VirtAddr {
+        self.addr
+    }
+
+    pub fn kind(&self) -> DebugBreakpointKind {
+        self.kind
+    }
+
+    /// Returns the R/W and LEN fields of DR7 for this breakpoint.
+    fn dr7_control(&self) -> u64 {
+        let rw = match self.kind {
+            DebugBreakpointKind::Execute => 0b00,
+            DebugBreakpointKind::Write => 0b01,
+            DebugBreakpointKind::Access => 0b11,
+        };
+        let len = match self.len {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        rw | (len << 2)
+    }
+}
+
+/// The contents of the debug address registers and of DR7
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DebugRegs {
+    slots: [Option<DebugBreakpoint>; HW_BREAKPOINT_COUNT],
+}
+
+impl DebugRegs {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; HW_BREAKPOINT_COUNT],
+        }
+    }
+
+    /// Adds a breakpoint to a free debug address register.
+    ///
+    /// # Returns
+    ///
+    /// `true` on success, `false` when all debug address registers are in
+    /// use.
+    pub fn add(&mut self, bp: DebugBreakpoint) -> bool {
+        let Some(slot) = self.slots.iter_mut().find(|s| s.is_none()) else {
+            return false;
+        };
+        *slot = Some(bp);
+        true
+    }
+
+    /// Removes a breakpoint, returns `false` when it is not installed.
+    pub fn remove(&mut self, bp: &DebugBreakpoint) -> bool {
+        let Some(slot) = self.slots.iter_mut().find(|s| s.as_ref() == Some(bp)) else {
+            return false;
+        };
+        *slot = None;
+        true
+    }
+
+    /// Returns the breakpoint in debug address register `index`.
+    pub fn get(&self, index: usize) -> Option<DebugBreakpoint> {
+        self.slots.get(index).copied().flatten()
+    }
+
+    /// Computes the value of DR7 for the installed breakpoints.
+    pub fn dr7(&self) -> u64 {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.map(|bp| (i, bp)))
+            .fold(DR7_DEFAULT, |dr7, (i, bp)| {
+                // Use the global enable bits, the SVSM does not use hardware
+                // task switching.
+                dr7 | (1 << (i * 2 + 1)) | (bp.dr7_control() << (16 + i * 4))
+            })
+    }
+
+    /// Programs the debug registers of the current CPU.
+    fn load(&self) {
+        // Disable all breakpoints while the address registers change.
+        write_dr7(DR7_DEFAULT);
+        for (i, slot) in self.slots.iter().enumerate() {
+            write_dr(i, slot.map_or(0, |bp| bp.addr.bits() as u64));
+        }
+        write_dr7(self.dr7());
+    }
+}
+
+/// Global breakpoint configuration and its generation number
+static DEBUG_REGS: SpinLockIrqSafe<(u64, DebugRegs)> = SpinLockIrqSafe::new((0, DebugRegs::new()));
+static DEBUG_REGS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// IPI message asking a CPU to reload its debug registers
+#[derive(Clone, Copy, Debug)]
+struct DebugRegsSync;
+
+// SAFETY: The DebugRegsSync structure contains no references and can safely
+// rely on the default implementation of the IPI message copy routines.
+unsafe impl IpiMessage for DebugRegsSync {
+    fn invoke(&self) {
+        sync_debug_regs();
+    }
+}
+
+/// Installs a new breakpoint configuration on all CPUs.
+pub fn set_debug_regs(regs: &DebugRegs) {
+    {
+        let mut guard = DEBUG_REGS.lock();
+        guard.0 += 1;
+        guard.1 = *regs;
+        DEBUG_REGS_GENERATION.store(guard.0, Ordering::Release);
+    }
+    sync_debug_regs();
+
+    // Sending an IPI is only possible below TPR_SYNCH. Otherwise the other
+    // CPUs load the new configuration at their next task switch.
+    if ipi_available() && raw_get_tpr() < TPR_SYNCH {
+        send_multicast_ipi(IpiTarget::AllButSelf, &DebugRegsSync);
+    }
+}
+
+/// Loads the current breakpoint configuration into the debug registers of
+/// this CPU, unless they are already up to date.
+pub fn sync_debug_regs() {
+    let cpu = this_cpu();
+    if DEBUG_REGS_GENERATION.load(Ordering::Acquire) == cpu.debug_regs_generation() {
+        return;
+    }
+    let guard = DEBUG_REGS.lock();
+    guard.1.load();
+    cpu.set_debug_regs_generation(guard.0);
+}
+
+/// Returns the debug address register indices of the breakpoints which
+/// triggered the current debug exception and clears them in DR6.
+pub fn take_triggered_breakpoints() -> impl Iterator<Item = usize> {
+    let dr6 = read_dr6();
+    if dr6 & 0xf != 0 {
+        write_dr6(DR6_DEFAULT);
+    }
+    (0..HW_BREAKPOINT_COUNT).filter(move |i| dr6 & (1 << i) != 0)
+}
+
+fn write_dr(index: usize, value: u64) {
+    // SAFETY: writing the debug address registers does not affect memory
+    // safety.
+    unsafe {
+        match index {
+            0 => asm!("mov {0}, %dr0", in(reg) value, options(att_syntax)),
+            1 => asm!("mov {0}, %dr1", in(reg) value, options(att_syntax)),
+            2 => asm!("mov {0}, %dr2", in(reg) value, options(att_syntax)),
+            3 => asm!("mov {0}, %dr3", in(reg) value, options(att_syntax)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn read_dr6() -> u64 {
+    let out;
+    // SAFETY: reading DR6 does not affect memory safety.
+    unsafe { asm!("mov %dr6, {0}", out(reg) out, options(att_syntax)) };
+    out
+}
+
+fn write_dr6(value: u64) {
+    // SAFETY: writing DR6 does not affect memory safety.
+    unsafe { asm!("mov {0}, %dr6", in(reg) value, options(att_syntax)) };
+}
+
+fn write_dr7(value: u64) {
+    // SAFETY: writing DR7 does not affect memory safety, breakpoints only
+    // raise debug exceptions.
+    unsafe { asm!("mov {0}, %dr7", in(reg) value, options(att_syntax)) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_regs_dr7() {
+        let mut regs = DebugRegs::new();
+        assert_eq!(regs.dr7(), DR7_DEFAULT);
+
+        let exec =
+            DebugBreakpoint::new(VirtAddr::from(0x1000usize), DebugBreakpointKind::Execute, 1)
+                .unwrap();
+        let watch =
+            DebugBreakpoint::new(VirtAddr::from(0x2008usize), DebugBreakpointKind::Write, 8)
+                .unwrap();
+        assert!(regs.add(exec));
+        assert!(regs.add(watch));
+        assert_eq!(regs.dr7(), 0x400 | 0x2 | 0x8 | (0b1001 << 20));
+
+        assert!(regs.remove(&exec));
+        assert!(!regs.remove(&exec));
+        assert_eq!(regs.get(1), Some(watch));
+        assert_eq!(regs.dr7(), 0x400 | 0x8 | (0b1001 << 20));
+
+        let access =
+            DebugBreakpoint::new(VirtAddr::from(0x3004usize), DebugBreakpointKind::Access, 4)
+                .unwrap();
+        assert!(regs.add(access));
+        assert_eq!(regs.get(0), Some(access));
+        assert!(regs.add(exec));
+        assert!(regs.add(exec));
+        assert!(!regs.add(exec));
+    }
+
+    #[test]
+    fn test_debug_breakpoint_validation() {
+        let addr = VirtAddr::from(0x1002usize);
+        assert!(DebugBreakpoint::new(addr, DebugBreakpointKind::Execute, 2).is_err());
+        assert!(DebugBreakpoint::new(addr, DebugBreakpointKind::Write, 4).is_err());
+        assert!(DebugBreakpoint::new(addr, DebugBreakpointKind::Write, 3).is_err());
+        assert!(DebugBreakpoint::new(addr, DebugBreakpointKind::Access, 2).is_ok());
+    }
+}
@@ -6,13 +6,14 @@
 
 extern crate alloc;
 
-use crate::address::{Address, VirtAddr};
+use crate::address::{Address, PhysAddr, VirtAddr};
 use crate::cpu::control_regs::{read_cr0, read_cr4};
 use crate::cpu::efer::read_efer;
 use crate::cpu::gdt::GLOBAL_GDT;
 use crate::cpu::registers::{X86GeneralRegs, X86InterruptFrame};
 use crate::cpu::shadow_stack::is_cet_ss_supported;
 use crate::insn_decode::{InsnError, InsnMachineCtx, InsnMachineMem, Register, SegRegister};
+use crate::mm::pagetable::PageTable;
 use crate::mm::{GuestPtr, PageBox};
 use crate::platform::SVSM_PLATFORM;
 use crate::types::{Bytes, SVSM_CS};
@@ -46,6 +47,7 @@ pub const VC_VECTOR: usize = 29;
 pub const SX_VECTOR: usize = 30;
 
 pub const INT_INJ_VECTOR: usize = 0x50;
+pub const TIMER_VECTOR: usize = 0xD0;
 pub const IPI_VECTOR: usize = 0xE0;
 
 bitflags::bitflags! {
@@ -120,7 +122,7 @@ impl InsnMachineCtx for X86ExceptionContext {
             Register::Rax => self.regs.rax,
             Register::Rdx => self.regs.rdx,
             Register::Rcx => self.regs.rcx,
-            Register::Rbx => self.regs.rdx,
+            Register::Rbx => self.regs.rbx,
             Register::Rsp => self.frame.rsp,
             Register::Rbp => self.regs.rbp,
             Register::Rdi => self.regs.rdi,
@@ -146,7 +148,7 @@ impl InsnMachineCtx for X86ExceptionContext {
             Register::Rax => self.regs.rax = val,
             Register::Rdx => self.regs.rdx = val,
             Register::Rcx => self.regs.rcx = val,
-            Register::Rbx => self.regs.rdx = val,
+            Register::Rbx => self.regs.rbx = val,
             Register::Rsp => self.frame.rsp = val,
             Register::Rbp => self.regs.rbp = val,
             Register::Rdi => self.regs.rdi = val,
@@ -206,6 +208,59 @@ impl InsnMachineCtx for X86ExceptionContext {
         }
         Ok(())
     }
+
+    fn translate_linear_addr(
+        &self,
+        la: usize,
+        _write: bool,
+        _fetch: bool,
+    ) -> Result<(usize, bool), InsnError> {
+        // MMIO is only emulated for accesses of the SVSM kernel itself.
+        if user_mode(self) {
+            return Err(InsnError::TranslateLinearAddr);
+        }
+        PageTable::virt_to_frame_shared(VirtAddr::from(la))
+            .map(|(frame, shared)| (frame.address().bits(), shared))
+            .ok_or(InsnError::TranslateLinearAddr)
+    }
+
+    fn handle_mmio_read(&self, pa: usize, shared: bool, size: Bytes) -> Result<u64, InsnError> {
+        // Private memory is never emulated by the host.
+        if !shared {
+            return Err(InsnError::HandleMmioRead);
+        }
+        let mut data = [0u8; 8];
+        let buf = data
+            .get_mut(..size as usize)
+            .ok_or(InsnError::HandleMmioRead)?;
+        // SAFETY: `pa` is the shared address accessed by the instruction
+        // which raised the exception, with the access size of the
+        // instruction.
+        unsafe { SVSM_PLATFORM.mmio_read(PhysAddr::from(pa), buf) }
+            .map_err(|_| InsnError::HandleMmioRead)?;
+        Ok(u64::from_le_bytes(data))
+    }
+
+    fn handle_mmio_write(
+        &mut self,
+        pa: usize,
+        shared: bool,
+        size: Bytes,
+        data: u64,
+    ) -> Result<(), InsnError> {
+        if !shared {
+            return Err(InsnError::HandleMmioWrite);
+        }
+        let data = data.to_le_bytes();
+        let buf = data
+            .get(..size as usize)
+            .ok_or(InsnError::HandleMmioWrite)?;
+        // SAFETY: `pa` is the shared address accessed by the instruction
+        // which raised the exception, with the access size of the
+        // instruction.
+        unsafe { SVSM_PLATFORM.mmio_write(PhysAddr::from(pa), buf) }
+            .map_err(|_| InsnError::HandleMmioWrite)
+    }
 }
 
 pub fn user_mode(ctxt: &X86ExceptionContext) -> bool {
@@ -14,7 +14,7 @@ use super::common::{
     user_mode, IdtEntry, IdtEventType, PageFaultError, AC_VECTOR, BP_VECTOR, BR_VECTOR, CP_VECTOR,
     DB_VECTOR, DE_VECTOR, DF_VECTOR, GP_VECTOR, HV_VECTOR, IDT, INT_INJ_VECTOR, IPI_VECTOR,
     MCE_VECTOR, MF_VECTOR, NMI_VECTOR, NM_VECTOR, NP_VECTOR, OF_VECTOR, PF_VECTOR, SS_VECTOR,
-    SX_VECTOR, TS_VECTOR, UD_VECTOR, VC_VECTOR, VE_VECTOR, XF_VECTOR,
+    SX_VECTOR, TIMER_VECTOR, TS_VECTOR, UD_VECTOR, VC_VECTOR, VE_VECTOR, XF_VECTOR,
 };
 use crate::address::VirtAddr;
 use crate::cpu::irq_state::{raw_get_tpr, raw_set_tpr, tpr_from_vector};
@@ -76,6 +76,7 @@ extern "C" {
     fn asm_entry_int80();
     fn asm_entry_irq_int_inj();
     fn asm_entry_irq_ipi();
+    fn asm_entry_irq_timer();
 
     pub static mut HV_DOORBELL_ADDR: usize;
 }
@@ -141,6 +142,7 @@ pub fn idt_init() -> Result<(), SvsmError> {
     idt.set_entry(INT_INJ_VECTOR, IdtEntry::entry(asm_entry_irq_int_inj));
     idt.set_entry(0x80, IdtEntry::user_entry(asm_entry_int80));
     idt.set_entry(IPI_VECTOR, IdtEntry::entry(asm_entry_irq_ipi));
+    idt.set_entry(TIMER_VECTOR, IdtEntry::entry(asm_entry_irq_timer));
 
     // Set IST vectors
     init_ist_vectors(&mut idt);
@@ -391,8 +393,27 @@ extern "C" fn ex_handler_system_call(
     ctxt.regs.rax = match input {
         // Class 0 SysCalls.
         SYS_EXIT => sys_exit(ctxt.regs.rdi as u32),
-        SYS_EXEC => sys_exec(ctxt.regs.rdi, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_THREAD_CREATE => sys_thread_create(ctxt.regs.rdi, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_THREAD_JOIN => sys_thread_join(ctxt.regs.rdi as u32),
+        SYS_THREAD_DETACH => sys_thread_detach(ctxt.regs.rdi as u32),
+        SYS_WAIT => sys_wait(ctxt.regs.rdi as u32, ctxt.regs.rsi),
+        SYS_EXEC => sys_exec(
+            ctxt.regs.rdi,
+            ctxt.regs.rsi,
+            ctxt.regs.r8,
+            ctxt.regs.r9,
+            ctxt.regs.r10,
+        ),
         SYS_CLOSE => sys_close(ctxt.regs.rdi as u32),
+        SYS_MMAP => sys_mmap(
+            ctxt.regs.rdi as u32,
+            ctxt.regs.rsi,
+            ctxt.regs.r8,
+            ctxt.regs.r9,
+            ctxt.regs.r10,
+        ),
+        SYS_MUNMAP => sys_munmap(ctxt.regs.rdi),
+        SYS_MPROTECT => sys_mprotect(ctxt.regs.rdi, ctxt.regs.rsi),
         // Class 1 SysCalls.
         SYS_OPEN => sys_open(ctxt.regs.rdi, ctxt.regs.rsi, ctxt.regs.r8),
         SYS_READ => sys_read(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
@@ -404,8 +425,27 @@ extern "C" fn ex_handler_system_call(
         SYS_READDIR => sys_readdir(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
         SYS_MKDIR => sys_mkdir(ctxt.regs.rdi),
         SYS_RMDIR => sys_rmdir(ctxt.regs.rdi),
+        // Class 2 SysCalls.
+        SYS_CHANNEL_CREATE => sys_channel_create(ctxt.regs.rdi),
+        SYS_CHANNEL_SEND => sys_channel_send(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_CHANNEL_RECV => sys_channel_recv(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_PROTOCOL_REGISTER => {
+            sys_protocol_register(ctxt.regs.rdi as u32, ctxt.regs.rsi as u32, ctxt.regs.r8)
+        }
+        SYS_VSOCK_CONNECT => sys_vsock_connect(ctxt.regs.rdi as u64, ctxt.regs.rsi as u32),
+        SYS_VSOCK_SEND => sys_vsock_send(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_VSOCK_RECV => sys_vsock_recv(ctxt.regs.rdi as u32, ctxt.regs.rsi, ctxt.regs.r8),
         // Class 3 SysCalls.
         SYS_CAPABILITIES => sys_capabilities(ctxt.regs.rdi as u32),
+        SYS_RTMR_EXTEND => sys_rtmr_extend(
+            ctxt.regs.rdi as u32,
+            ctxt.regs.rsi as u32,
+            ctxt.regs.r8,
+            ctxt.regs.r9,
+        ),
+        SYS_MIGRATION_NONCE => sys_migration_nonce(ctxt.regs.rdi),
+        SYS_MIGRATION_EXPORT => sys_migration_export(ctxt.regs.rdi, ctxt.regs.rsi, ctxt.regs.r8),
+        SYS_MIGRATION_IMPORT => sys_migration_import(ctxt.regs.rdi, ctxt.regs.rsi),
         _ => Err(SysCallError::EINVAL),
     }
     .map_or_else(|e| e as usize, |v| v as usize);
@@ -455,6 +495,7 @@ pub fn common_isr_handler(vector: usize) {
     // Process the requested interrupt vector.
     match vector {
         IPI_VECTOR => this_cpu().handle_ipi_interrupt(),
+        TIMER_VECTOR => this_cpu().handle_timer_interrupt(),
         _ => {
             // Ignore all unrecognized interrupt vectors and treat them as
             // spurious interrupts.
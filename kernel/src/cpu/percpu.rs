@@ -48,7 +48,7 @@ use crate::sev::ghcb::{GhcbPage, GHCB};
 use crate::sev::hv_doorbell::{allocate_hv_doorbell_page, HVDoorbell};
 use crate::sev::utils::RMPFlags;
 use crate::sev::vmsa::{VMSAControl, VmsaPage};
-use crate::task::{schedule, schedule_task, RunQueue, Task, TaskPointer};
+use crate::task::{schedule, schedule_task, RunQueue, SchedTimer, Task, TaskPointer};
 use crate::types::{
     PAGE_SHIFT, PAGE_SHIFT_2M, PAGE_SIZE, PAGE_SIZE_2M, SVSM_TR_ATTRIBUTES, SVSM_TSS,
 };
@@ -301,6 +301,12 @@ impl PerCpuShared {
         locked.update_caa(Some(caa));
     }
 
+    /// Returns the addresses of the guest VMSA and calling area of this CPU.
+    pub fn guest_vmsa_caa(&self) -> (Option<PhysAddr>, Option<PhysAddr>) {
+        let locked = self.guest_vmsa.lock();
+        (locked.vmsa_phys(), locked.caa_phys())
+    }
+
     pub fn clear_guest_vmsa_if_match(&self, paddr: PhysAddr) {
         let mut locked = self.guest_vmsa.lock();
         if locked.vmsa.is_none() {
@@ -393,6 +399,8 @@ pub struct PerCpu {
     pub vrange_2m: RefCell<VirtualRange>,
     /// Task list that has been assigned for scheduling on this CPU
     runqueue: RWLockIrqSafe<RunQueue>,
+    /// State of the timer driving the scheduler
+    sched_timer: SchedTimer,
     /// Local APIC state for APIC emulation if enabled
     guest_apic: RefCell<Option<LocalApic>>,
 
@@ -411,6 +419,10 @@ pub struct PerCpu {
 
     /// Stack boundaries of the currently running task.
     current_stack: Cell<MemoryRegion<VirtAddr>>,
+
+    /// Generation of the breakpoint configuration loaded into the debug
+    /// registers of this CPU.
+    debug_regs_generation: Cell<u64>,
 }
 
 impl PerCpu {
@@ -433,6 +445,7 @@ impl PerCpu {
             vrange_4k: RefCell::new(VirtualRange::new()),
             vrange_2m: RefCell::new(VirtualRange::new()),
             runqueue: RWLockIrqSafe::new(RunQueue::new()),
+            sched_timer: SchedTimer::new(),
             guest_apic: RefCell::new(None),
 
             shared,
@@ -443,6 +456,7 @@ impl PerCpu {
             context_switch_stack: Cell::new(None),
             ist: IstStacks::new(),
             current_stack: Cell::new(MemoryRegion::new(VirtAddr::null(), 0)),
+            debug_regs_generation: Cell::new(0),
         }
     }
 
@@ -584,6 +598,13 @@ impl PerCpu {
         handle_ipi_interrupt(&self.shared.ipi_requests);
     }
 
+    /// Handles a scheduler timer interrupt.
+    pub fn handle_timer_interrupt(&self) {
+        if self.sched_timer.handle_interrupt() {
+            self.runqueue.lock_write().timer_tick();
+        }
+    }
+
     /// Sets up the CPU-local GHCB page.
     pub fn setup_ghcb(&self) -> Result<(), SvsmError> {
         let page = GhcbPage::new()?;
@@ -656,6 +677,14 @@ impl PerCpu {
         self.current_stack.set(stack);
     }
 
+    pub fn debug_regs_generation(&self) -> u64 {
+        self.debug_regs_generation.get()
+    }
+
+    pub fn set_debug_regs_generation(&self, generation: u64) {
+        self.debug_regs_generation.set(generation);
+    }
+
     pub fn get_cpu_index(&self) -> usize {
         self.shared.cpu_index()
     }
@@ -1074,13 +1103,21 @@ impl PerCpu {
         ret
     }
 
-    pub fn disable_apic_emulation(&self) {
+    pub fn check_apic_handoff(&self) -> Result<(), SvsmError> {
+        match self.guest_apic_mut() {
+            Some(mut apic) => apic.check_handoff(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn disable_apic_emulation(&self) -> Result<(), SvsmError> {
         if let Some(mut apic) = self.guest_apic_mut() {
             let mut vmsa_ref = self.guest_vmsa_ref();
             let caa_addr = vmsa_ref.caa_addr();
             let vmsa = vmsa_ref.vmsa();
-            apic.disable_apic_emulation(vmsa, caa_addr);
+            apic.disable_apic_emulation(vmsa, caa_addr)?;
         }
+        Ok(())
     }
 
     pub fn clear_pending_interrupts(&self) {
@@ -1095,6 +1132,11 @@ impl PerCpu {
     pub fn update_apic_emulation(&self, vmsa: &mut VMSA, caa_addr: Option<VirtAddr>) {
         if let Some(mut apic) = self.guest_apic_mut() {
             apic.present_interrupts(self.shared(), vmsa, caa_addr);
+
+            // Make sure the guest is interrupted when its APIC timer expires.
+            if let Some(deadline) = apic.timer_deadline() {
+                self.sched_timer.expire_by(deadline);
+            }
         }
     }
 
@@ -30,6 +30,7 @@ pub const SVM_EXIT_CPUID: usize = 0x72;
 pub const SVM_EXIT_IOIO: usize = 0x7b;
 pub const SVM_EXIT_MSR: usize = 0x7c;
 pub const SVM_EXIT_RDTSCP: usize = 0x87;
+pub const SVM_EXIT_NPF: usize = 0x400;
 pub const X86_TRAP_DB: usize = 0x01;
 pub const X86_TRAP: usize = SVM_EXIT_EXCP_BASE + X86_TRAP_DB;
 
@@ -173,6 +174,7 @@ pub fn handle_vc_exception(ctx: &mut X86ExceptionContext, vector: usize) -> Resu
         (SVM_EXIT_MSR, Some(ins)) => handle_msr(ctx, ghcb, ins),
         (SVM_EXIT_RDTSC, Some(DecodedInsn::Rdtsc)) => ghcb.rdtsc_regs(&mut ctx.regs),
         (SVM_EXIT_RDTSCP, Some(DecodedInsn::Rdtsc)) => ghcb.rdtscp_regs(&mut ctx.regs),
+        (SVM_EXIT_NPF, Some(_)) => handle_mmio(ctx, &insn_ctx.unwrap()),
         _ => Err(VcError::new(ctx, VcErrorType::Unsupported).into()),
     }?;
 
@@ -299,6 +301,12 @@ fn handle_ioio(ctx: &mut X86ExceptionContext, insn_ctx: &DecodedInsnCtx) -> Resu
     insn_ctx.emulate_ioio(ctx).map_err(SvsmError::from)
 }
 
+/// Emulates an access to emulated MMIO, which raises a #VC exception with
+/// an NPF exit code, through the GHCB MMIO requests.
+fn handle_mmio(ctx: &mut X86ExceptionContext, insn_ctx: &DecodedInsnCtx) -> Result<(), SvsmError> {
+    insn_ctx.emulate_mmio(ctx).map_err(SvsmError::from)
+}
+
 fn vc_decode_insn(ctx: &X86ExceptionContext) -> Result<Option<DecodedInsnCtx>, SvsmError> {
     if !vc_decoding_needed(ctx.error_code) {
         return Ok(None);
@@ -4,9 +4,12 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
+use crate::cpu::msr::rdtsc;
 use crate::cpu::percpu::this_cpu;
 use crate::error::SvsmError;
+use crate::platform::SVSM_PLATFORM;
 use core::cell::OnceCell;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 pub trait ApicAccess: core::fmt::Debug {
     /// Updates the APIC_BASE MSR by reading the current value, applying the
@@ -71,6 +74,14 @@ pub const APIC_OFFSET_SPIV: usize = 0xF;
 pub const APIC_OFFSET_ISR: usize = 0x10;
 /// Interrupt-Control-Register register MSR offset
 pub const APIC_OFFSET_ICR: usize = 0x30;
+/// LVT Timer register MSR offset
+pub const APIC_OFFSET_LVT_TIMER: usize = 0x32;
+/// Timer Initial-Count register MSR offset
+pub const APIC_OFFSET_TIMER_ICR: usize = 0x38;
+/// Timer Current-Count register MSR offset
+pub const APIC_OFFSET_TIMER_CCR: usize = 0x39;
+/// Timer Divide-Configuration register MSR offset
+pub const APIC_OFFSET_TIMER_DCR: usize = 0x3E;
 /// SELF-IPI register MSR offset (x2APIC only)
 pub const APIC_OFFSET_SELF_IPI: usize = 0x3F;
 
@@ -78,6 +89,57 @@ pub const APIC_OFFSET_SELF_IPI: usize = 0x3F;
 const APIC_SPIV_VECTOR_MASK: u64 = (1u64 << 8) - 1;
 const APIC_SPIV_SW_ENABLE_MASK: u64 = 1 << 8;
 
+// LVT Timer bits
+const APIC_LVT_VECTOR_MASK: u64 = (1u64 << 8) - 1;
+const APIC_LVT_MASKED: u64 = 1 << 16;
+const APIC_LVT_TIMER_PERIODIC: u64 = 1 << 17;
+
+// Timer Divide-Configuration value for a divisor of 16
+const APIC_TIMER_DIVIDE_16: u64 = 0x3;
+// Divisor configured by APIC_TIMER_DIVIDE_16
+const APIC_TIMER_DIVISOR: u64 = 16;
+
+/// APIC bus frequency in Hz assumed when the platform neither enumerates it
+/// nor provides a PIT to calibrate it against. This is the bus frequency of
+/// the APIC timer emulated by KVM.
+const DEFAULT_APIC_BUS_FREQUENCY: u64 = 1_000_000_000;
+
+/// Frequency of the input clock of the legacy PIT in Hz
+const PIT_FREQUENCY: u64 = 1_193_182;
+/// PIT channel 2 data port
+const PIT_PORT_CH2: u16 = 0x42;
+/// PIT mode/command port
+const PIT_PORT_COMMAND: u16 = 0x43;
+/// NMI status and control port, which also controls the gate of and
+/// reports the output of PIT channel 2
+const PIT_PORT_B: u16 = 0x61;
+const PIT_PORT_B_GATE2: u8 = 1 << 0;
+const PIT_PORT_B_SPEAKER: u8 = 1 << 1;
+const PIT_PORT_B_OUT2: u8 = 1 << 5;
+/// Selects channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal
+/// count) and binary counting.
+const PIT_CH2_ONESHOT: u8 = 0xb0;
+/// PIT counts the APIC timer is calibrated over, about 10ms
+const PIT_CALIBRATION_COUNTS: u16 = 11_932;
+/// Upper bound of the PIT output polls while calibrating, so that a PIT
+/// which never fires can not hang the calibration.
+const PIT_CALIBRATION_MAX_POLLS: u32 = 1_000_000;
+
+/// Frequency of the APIC timer in counts per second, 0 while it has not been
+/// determined by [`apic_timer_frequency()`].
+static APIC_TIMER_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// Number of APIC timer counts the TSC is measured over for calibration
+const APIC_CALIBRATION_COUNTS: u32 = 10_000;
+
+/// Number of reads of the APIC timer after which the TSC calibration gives
+/// up, e.g. when the initial count is below [`APIC_CALIBRATION_COUNTS`]
+const APIC_CALIBRATION_MAX_POLLS: u32 = 100_000;
+
+/// TSC ticks per APIC bus cycle as a 32.32 fixed-point number, 0 while the
+/// TSC has not been calibrated.
+static TSC_PER_APIC_CYCLE: AtomicU64 = AtomicU64::new(0);
+
 /// Get the MSR offset relative to a bitmap base MSR and the mask for the MSR
 /// value to check for a specific vector bit being set in IRR, ISR, or TMR.
 ///
@@ -191,6 +253,55 @@ impl X86Apic {
             | ((vector as u64) & APIC_SPIV_VECTOR_MASK);
         self.regs().apic_write(APIC_OFFSET_SPIV, apic_spiv);
     }
+
+    /// Starts the APIC timer in periodic mode. The timer counts down with
+    /// the APIC bus frequency divided by 16.
+    ///
+    /// # Arguments
+    ///
+    /// - `vector` - The IRQ vector to deliver timer interrupts to.
+    /// - `initial_count` - Number of timer counts between two interrupts.
+    pub fn timer_start_periodic(&self, vector: u8, initial_count: u32) {
+        let lvt = APIC_LVT_TIMER_PERIODIC | ((vector as u64) & APIC_LVT_VECTOR_MASK);
+        self.regs()
+            .apic_write(APIC_OFFSET_TIMER_DCR, APIC_TIMER_DIVIDE_16);
+        self.regs().apic_write(APIC_OFFSET_LVT_TIMER, lvt);
+        // Writing the initial count starts the timer
+        self.regs()
+            .apic_write(APIC_OFFSET_TIMER_ICR, initial_count as u64);
+    }
+
+    /// Starts the APIC timer in one-shot mode with its interrupt masked. The
+    /// timer counts down with the APIC bus frequency divided by 16.
+    ///
+    /// # Arguments
+    ///
+    /// - `initial_count` - Number of timer counts until the timer expires.
+    pub fn timer_start_masked(&self, initial_count: u32) {
+        self.regs()
+            .apic_write(APIC_OFFSET_TIMER_DCR, APIC_TIMER_DIVIDE_16);
+        self.regs()
+            .apic_write(APIC_OFFSET_LVT_TIMER, APIC_LVT_MASKED);
+        self.regs()
+            .apic_write(APIC_OFFSET_TIMER_ICR, initial_count as u64);
+    }
+
+    /// Stops the APIC timer and masks its interrupt.
+    pub fn timer_stop(&self) {
+        self.regs().apic_write(APIC_OFFSET_TIMER_ICR, 0);
+        self.regs()
+            .apic_write(APIC_OFFSET_LVT_TIMER, APIC_LVT_MASKED);
+    }
+
+    /// Reads the current count of the APIC timer.
+    ///
+    /// # Returns
+    ///
+    /// The number of timer counts left until the next timer interrupt.
+    #[inline(always)]
+    pub fn timer_current_count(&self) -> u32 {
+        self.regs().apic_read(APIC_OFFSET_TIMER_CCR) as u32
+    }
 }
 
 /// Initialize the APIC  by setting an accessor object. This function
@@ -230,6 +341,206 @@ pub fn apic_eoi() {
     this_cpu().get_apic().eoi();
 }
 
+/// Starts the local APIC timer of the current CPU in periodic mode.
+///
+/// # Arguments
+///
+/// - `vector` - The IRQ vector to deliver timer interrupts to.
+/// - `initial_count` - Number of timer counts between two interrupts.
+pub fn apic_timer_start(vector: u8, initial_count: u32) {
+    this_cpu()
+        .get_apic()
+        .timer_start_periodic(vector, initial_count);
+}
+
+/// Returns the frequency of the APIC timer as configured by
+/// [`apic_timer_start()`] in counts per second.
+///
+/// The APIC timer runs with the core crystal clock when its frequency is
+/// enumerated in CPUID leaf 0x15, which is the case on TDX. Otherwise, which
+/// is the normal case on SNP, the frequency is calibrated against the PIT
+/// on the first call. The APIC bus frequency of KVM is only assumed when
+/// neither is available.
+pub fn apic_timer_frequency() -> u64 {
+    match APIC_TIMER_FREQUENCY.load(Ordering::Relaxed) {
+        0 => {
+            let frequency = apic_enumerated_bus_frequency()
+                .map(|hz| hz / APIC_TIMER_DIVISOR)
+                .or_else(apic_calibrate_timer_frequency)
+                .unwrap_or_else(|| {
+                    log::warn!("APIC timer calibration failed, assuming default bus frequency");
+                    DEFAULT_APIC_BUS_FREQUENCY / APIC_TIMER_DIVISOR
+                })
+                .max(1);
+            APIC_TIMER_FREQUENCY.store(frequency, Ordering::Relaxed);
+            frequency
+        }
+        frequency => frequency,
+    }
+}
+
+/// Returns the APIC bus frequency in Hz from CPUID leaf 0x15, or `None` when
+/// the leaf does not enumerate it.
+fn apic_enumerated_bus_frequency() -> Option<u64> {
+    let max_leaf = SVSM_PLATFORM.cpuid(0, 0).map_or(0, |res| res.eax);
+    (max_leaf >= 0x15)
+        .then(|| SVSM_PLATFORM.cpuid(0x15, 0))
+        .flatten()
+        .map(|res| u64::from(res.ecx))
+        .filter(|hz| *hz != 0)
+}
+
+/// Measures the frequency of the APIC timer of the current CPU against
+/// channel 2 of the legacy PIT. The APIC timer is stopped afterwards.
+///
+/// # Returns
+///
+/// The frequency of the APIC timer in counts per second, or `None` when
+/// there is no PIT or it did not expire in time.
+fn apic_calibrate_timer_frequency() -> Option<u64> {
+    let io = SVSM_PLATFORM.get_io_port();
+    let apic = this_cpu().get_apic();
+
+    // Enable the gate of channel 2 with the speaker turned off and program
+    // the channel to raise its output after PIT_CALIBRATION_COUNTS.
+    let port_b = io.inb(PIT_PORT_B);
+    io.outb(
+        PIT_PORT_B,
+        (port_b & !PIT_PORT_B_SPEAKER) | PIT_PORT_B_GATE2,
+    );
+    io.outb(PIT_PORT_COMMAND, PIT_CH2_ONESHOT);
+    // A missing PIT reads as all ones, including an already raised output
+    let present = io.inb(PIT_PORT_B) & PIT_PORT_B_OUT2 == 0;
+
+    let [low, high] = PIT_CALIBRATION_COUNTS.to_le_bytes();
+    apic.timer_start_masked(u32::MAX);
+    io.outb(PIT_PORT_CH2, low);
+    io.outb(PIT_PORT_CH2, high);
+    let start_count = apic.timer_current_count();
+    let expired = present
+        && (0..PIT_CALIBRATION_MAX_POLLS).any(|_| io.inb(PIT_PORT_B) & PIT_PORT_B_OUT2 != 0);
+    let end_count = apic.timer_current_count();
+
+    apic.timer_stop();
+    io.outb(PIT_PORT_B, port_b);
+
+    let counts = u64::from(start_count.checked_sub(end_count)?);
+    (expired && counts != 0).then(|| counts * PIT_FREQUENCY / u64::from(PIT_CALIBRATION_COUNTS))
+}
+
+/// Stops the local APIC timer of the current CPU.
+pub fn apic_timer_stop() {
+    this_cpu().get_apic().timer_stop();
+}
+
+/// Measures the TSC frequency relative to the APIC bus frequency with the
+/// running APIC timer of the current CPU. The measurement is only done on
+/// the first call, later calls return the stored result.
+///
+/// # Returns
+///
+/// TSC ticks per APIC bus cycle as a 32.32 fixed-point number, or `None`
+/// when the APIC timer is not running or does not count down
+/// [`APIC_CALIBRATION_COUNTS`] within [`APIC_CALIBRATION_MAX_POLLS`] reads.
+pub fn apic_calibrate_tsc() -> Option<u64> {
+    if let Some(ratio) = apic_tsc_ratio() {
+        return Some(ratio);
+    }
+
+    let Some(ratio) = apic_measure_tsc_ratio() else {
+        log::warn!("TSC calibration against the APIC timer failed");
+        return None;
+    };
+    TSC_PER_APIC_CYCLE.store(ratio, Ordering::Relaxed);
+    Some(ratio)
+}
+
+fn apic_measure_tsc_ratio() -> Option<u64> {
+    let apic = this_cpu().get_apic();
+    let mut polls = 0u32;
+    let mut read_count = || {
+        polls += 1;
+        (polls <= APIC_CALIBRATION_MAX_POLLS).then(|| apic.timer_current_count())
+    };
+
+    loop {
+        let start_count = read_count()?;
+        if start_count == 0 {
+            return None;
+        } else if start_count < APIC_CALIBRATION_COUNTS {
+            // Wait for the timer to be reloaded
+            continue;
+        }
+
+        let start_tsc = rdtsc();
+        let mut count = start_count;
+        while count <= start_count && start_count - count < APIC_CALIBRATION_COUNTS {
+            count = read_count()?;
+        }
+        let end_tsc = rdtsc();
+
+        // Start over when the timer was reloaded in between
+        if count > start_count {
+            continue;
+        }
+
+        let cycles = u128::from(start_count - count) * u128::from(APIC_TIMER_DIVISOR);
+        let ticks = u128::from(end_tsc.wrapping_sub(start_tsc));
+        return Some(u64::try_from((ticks << 32) / cycles).ok()?.max(1));
+    }
+}
+
+/// Returns the result of [`apic_calibrate_tsc()`], or `None` when the TSC
+/// has not been calibrated yet.
+pub fn apic_tsc_ratio() -> Option<u64> {
+    match TSC_PER_APIC_CYCLE.load(Ordering::Relaxed) {
+        0 => None,
+        ratio => Some(ratio),
+    }
+}
+
+/// Converts a number of APIC bus cycles into TSC ticks.
+///
+/// # Returns
+///
+/// The number of TSC ticks, or `None` when the TSC has not been calibrated.
+pub fn apic_cycles_to_tsc(cycles: u64) -> Option<u64> {
+    let ticks = (u128::from(cycles) * u128::from(apic_tsc_ratio()?)) >> 32;
+    Some(u64::try_from(ticks).unwrap_or(u64::MAX))
+}
+
+/// Converts a number of TSC ticks into APIC bus cycles.
+///
+/// # Returns
+///
+/// The number of APIC bus cycles, or `None` when the TSC has not been
+/// calibrated.
+pub fn tsc_to_apic_cycles(ticks: u64) -> Option<u64> {
+    let cycles = (u128::from(ticks) << 32) / u128::from(apic_tsc_ratio()?);
+    Some(u64::try_from(cycles).unwrap_or(u64::MAX))
+}
+
+/// Converts a number of TSC ticks into counts of the APIC timer as it is
+/// configured by [`apic_timer_start()`].
+///
+/// # Returns
+///
+/// The number of timer counts, or `None` when the TSC has not been
+/// calibrated.
+pub fn tsc_to_apic_timer_counts(ticks: u64) -> Option<u64> {
+    Some(tsc_to_apic_cycles(ticks)? / APIC_TIMER_DIVISOR)
+}
+
+/// Converts a number of APIC timer counts, as configured by
+/// [`apic_timer_start()`], into TSC ticks.
+///
+/// # Returns
+///
+/// The number of TSC ticks, or `None` when the TSC has not been calibrated.
+pub fn apic_timer_counts_to_tsc(counts: u64) -> Option<u64> {
+    apic_cycles_to_tsc(counts.saturating_mul(APIC_TIMER_DIVISOR))
+}
+
 /// Check whether a given IRQ vector is currently being serviced by returning
 /// the value of its ISR bit from X2APIC.
 ///
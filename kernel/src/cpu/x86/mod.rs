@@ -9,7 +9,9 @@ pub mod smap;
 pub mod x2apic;
 
 pub use apic::{
-    apic_enable, apic_eoi, apic_in_service, apic_initialize, apic_post_irq, apic_sw_enable,
-    ApicAccess, X86Apic, MSR_APIC_BASE,
+    apic_calibrate_tsc, apic_cycles_to_tsc, apic_enable, apic_eoi, apic_in_service,
+    apic_initialize, apic_post_irq, apic_sw_enable, apic_timer_counts_to_tsc, apic_timer_frequency,
+    apic_timer_start, apic_timer_stop, apic_tsc_ratio, tsc_to_apic_cycles,
+    tsc_to_apic_timer_counts, ApicAccess, X86Apic, MSR_APIC_BASE,
 };
 pub use x2apic::{X2ApicAccessor, X2APIC_ACCESSOR};
@@ -93,11 +93,127 @@ pub mod digest {
         fn digest(input: &[u8]) -> Vec<u8>;
     }
 
+    /// Sha384 type
+    #[derive(Copy, Clone, Debug)]
+    pub struct Sha384;
+
     /// Sha512 type
     #[derive(Copy, Clone, Debug)]
     pub struct Sha512;
 }
 
+pub mod kdf {
+    //! API for key derivation functions.
+
+    use crate::error::SvsmError;
+
+    /// HKDF (RFC 5869) with HMAC-SHA512
+    pub trait HkdfSha512Trait {
+        /// Derive key material from an input key.
+        ///
+        /// # Arguments
+        ///
+        /// * `ikm`: Input key material
+        /// * `salt`: Optional salt of the extract step
+        /// * `info`: Context of the derived key, which separates keys
+        ///   derived for different purposes
+        /// * `okm`: Buffer which receives the derived key material
+        ///
+        /// # Returns
+        ///
+        /// `()` on success, [`SvsmError::Crypto`] if `okm` is too large.
+        fn derive(
+            ikm: &[u8],
+            salt: Option<&[u8]>,
+            info: &[u8],
+            okm: &mut [u8],
+        ) -> Result<(), SvsmError>;
+    }
+
+    /// HkdfSha512 type
+    #[derive(Copy, Clone, Debug)]
+    pub struct HkdfSha512;
+}
+
+pub mod signature {
+    //! API for verifying digital signatures.
+
+    use crate::error::SvsmError;
+
+    // The sizes are shared with the build tools, which sign the filesystem
+    // archive.
+    pub use bootlib::fs_archive::{P384_PUBLIC_KEY_SIZE, P384_SIGNATURE_SIZE};
+
+    /// ECDSA with the NIST P-384 curve and SHA-384
+    pub trait EcdsaP384Trait {
+        /// Verify the signature over a message.
+        ///
+        /// # Arguments
+        ///
+        /// * `public_key`: SEC1-encoded uncompressed public key
+        /// * `msg`: The signed message
+        /// * `signature`: Big-endian `r` and `s` values of the signature
+        ///
+        /// # Returns
+        ///
+        /// `()` if the signature is valid, [`SvsmError::Crypto`] otherwise.
+        fn verify(
+            public_key: &[u8; P384_PUBLIC_KEY_SIZE],
+            msg: &[u8],
+            signature: &[u8; P384_SIGNATURE_SIZE],
+        ) -> Result<(), SvsmError>;
+    }
+
+    /// EcdsaP384 type
+    #[derive(Copy, Clone, Debug)]
+    pub struct EcdsaP384;
+}
+
+pub mod rng {
+    //! Random number generation based on the RDRAND instruction.
+
+    use crate::error::SvsmError;
+    use core::arch::asm;
+
+    /// Number of RDRAND attempts before giving up, as recommended by the
+    /// Intel DRNG software implementation guide.
+    const RDRAND_RETRIES: usize = 10;
+
+    fn rdrand64() -> Option<u64> {
+        for _ in 0..RDRAND_RETRIES {
+            let value: u64;
+            let ok: u8;
+            // SAFETY: RDRAND only writes the output register and the carry
+            // flag, it has no memory side effects.
+            unsafe {
+                asm!("rdrand {0}",
+                     "setc {1}",
+                     out(reg) value,
+                     out(reg_byte) ok,
+                     options(nomem, nostack));
+            }
+            if ok != 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Fills a buffer with random bytes.
+    ///
+    /// # Returns
+    ///
+    /// `()` on success, [`SvsmError::Crypto`] when the hardware random number
+    /// generator failed to deliver random data.
+    pub fn fill_random(buf: &mut [u8]) -> Result<(), SvsmError> {
+        for chunk in buf.chunks_mut(8) {
+            let value = rdrand64().ok_or(SvsmError::Crypto)?;
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
 // Crypto implementations supported. Only one of them must be compiled-in.
 
 pub mod rustcrypto;
@@ -13,13 +13,23 @@ use aes_gcm::{
     Aes256Gcm, Key, KeyInit, Nonce,
 };
 use alloc::vec::Vec;
-use sha2::{Digest, Sha512};
+use hkdf::Hkdf;
+use p384::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use sha2::{Digest, Sha384, Sha512};
 
 use crate::{
     crypto::aead::{
         Aes256Gcm as CryptoAes256Gcm, Aes256GcmTrait as CryptoAes256GcmTrait, IV_SIZE, KEY_SIZE,
     },
-    crypto::digest::{Algorithm as CryptoHashTrait, Sha512 as CryptoSha512},
+    crypto::digest::{
+        Algorithm as CryptoHashTrait, Sha384 as CryptoSha384, Sha512 as CryptoSha512,
+    },
+    crypto::kdf::{HkdfSha512 as CryptoHkdfSha512, HkdfSha512Trait as CryptoHkdfSha512Trait},
+    crypto::signature::{
+        EcdsaP384 as CryptoEcdsaP384, EcdsaP384Trait as CryptoEcdsaP384Trait, P384_PUBLIC_KEY_SIZE,
+        P384_SIGNATURE_SIZE,
+    },
+    error::SvsmError,
     protocols::errors::SvsmReqError,
 };
 
@@ -86,3 +96,34 @@ impl CryptoHashTrait for CryptoSha512 {
         Sha512::digest(input).to_vec()
     }
 }
+
+impl CryptoHashTrait for CryptoSha384 {
+    fn digest(input: &[u8]) -> Vec<u8> {
+        Sha384::digest(input).to_vec()
+    }
+}
+
+impl CryptoHkdfSha512Trait for CryptoHkdfSha512 {
+    fn derive(
+        ikm: &[u8],
+        salt: Option<&[u8]>,
+        info: &[u8],
+        okm: &mut [u8],
+    ) -> Result<(), SvsmError> {
+        Hkdf::<Sha512>::new(salt, ikm)
+            .expand(info, okm)
+            .map_err(|_| SvsmError::Crypto)
+    }
+}
+
+impl CryptoEcdsaP384Trait for CryptoEcdsaP384 {
+    fn verify(
+        public_key: &[u8; P384_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; P384_SIGNATURE_SIZE],
+    ) -> Result<(), SvsmError> {
+        let key = VerifyingKey::from_sec1_bytes(public_key).map_err(|_| SvsmError::Crypto)?;
+        let signature = Signature::from_slice(signature).map_err(|_| SvsmError::Crypto)?;
+        key.verify(msg, &signature).map_err(|_| SvsmError::Crypto)
+    }
+}
@@ -0,0 +1,845 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! ELF core dumps of the SVSM on a panic.
+//!
+//! Crash dumps are only written by SVSM builds with the `enable-crashdump`
+//! feature, which changes the measurement of the SVSM. The dump target is
+//! configured by the host through the [`CRASH_DUMP_CONFIG_FILE`] fw_cfg file and is either a region of guest
+//! memory or a virtio-blk device. On a panic the SVSM writes an ELF core
+//! file to the target, in the format described in [`bootlib::crashdump`].
+//!
+//! The core contains the registers of the panicking CPU, notes with the
+//! panic message, the CPU list, the task list and the kernel log, the stacks
+//! of the panicking CPU, the kernel data and BSS sections and any range
+//! registered with [`crash_dump_add_range()`]. All memory needed to write the dump is
+//! allocated when the target is configured, so that a dump can be written
+//! even when the heap is corrupted.
+//!
+//! The dump is not encrypted. It exposes SVSM memory to everyone who can
+//! read the target and must only be configured for debugging.
+
+extern crate alloc;
+
+use crate::address::{Address, PhysAddr, VirtAddr};
+use crate::block::api::BlockDriver;
+use crate::cpu::percpu::{try_this_cpu, PERCPU_AREAS};
+use crate::error::SvsmError;
+use crate::fw_cfg::FwCfg;
+use crate::locking::SpinLock;
+use crate::log_buffer::{try_with_log_buffer, LOG_BUFFER_SIZE};
+use crate::mm::memory::valid_phys_region;
+use crate::mm::{PerCPUPageMappingGuard, STACK_SIZE};
+use crate::platform::SVSM_PLATFORM;
+use crate::task::TASKLIST;
+use crate::types::PAGE_SIZE;
+use crate::utils::{align_up, MemoryRegion};
+use alloc::boxed::Box;
+use alloc::vec;
+use bootlib::crashdump::note::{NT_SVSM_CPUS, NT_SVSM_LOG, NT_SVSM_PANIC, NT_SVSM_TASKS};
+use bootlib::crashdump::{
+    crash_dump_checksum, CrashDumpConfig, CrashDumpCpu, CrashDumpHeader, CrashDumpTask,
+    CRASH_DUMP_CHECKSUM_INIT, CRASH_DUMP_CONFIG_FILE, CRASH_DUMP_DATA_OFFSET,
+    CRASH_DUMP_FLAG_TRUNCATED, CRASH_DUMP_MAGIC, CRASH_DUMP_TARGET_MEMORY,
+    CRASH_DUMP_TARGET_VIRTIO_BLK, CRASH_DUMP_VERSION,
+};
+use core::arch::asm;
+use core::fmt;
+use core::mem::size_of;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use zerocopy::{Immutable, IntoBytes};
+
+/// Size of the buffer the notes of the core file are built in, with room
+/// for the log buffer
+const NOTES_SIZE: usize = LOG_BUFFER_SIZE + 64 * 1024;
+/// Maximum number of memory ranges registered with [`crash_dump_add_range()`]
+const MAX_DUMP_RANGES: usize = 16;
+/// Maximum number of memory ranges in a core file
+const MAX_CORE_RANGES: usize = MAX_DUMP_RANGES + 3;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const SIGABRT: u16 = 6;
+
+#[repr(C)]
+#[derive(Debug, Default, IntoBytes, Immutable)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, IntoBytes, Immutable)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+/// Description of an NT_PRSTATUS note on x86-64 (`struct elf_prstatus`)
+#[repr(C)]
+#[derive(Debug, Default, IntoBytes, Immutable)]
+struct ElfPrStatus {
+    si_signo: u32,
+    si_code: u32,
+    si_errno: u32,
+    pr_cursig: u16,
+    _pad0: u16,
+    pr_sigpend: u64,
+    pr_sighold: u64,
+    pr_pid: u32,
+    pr_ppid: u32,
+    pr_pgrp: u32,
+    pr_sid: u32,
+    pr_times: [u64; 8],
+    /// Registers in the layout of `struct user_regs_struct`
+    pr_reg: [u64; 27],
+    pr_fpvalid: u32,
+    _pad1: u32,
+}
+
+/// Register state of the panicking CPU, captured by [`capture_regs()`]
+#[derive(Clone, Copy, Debug, Default)]
+struct CrashRegs {
+    rbx: u64,
+    rbp: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rsp: u64,
+    rip: u64,
+    rflags: u64,
+    cs: u64,
+    ss: u64,
+}
+
+impl CrashRegs {
+    /// Returns the registers in the layout of `struct user_regs_struct`.
+    fn user_regs(&self) -> [u64; 27] {
+        let mut regs = [0u64; 27];
+        regs[0] = self.r15;
+        regs[1] = self.r14;
+        regs[2] = self.r13;
+        regs[3] = self.r12;
+        regs[4] = self.rbp;
+        regs[5] = self.rbx;
+        regs[16] = self.rip;
+        regs[17] = self.cs;
+        regs[18] = self.rflags;
+        regs[19] = self.rsp;
+        regs[20] = self.ss;
+        regs
+    }
+}
+
+/// Captures the callee-saved registers, the stack pointer and the
+/// instruction pointer of the caller. The other general purpose registers
+/// have been clobbered by the panic handler and are not captured.
+#[inline(always)]
+fn capture_regs() -> CrashRegs {
+    let mut regs = CrashRegs::default();
+    // SAFETY: the assembly only reads registers.
+    unsafe {
+        asm!(
+            "movq %rbx, %rax",
+            "movq %rbp, %rcx",
+            "movq %r12, %rdx",
+            "movq %r13, %rsi",
+            "movq %r14, %rdi",
+            "movq %r15, %r8",
+            "movq %rsp, %r9",
+            "leaq 0(%rip), %r10",
+            "pushfq",
+            "popq %r11",
+            out("rax") regs.rbx,
+            out("rcx") regs.rbp,
+            out("rdx") regs.r12,
+            out("rsi") regs.r13,
+            out("rdi") regs.r14,
+            out("r8") regs.r15,
+            out("r9") regs.rsp,
+            out("r10") regs.rip,
+            out("r11") regs.rflags,
+            options(att_syntax)
+        );
+        asm!(
+            "movw %cs, %ax",
+            "movw %ss, %cx",
+            out("rax") regs.cs,
+            out("rcx") regs.ss,
+            options(att_syntax, nomem, nostack)
+        );
+    }
+    regs.cs &= 0xffff;
+    regs.ss &= 0xffff;
+    regs
+}
+
+/// Storage a crash dump is written to.
+trait DumpTarget: fmt::Debug {
+    /// Returns the size of the target in bytes.
+    fn size(&self) -> usize;
+
+    /// Writes a page of data at the page-aligned `offset`.
+    fn write_page(&mut self, offset: usize, data: &[u8; PAGE_SIZE]) -> Result<(), SvsmError>;
+
+    /// Makes sure all written data reached the target.
+    fn flush(&mut self) -> Result<(), SvsmError> {
+        Ok(())
+    }
+}
+
+/// A crash dump target in guest memory
+#[derive(Debug)]
+struct MemoryTarget {
+    region: MemoryRegion<PhysAddr>,
+}
+
+impl DumpTarget for MemoryTarget {
+    fn size(&self) -> usize {
+        self.region.len()
+    }
+
+    fn write_page(&mut self, offset: usize, data: &[u8; PAGE_SIZE]) -> Result<(), SvsmError> {
+        let guard = PerCPUPageMappingGuard::create_4k(self.region.start() + offset)?;
+        // SAFETY: the mapping covers a full page of guest memory, which was
+        // checked to not belong to the SVSM when the target was configured.
+        unsafe {
+            guard
+                .virt_addr()
+                .as_mut_ptr::<[u8; PAGE_SIZE]>()
+                .write_volatile(*data)
+        };
+        Ok(())
+    }
+}
+
+/// A crash dump target on a block device
+struct BlockTarget {
+    dev: Box<dyn BlockDriver + Send>,
+    size: usize,
+}
+
+impl fmt::Debug for BlockTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockTarget")
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl DumpTarget for BlockTarget {
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn write_page(&mut self, offset: usize, data: &[u8; PAGE_SIZE]) -> Result<(), SvsmError> {
+        self.dev
+            .write_blocks(offset >> self.dev.block_size_log2(), data)
+    }
+
+    fn flush(&mut self) -> Result<(), SvsmError> {
+        self.dev.flush()
+    }
+}
+
+/// Streams an ELF core file to a [`DumpTarget`], page by page.
+#[derive(Debug)]
+struct CoreWriter<'a> {
+    target: &'a mut dyn DumpTarget,
+    page: &'a mut [u8; PAGE_SIZE],
+    /// Number of bytes of the core file written so far
+    pos: usize,
+    /// Maximum size of the core file
+    capacity: usize,
+    checksum: u64,
+    truncated: bool,
+}
+
+impl<'a> CoreWriter<'a> {
+    fn new(target: &'a mut dyn DumpTarget, page: &'a mut [u8; PAGE_SIZE]) -> Self {
+        let capacity = target.size().saturating_sub(CRASH_DUMP_DATA_OFFSET);
+        Self {
+            target,
+            page,
+            pos: 0,
+            capacity,
+            checksum: CRASH_DUMP_CHECKSUM_INIT,
+            truncated: false,
+        }
+    }
+
+    fn flush_page(&mut self) -> Result<(), SvsmError> {
+        let page_start = (self.pos - 1) & !(PAGE_SIZE - 1);
+        self.target
+            .write_page(CRASH_DUMP_DATA_OFFSET + page_start, self.page)?;
+        self.page.fill(0);
+        Ok(())
+    }
+
+    /// Appends `data` to the core file. Data which does not fit into the
+    /// target is dropped.
+    fn write(&mut self, data: &[u8]) -> Result<(), SvsmError> {
+        let len = data.len().min(self.capacity - self.pos);
+        self.truncated |= len < data.len();
+        self.checksum = crash_dump_checksum(self.checksum, &data[..len]);
+
+        let mut data = &data[..len];
+        while !data.is_empty() {
+            let offset = self.pos % PAGE_SIZE;
+            let n = data.len().min(PAGE_SIZE - offset);
+            self.page[offset..offset + n].copy_from_slice(&data[..n]);
+            self.pos += n;
+            data = &data[n..];
+            if self.pos % PAGE_SIZE == 0 {
+                self.flush_page()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends zeroes until the size of the core file is a multiple of
+    /// `align`.
+    fn pad(&mut self, align: usize) -> Result<(), SvsmError> {
+        let zeroes = [0u8; 64];
+        while self.pos % align != 0 && !self.truncated {
+            let n = (align - self.pos % align).min(zeroes.len());
+            self.write(&zeroes[..n])?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last partial page and the dump header.
+    fn finish(mut self) -> Result<(), SvsmError> {
+        if self.pos % PAGE_SIZE != 0 {
+            self.flush_page()?;
+        }
+
+        let header = CrashDumpHeader {
+            magic: CRASH_DUMP_MAGIC,
+            version: CRASH_DUMP_VERSION,
+            flags: if self.truncated {
+                CRASH_DUMP_FLAG_TRUNCATED
+            } else {
+                0
+            },
+            size: self.pos as u64,
+            checksum: self.checksum,
+        };
+        // Only write the header when the core file is on the target.
+        self.target.flush()?;
+        self.page.fill(0);
+        self.page[..size_of::<CrashDumpHeader>()].copy_from_slice(header.as_bytes());
+        self.target.write_page(0, self.page)?;
+        self.target.flush()
+    }
+}
+
+/// Buffer for the notes of a core file. Notes which do not fit are
+/// truncated.
+#[derive(Debug)]
+struct NoteBuffer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    /// Offset of the note currently being built
+    current: usize,
+}
+
+impl<'a> NoteBuffer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            current: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Appends `data` if it fits into the buffer.
+    fn push(&mut self, data: &[u8]) -> bool {
+        let Some(dst) = self.buf.get_mut(self.len..self.len + data.len()) else {
+            return false;
+        };
+        dst.copy_from_slice(data);
+        self.len += data.len();
+        true
+    }
+
+    fn push_padding(&mut self) {
+        while self.len % 4 != 0 && self.push(&[0]) {}
+    }
+
+    /// Starts a new note, its description is added with [`Self::push()`].
+    fn begin(&mut self, name: &str, n_type: u32) -> bool {
+        let namesz = name.len() as u32 + 1;
+        self.current = self.len;
+        let ok = self.push(&namesz.to_le_bytes())
+            && self.push(&0u32.to_le_bytes())
+            && self.push(&n_type.to_le_bytes())
+            && self.push(name.as_bytes())
+            && self.push(&[0]);
+        self.push_padding();
+        if !ok {
+            self.len = self.current;
+        }
+        ok
+    }
+
+    /// Completes the note started by [`Self::begin()`].
+    fn end(&mut self) {
+        let namesz =
+            u32::from_le_bytes(self.buf[self.current..self.current + 4].try_into().unwrap());
+        let desc_start = self.current + 12 + align_up(namesz as usize, 4);
+        let descsz = (self.len - desc_start) as u32;
+        self.buf[self.current + 4..self.current + 8].copy_from_slice(&descsz.to_le_bytes());
+        self.push_padding();
+    }
+
+    fn add(&mut self, name: &str, n_type: u32, desc: &[u8]) {
+        if self.begin(name, n_type) {
+            self.push(desc);
+            self.end();
+        }
+    }
+}
+
+impl fmt::Write for NoteBuffer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Keep as much of the text as fits.
+        let n = s.len().min(self.buf.len() - self.len);
+        self.push(&s.as_bytes()[..n]);
+        Ok(())
+    }
+}
+
+/// Writes an ELF core file consisting of `notes` and the memory in
+/// `ranges`.
+fn write_core(
+    w: &mut CoreWriter<'_>,
+    notes: &[u8],
+    ranges: &[MemoryRegion<VirtAddr>],
+) -> Result<(), SvsmError> {
+    let phnum = ranges.len() + 1;
+    let headers_size = size_of::<Elf64Ehdr>() + phnum * size_of::<Elf64Phdr>();
+    let notes_offset = headers_size;
+    let mut data_offset = align_up(notes_offset + notes.len(), PAGE_SIZE);
+
+    let mut e_ident = [0u8; 16];
+    e_ident[..4].copy_from_slice(b"\x7fELF");
+    // ELFCLASS64, ELFDATA2LSB, EV_CURRENT
+    e_ident[4..7].copy_from_slice(&[2, 1, 1]);
+    let ehdr = Elf64Ehdr {
+        e_ident,
+        e_type: ET_CORE,
+        e_machine: EM_X86_64,
+        e_version: 1,
+        e_phoff: size_of::<Elf64Ehdr>() as u64,
+        e_ehsize: size_of::<Elf64Ehdr>() as u16,
+        e_phentsize: size_of::<Elf64Phdr>() as u16,
+        e_phnum: phnum as u16,
+        ..Default::default()
+    };
+    w.write(ehdr.as_bytes())?;
+
+    let note_phdr = Elf64Phdr {
+        p_type: PT_NOTE,
+        p_offset: notes_offset as u64,
+        p_filesz: notes.len() as u64,
+        p_align: 4,
+        ..Default::default()
+    };
+    w.write(note_phdr.as_bytes())?;
+
+    for range in ranges {
+        let phdr = Elf64Phdr {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_W,
+            p_offset: data_offset as u64,
+            p_vaddr: range.start().bits() as u64,
+            p_filesz: range.len() as u64,
+            p_memsz: range.len() as u64,
+            p_align: PAGE_SIZE as u64,
+            ..Default::default()
+        };
+        w.write(phdr.as_bytes())?;
+        data_offset = align_up(data_offset + range.len(), PAGE_SIZE);
+    }
+
+    w.write(notes)?;
+    for range in ranges {
+        w.pad(PAGE_SIZE)?;
+        let start = range.start();
+        // SAFETY: the ranges in the core file are mapped kernel memory.
+        let data = unsafe { start.to_slice::<u8>(range.len()) };
+        w.write(data)?;
+    }
+    Ok(())
+}
+
+/// Adds the notes describing the state of the SVSM at the time of the panic.
+fn build_notes(notes: &mut NoteBuffer<'_>, info: &PanicInfo<'_>, regs: &CrashRegs) {
+    let cpu_index = try_this_cpu().map(|cpu| cpu.get_cpu_index());
+
+    let prstatus = ElfPrStatus {
+        pr_cursig: SIGABRT,
+        // Thread IDs must not be 0.
+        pr_pid: cpu_index.unwrap_or(0) as u32 + 1,
+        pr_reg: regs.user_regs(),
+        ..Default::default()
+    };
+    notes.add("CORE", NT_PRSTATUS, prstatus.as_bytes());
+
+    if notes.begin("SVSM", NT_SVSM_PANIC) {
+        let _ = fmt::write(notes, format_args!("{info}"));
+        notes.end();
+    }
+
+    if notes.begin("SVSM", NT_SVSM_CPUS) {
+        for cpu in PERCPU_AREAS.iter() {
+            let entry = CrashDumpCpu {
+                cpu_index: cpu.cpu_index() as u32,
+                apic_id: cpu.apic_id(),
+                online: cpu.is_online().into(),
+                crashed: (Some(cpu.cpu_index()) == cpu_index).into(),
+            };
+            if !notes.push(entry.as_bytes()) {
+                break;
+            }
+        }
+        notes.end();
+    }
+
+    // Skip the task list if it is locked, most likely by the panicking CPU.
+    if let Some(mut tl) = TASKLIST.try_lock() {
+        if notes.begin("SVSM", NT_SVSM_TASKS) {
+            let mut cursor = tl.list().front_mut();
+            while let Some(task) = cursor.get() {
+                let name = task.get_task_name().as_bytes();
+                let entry = CrashDumpTask {
+                    task_id: task.get_task_id(),
+                    process_id: task.get_process_id(),
+                    cpu_index: task.cpu_index() as u32,
+                    state: if task.is_terminated() {
+                        2
+                    } else {
+                        task.is_running().into()
+                    },
+                    user: task.is_user_task().into(),
+                    name_len: name.len() as u32,
+                };
+                if notes.buf.len() - notes.len < size_of::<CrashDumpTask>() + name.len() {
+                    break;
+                }
+                notes.push(entry.as_bytes());
+                notes.push(name);
+                cursor.move_next();
+            }
+            notes.end();
+        }
+    }
+
+    if notes.begin("SVSM", NT_SVSM_LOG) {
+        try_with_log_buffer(|older, newer| notes.push(older) && notes.push(newer));
+        notes.end();
+    }
+}
+
+#[derive(Debug)]
+struct CrashDump {
+    target: Box<dyn DumpTarget + Send>,
+    page: Box<[u8; PAGE_SIZE]>,
+    notes: Box<[u8]>,
+}
+
+static CRASH_DUMP: SpinLock<Option<CrashDump>> = SpinLock::new(None);
+static DUMP_RANGES: SpinLock<[Option<MemoryRegion<VirtAddr>>; MAX_DUMP_RANGES]> =
+    SpinLock::new([None; MAX_DUMP_RANGES]);
+static DUMP_STARTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    static data_start: u8;
+    static bss_end: u8;
+}
+
+#[cfg(feature = "virtio-drivers")]
+fn block_target(address: u64) -> Result<Box<dyn BlockDriver + Send>, SvsmError> {
+    use crate::block::virtio_blk::VirtIOBlkDriver;
+    use crate::virtio::VirtioDeviceAddress;
+
+    Ok(Box::new(VirtIOBlkDriver::new(VirtioDeviceAddress::Mmio(
+        PhysAddr::from(address),
+    ))?))
+}
+
+#[cfg(not(feature = "virtio-drivers"))]
+fn block_target(_address: u64) -> Result<Box<dyn BlockDriver + Send>, SvsmError> {
+    Err(SvsmError::NotSupported)
+}
+
+fn dump_target(config: &CrashDumpConfig) -> Result<Box<dyn DumpTarget + Send>, SvsmError> {
+    let size = usize::try_from(config.size).map_err(|_| SvsmError::InvalidAddress)?;
+    match config.target {
+        CRASH_DUMP_TARGET_MEMORY => {
+            let start = PhysAddr::from(config.address);
+            let region = MemoryRegion::checked_new(start, size).ok_or(SvsmError::InvalidAddress)?;
+            if !start.is_page_aligned() || !valid_phys_region(&region) {
+                return Err(SvsmError::InvalidAddress);
+            }
+            Ok(Box::new(MemoryTarget { region }))
+        }
+        CRASH_DUMP_TARGET_VIRTIO_BLK => {
+            let dev = block_target(config.address)?;
+            if usize::from(dev.block_size_log2()) > PAGE_SIZE.ilog2() as usize {
+                return Err(SvsmError::NotSupported);
+            }
+            let size = match size {
+                0 => dev.size(),
+                size => size.min(dev.size()),
+            };
+            Ok(Box::new(BlockTarget {
+                dev,
+                size: size & !(PAGE_SIZE - 1),
+            }))
+        }
+        _ => Err(SvsmError::NotSupported),
+    }
+}
+
+/// Configures the crash dump target from the [`CRASH_DUMP_CONFIG_FILE`]
+/// fw_cfg file. Crash dumps stay disabled when the file does not exist or
+/// the SVSM is built without the `enable-crashdump` feature.
+pub fn crash_dump_init() -> Result<(), SvsmError> {
+    let cfg = FwCfg::new(SVSM_PLATFORM.get_io_port());
+    let Ok(file) = cfg.file_selector(CRASH_DUMP_CONFIG_FILE) else {
+        return Ok(());
+    };
+    if !cfg!(feature = "enable-crashdump") {
+        log::warn!("Crash dumps are not supported by this build, ignoring the configuration");
+        return Ok(());
+    }
+    if file.size() as usize != size_of::<CrashDumpConfig>() {
+        return Err(SvsmError::NotSupported);
+    }
+    cfg.select(file.selector());
+    let config: CrashDumpConfig = cfg.read();
+
+    let target = dump_target(&config)?;
+    if target.size() <= CRASH_DUMP_DATA_OFFSET {
+        return Err(SvsmError::InvalidAddress);
+    }
+
+    let data = MemoryRegion::from_addresses(
+        VirtAddr::from(&raw const data_start),
+        VirtAddr::from(&raw const bss_end),
+    );
+    crash_dump_add_range(data)?;
+
+    log::info!("Crash dumps enabled: {target:?}");
+    *CRASH_DUMP.lock() = Some(CrashDump {
+        target,
+        page: Box::new([0; PAGE_SIZE]),
+        notes: vec![0; NOTES_SIZE].into_boxed_slice(),
+    });
+    Ok(())
+}
+
+/// Includes a range of kernel memory in crash dumps. The range must stay
+/// mapped for the lifetime of the SVSM.
+pub fn crash_dump_add_range(region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
+    let mut ranges = DUMP_RANGES.lock();
+    let slot = ranges
+        .iter_mut()
+        .find(|r| r.is_none())
+        .ok_or(SvsmError::Mem)?;
+    *slot = Some(region);
+    Ok(())
+}
+
+/// Returns the stacks of the current CPU, which are included in every dump.
+fn cpu_stacks() -> [Option<MemoryRegion<VirtAddr>>; 3] {
+    let Some(cpu) = try_this_cpu() else {
+        return [None; 3];
+    };
+    let top_of_cs_stack = cpu.get_top_of_context_switch_stack();
+    let top_of_df_stack = cpu.get_top_of_df_stack();
+    [
+        Some(cpu.get_current_stack()),
+        Some(MemoryRegion::from_addresses(
+            top_of_cs_stack - STACK_SIZE,
+            top_of_cs_stack,
+        )),
+        Some(MemoryRegion::from_addresses(
+            top_of_df_stack - STACK_SIZE,
+            top_of_df_stack,
+        )),
+    ]
+}
+
+/// Writes a crash dump to the configured target. Only the first panicking
+/// CPU writes a dump. Called from the panic handler.
+pub fn write_crash_dump(info: &PanicInfo<'_>) {
+    let regs = capture_regs();
+
+    if DUMP_STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let Some(mut guard) = CRASH_DUMP.try_lock() else {
+        return;
+    };
+    let Some(dump) = guard.as_mut() else {
+        return;
+    };
+
+    let mut ranges = [MemoryRegion::new(VirtAddr::null(), 0); MAX_CORE_RANGES];
+    let mut nr_ranges = 0;
+    let registered = DUMP_RANGES.try_lock().map(|r| *r).unwrap_or_default();
+    for range in cpu_stacks().into_iter().chain(registered).flatten() {
+        // Skip ranges which are already part of the dump, like the boot
+        // stack which is in the BSS section.
+        if !ranges[..nr_ranges]
+            .iter()
+            .any(|r| r.contains_region(&range))
+        {
+            ranges[nr_ranges] = range;
+            nr_ranges += 1;
+        }
+    }
+
+    let mut notes = NoteBuffer::new(&mut dump.notes);
+    build_notes(&mut notes, info, &regs);
+
+    log::error!("Writing crash dump");
+    let mut writer = CoreWriter::new(&mut *dump.target, &mut dump.page);
+    let result = write_core(&mut writer, notes.as_bytes(), &ranges[..nr_ranges])
+        .and_then(|_| writer.finish());
+    match result {
+        Ok(()) => log::error!("Crash dump complete"),
+        Err(e) => log::error!("Failed to write crash dump: {e:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use zerocopy::FromBytes;
+
+    #[derive(Debug)]
+    struct VecTarget(Vec<u8>);
+
+    impl DumpTarget for VecTarget {
+        fn size(&self) -> usize {
+            self.0.len()
+        }
+
+        fn write_page(&mut self, offset: usize, data: &[u8; PAGE_SIZE]) -> Result<(), SvsmError> {
+            self.0[offset..offset + PAGE_SIZE].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    fn read_u32(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_crash_dump_core() {
+        let memory: Vec<u8> = (0..PAGE_SIZE + 100).map(|i| i as u8).collect();
+        let range = MemoryRegion::new(VirtAddr::from(memory.as_ptr()), memory.len());
+
+        let mut note_buf = [0u8; 256];
+        let mut notes = NoteBuffer::new(&mut note_buf);
+        notes.add("SVSM", NT_SVSM_PANIC, b"panic");
+        assert_eq!(notes.as_bytes().len(), 12 + 8 + 8);
+
+        let mut target = VecTarget(vec![0; 4 * PAGE_SIZE + CRASH_DUMP_DATA_OFFSET]);
+        let mut page = [0u8; PAGE_SIZE];
+        let mut w = CoreWriter::new(&mut target, &mut page);
+        write_core(&mut w, notes.as_bytes(), &[range]).unwrap();
+        w.finish().unwrap();
+
+        let dump = &target.0;
+        let header = CrashDumpHeader::read_from_prefix(dump).unwrap().0;
+        assert_eq!(header.magic, CRASH_DUMP_MAGIC);
+        assert_eq!(header.flags, 0);
+        let size = header.size as usize;
+        assert_eq!(size, PAGE_SIZE + memory.len());
+
+        let core = &dump[CRASH_DUMP_DATA_OFFSET..CRASH_DUMP_DATA_OFFSET + size];
+        assert_eq!(
+            header.checksum,
+            crash_dump_checksum(CRASH_DUMP_CHECKSUM_INIT, core)
+        );
+        assert_eq!(&core[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([core[16], core[17]]), ET_CORE);
+        assert_eq!(u16::from_le_bytes([core[56], core[57]]), 2);
+
+        // Note segment followed by the memory range at the next page
+        let phdr = &core[64..];
+        assert_eq!(read_u32(phdr, 0), PT_NOTE);
+        assert_eq!(read_u32(phdr, 56), PT_LOAD);
+        assert_eq!(read_u32(core, 64 + 2 * 56 + 8), NT_SVSM_PANIC);
+        assert_eq!(&core[PAGE_SIZE..], &memory[..]);
+    }
+
+    #[test]
+    fn test_crash_dump_truncated() {
+        let memory = vec![0xaau8; 2 * PAGE_SIZE];
+        let range = MemoryRegion::new(VirtAddr::from(memory.as_ptr()), memory.len());
+
+        let mut target = VecTarget(vec![0; 2 * PAGE_SIZE + CRASH_DUMP_DATA_OFFSET]);
+        let mut page = [0u8; PAGE_SIZE];
+        let mut w = CoreWriter::new(&mut target, &mut page);
+        write_core(&mut w, &[], &[range]).unwrap();
+        w.finish().unwrap();
+
+        let header = CrashDumpHeader::read_from_prefix(&target.0).unwrap().0;
+        assert_eq!(header.flags, CRASH_DUMP_FLAG_TRUNCATED);
+        assert_eq!(header.size as usize, 2 * PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_note_buffer() {
+        let mut buf = [0u8; 32];
+        let mut notes = NoteBuffer::new(&mut buf);
+        assert!(notes.begin("CORE", NT_PRSTATUS));
+        assert!(notes.push(&[1, 2, 3]));
+        notes.end();
+        assert_eq!(notes.len, 24);
+        assert_eq!(read_u32(notes.as_bytes(), 0), 5);
+        assert_eq!(read_u32(notes.as_bytes(), 4), 3);
+        // Does not fit anymore
+        assert!(!notes.begin("SVSM", NT_SVSM_CPUS));
+        assert_eq!(notes.len, 24);
+    }
+}
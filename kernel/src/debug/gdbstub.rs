@@ -11,20 +11,31 @@
 //
 #[cfg(feature = "enable-gdb")]
 pub mod svsm_gdbstub {
+    extern crate alloc;
+
     use crate::address::{Address, VirtAddr};
     use crate::cpu::control_regs::read_cr3;
+    use crate::cpu::debug_regs::{
+        set_debug_regs, sync_debug_regs, take_triggered_breakpoints, DebugBreakpoint,
+        DebugBreakpointKind, DebugRegs,
+    };
     use crate::cpu::idt::common::{X86ExceptionContext, BP_VECTOR, DB_VECTOR, VC_VECTOR};
+    use crate::cpu::ipi::ipi_available;
     use crate::cpu::percpu::this_cpu;
-    use crate::cpu::X86GeneralRegs;
+    use crate::cpu::{TprGuard, X86GeneralRegs};
     use crate::error::SvsmError;
     use crate::locking::{LockGuard, SpinLock};
-    use crate::mm::guestmem::{read_u8, write_u8};
-    use crate::mm::PerCPUPageMappingGuard;
+    use crate::mm::guestmem::{copy_from_user, copy_to_user, read_u8, write_u8};
+    use crate::mm::{PerCPUPageMappingGuard, USER_MEM_END};
     use crate::platform::SvsmPlatform;
     use crate::serial::{SerialPort, Terminal};
-    use crate::task::{is_current_task, TaskContext, INITIAL_TASK_ID, TASKLIST};
+    use crate::task::{is_current_task, TaskContext, TaskModule, INITIAL_TASK_ID, TASKLIST};
+    use crate::types::TPR_IPI;
+    use alloc::string::String;
+    use alloc::vec::Vec;
     use core::arch::asm;
     use core::fmt;
+    use core::fmt::Write;
     use core::sync::atomic::{AtomicBool, Ordering};
     use gdbstub::common::{Signal, Tid};
     use gdbstub::conn::Connection;
@@ -35,13 +46,18 @@ pub mod svsm_gdbstub {
         MultiThreadSingleStepOps,
     };
     use gdbstub::target::ext::base::BaseOps;
-    use gdbstub::target::ext::breakpoints::{Breakpoints, SwBreakpoint};
+    use gdbstub::target::ext::breakpoints::{
+        Breakpoints, HwBreakpoint, HwWatchpoint, SwBreakpoint, WatchKind,
+    };
+    use gdbstub::target::ext::libraries::LibrariesSvr4;
     use gdbstub::target::ext::thread_extra_info::ThreadExtraInfo;
     use gdbstub::target::{Target, TargetError};
     use gdbstub_arch::x86::reg::X86_64CoreRegs;
     use gdbstub_arch::x86::X86_64_SSE;
 
     const INT3_INSTR: u8 = 0xcc;
+    const RFLAGS_TF: u64 = 1 << 8;
+    const RFLAGS_RF: u64 = 1 << 16;
     const MAX_BREAKPOINTS: usize = 32;
 
     // The static mutable reference to the stack is protected by the GDB_STATE lock.
@@ -84,28 +100,11 @@ pub mod svsm_gdbstub {
     pub fn handle_debug_exception(ctx: &mut X86ExceptionContext, exception: usize) {
         let exception_type = ExceptionType::from(exception);
         let id = this_cpu().runqueue().lock_read().current_task_id();
-        let mut task_ctx = TaskContext {
-            regs: X86GeneralRegs {
-                r15: ctx.regs.r15,
-                r14: ctx.regs.r14,
-                r13: ctx.regs.r13,
-                r12: ctx.regs.r12,
-                r11: ctx.regs.r11,
-                r10: ctx.regs.r10,
-                r9: ctx.regs.r9,
-                r8: ctx.regs.r8,
-                rbp: ctx.regs.rbp,
-                rdi: ctx.regs.rdi,
-                rsi: ctx.regs.rsi,
-                rdx: ctx.regs.rdx,
-                rcx: ctx.regs.rcx,
-                rbx: ctx.regs.rbx,
-                rax: ctx.regs.rax,
-            },
-            rsp: ctx.frame.rsp as u64,
-            flags: ctx.frame.flags as u64,
-            ret_addr: ctx.frame.rip as u64,
-        };
+        let mut task_ctx = task_context_from(ctx);
+
+        // Catch up with breakpoint changes which have not reached this CPU
+        // yet.
+        sync_debug_regs();
 
         // Locking the GDB state for the duration of the stop will cause any other
         // APs that hit a breakpoint to busy-wait until the current CPU releases
@@ -115,7 +114,17 @@ pub mod svsm_gdbstub {
         // the GDB handling code itself then this will cause a re-entrant state
         // within the same CPU causing a deadlock.
         loop {
-            let mut gdb_state = GDB_STATE.lock();
+            let Some(mut gdb_state) = GDB_STATE.try_lock() else {
+                // The CPU holding the GDB state might send an IPI to update
+                // the debug registers. Interrupts are disabled here, so poll
+                // for IPIs while waiting to avoid a deadlock.
+                if ipi_available() {
+                    let _tpr_guard = TprGuard::raise(TPR_IPI);
+                    this_cpu().handle_ipi_interrupt();
+                }
+                core::hint::spin_loop();
+                continue;
+            };
             if let Some(stub) = gdb_state.as_ref() {
                 if stub.target.is_single_step != 0 && stub.target.is_single_step != id {
                     continue;
@@ -141,29 +150,110 @@ pub mod svsm_gdbstub {
                     options(att_syntax));
             }
 
-            ctx.frame.rip = task_ctx.ret_addr as usize;
-            ctx.frame.flags = task_ctx.flags as usize;
-            ctx.frame.rsp = task_ctx.rsp as usize;
-            ctx.regs.rax = task_ctx.regs.rax;
-            ctx.regs.rbx = task_ctx.regs.rbx;
-            ctx.regs.rcx = task_ctx.regs.rcx;
-            ctx.regs.rdx = task_ctx.regs.rdx;
-            ctx.regs.rsi = task_ctx.regs.rsi;
-            ctx.regs.rdi = task_ctx.regs.rdi;
-            ctx.regs.rbp = task_ctx.regs.rbp;
-            ctx.regs.r8 = task_ctx.regs.r8;
-            ctx.regs.r9 = task_ctx.regs.r9;
-            ctx.regs.r10 = task_ctx.regs.r10;
-            ctx.regs.r11 = task_ctx.regs.r11;
-            ctx.regs.r12 = task_ctx.regs.r12;
-            ctx.regs.r13 = task_ctx.regs.r13;
-            ctx.regs.r14 = task_ctx.regs.r14;
-            ctx.regs.r15 = task_ctx.regs.r15;
+            update_exception_context(ctx, &task_ctx);
 
             break;
         }
     }
 
+    /// Builds the register state reported to GDB from an exception context.
+    fn task_context_from(ctx: &X86ExceptionContext) -> TaskContext {
+        TaskContext {
+            regs: X86GeneralRegs {
+                r15: ctx.regs.r15,
+                r14: ctx.regs.r14,
+                r13: ctx.regs.r13,
+                r12: ctx.regs.r12,
+                r11: ctx.regs.r11,
+                r10: ctx.regs.r10,
+                r9: ctx.regs.r9,
+                r8: ctx.regs.r8,
+                rbp: ctx.regs.rbp,
+                rdi: ctx.regs.rdi,
+                rsi: ctx.regs.rsi,
+                rdx: ctx.regs.rdx,
+                rcx: ctx.regs.rcx,
+                rbx: ctx.regs.rbx,
+                rax: ctx.regs.rax,
+            },
+            rsp: ctx.frame.rsp as u64,
+            flags: ctx.frame.flags as u64,
+            ret_addr: ctx.frame.rip as u64,
+        }
+    }
+
+    /// Writes register changes made by GDB back to an exception context.
+    fn update_exception_context(ctx: &mut X86ExceptionContext, task_ctx: &TaskContext) {
+        ctx.frame.rip = task_ctx.ret_addr as usize;
+        ctx.frame.flags = task_ctx.flags as usize;
+        ctx.frame.rsp = task_ctx.rsp as usize;
+        ctx.regs.rax = task_ctx.regs.rax;
+        ctx.regs.rbx = task_ctx.regs.rbx;
+        ctx.regs.rcx = task_ctx.regs.rcx;
+        ctx.regs.rdx = task_ctx.regs.rdx;
+        ctx.regs.rsi = task_ctx.regs.rsi;
+        ctx.regs.rdi = task_ctx.regs.rdi;
+        ctx.regs.rbp = task_ctx.regs.rbp;
+        ctx.regs.r8 = task_ctx.regs.r8;
+        ctx.regs.r9 = task_ctx.regs.r9;
+        ctx.regs.r10 = task_ctx.regs.r10;
+        ctx.regs.r11 = task_ctx.regs.r11;
+        ctx.regs.r12 = task_ctx.regs.r12;
+        ctx.regs.r13 = task_ctx.regs.r13;
+        ctx.regs.r14 = task_ctx.regs.r14;
+        ctx.regs.r15 = task_ctx.regs.r15;
+    }
+
+    /// Applies registers written by GDB to a task context.
+    fn set_task_context(context: &mut TaskContext, regs: &X86_64CoreRegs) {
+        context.ret_addr = regs.rip;
+        context.regs.rax = regs.regs[0] as usize;
+        context.regs.rbx = regs.regs[1] as usize;
+        context.regs.rcx = regs.regs[2] as usize;
+        context.regs.rdx = regs.regs[3] as usize;
+        context.regs.rsi = regs.regs[4] as usize;
+        context.regs.rdi = regs.regs[5] as usize;
+        context.regs.rbp = regs.regs[6] as usize;
+        context.rsp = regs.regs[7];
+        context.regs.r8 = regs.regs[8] as usize;
+        context.regs.r9 = regs.regs[9] as usize;
+        context.regs.r10 = regs.regs[10] as usize;
+        context.regs.r11 = regs.regs[11] as usize;
+        context.regs.r12 = regs.regs[12] as usize;
+        context.regs.r13 = regs.regs[13] as usize;
+        context.regs.r14 = regs.regs[14] as usize;
+        context.regs.r15 = regs.regs[15] as usize;
+        context.flags = regs.eflags as u64;
+    }
+
+    /// Builds the `qXfer:libraries-svr4:read` document listing the ELF
+    /// modules of all user processes, so that GDB can load their symbols.
+    fn libraries_svr4_xml(modules: &[TaskModule]) -> String {
+        let mut xml = String::from("<library-list-svr4 version=\"1.0\">");
+        for (index, module) in modules.iter().enumerate() {
+            xml.push_str("<library name=\"");
+            for c in module.path.chars() {
+                match c {
+                    '&' => xml.push_str("&amp;"),
+                    '<' => xml.push_str("&lt;"),
+                    '>' => xml.push_str("&gt;"),
+                    '"' => xml.push_str("&quot;"),
+                    c => xml.push(c),
+                }
+            }
+            // There are no link maps and dynamic sections, but GDB uses the
+            // link map address to tell the libraries apart.
+            let _ = write!(
+                xml,
+                "\" lm=\"{:#x}\" l_addr=\"{:#x}\" l_ld=\"0x0\"/>",
+                index + 1,
+                module.load_bias
+            );
+        }
+        xml.push_str("</library-list-svr4>");
+        xml
+    }
+
     pub fn debug_break() {
         if GDB_INITIALISED.load(Ordering::Acquire) {
             log::info!("***********************************");
@@ -240,20 +330,33 @@ pub mod svsm_gdbstub {
         let SvsmGdbStub { gdb, mut target } = gdb_state.take().expect("Invalid GDB state");
 
         target.set_regs(ctx);
+        let hw_breakpoints = target.hw_breakpoints;
+
+        // Find the hardware breakpoint which caused the debug exception, if
+        // any.
+        let hw_bp = if exception_type == ExceptionType::Debug {
+            take_triggered_breakpoints().find_map(|i| hw_breakpoints.get(i))
+        } else {
+            None
+        };
+
+        let tid = Tid::new(this_cpu().runqueue().lock_read().current_task_id() as usize)
+            .expect("Current task has invalid ID");
+        // Memory accesses without a thread, like breakpoint changes, go to
+        // the stopped task until GDB selects another one.
+        target.selected_task = tid.get() as u32;
 
         let hardcoded_bp = (exception_type == ExceptionType::SwBreakpoint)
-            && !target.is_breakpoint(ctx.ret_addr as usize - 1);
+            && !target.is_breakpoint(tid.get() as u32, ctx.ret_addr as usize - 1);
 
         // If the current address is on a breakpoint then we need to
         // move the IP back by one byte
         if (exception_type == ExceptionType::SwBreakpoint)
-            && target.is_breakpoint(ctx.ret_addr as usize - 1)
+            && target.is_breakpoint(tid.get() as u32, ctx.ret_addr as usize - 1)
         {
             ctx.ret_addr -= 1;
         }
 
-        let tid = Tid::new(this_cpu().runqueue().lock_read().current_task_id() as usize)
-            .expect("Current task has invalid ID");
         let mut new_gdb = match gdb {
             GdbStubStateMachine::Running(gdb_inner) => {
                 let reason = if hardcoded_bp {
@@ -266,6 +369,27 @@ pub mod svsm_gdbstub {
                         tid,
                         signal: Signal::SIGSEGV,
                     }
+                } else if let Some(bp) = hw_bp {
+                    let addr = bp.addr().bits() as u64;
+                    match bp.kind() {
+                        DebugBreakpointKind::Execute => {
+                            // Instruction breakpoints trigger before the
+                            // instruction executes, so it must be resumed
+                            // with RF set to not trigger again.
+                            ctx.flags |= RFLAGS_RF;
+                            MultiThreadStopReason::HwBreak(tid)
+                        }
+                        DebugBreakpointKind::Write => MultiThreadStopReason::Watch {
+                            tid,
+                            kind: WatchKind::Write,
+                            addr,
+                        },
+                        DebugBreakpointKind::Access => MultiThreadStopReason::Watch {
+                            tid,
+                            kind: WatchKind::ReadWrite,
+                            addr,
+                        },
+                    }
                 } else {
                     MultiThreadStopReason::SwBreak(tid)
                 };
@@ -300,9 +424,15 @@ pub mod svsm_gdbstub {
             };
         }
         if target.is_single_step == tid.get() as u32 {
-            ctx.flags |= 0x100;
+            ctx.flags |= RFLAGS_TF;
         } else {
-            ctx.flags &= !0x100;
+            ctx.flags &= !RFLAGS_TF;
+        }
+
+        // Program breakpoint changes made by GDB on all CPUs. CPUs waiting
+        // for the GDB state poll for the IPI.
+        if target.hw_breakpoints != hw_breakpoints {
+            set_debug_regs(&target.hw_breakpoints);
         }
         **gdb_state = Some(SvsmGdbStub {
             gdb: new_gdb,
@@ -343,12 +473,15 @@ pub mod svsm_gdbstub {
     struct GdbStubBreakpoint {
         addr: VirtAddr,
         inst: u8,
+        task: u32,
     }
 
     struct GdbStubTarget {
         ctx: *mut TaskContext,
         breakpoints: [GdbStubBreakpoint; MAX_BREAKPOINTS],
+        hw_breakpoints: DebugRegs,
         is_single_step: u32,
+        selected_task: u32,
     }
 
     // SAFETY: this can only be unsafe via aliasing of the ctx field,
@@ -365,8 +498,11 @@ pub mod svsm_gdbstub {
                 breakpoints: [GdbStubBreakpoint {
                     addr: VirtAddr::null(),
                     inst: 0,
+                    task: 0,
                 }; MAX_BREAKPOINTS],
+                hw_breakpoints: DebugRegs::new(),
                 is_single_step: 0,
+                selected_task: INITIAL_TASK_ID,
             }
         }
 
@@ -390,16 +526,40 @@ pub mod svsm_gdbstub {
             self.ctx = core::ptr::from_mut(ctx)
         }
 
-        fn is_breakpoint(&self, rip: usize) -> bool {
-            self.breakpoints.iter().any(|b| b.addr.bits() == rip)
+        fn is_breakpoint(&self, task: u32, rip: usize) -> bool {
+            // User-mode breakpoints only exist in the address space of the
+            // task they were set in.
+            self.breakpoints
+                .iter()
+                .any(|b| b.addr.bits() == rip && (b.addr >= USER_MEM_END || b.task == task))
+        }
+
+        fn read_bp_address(addr: VirtAddr) -> Result<u8, SvsmError> {
+            if addr < USER_MEM_END {
+                let mut inst = [0u8; 1];
+                copy_from_user(addr, &mut inst)?;
+                return Ok(inst[0]);
+            }
+            read_u8(addr)
         }
 
-        fn write_bp_address(addr: VirtAddr, value: u8) -> Result<(), SvsmError> {
+        fn write_bp_address(task: u32, addr: VirtAddr, value: u8) -> Result<(), SvsmError> {
             // Virtual addresses in code are likely to be in read-only memory. If we
             // can get the physical address for this VA then create a temporary
-            // mapping
+            // mapping. User-mode addresses are looked up in the page table of
+            // the task.
+            let phys = if addr < USER_MEM_END {
+                let task = TASKLIST
+                    .lock()
+                    .get_task(task)
+                    .ok_or(SvsmError::InvalidAddress)?;
+                let phys = task.page_table.lock().phys_addr(addr);
+                phys
+            } else {
+                this_cpu().get_pgtable().phys_addr(addr)
+            };
 
-            let Ok(phys) = this_cpu().get_pgtable().phys_addr(addr) else {
+            let Ok(phys) = phys else {
                 // The virtual address is not one that SVSM has mapped.
                 // Try safely writing it to the original virtual address
                 // SAFETY: it is up to the user to ensure that the address we
@@ -434,6 +594,46 @@ pub mod svsm_gdbstub {
         ) -> Option<gdbstub::target::ext::breakpoints::BreakpointsOps<'_, Self>> {
             Some(self)
         }
+
+        #[inline(always)]
+        fn support_libraries_svr4(
+            &mut self,
+        ) -> Option<gdbstub::target::ext::libraries::LibrariesSvr4Ops<'_, Self>> {
+            Some(self)
+        }
+    }
+
+    impl LibrariesSvr4 for GdbStubTarget {
+        fn get_libraries_svr4(
+            &self,
+            offset: u64,
+            length: usize,
+            buf: &mut [u8],
+        ) -> gdbstub::target::TargetResult<usize, Self> {
+            let mut modules: Vec<TaskModule> = Vec::new();
+            let mut tl = TASKLIST.lock();
+            let mut cursor = tl.list().front_mut();
+            while let Some(task) = cursor.get() {
+                // Threads of a process share the module list.
+                for module in task.modules() {
+                    if !modules.contains(&module) {
+                        modules.push(module);
+                    }
+                }
+                cursor.move_next();
+            }
+            drop(tl);
+
+            let xml = libraries_svr4_xml(&modules);
+            let data = xml.as_bytes();
+            let start = usize::try_from(offset)
+                .unwrap_or(usize::MAX)
+                .min(data.len());
+            let data = &data[start..];
+            let len = data.len().min(length).min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            Ok(len)
+        }
     }
 
     impl From<&TaskContext> for X86_64CoreRegs {
@@ -469,6 +669,7 @@ pub mod svsm_gdbstub {
             regs: &mut <Self::Arch as gdbstub::arch::Arch>::Registers,
             tid: Tid,
         ) -> gdbstub::target::TargetResult<(), Self> {
+            self.selected_task = tid.get() as u32;
             if is_current_task(tid.get() as u32) {
                 *regs = X86_64CoreRegs::from(self.ctx().unwrap());
             } else {
@@ -476,11 +677,30 @@ pub mod svsm_gdbstub {
                 if let Some(task) = task {
                     // The registers are stored in the top of the task stack as part of the
                     // saved context. We need to switch to the task pagetable to access them.
+                    // Tasks running on other CPUs have no saved context.
                     let _task_context = GdbTaskContext::switch_to_task(tid.get() as u32);
-                    unsafe {
-                        *regs = X86_64CoreRegs::from(&*(task.rsp as *const TaskContext));
-                    };
-                    regs.regs[7] = task.rsp;
+                    *regs = task
+                        .with_saved_context(|| {
+                            if let Some(addr) = task.user_context_addr() {
+                                // Report user tasks with their user-mode
+                                // registers, which were saved on the last
+                                // entry to the kernel.
+                                // SAFETY: the user register frame is at the
+                                // top of the task stack, which is mapped in
+                                // the task page table.
+                                let frame = unsafe { addr.as_ptr::<X86ExceptionContext>().read() };
+                                X86_64CoreRegs::from(&task_context_from(&frame))
+                            } else {
+                                // SAFETY: the task is switched out, so its
+                                // saved context is at its stack pointer.
+                                let mut regs = unsafe {
+                                    X86_64CoreRegs::from(&*(task.rsp as *const TaskContext))
+                                };
+                                regs.regs[7] = task.rsp;
+                                regs
+                            }
+                        })
+                        .ok_or(TargetError::NonFatal)?;
                 } else {
                     *regs = <Self::Arch as gdbstub::arch::Arch>::Registers::default();
                 }
@@ -493,31 +713,36 @@ pub mod svsm_gdbstub {
             regs: &<Self::Arch as gdbstub::arch::Arch>::Registers,
             tid: Tid,
         ) -> gdbstub::target::TargetResult<(), Self> {
-            if !is_current_task(tid.get() as u32) {
-                return Err(TargetError::NonFatal);
+            self.selected_task = tid.get() as u32;
+            if is_current_task(tid.get() as u32) {
+                set_task_context(self.ctx_mut().unwrap(), regs);
+                return Ok(());
             }
 
-            let context = self.ctx_mut().unwrap();
-
-            context.ret_addr = regs.rip;
-            context.regs.rax = regs.regs[0] as usize;
-            context.regs.rbx = regs.regs[1] as usize;
-            context.regs.rcx = regs.regs[2] as usize;
-            context.regs.rdx = regs.regs[3] as usize;
-            context.regs.rsi = regs.regs[4] as usize;
-            context.regs.rdi = regs.regs[5] as usize;
-            context.regs.rbp = regs.regs[6] as usize;
-            context.rsp = regs.regs[7];
-            context.regs.r8 = regs.regs[8] as usize;
-            context.regs.r9 = regs.regs[9] as usize;
-            context.regs.r10 = regs.regs[10] as usize;
-            context.regs.r11 = regs.regs[11] as usize;
-            context.regs.r12 = regs.regs[12] as usize;
-            context.regs.r13 = regs.regs[13] as usize;
-            context.regs.r14 = regs.regs[14] as usize;
-            context.regs.r15 = regs.regs[15] as usize;
-            context.flags = regs.eflags as u64;
-            Ok(())
+            // Only the user-mode registers of other tasks can be changed,
+            // and only while they are not running on another CPU.
+            let task = TASKLIST.lock().get_task(tid.get() as u32);
+            let Some(task) = task else {
+                return Err(TargetError::NonFatal);
+            };
+            let Some(addr) = task.user_context_addr() else {
+                return Err(TargetError::NonFatal);
+            };
+            let _task_context = GdbTaskContext::switch_to_task(tid.get() as u32);
+            let frame_ptr = addr.as_mut_ptr::<X86ExceptionContext>();
+            task.with_saved_context(|| {
+                // SAFETY: the user register frame is at the top of the task
+                // stack, which is mapped in the task page table. The task
+                // can not run while its saved context is accessed.
+                unsafe {
+                    let mut frame = frame_ptr.read();
+                    let mut context = task_context_from(&frame);
+                    set_task_context(&mut context, regs);
+                    update_exception_context(&mut frame, &context);
+                    frame_ptr.write(frame);
+                }
+            })
+            .ok_or(TargetError::NonFatal)
         }
 
         fn read_addrs(
@@ -526,10 +751,14 @@ pub mod svsm_gdbstub {
             data: &mut [u8],
             tid: Tid,
         ) -> gdbstub::target::TargetResult<(), Self> {
+            self.selected_task = tid.get() as u32;
             // Switch to the task pagetable if necessary. The switch back will
             // happen automatically when the variable falls out of scope
             let _task_context = GdbTaskContext::switch_to_task(tid.get() as u32);
             let start_addr = VirtAddr::from(start_addr);
+            if start_addr < USER_MEM_END {
+                return copy_from_user(start_addr, data).map_err(|_| TargetError::NonFatal);
+            }
             for (off, dst) in data.iter_mut().enumerate() {
                 let Ok(val) = read_u8(start_addr + off) else {
                     return Err(TargetError::NonFatal);
@@ -543,9 +772,14 @@ pub mod svsm_gdbstub {
             &mut self,
             start_addr: <Self::Arch as gdbstub::arch::Arch>::Usize,
             data: &[u8],
-            _tid: Tid,
+            tid: Tid,
         ) -> gdbstub::target::TargetResult<(), Self> {
+            self.selected_task = tid.get() as u32;
+            let _task_context = GdbTaskContext::switch_to_task(tid.get() as u32);
             let start_addr = VirtAddr::from(start_addr);
+            if start_addr < USER_MEM_END {
+                return copy_to_user(data, start_addr).map_err(|_| TargetError::NonFatal);
+            }
             for (off, src) in data.iter().enumerate() {
                 let dst = start_addr.checked_add(off).ok_or(TargetError::NonFatal)?;
 
@@ -667,14 +901,14 @@ pub mod svsm_gdbstub {
         fn support_hw_breakpoint(
             &mut self,
         ) -> Option<gdbstub::target::ext::breakpoints::HwBreakpointOps<'_, Self>> {
-            None
+            Some(self)
         }
 
         #[inline(always)]
         fn support_hw_watchpoint(
             &mut self,
         ) -> Option<gdbstub::target::ext::breakpoints::HwWatchpointOps<'_, Self>> {
-            None
+            Some(self)
         }
     }
 
@@ -689,15 +923,22 @@ pub mod svsm_gdbstub {
                 return Ok(false);
             };
             // The breakpoint works by taking the opcode at the bp address, storing
-            // it and replacing it with an INT3 instruction
+            // it and replacing it with an INT3 instruction. User-mode addresses
+            // refer to the address space of the task selected in GDB.
+            let task = self.selected_task;
+            let _task_context = GdbTaskContext::switch_to_task(task);
             let vaddr = VirtAddr::from(addr);
-            let Ok(inst) = read_u8(vaddr) else {
+            let Ok(inst) = GdbStubTarget::read_bp_address(vaddr) else {
                 return Ok(false);
             };
-            let Ok(_) = GdbStubTarget::write_bp_address(vaddr, INT3_INSTR) else {
+            let Ok(_) = GdbStubTarget::write_bp_address(task, vaddr, INT3_INSTR) else {
                 return Ok(false);
             };
-            *free_bp = GdbStubBreakpoint { addr: vaddr, inst };
+            *free_bp = GdbStubBreakpoint {
+                addr: vaddr,
+                inst,
+                task,
+            };
             Ok(true)
         }
 
@@ -707,10 +948,16 @@ pub mod svsm_gdbstub {
             _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
         ) -> gdbstub::target::TargetResult<bool, Self> {
             let vaddr = VirtAddr::from(addr);
-            let Some(bp) = self.breakpoints.iter_mut().find(|b| b.addr == vaddr) else {
+            let task = self.selected_task;
+            let Some(bp) = self
+                .breakpoints
+                .iter_mut()
+                .find(|b| b.addr == vaddr && (vaddr >= USER_MEM_END || b.task == task))
+            else {
                 return Ok(false);
             };
-            let Ok(_) = GdbStubTarget::write_bp_address(vaddr, bp.inst) else {
+            let _task_context = GdbTaskContext::switch_to_task(bp.task);
+            let Ok(_) = GdbStubTarget::write_bp_address(bp.task, vaddr, bp.inst) else {
                 return Ok(false);
             };
             bp.addr = VirtAddr::null();
@@ -718,12 +965,78 @@ pub mod svsm_gdbstub {
         }
     }
 
+    impl HwBreakpoint for GdbStubTarget {
+        fn add_hw_breakpoint(
+            &mut self,
+            addr: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+        ) -> gdbstub::target::TargetResult<bool, Self> {
+            let Ok(bp) =
+                DebugBreakpoint::new(VirtAddr::from(addr), DebugBreakpointKind::Execute, 1)
+            else {
+                return Ok(false);
+            };
+            Ok(self.hw_breakpoints.add(bp))
+        }
+
+        fn remove_hw_breakpoint(
+            &mut self,
+            addr: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+        ) -> gdbstub::target::TargetResult<bool, Self> {
+            let Ok(bp) =
+                DebugBreakpoint::new(VirtAddr::from(addr), DebugBreakpointKind::Execute, 1)
+            else {
+                return Ok(false);
+            };
+            Ok(self.hw_breakpoints.remove(&bp))
+        }
+    }
+
+    fn watchpoint(addr: u64, len: u64, kind: WatchKind) -> Option<DebugBreakpoint> {
+        // The debug registers cannot trap on reads only, so read watchpoints
+        // also trigger on writes.
+        let kind = match kind {
+            WatchKind::Write => DebugBreakpointKind::Write,
+            WatchKind::Read | WatchKind::ReadWrite => DebugBreakpointKind::Access,
+        };
+        DebugBreakpoint::new(VirtAddr::from(addr), kind, len as usize).ok()
+    }
+
+    impl HwWatchpoint for GdbStubTarget {
+        fn add_hw_watchpoint(
+            &mut self,
+            addr: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            len: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            kind: WatchKind,
+        ) -> gdbstub::target::TargetResult<bool, Self> {
+            let Some(bp) = watchpoint(addr, len, kind) else {
+                return Ok(false);
+            };
+            Ok(self.hw_breakpoints.add(bp))
+        }
+
+        fn remove_hw_watchpoint(
+            &mut self,
+            addr: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            len: <Self::Arch as gdbstub::arch::Arch>::Usize,
+            kind: WatchKind,
+        ) -> gdbstub::target::TargetResult<bool, Self> {
+            let Some(bp) = watchpoint(addr, len, kind) else {
+                return Ok(false);
+            };
+            Ok(self.hw_breakpoints.remove(&bp))
+        }
+    }
+
     #[cfg(test)]
     pub mod tests {
         extern crate alloc;
 
-        use super::ExceptionType;
+        use super::{libraries_svr4_xml, ExceptionType};
         use crate::cpu::idt::common::{BP_VECTOR, VC_VECTOR};
+        use crate::task::TaskModule;
+        use alloc::string::String;
         use alloc::vec;
         use alloc::vec::Vec;
 
@@ -742,6 +1055,27 @@ pub mod svsm_gdbstub {
                 ]
             );
         }
+
+        #[test]
+        fn libraries_svr4() {
+            let modules = [
+                TaskModule {
+                    path: String::from("/init"),
+                    load_bias: 0,
+                },
+                TaskModule {
+                    path: String::from("/a&b"),
+                    load_bias: 0x10000,
+                },
+            ];
+            assert_eq!(
+                libraries_svr4_xml(&modules),
+                "<library-list-svr4 version=\"1.0\">\
+                 <library name=\"/init\" lm=\"0x1\" l_addr=\"0x0\" l_ld=\"0x0\"/>\
+                 <library name=\"/a&amp;b\" lm=\"0x2\" l_addr=\"0x10000\" l_ld=\"0x0\"/>\
+                 </library-list-svr4>"
+            );
+        }
     }
 }
 
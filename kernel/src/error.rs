@@ -22,6 +22,8 @@ use crate::cpu::vc::VcError;
 use crate::fs::FsError;
 use crate::fw_cfg::FwCfgError;
 use crate::insn_decode::InsnError;
+use crate::ipc::ChannelError;
+use crate::migration::MigrationError;
 use crate::mm::alloc::AllocError;
 use crate::sev::ghcb::GhcbError;
 use crate::sev::msr_protocol::GhcbMsrError;
@@ -31,6 +33,7 @@ use crate::task::TaskError;
 use crate::tdx::TdxError;
 #[cfg(feature = "virtio-drivers")]
 use crate::virtio::VirtioError;
+use crate::vsock::VsockError;
 use elf::ElfError;
 use syscall::SysCallError;
 
@@ -109,6 +112,8 @@ pub enum SvsmError {
     Obj(ObjError),
     /// Task management errors,
     Task(TaskError),
+    /// Errors from IPC channels
+    Channel(ChannelError),
     /// Errors from #VC handler
     Vc(VcError),
     /// The operation is not supported.
@@ -119,11 +124,17 @@ pub enum SvsmError {
     Attestation(AttestError),
     /// Errors related to Hyper-V.
     HyperV(u16),
+    /// Errors from cryptographic operations or the random number generator.
+    Crypto,
+    /// Errors related to live migration.
+    Migration(MigrationError),
     /// Errors related to Virtio drivers.
     #[cfg(feature = "virtio-drivers")]
     Virtio(VirtioError),
     /// Errors related to block devices.
     Block(BlockDeviceError),
+    /// Errors from vsock connections.
+    Vsock(VsockError),
 }
 
 impl From<ElfError> for SvsmError {
@@ -177,6 +188,32 @@ impl From<SvsmError> for SysCallError {
 
             SvsmError::NotSupported => SysCallError::ENOTSUPP,
 
+            SvsmError::Task(TaskError::NotJoinable) => SysCallError::ENOTFOUND,
+            SvsmError::Task(TaskError::NoChild) => SysCallError::ENOTFOUND,
+            SvsmError::Task(TaskError::JoinBusy | TaskError::WaitBusy) => SysCallError::EBUSY,
+            SvsmError::Obj(ObjError::Busy) => SysCallError::EBUSY,
+
+            SvsmError::Channel(
+                ChannelError::QueueFull | ChannelError::RecvBusy | ChannelError::TimedOut,
+            ) => SysCallError::EBUSY,
+            SvsmError::Channel(ChannelError::PeerClosed) => SysCallError::EPIPE,
+            SvsmError::Channel(ChannelError::MessageTooLarge | ChannelError::BufferTooSmall) => {
+                SysCallError::EINVAL
+            }
+
+            SvsmError::Migration(MigrationError::VcpuRunning) => SysCallError::EBUSY,
+            SvsmError::Migration(MigrationError::SessionMismatch | MigrationError::Rollback) => {
+                SysCallError::EPERM
+            }
+            SvsmError::Migration(_) => SysCallError::EINVAL,
+
+            SvsmError::Vsock(VsockError::NoDevice) => SysCallError::ENOTSUPP,
+            SvsmError::Vsock(VsockError::ConnectionRefused) => SysCallError::ENOTFOUND,
+            SvsmError::Vsock(VsockError::NotConnected) => SysCallError::EPIPE,
+            SvsmError::Vsock(VsockError::AddressInUse | VsockError::TimedOut) => {
+                SysCallError::EBUSY
+            }
+
             SvsmError::FileSystem(FsError::Inval)
             | SvsmError::Obj(ObjError::InvalidHandle)
             | SvsmError::Mem
@@ -34,6 +34,7 @@ pub enum FsError {
     NotEmpty,
     IsFile,
     IsDir,
+    BadSignature,
     PackIt(PackItError),
 }
 
@@ -76,6 +77,7 @@ impl FsError {
     impl_fs_err!(not_empty, NotEmpty);
     impl_fs_err!(is_dir, IsDir);
     impl_fs_err!(is_file, IsFile);
+    impl_fs_err!(bad_signature, BadSignature);
 }
 
 /// Represents file operations
@@ -174,6 +176,15 @@ pub trait File: Debug + Send + Sync {
     fn mapping(&self, _offset: usize) -> Option<PageRef> {
         None
     }
+
+    /// Used to check whether the file can be opened for writing.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the file contents can never be modified.
+    fn writable(&self) -> bool {
+        true
+    }
 }
 
 /// Represents directory operations
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Read-only filesystem which serves files directly from the filesystem
+//! archive loaded into memory at boot.
+
+extern crate alloc;
+
+use super::{Buffer, DirEntry, Directory, File, FileName, FsError};
+use crate::address::{Address, PhysAddr, VirtAddr};
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::mm::{PageRef, PAGE_SIZE};
+use crate::utils::align_down;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
+use packit::PackItArchiveDecoder;
+
+/// A read-only file backed by the pages of the filesystem archive
+#[derive(Debug)]
+struct ArchiveFile {
+    /// File contents within the archive mapping
+    data: &'static [u8],
+    /// Physical address of the first byte of the file contents
+    paddr: PhysAddr,
+    /// Copies of the pages which cannot be handed out from the archive,
+    /// indexed by their offset in the file
+    copies: SpinLock<BTreeMap<usize, PageRef>>,
+}
+
+impl ArchiveFile {
+    fn new(data: &'static [u8], paddr: PhysAddr) -> Self {
+        Self {
+            data,
+            paddr,
+            copies: SpinLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl File for ArchiveFile {
+    fn read(&self, buf: &mut [u8], offset: usize) -> Result<usize, SvsmError> {
+        let data = self.data.get(offset..).unwrap_or_default();
+        let size = min(buf.len(), data.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        Ok(size)
+    }
+
+    fn read_buffer(&self, buffer: &mut dyn Buffer, offset: usize) -> Result<usize, SvsmError> {
+        let data = self.data.get(offset..).unwrap_or_default();
+        let size = min(buffer.size(), data.len());
+        buffer.write_buffer(&data[..size], 0)
+    }
+
+    fn write(&self, _buf: &[u8], _offset: usize) -> Result<usize, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn truncate(&self, _size: usize) -> Result<usize, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn mapping(&self, offset: usize) -> Option<PageRef> {
+        if offset >= self.size() {
+            return None;
+        }
+
+        let page_offset = align_down(offset, PAGE_SIZE);
+        let page_end = min(page_offset + PAGE_SIZE, self.size());
+        let page_data = &self.data[page_offset..page_end];
+
+        // Hand out the archive page itself when it only contains data of
+        // this file. Pages shared with other files or archive metadata are
+        // copied so that nothing else leaks into the mapping. The copy is
+        // kept so that all mappings of the file share the same page.
+        if self.paddr.is_page_aligned() && page_data.len() == PAGE_SIZE {
+            let vaddr = VirtAddr::from(page_data.as_ptr());
+            // SAFETY: the archive stays mapped for the lifetime of the SVSM
+            // and its pages are never returned to the page allocator.
+            return Some(unsafe { PageRef::new_static(vaddr, self.paddr + page_offset) });
+        }
+
+        let mut copies = self.copies.lock();
+        if let Some(page) = copies.get(&page_offset) {
+            return Some(page.clone());
+        }
+        let page = PageRef::new().ok()?;
+        page.write(0, page_data);
+        copies.insert(page_offset, page.clone());
+        Some(page)
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+}
+
+/// A read-only directory of the filesystem archive
+#[derive(Debug, Default)]
+pub struct ArchiveDirectory {
+    files: Vec<(FileName, Arc<ArchiveFile>)>,
+    dirs: Vec<(FileName, Arc<ArchiveDirectory>)>,
+}
+
+impl ArchiveDirectory {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the directory tree of a filesystem archive.
+    ///
+    /// # Arguments
+    ///
+    /// - `data`: the archive contents, which must stay mapped for the
+    ///   lifetime of the SVSM.
+    /// - `paddr`: physical address of the first byte of the archive.
+    ///
+    /// # Returns
+    ///
+    /// [`Result<ArchiveDirectory, SvsmError>`]: the root directory of the
+    /// archive on success, [`SvsmError`] if the archive is malformed.
+    pub fn load(data: &'static [u8], paddr: PhysAddr) -> Result<Self, SvsmError> {
+        let mut root = Self::new();
+
+        for file in PackItArchiveDecoder::load(data)? {
+            let file = file?;
+            let contents = file.data();
+            let offset = contents.as_ptr() as usize - data.as_ptr() as usize;
+            let archive_file = ArchiveFile::new(contents, paddr + offset);
+            if !archive_file.paddr.is_page_aligned() {
+                log::warn!("  {} is not page-aligned in the archive", file.name());
+            }
+            root.add_file(file.name(), Arc::new(archive_file))?;
+
+            log::info!("  Loaded {}", file.name());
+        }
+
+        Ok(root)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.files.iter().any(|(n, _)| n == name) || self.dirs.iter().any(|(n, _)| n == name)
+    }
+
+    fn add_file(&mut self, path: &str, file: Arc<ArchiveFile>) -> Result<(), SvsmError> {
+        let Some((dir_name, rest)) = path.split_once('/') else {
+            if path.is_empty() {
+                return Err(SvsmError::FileSystem(FsError::inval()));
+            }
+            if self.contains(path) {
+                return Err(SvsmError::FileSystem(FsError::file_exists()));
+            }
+            self.files.push((FileName::from(path), file));
+            return Ok(());
+        };
+
+        if dir_name.is_empty() {
+            return self.add_file(rest, file);
+        }
+        if self.files.iter().any(|(n, _)| n == dir_name) {
+            return Err(SvsmError::FileSystem(FsError::is_file()));
+        }
+
+        let index = match self.dirs.iter().position(|(n, _)| n == dir_name) {
+            Some(index) => index,
+            None => {
+                self.dirs
+                    .push((FileName::from(dir_name), Arc::new(Self::new())));
+                self.dirs.len() - 1
+            }
+        };
+
+        // The tree is not shared with anyone while it is being built.
+        Arc::get_mut(&mut self.dirs[index].1)
+            .ok_or(SvsmError::FileSystem(FsError::busy()))?
+            .add_file(rest, file)
+    }
+
+    /// Iterate over the sub-directories of this directory.
+    pub fn subdirs(&self) -> impl Iterator<Item = (&FileName, &Arc<ArchiveDirectory>)> {
+        self.dirs.iter().map(|(name, dir)| (name, dir))
+    }
+}
+
+impl Directory for ArchiveDirectory {
+    fn list(&self) -> Vec<FileName> {
+        self.dirs
+            .iter()
+            .map(|(name, _)| name)
+            .chain(self.files.iter().map(|(name, _)| name))
+            .cloned()
+            .collect()
+    }
+
+    fn prepare_remove(&self) -> Result<(), SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn lookup_entry(&self, name: &FileName) -> Result<DirEntry, SvsmError> {
+        if let Some((_, dir)) = self.dirs.iter().find(|(n, _)| n == name) {
+            return Ok(DirEntry::Directory(dir.clone()));
+        }
+        if let Some((_, file)) = self.files.iter().find(|(n, _)| n == name) {
+            return Ok(DirEntry::File(file.clone()));
+        }
+        Err(SvsmError::FileSystem(FsError::file_not_found()))
+    }
+
+    fn create_file(&self, _name: FileName) -> Result<Arc<dyn File>, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn create_directory(&self, _name: FileName) -> Result<Arc<dyn Directory>, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn unlink(&self, _name: &FileName) -> Result<(), SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::alloc::{TestRootMem, DEFAULT_TEST_MEMORY_SIZE};
+
+    static ARCHIVE_DATA: [u8; 16] = *b"0123456789abcdef";
+
+    fn test_file(start: usize, end: usize) -> Arc<ArchiveFile> {
+        Arc::new(ArchiveFile::new(
+            &ARCHIVE_DATA[start..end],
+            PhysAddr::from(0x1000u64 + start as u64),
+        ))
+    }
+
+    #[test]
+    fn test_archive_file_read() {
+        let file = test_file(4, 12);
+        let mut buf = [0u8; 6];
+        assert_eq!(file.read(&mut buf, 0).unwrap(), 6);
+        assert_eq!(&buf, b"456789");
+        assert_eq!(file.read(&mut buf, 5).unwrap(), 3);
+        assert_eq!(&buf[..3], b"9ab");
+        assert_eq!(file.read(&mut buf, 8).unwrap(), 0);
+        assert!(file.write(b"x", 0).is_err());
+        assert!(file.truncate(0).is_err());
+        assert!(!file.writable());
+    }
+
+    #[test]
+    fn test_archive_file_mapping_copy() {
+        let _test_mem = TestRootMem::setup(DEFAULT_TEST_MEMORY_SIZE);
+
+        // The file does not fill a page, so its contents are copied, and
+        // all mappings share the same copy.
+        let file = test_file(4, 12);
+        let page = file.mapping(0).unwrap();
+        let mut buf = [0u8; 9];
+        page.read(0, &mut buf);
+        assert_eq!(&buf, b"456789ab\0");
+        assert_eq!(file.mapping(0).unwrap().phys_addr(), page.phys_addr());
+        assert!(file.mapping(8).is_none());
+    }
+
+    #[test]
+    fn test_archive_directory_tree() {
+        let mut root = ArchiveDirectory::new();
+        root.add_file("init", test_file(0, 4)).unwrap();
+        root.add_file("/bin/hello", test_file(4, 8)).unwrap();
+        root.add_file("bin/world", test_file(8, 16)).unwrap();
+
+        assert!(root.add_file("init", test_file(0, 1)).is_err());
+        assert!(root.add_file("init/file", test_file(0, 1)).is_err());
+        assert!(root.add_file("bin/", test_file(0, 1)).is_err());
+
+        let mut list = root.list();
+        list.sort();
+        assert_eq!(list, ["bin", "init"]);
+        assert!(root
+            .lookup_entry(&FileName::from("init"))
+            .unwrap()
+            .is_file());
+
+        let DirEntry::Directory(bin) = root.lookup_entry(&FileName::from("bin")).unwrap() else {
+            panic!("bin is not a directory");
+        };
+        let mut list = bin.list();
+        list.sort();
+        assert_eq!(list, ["hello", "world"]);
+
+        let DirEntry::File(world) = bin.lookup_entry(&FileName::from("world")).unwrap() else {
+            panic!("world is not a file");
+        };
+        assert_eq!(world.size(), 8);
+
+        assert!(root.create_file(FileName::from("new")).is_err());
+        assert!(root.create_directory(FileName::from("new")).is_err());
+        assert!(bin.unlink(&FileName::from("hello")).is_err());
+    }
+}
@@ -4,6 +4,9 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
+use super::archive::ArchiveDirectory;
+use super::overlay::OverlayDirectory;
+use super::proc::{ProcDirectory, PROC_DIR_NAME};
 use super::ramfs::RamDirectory;
 use super::*;
 
@@ -276,11 +279,16 @@ impl FileHandle {
 #[derive(Debug)]
 struct SvsmFs {
     root: Option<Arc<RamDirectory>>,
+    /// Read-only filesystem archive overlaid by the root directory
+    archive: Option<Arc<ArchiveDirectory>>,
 }
 
 impl SvsmFs {
     const fn new() -> Self {
-        SvsmFs { root: None }
+        SvsmFs {
+            root: None,
+            archive: None,
+        }
     }
 
     /// Used to set the root directory of the SVSM filesystem.
@@ -297,6 +305,7 @@ impl SvsmFs {
     #[cfg(all(any(test, fuzzing), not(test_in_svsm)))]
     fn uninitialize(&mut self) {
         self.root = None;
+        self.archive = None;
     }
 
     /// Used to check if the filesystem is initialized.
@@ -315,7 +324,11 @@ impl SvsmFs {
     /// [`Arc<dyn Directory>`]: root directory of the filesystem.
     fn root_dir(&self) -> Arc<dyn Directory> {
         assert!(self.initialized());
-        self.root.as_ref().unwrap().clone()
+        let root = self.root.as_ref().unwrap().clone();
+        match &self.archive {
+            Some(archive) => Arc::new(OverlayDirectory::new(root, archive.clone())),
+            None => root,
+        }
     }
 }
 
@@ -328,6 +341,57 @@ pub fn initialize_fs() {
     FS_ROOT.lock_write().initialize(&root_dir);
 }
 
+/// Adds the read-only `/proc` directory to the root of the filesystem.
+pub fn mount_proc_fs() -> Result<(), SvsmError> {
+    let fs = FS_ROOT.lock_read();
+    assert!(fs.initialized());
+    let proc_dir = Arc::new(ProcDirectory::new());
+    fs.root
+        .as_ref()
+        .unwrap()
+        .add_entry(FileName::from(PROC_DIR_NAME), DirEntry::Directory(proc_dir))
+}
+
+/// Creates the directories of `archive` below `dir`, so that each archive
+/// directory has a writable counterpart in the RAM filesystem.
+fn create_archive_dirs(
+    dir: &Arc<dyn Directory>,
+    archive: &ArchiveDirectory,
+) -> Result<(), SvsmError> {
+    for (name, subdir) in archive.subdirs() {
+        let upper = match dir.lookup_entry(name) {
+            Ok(DirEntry::Directory(upper)) => upper,
+            Ok(DirEntry::File(_)) => return Err(SvsmError::FileSystem(FsError::is_file())),
+            Err(_) => dir.create_directory(name.clone())?,
+        };
+        create_archive_dirs(&upper, subdir)?;
+    }
+    Ok(())
+}
+
+/// Mounts a read-only filesystem archive below the root directory. The
+/// files of the archive become visible in the filesystem, while new files
+/// are created in the RAM filesystem on top of it.
+///
+/// # Arguments
+///
+/// - `archive`: root directory of the filesystem archive.
+///
+/// # Returns
+///
+/// [`Result<(), SvsmError>`]: A [`Result`] containing the unit value if
+/// successful, [`SvsmError`] otherwise.
+pub(super) fn mount_archive_fs(archive: ArchiveDirectory) -> Result<(), SvsmError> {
+    let mut fs = FS_ROOT.lock_write();
+    assert!(fs.initialized());
+    assert!(fs.archive.is_none());
+
+    let root: Arc<dyn Directory> = fs.root.as_ref().unwrap().clone();
+    create_archive_dirs(&root, &archive)?;
+    fs.archive = Some(Arc::new(archive));
+    Ok(())
+}
+
 #[cfg(any(test, fuzzing))]
 #[cfg_attr(test_in_svsm, derive(Clone, Copy))]
 #[derive(Debug)]
@@ -514,6 +578,9 @@ pub fn open_root(
 
     match dir_entry {
         DirEntry::Directory(_) => Err(SvsmError::FileSystem(FsError::file_not_found())),
+        DirEntry::File(f) if write && !f.writable() => {
+            Err(SvsmError::FileSystem(FsError::read_only()))
+        }
         DirEntry::File(f) => Ok(FileHandle::new(&f, read, write)),
     }
 }
@@ -5,21 +5,136 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use crate::address::{Address, PhysAddr};
+use crate::crypto::digest::{Algorithm, Sha384};
+use crate::crypto::signature::{EcdsaP384, EcdsaP384Trait};
 use crate::error::SvsmError;
-use crate::mm::ptguards::PerCPUPageMappingGuard;
-use packit::PackItArchiveDecoder;
+use crate::mm::pagetable::PTEntryFlags;
+use crate::mm::{map_global_range_4k_private_large, GlobalRangeGuard};
+use crate::tdx::rtmr::rtmr_measure;
+use crate::utils::immut_after_init::ImmutAfterInitCell;
 
-use super::*;
+use super::archive::ArchiveDirectory;
+use super::filesystem::mount_archive_fs;
+use super::FsError;
+use bootlib::fs_archive::{
+    FsPublicKey, FsSignatureHeader, FsSignedData, FS_SIGNATURE_HEADER_SIZE, FS_SIGNATURE_MAGIC,
+};
+use zerocopy::{FromBytes, IntoBytes};
 
 extern crate alloc;
 use alloc::slice;
 
-/// Used to create a SVSM RAM filesystem from a filesystem archive.
+/// Mapping of the filesystem archive. Files of the archive are served
+/// directly from this mapping, so it is kept for the lifetime of the SVSM.
+static FS_ARCHIVE_MAPPING: ImmutAfterInitCell<GlobalRangeGuard> = ImmutAfterInitCell::uninit();
+
+/// RTMR which a signed archive is measured into. RTMR 0 to 2 are used by
+/// the guest firmware and the guest OS.
+const FS_ARCHIVE_RTMR: usize = 3;
+
+/// TCG event type of the archive measurement
+const EV_IPL: u32 = 0xd;
+
+/// Extracts the archive from a filesystem image and checks its signature.
+///
+/// # Arguments
+///
+/// - `image`: the filesystem image, which is either a plain archive or a
+///   signed archive starting with a [`FsSignatureHeader`].
+/// - `public_key`: the key the archive must be signed with, if any.
+/// - `min_version`: the minimum version of a signed archive.
+///
+/// # Returns
+///
+/// [`Result<(&[u8], Option<FsSignedData>), SvsmError>`]: the archive
+/// contained in the image and, if its signature was checked, the signed
+/// data, or [`SvsmError`] if the image is malformed, the signature check
+/// failed or the archive is older than `min_version`.
+fn fs_archive_data(
+    image: &[u8],
+    public_key: Option<FsPublicKey>,
+    min_version: u64,
+) -> Result<(&[u8], Option<FsSignedData>), SvsmError> {
+    let header = FsSignatureHeader::ref_from_prefix(image)
+        .ok()
+        .map(|(header, _)| header)
+        .filter(|header| header.magic == FS_SIGNATURE_MAGIC);
+
+    let Some(header) = header else {
+        if public_key.is_some() {
+            log::error!("FS archive is not signed");
+            return Err(SvsmError::FileSystem(FsError::bad_signature()));
+        }
+        return Ok((image, None));
+    };
+
+    let offset = usize::try_from(header.archive_offset)
+        .ok()
+        .filter(|offset| *offset >= FS_SIGNATURE_HEADER_SIZE);
+    let size = usize::try_from(header.archive_size).ok();
+    let archive = offset
+        .zip(size)
+        .and_then(|(offset, size)| image.get(offset..)?.get(..size))
+        .ok_or(SvsmError::FileSystem(FsError::inval()))?;
+
+    let Some(key) = public_key else {
+        if header.signature.iter().all(|b| *b == 0) {
+            log::info!("FS archive is not signed");
+        } else {
+            log::warn!("No public key configured, FS archive signature not checked");
+        }
+        return Ok((archive, None));
+    };
+
+    let signed = FsSignedData {
+        version: header.version,
+        digest: Sha384::digest(archive)
+            .try_into()
+            .map_err(|_| SvsmError::Crypto)?,
+    };
+    EcdsaP384::verify(&key.0, signed.as_bytes(), &header.signature).map_err(|_| {
+        log::error!("FS archive signature verification failed");
+        SvsmError::FileSystem(FsError::bad_signature())
+    })?;
+
+    // The version is only trustworthy once the signature is verified.
+    let version = signed.version;
+    if version < min_version {
+        log::error!("FS archive version {version} is older than the minimum version {min_version}");
+        return Err(SvsmError::FileSystem(FsError::bad_signature()));
+    }
+    log::info!("FS archive signature verified, version {version}");
+
+    Ok((archive, Some(signed)))
+}
+
+/// Measures the signed data of a verified archive into an RTMR and records
+/// it in the RTMR event log. A signed archive is not part of the launch
+/// measurement, so this makes it visible to a verifier.
+fn measure_fs_archive(signed: &FsSignedData) -> Result<(), SvsmError> {
+    match rtmr_measure(FS_ARCHIVE_RTMR, EV_IPL, signed.as_bytes()) {
+        Ok(()) => {
+            log::info!("FS archive measured into RTMR{FS_ARCHIVE_RTMR}");
+            Ok(())
+        }
+        Err(SvsmError::NotSupported) => {
+            log::warn!("No RTMRs available, FS archive is not measured");
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Used to make the contents of a filesystem archive available in the SVSM
+/// filesystem. The archive is mounted read-only below the RAM filesystem.
 ///
 /// # Arguments
 ///
 /// - `kernel_fs_start`: denotes the physical address at which the archive starts.
 /// - `kernel_fs_end`: denotes the physical address at which the archive ends.
+/// - `public_key`: if present, the archive must carry a valid signature
+///   made with this key.
+/// - `min_version`: the minimum version of a signed archive.
 ///
 /// # Assertion
 ///
@@ -28,7 +143,12 @@ use alloc::slice;
 /// # Returns
 /// [`Result<(), SvsmError>`]: A [`Result`] containing the unit value if successful,
 /// [`SvsmError`] otherwise.
-pub fn populate_ram_fs(kernel_fs_start: u64, kernel_fs_end: u64) -> Result<(), SvsmError> {
+pub fn mount_fs_archive(
+    kernel_fs_start: u64,
+    kernel_fs_end: u64,
+    public_key: Option<FsPublicKey>,
+    min_version: u64,
+) -> Result<(), SvsmError> {
     assert!(kernel_fs_end >= kernel_fs_start);
 
     let pstart = PhysAddr::from(kernel_fs_start);
@@ -36,32 +156,140 @@ pub fn populate_ram_fs(kernel_fs_start: u64, kernel_fs_end: u64) -> Result<(), S
     let size = pend - pstart;
 
     if size == 0 {
+        if public_key.is_some() {
+            log::error!("No FS archive present, but a signed archive is required");
+            return Err(SvsmError::FileSystem(FsError::bad_signature()));
+        }
         return Ok(());
     }
 
-    log::info!("Unpacking FS archive...");
-
-    let guard = PerCPUPageMappingGuard::create(pstart.page_align(), pend.page_align_up(), 0)?;
-    let vstart = guard.virt_addr() + pstart.page_offset();
+    log::info!("Mounting FS archive...");
 
-    // SAFETY: `vstart` is just mapped and the mapping covers the entire `size`
-    let data: &[u8] = unsafe { slice::from_raw_parts(vstart.as_ptr(), size) };
-    let archive = PackItArchiveDecoder::load(data)?;
+    // Map only the pages of the archive, so that no other memory becomes
+    // accessible through the mapping.
+    let map_start = pstart.page_align();
+    let map_end = pend.page_align_up();
+    let guard =
+        map_global_range_4k_private_large(map_start, map_end - map_start, PTEntryFlags::data_ro())?;
+    let vstart = guard.addr() + (pstart - map_start);
 
-    for file in archive {
-        let file = file?;
-        let handle = create_all(file.name())?;
-        handle.truncate(0)?;
-        let written = handle.write(file.data())?;
-        if written != file.data().len() {
-            log::error!("Incomplete data write to {}", file.name());
-            return Err(SvsmError::FileSystem(FsError::inval()));
-        }
+    FS_ARCHIVE_MAPPING
+        .init(guard)
+        .expect("FS archive is already mapped");
 
-        log::info!("  Unpacked {}", file.name());
+    // SAFETY: `vstart` is just mapped and the mapping covers the entire
+    // `size`. The mapping is stored in `FS_ARCHIVE_MAPPING` and never
+    // removed.
+    let image: &'static [u8] = unsafe { slice::from_raw_parts(vstart.as_ptr(), size) };
+    let (data, signed) = fs_archive_data(image, public_key, min_version)?;
+    if let Some(signed) = signed {
+        measure_fs_archive(&signed)?;
     }
+    let archive_start = pstart + (data.as_ptr() as usize - image.as_ptr() as usize);
+    let archive = ArchiveDirectory::load(data, archive_start)?;
+
+    mount_archive_fs(archive)?;
 
-    log::info!("Unpacking done");
+    log::info!("Mounting done");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::mem::offset_of;
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::{Signature, SigningKey};
+
+    fn signed_image(key: &SigningKey, archive: &[u8], version: u64, offset: usize) -> Vec<u8> {
+        let signed = FsSignedData {
+            version,
+            digest: Sha384::digest(archive).try_into().unwrap(),
+        };
+        let signature: Signature = key.sign(signed.as_bytes());
+        let header = FsSignatureHeader {
+            magic: FS_SIGNATURE_MAGIC,
+            version,
+            archive_size: archive.len() as u64,
+            archive_offset: offset as u64,
+            signature: signature.to_bytes().as_slice().try_into().unwrap(),
+        };
+        let mut image = header.as_bytes().to_vec();
+        image.resize(offset, 0);
+        image.extend_from_slice(archive);
+        // Unused space in the filesystem region
+        image.resize(image.len() + 64, 0);
+        image
+    }
+
+    fn public_key(key: &SigningKey) -> FsPublicKey {
+        let point = key.verifying_key().to_encoded_point(false);
+        FsPublicKey(point.as_bytes().try_into().unwrap())
+    }
+
+    fn archive_data(image: &[u8], public_key: Option<FsPublicKey>) -> Result<&[u8], SvsmError> {
+        fs_archive_data(image, public_key, 0).map(|(archive, _)| archive)
+    }
+
+    #[test]
+    fn test_fs_archive_signature() {
+        let key = SigningKey::from_slice(&[0x42; 48]).unwrap();
+        let other_key = SigningKey::from_slice(&[0x17; 48]).unwrap();
+        let archive = b"archive contents";
+        let image = signed_image(&key, archive, 0, FS_SIGNATURE_HEADER_SIZE);
+
+        assert_eq!(
+            archive_data(&image, Some(public_key(&key))).unwrap(),
+            archive
+        );
+        assert_eq!(archive_data(&image, None).unwrap(), archive);
+        assert!(archive_data(&image, Some(public_key(&other_key))).is_err());
+
+        // Unsigned archives are only accepted without a public key
+        assert_eq!(archive_data(archive, None).unwrap(), archive);
+        assert!(archive_data(archive, Some(public_key(&key))).is_err());
+
+        let mut tampered = image.clone();
+        tampered[FS_SIGNATURE_HEADER_SIZE] ^= 1;
+        assert!(archive_data(&tampered, Some(public_key(&key))).is_err());
+
+        let truncated = &image[..FS_SIGNATURE_HEADER_SIZE + archive.len() - 1];
+        assert!(archive_data(truncated, None).is_err());
+
+        // The archive can be placed further behind the header, but not
+        // within it.
+        let image = signed_image(&key, archive, 0, FS_SIGNATURE_HEADER_SIZE + 0x123);
+        assert_eq!(
+            archive_data(&image, Some(public_key(&key))).unwrap(),
+            archive
+        );
+        let image = signed_image(&key, archive, 0, FS_SIGNATURE_HEADER_SIZE - 1);
+        assert!(archive_data(&image, None).is_err());
+    }
+
+    #[test]
+    fn test_fs_archive_version() {
+        let key = SigningKey::from_slice(&[0x42; 48]).unwrap();
+        let archive = b"archive contents";
+        let image = signed_image(&key, archive, 5, FS_SIGNATURE_HEADER_SIZE);
+
+        let (_, signed) = fs_archive_data(&image, Some(public_key(&key)), 5).unwrap();
+        let signed = signed.unwrap();
+        assert_eq!({ signed.version }, 5);
+        assert_eq!(&signed.digest[..], &Sha384::digest(archive)[..]);
+
+        // Older images are refused.
+        assert!(fs_archive_data(&image, Some(public_key(&key)), 6).is_err());
+
+        // The version is covered by the signature.
+        let mut tampered = image.clone();
+        tampered[offset_of!(FsSignatureHeader, version)] = 6;
+        assert!(fs_archive_data(&tampered, Some(public_key(&key)), 6).is_err());
+
+        // Nothing is checked or measured without a public key.
+        let (_, signed) = fs_archive_data(&image, None, 6).unwrap();
+        assert!(signed.is_none());
+    }
+}
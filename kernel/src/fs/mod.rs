@@ -5,16 +5,19 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 mod api;
+mod archive;
 mod buffer;
 mod console;
 mod filesystem;
 mod init;
 mod obj;
+mod overlay;
+mod proc;
 mod ramfs;
 
 pub use api::*;
 pub use buffer::*;
 pub use console::{stdout_open, ConsoleFile};
 pub use filesystem::*;
-pub use init::populate_ram_fs;
+pub use init::mount_fs_archive;
 pub use obj::FsObj;
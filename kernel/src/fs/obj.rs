@@ -121,6 +121,14 @@ impl FsObj {
         fh.truncate(length)
     }
 
+    pub fn file_handle(&self) -> Result<&FileHandle, SvsmError> {
+        let FsObjEntry::File(fh) = &self.entry else {
+            return Err(SvsmError::NotSupported);
+        };
+
+        Ok(fh)
+    }
+
     pub fn readdir(&self) -> Result<Option<(FileName, DirEntry)>, SvsmError> {
         let FsObjEntry::Directory(dh) = &self.entry else {
             return Err(SvsmError::NotSupported);
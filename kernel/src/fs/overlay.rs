@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Directory which overlays a writable upper directory over a read-only
+//! lower directory.
+
+extern crate alloc;
+
+use super::{DirEntry, Directory, File, FileName, FsError};
+use crate::error::SvsmError;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Overlay of two directories. Lookups are served from the upper directory
+/// first and fall back to the lower directory. New entries are always
+/// created in the upper directory, entries of the lower directory can
+/// neither be replaced nor removed.
+///
+/// Every sub-directory of the lower directory needs a counterpart in the
+/// upper directory, so that files can be created below it.
+#[derive(Debug)]
+pub struct OverlayDirectory {
+    upper: Arc<dyn Directory>,
+    lower: Arc<dyn Directory>,
+}
+
+impl OverlayDirectory {
+    pub fn new(upper: Arc<dyn Directory>, lower: Arc<dyn Directory>) -> Self {
+        Self { upper, lower }
+    }
+
+    fn in_lower(&self, name: &FileName) -> bool {
+        self.lower.lookup_entry(name).is_ok()
+    }
+}
+
+impl Directory for OverlayDirectory {
+    fn list(&self) -> Vec<FileName> {
+        let mut list = self.upper.list();
+        for name in self.lower.list() {
+            if !list.contains(&name) {
+                list.push(name);
+            }
+        }
+        list
+    }
+
+    fn prepare_remove(&self) -> Result<(), SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn lookup_entry(&self, name: &FileName) -> Result<DirEntry, SvsmError> {
+        match (self.upper.lookup_entry(name), self.lower.lookup_entry(name)) {
+            (Ok(DirEntry::Directory(upper)), Ok(DirEntry::Directory(lower))) => Ok(
+                DirEntry::Directory(Arc::new(OverlayDirectory::new(upper, lower))),
+            ),
+            (Ok(entry), _) => Ok(entry),
+            (Err(_), lower) => lower,
+        }
+    }
+
+    fn create_file(&self, name: FileName) -> Result<Arc<dyn File>, SvsmError> {
+        if self.in_lower(&name) {
+            return Err(SvsmError::FileSystem(FsError::file_exists()));
+        }
+        self.upper.create_file(name)
+    }
+
+    fn create_directory(&self, name: FileName) -> Result<Arc<dyn Directory>, SvsmError> {
+        if self.in_lower(&name) {
+            return Err(SvsmError::FileSystem(FsError::file_exists()));
+        }
+        self.upper.create_directory(name)
+    }
+
+    fn unlink(&self, name: &FileName) -> Result<(), SvsmError> {
+        if self.in_lower(name) {
+            return Err(SvsmError::FileSystem(FsError::read_only()));
+        }
+        self.upper.unlink(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::archive::ArchiveDirectory;
+    use crate::fs::ramfs::RamDirectory;
+    use crate::mm::alloc::{TestRootMem, DEFAULT_TEST_MEMORY_SIZE};
+
+    #[test]
+    fn test_overlay_directory() {
+        let _test_mem = TestRootMem::setup(DEFAULT_TEST_MEMORY_SIZE);
+
+        let lower = Arc::new(RamDirectory::new());
+        let lower_file = lower.create_file(FileName::from("lower")).unwrap();
+        lower_file.write(b"lower", 0).unwrap();
+        lower.create_directory(FileName::from("dir")).unwrap();
+
+        let upper = Arc::new(RamDirectory::new());
+        upper.create_directory(FileName::from("dir")).unwrap();
+
+        let overlay = OverlayDirectory::new(upper.clone(), lower.clone());
+        overlay.create_file(FileName::from("upper")).unwrap();
+
+        let mut list = overlay.list();
+        list.sort();
+        assert_eq!(list, ["dir", "lower", "upper"]);
+        assert!(upper.lookup_entry(&FileName::from("upper")).is_ok());
+        assert!(lower.lookup_entry(&FileName::from("upper")).is_err());
+
+        // Entries of the lower directory can't be replaced or removed
+        assert!(overlay.create_file(FileName::from("lower")).is_err());
+        assert!(overlay.create_directory(FileName::from("dir")).is_err());
+        assert!(overlay.unlink(&FileName::from("lower")).is_err());
+        assert!(overlay.unlink(&FileName::from("upper")).is_ok());
+
+        // Files can be created in directories shared by both layers
+        let DirEntry::Directory(dir) = overlay.lookup_entry(&FileName::from("dir")).unwrap() else {
+            panic!("dir is not a directory");
+        };
+        dir.create_file(FileName::from("file")).unwrap();
+        assert!(overlay
+            .lookup_entry(&FileName::from("lower"))
+            .unwrap()
+            .is_file());
+
+        let empty = Arc::new(ArchiveDirectory::default());
+        let overlay = OverlayDirectory::new(upper, empty);
+        assert!(overlay
+            .lookup_entry(&FileName::from("dir"))
+            .unwrap()
+            .is_dir());
+    }
+}
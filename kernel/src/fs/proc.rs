@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Read-only `/proc` directory with files that expose kernel state.
+
+extern crate alloc;
+
+use super::{Buffer, DirEntry, Directory, File, FileName, FsError};
+use crate::error::SvsmError;
+use crate::log_buffer::log_buffer_read;
+use crate::tdx::rtmr::rtmr_event_log;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+/// Name of the `/proc` directory in the root directory
+pub const PROC_DIR_NAME: &str = "proc";
+
+const LOG_FILE_NAME: &str = "log";
+const RTMR_LOG_FILE_NAME: &str = "rtmr_log";
+
+/// A read-only file with contents generated when it is opened
+#[derive(Debug)]
+struct ProcFile {
+    data: Vec<u8>,
+}
+
+impl ProcFile {
+    fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+impl File for ProcFile {
+    fn read(&self, buf: &mut [u8], offset: usize) -> Result<usize, SvsmError> {
+        let data = self.data.get(offset..).unwrap_or_default();
+        let size = min(buf.len(), data.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        Ok(size)
+    }
+
+    fn read_buffer(&self, buffer: &mut dyn Buffer, offset: usize) -> Result<usize, SvsmError> {
+        let data = self.data.get(offset..).unwrap_or_default();
+        let size = min(buffer.size(), data.len());
+        buffer.write_buffer(&data[..size], 0)
+    }
+
+    fn write(&self, _buf: &[u8], _offset: usize) -> Result<usize, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn truncate(&self, _size: usize) -> Result<usize, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::read_only()))
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// The `/proc` directory. Every lookup of a file returns a new snapshot of
+/// its contents, so that reads through one file handle are consistent.
+#[derive(Debug, Default)]
+pub struct ProcDirectory;
+
+impl ProcDirectory {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Directory for ProcDirectory {
+    fn list(&self) -> Vec<FileName> {
+        vec![
+            FileName::from(LOG_FILE_NAME),
+            FileName::from(RTMR_LOG_FILE_NAME),
+        ]
+    }
+
+    fn prepare_remove(&self) -> Result<(), SvsmError> {
+        Err(SvsmError::FileSystem(FsError::not_supported()))
+    }
+
+    fn lookup_entry(&self, name: &FileName) -> Result<DirEntry, SvsmError> {
+        match name.as_str() {
+            LOG_FILE_NAME => Ok(DirEntry::File(Arc::new(ProcFile::new(log_buffer_read())))),
+            RTMR_LOG_FILE_NAME => Ok(DirEntry::File(Arc::new(ProcFile::new(rtmr_event_log())))),
+            _ => Err(SvsmError::FileSystem(FsError::file_not_found())),
+        }
+    }
+
+    fn create_file(&self, _name: FileName) -> Result<Arc<dyn File>, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::not_supported()))
+    }
+
+    fn create_directory(&self, _name: FileName) -> Result<Arc<dyn Directory>, SvsmError> {
+        Err(SvsmError::FileSystem(FsError::not_supported()))
+    }
+
+    fn unlink(&self, _name: &FileName) -> Result<(), SvsmError> {
+        Err(SvsmError::FileSystem(FsError::not_supported()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proc_file_read() {
+        let file = ProcFile::new(Vec::from(*b"log line\n"));
+        let mut buf = [0u8; 4];
+        assert_eq!(file.read(&mut buf, 0).unwrap(), 4);
+        assert_eq!(&buf, b"log ");
+        assert_eq!(file.read(&mut buf, 7).unwrap(), 2);
+        assert_eq!(&buf[..2], b"e\n");
+        assert_eq!(file.read(&mut buf, 20).unwrap(), 0);
+        assert!(file.write(b"x", 0).is_err());
+
+        let dir = ProcDirectory::new();
+        assert!(dir.lookup_entry(&FileName::from("log")).unwrap().is_file());
+        assert!(dir
+            .lookup_entry(&FileName::from("rtmr_log"))
+            .unwrap()
+            .is_file());
+        assert!(dir.lookup_entry(&FileName::from("none")).is_err());
+    }
+}
@@ -402,6 +402,17 @@ impl RawRamDirectory {
         Ok(new_dir)
     }
 
+    fn add_entry(&mut self, name: FileName, entry: DirEntry) -> Result<(), SvsmError> {
+        self.check_remove()?;
+
+        if self.has_entry(&name) {
+            return Err(SvsmError::FileSystem(FsError::file_exists()));
+        }
+
+        self.entries.push(DirectoryEntry::new(name, entry));
+        Ok(())
+    }
+
     fn unlink(&mut self, name: &FileName) -> Result<(), SvsmError> {
         let pos = self.entries.iter().position(|e| &e.name == name);
 
@@ -428,6 +439,21 @@ impl RamDirectory {
             directory: RWLock::new(RawRamDirectory::new()),
         }
     }
+
+    /// Adds a file or directory which is not backed by the RAM filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: name of the new entry.
+    /// - `entry`: the file or directory to add.
+    ///
+    /// # Returns
+    ///
+    /// [`Result<(), SvsmError>`]: A [`Result`] containing the empty
+    /// value on success, or an [`SvsmError`] on failure
+    pub fn add_entry(&self, name: FileName, entry: DirEntry) -> Result<(), SvsmError> {
+        self.directory.lock_write().add_entry(name, entry)
+    }
 }
 
 impl Directory for RamDirectory {
@@ -199,6 +199,7 @@ impl SnpGuestRequestDriver {
     ///
     /// * `req_class`: whether this is a regular or extended `SNP_GUEST_REQUEST` command
     /// * `msg_type`: type of the command stored in `buffer`, e.g. SNP_MSG_REPORT_REQ
+    ///   or SNP_MSG_KEY_REQ
     /// * `buffer`: buffer with the `SNP_GUEST_REQUEST` command to be sent.
     ///   The same buffer will also be used to store the response.
     /// * `command_len`: Size (in bytes) of the command stored in `buffer`
@@ -303,6 +304,10 @@ impl SnpGuestRequestDriver {
         command_len: usize,
         certs: &mut [u8],
     ) -> Result<usize, SvsmReqError> {
+        // Only attestation reports come with certificates
+        if msg_type != SnpGuestRequestMsgType::ReportRequest {
+            return Err(SvsmReqError::invalid_parameter());
+        }
         self.set_user_extdata_size(certs.len())?;
 
         let outbuf_len: usize = self.send_request(
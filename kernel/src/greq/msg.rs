@@ -35,8 +35,16 @@ pub enum SnpGuestRequestAead {
 #[repr(u8)]
 pub enum SnpGuestRequestMsgType {
     Invalid = 0,
+    KeyRequest = 3,
+    KeyResponse = 4,
     ReportRequest = 5,
     ReportResponse = 6,
+    ExportRequest = 7,
+    ExportResponse = 8,
+    ImportRequest = 9,
+    ImportResponse = 10,
+    AbsorbRequest = 11,
+    AbsorbResponse = 12,
 }
 
 impl TryFrom<u8> for SnpGuestRequestMsgType {
@@ -45,8 +53,16 @@ impl TryFrom<u8> for SnpGuestRequestMsgType {
     fn try_from(v: u8) -> Result<Self, Self::Error> {
         match v {
             x if x == Self::Invalid as u8 => Ok(Self::Invalid),
+            x if x == Self::KeyRequest as u8 => Ok(Self::KeyRequest),
+            x if x == Self::KeyResponse as u8 => Ok(Self::KeyResponse),
             x if x == Self::ReportRequest as u8 => Ok(Self::ReportRequest),
             x if x == Self::ReportResponse as u8 => Ok(Self::ReportResponse),
+            x if x == Self::ExportRequest as u8 => Ok(Self::ExportRequest),
+            x if x == Self::ExportResponse as u8 => Ok(Self::ExportResponse),
+            x if x == Self::ImportRequest as u8 => Ok(Self::ImportRequest),
+            x if x == Self::ImportResponse as u8 => Ok(Self::ImportResponse),
+            x if x == Self::AbsorbRequest as u8 => Ok(Self::AbsorbRequest),
+            x if x == Self::AbsorbResponse as u8 => Ok(Self::AbsorbResponse),
             _ => Err(SvsmReqError::invalid_parameter()),
         }
     }
@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! `SNP_GUEST_REQUEST` command to request a derived key.
+
+use bitflags::bitflags;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::{protocols::errors::SvsmReqError, sev::vmsa::VMPL_MAX};
+
+/// Size of the key derived by the PSP
+pub const DERIVED_KEY_SIZE: usize = 32;
+
+/// Root key the PSP derives the key from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootKeySelect {
+    /// The VCEK, or the VLEK if selected by [`KeySelect`]
+    #[default]
+    Vcek,
+    /// The VM root key provided by the migration agent at launch
+    Vmrk,
+}
+
+/// Selects between the VCEK and the VLEK when [`RootKeySelect::Vcek`] is
+/// used
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeySelect {
+    /// The VLEK if one is installed, otherwise the VCEK
+    #[default]
+    Default,
+    /// The VCEK
+    Vcek,
+    /// The VLEK
+    Vlek,
+}
+
+bitflags! {
+    /// Guest data mixed into the derived key (AMD SEV-SNP spec. table 18,
+    /// GUEST_FIELD_SELECT)
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct GuestFieldSelect: u64 {
+        const GUEST_POLICY  = 1 << 0;
+        const IMAGE_ID      = 1 << 1;
+        const FAMILY_ID     = 1 << 2;
+        const MEASUREMENT   = 1 << 3;
+        const GUEST_SVN     = 1 << 4;
+        const TCB_VERSION   = 1 << 5;
+    }
+}
+
+/// MSG_KEY_REQ payload format (AMD SEV-SNP spec. table 18)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpKeyRequest {
+    /// 31:3 - Reserved
+    ///  2:1 - KEY_SEL. Selects between VCEK and VLEK, see [`KeySelect`]
+    ///    0 - ROOT_KEY_SEL. 0: VCEK or VLEK, 1: VMRK
+    root_key_select: u32,
+    /// Reserved, must be zero
+    rsvd: u32,
+    /// Guest data mixed into the key, see [`GuestFieldSelect`]
+    guest_field_select: u64,
+    /// The VMPL to mix into the key
+    vmpl: u32,
+    /// The guest SVN to mix into the key
+    guest_svn: u32,
+    /// The TCB version to mix into the key
+    tcb_version: u64,
+}
+
+impl SnpKeyRequest {
+    /// Returns a builder for a new [`SnpKeyRequest`].
+    pub fn builder() -> SnpKeyRequestBuilder {
+        SnpKeyRequestBuilder::default()
+    }
+}
+
+/// Builder for [`SnpKeyRequest`]s
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SnpKeyRequestBuilder {
+    root_key: RootKeySelect,
+    key_select: KeySelect,
+    guest_fields: GuestFieldSelect,
+    vmpl: u32,
+    guest_svn: u32,
+    tcb_version: u64,
+}
+
+impl SnpKeyRequestBuilder {
+    /// Sets the root key for the derivation, defaults to
+    /// [`RootKeySelect::Vcek`].
+    pub fn root_key(mut self, root_key: RootKeySelect) -> Self {
+        self.root_key = root_key;
+        self
+    }
+
+    /// Selects between VCEK and VLEK, defaults to [`KeySelect::Default`].
+    pub fn key_select(mut self, key_select: KeySelect) -> Self {
+        self.key_select = key_select;
+        self
+    }
+
+    /// Sets the guest data to mix into the key.
+    pub fn guest_fields(mut self, guest_fields: GuestFieldSelect) -> Self {
+        self.guest_fields = guest_fields;
+        self
+    }
+
+    /// Sets the VMPL to mix into the key, defaults to 0.
+    pub fn vmpl(mut self, vmpl: u32) -> Self {
+        self.vmpl = vmpl;
+        self
+    }
+
+    /// Sets the guest SVN to mix into the key. Requires
+    /// [`GuestFieldSelect::GUEST_SVN`].
+    pub fn guest_svn(mut self, guest_svn: u32) -> Self {
+        self.guest_svn = guest_svn;
+        self
+    }
+
+    /// Sets the TCB version to mix into the key. Requires
+    /// [`GuestFieldSelect::TCB_VERSION`].
+    pub fn tcb_version(mut self, tcb_version: u64) -> Self {
+        self.tcb_version = tcb_version;
+        self
+    }
+
+    /// Builds the [`SnpKeyRequest`].
+    ///
+    /// # Returns
+    ///
+    /// The request, or [`SvsmReqError`] when the VMPL is invalid, the VLEK is
+    /// selected together with the VMRK, or an SVN is set without mixing it
+    /// into the key.
+    pub fn build(self) -> Result<SnpKeyRequest, SvsmReqError> {
+        if self.vmpl as usize >= VMPL_MAX
+            || (self.root_key == RootKeySelect::Vmrk && self.key_select != KeySelect::Default)
+            || (self.guest_svn != 0 && !self.guest_fields.contains(GuestFieldSelect::GUEST_SVN))
+            || (self.tcb_version != 0 && !self.guest_fields.contains(GuestFieldSelect::TCB_VERSION))
+        {
+            return Err(SvsmReqError::invalid_parameter());
+        }
+
+        let root_key_sel = match self.root_key {
+            RootKeySelect::Vcek => 0,
+            RootKeySelect::Vmrk => 1,
+        };
+        let key_sel = match self.key_select {
+            KeySelect::Default => 0,
+            KeySelect::Vcek => 1,
+            KeySelect::Vlek => 2,
+        };
+
+        Ok(SnpKeyRequest {
+            root_key_select: root_key_sel | (key_sel << 1),
+            rsvd: 0,
+            guest_field_select: self.guest_fields.bits(),
+            vmpl: self.vmpl,
+            guest_svn: self.guest_svn,
+            tcb_version: self.tcb_version,
+        })
+    }
+}
+
+/// MSG_KEY_RSP payload format (AMD SEV-SNP spec. table 19)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpKeyResponse {
+    /// The status of the key derivation operation, see [`SnpKeyResponseStatus`]
+    status: u32,
+    /// Reserved
+    _reserved: [u8; 28],
+    /// The requested derived key
+    derived_key: [u8; DERIVED_KEY_SIZE],
+}
+
+/// Supported values for SnpKeyResponse.status
+#[repr(u32)]
+#[derive(Clone, Copy, Debug)]
+pub enum SnpKeyResponseStatus {
+    Success = 0,
+    InvalidParameters = 0x16,
+    InvalidKeySelection = 0x27,
+}
+
+impl SnpKeyResponse {
+    /// Validate the [`SnpKeyResponse`] fields
+    pub fn validate(&self) -> Result<(), SvsmReqError> {
+        if self.status != SnpKeyResponseStatus::Success as u32 {
+            log::error!("MSG_KEY_REQ failed, status={:#x}", { self.status });
+            return Err(SvsmReqError::invalid_request());
+        }
+        Ok(())
+    }
+
+    pub fn derived_key(&self) -> &[u8; DERIVED_KEY_SIZE] {
+        &self.derived_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    #[test]
+    fn test_snp_key_request_offsets() {
+        assert_eq!(offset_of!(SnpKeyRequest, root_key_select), 0x0);
+        assert_eq!(offset_of!(SnpKeyRequest, rsvd), 0x4);
+        assert_eq!(offset_of!(SnpKeyRequest, guest_field_select), 0x8);
+        assert_eq!(offset_of!(SnpKeyRequest, vmpl), 0x10);
+        assert_eq!(offset_of!(SnpKeyRequest, guest_svn), 0x14);
+        assert_eq!(offset_of!(SnpKeyRequest, tcb_version), 0x18);
+        assert_eq!(size_of::<SnpKeyRequest>(), 0x20);
+    }
+
+    #[test]
+    fn test_snp_key_response_offsets() {
+        assert_eq!(offset_of!(SnpKeyResponse, status), 0x0);
+        assert_eq!(offset_of!(SnpKeyResponse, _reserved), 0x4);
+        assert_eq!(offset_of!(SnpKeyResponse, derived_key), 0x20);
+        assert_eq!(size_of::<SnpKeyResponse>(), 0x40);
+    }
+
+    #[test]
+    fn test_snp_key_request_builder() {
+        let request = SnpKeyRequest::builder()
+            .key_select(KeySelect::Vlek)
+            .guest_fields(GuestFieldSelect::MEASUREMENT | GuestFieldSelect::GUEST_SVN)
+            .vmpl(1)
+            .guest_svn(2)
+            .build()
+            .unwrap();
+        assert_eq!({ request.root_key_select }, 4);
+        assert_eq!({ request.guest_field_select }, 0x18);
+        assert_eq!({ request.vmpl }, 1);
+        assert_eq!({ request.guest_svn }, 2);
+
+        assert!(SnpKeyRequest::builder().vmpl(4).build().is_err());
+        assert!(SnpKeyRequest::builder().tcb_version(1).build().is_err());
+        assert!(SnpKeyRequest::builder()
+            .root_key(RootKeySelect::Vmrk)
+            .key_select(KeySelect::Vcek)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! `SNP_GUEST_REQUEST` commands used by a migration agent to export, import
+//! and absorb the guest context of a guest.
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::{address::PhysAddr, protocols::errors::SvsmReqError};
+
+/// Size of the guest context transported by the migration messages
+pub const GUEST_CONTEXT_SIZE: usize = 0x200;
+
+/// Guest context of a migrated guest. Its contents are protected by the PSP
+/// and opaque to the SVSM.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpGuestContext {
+    raw: [u8; GUEST_CONTEXT_SIZE],
+}
+
+/// MSG_EXPORT_REQ payload format (AMD SEV-SNP spec. section 7.5)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpExportRequest {
+    /// System physical address of the guest context page of the guest to
+    /// export
+    gctx_paddr: u64,
+    /// 31:1 - Reserved
+    ///    0 - IMI_EN. Export the guest context for the import of an
+    ///        incoming migration image
+    flags: u32,
+    /// Reserved, must be zero
+    rsvd: [u8; 52],
+}
+
+impl SnpExportRequest {
+    pub fn new(gctx_paddr: PhysAddr, imi_en: bool) -> Self {
+        Self {
+            gctx_paddr: u64::from(gctx_paddr),
+            flags: imi_en as u32,
+            rsvd: [0; 52],
+        }
+    }
+}
+
+/// MSG_EXPORT_RSP payload format (AMD SEV-SNP spec. section 7.5)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpExportResponse {
+    /// The status of the export operation, see [`SnpMigrationResponseStatus`]
+    status: u32,
+    /// Reserved
+    _reserved: [u8; 28],
+    /// The exported guest context
+    gctx: SnpGuestContext,
+}
+
+impl SnpExportResponse {
+    /// Validate the [`SnpExportResponse`] fields
+    pub fn validate(&self) -> Result<(), SvsmReqError> {
+        validate_status(self.status)
+    }
+
+    pub fn guest_context(&self) -> &SnpGuestContext {
+        &self.gctx
+    }
+}
+
+/// MSG_IMPORT_REQ and MSG_ABSORB_REQ payload format (AMD SEV-SNP spec.
+/// sections 7.6 and 7.7)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpImportRequest {
+    /// System physical address of the guest context page of the guest the
+    /// context is imported into
+    gctx_paddr: u64,
+    /// Reserved, must be zero
+    rsvd: [u8; 24],
+    /// The guest context to import
+    in_gctx: SnpGuestContext,
+}
+
+impl SnpImportRequest {
+    pub fn new(gctx_paddr: PhysAddr, gctx: &SnpGuestContext) -> Self {
+        Self {
+            gctx_paddr: u64::from(gctx_paddr),
+            rsvd: [0; 24],
+            in_gctx: *gctx,
+        }
+    }
+}
+
+/// MSG_IMPORT_RSP and MSG_ABSORB_RSP payload format (AMD SEV-SNP spec.
+/// sections 7.6 and 7.7)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+pub struct SnpImportResponse {
+    /// The status of the operation, see [`SnpMigrationResponseStatus`]
+    status: u32,
+    /// Reserved
+    _reserved: [u8; 28],
+}
+
+impl SnpImportResponse {
+    /// Validate the [`SnpImportResponse`] fields
+    pub fn validate(&self) -> Result<(), SvsmReqError> {
+        validate_status(self.status)
+    }
+}
+
+/// Supported values for the status of migration responses
+#[repr(u32)]
+#[derive(Clone, Copy, Debug)]
+pub enum SnpMigrationResponseStatus {
+    Success = 0,
+    InvalidGuestState = 0x1,
+    InvalidGuest = 0x9,
+    InvalidParameters = 0x16,
+}
+
+fn validate_status(status: u32) -> Result<(), SvsmReqError> {
+    if status != SnpMigrationResponseStatus::Success as u32 {
+        log::error!("SNP migration request failed, status={:#x}", status);
+        return Err(SvsmReqError::invalid_request());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    #[test]
+    fn test_snp_migration_msg_offsets() {
+        assert_eq!(offset_of!(SnpExportRequest, gctx_paddr), 0x0);
+        assert_eq!(offset_of!(SnpExportRequest, flags), 0x8);
+        assert_eq!(size_of::<SnpExportRequest>(), 0x40);
+
+        assert_eq!(offset_of!(SnpExportResponse, status), 0x0);
+        assert_eq!(offset_of!(SnpExportResponse, gctx), 0x20);
+
+        assert_eq!(offset_of!(SnpImportRequest, gctx_paddr), 0x0);
+        assert_eq!(offset_of!(SnpImportRequest, in_gctx), 0x20);
+        assert_eq!(size_of::<SnpImportResponse>(), 0x20);
+    }
+}
@@ -6,12 +6,18 @@
 
 //! API to send `SNP_GUEST_REQUEST` commands to the PSP
 
-use zerocopy::FromBytes;
+use zerocopy::{FromBytes, IntoBytes};
 
 use crate::{
+    address::PhysAddr,
     greq::{
         driver::{send_extended_guest_request, send_regular_guest_request},
         msg::SnpGuestRequestMsgType,
+        pld_key::{SnpKeyRequest, SnpKeyResponse, DERIVED_KEY_SIZE},
+        pld_migration::{
+            SnpExportRequest, SnpExportResponse, SnpGuestContext, SnpImportRequest,
+            SnpImportResponse,
+        },
         pld_report::{SnpReportRequest, SnpReportResponse},
     },
     protocols::errors::SvsmReqError,
@@ -20,6 +26,8 @@ use core::mem::size_of;
 
 const REPORT_REQUEST_SIZE: usize = size_of::<SnpReportRequest>();
 const REPORT_RESPONSE_SIZE: usize = size_of::<SnpReportResponse>();
+const KEY_REQUEST_SIZE: usize = size_of::<SnpKeyRequest>();
+const KEY_RESPONSE_SIZE: usize = size_of::<SnpKeyResponse>();
 
 fn get_report(buffer: &mut [u8], certs: Option<&mut [u8]>) -> Result<usize, SvsmReqError> {
     let request: &SnpReportRequest = SnpReportRequest::try_from_as_ref(buffer)?;
@@ -108,6 +116,163 @@ pub fn get_extended_report(buffer: &mut [u8], certs: &mut [u8]) -> Result<usize,
     get_report(buffer, Some(certs))
 }
 
+/// Request a key derived by the PSP.
+///
+/// Use the `SNP_GUEST_REQUEST` driver to send a `MSG_KEY_REQ` command to the
+/// PSP. The key is derived from the selected root key and the guest data
+/// selected in the request, so it is bound to the platform and, for
+/// example, to the launch measurement of the guest.
+///
+/// The VMPCK0 is disabled for subsequent calls if this function fails in a way that
+/// the VM state can be compromised.
+///
+/// # Arguments
+///
+/// * `request`: The [`MSG_KEY_REQ`](SnpKeyRequest) command, usually created
+///   with [`SnpKeyRequest::builder()`].
+///
+/// # Returns
+///
+/// * Success
+///     * The derived key
+/// * Error
+///     * [`SvsmReqError`]
+pub fn get_derived_key(request: &SnpKeyRequest) -> Result<[u8; DERIVED_KEY_SIZE], SvsmReqError> {
+    let mut buffer = [0u8; KEY_RESPONSE_SIZE];
+    buffer[..KEY_REQUEST_SIZE].copy_from_slice(request.as_bytes());
+
+    let result = send_regular_guest_request(
+        SnpGuestRequestMsgType::KeyRequest,
+        &mut buffer,
+        KEY_REQUEST_SIZE,
+    )
+    .and_then(|response_len| {
+        if KEY_RESPONSE_SIZE > response_len {
+            return Err(SvsmReqError::invalid_request());
+        }
+        let response = SnpKeyResponse::ref_from_bytes(&buffer)
+            .map_err(|_| SvsmReqError::invalid_parameter())?;
+        response.validate()?;
+        Ok(*response.derived_key())
+    });
+
+    // Do not leave a copy of the key on the stack
+    buffer.fill(0);
+    result
+}
+
+/// Export the guest context of a guest to migrate.
+///
+/// Sends a `MSG_EXPORT_REQ` command to the PSP. This is only permitted when
+/// the SVSM runs in the migration agent of the guest.
+///
+/// # Arguments
+///
+/// * `gctx_paddr`: System physical address of the guest context page of the
+///   guest to export.
+/// * `imi_en`: Whether the guest context is exported to be imported through
+///   an incoming migration image.
+///
+/// # Returns
+///
+/// * Success
+///     * The exported [`SnpGuestContext`]
+/// * Error
+///     * [`SvsmReqError`]
+pub fn export_guest_context(
+    gctx_paddr: PhysAddr,
+    imi_en: bool,
+) -> Result<SnpGuestContext, SvsmReqError> {
+    let request = SnpExportRequest::new(gctx_paddr, imi_en);
+    let mut buffer = [0u8; size_of::<SnpExportResponse>()];
+    buffer[..size_of::<SnpExportRequest>()].copy_from_slice(request.as_bytes());
+
+    let result = send_regular_guest_request(
+        SnpGuestRequestMsgType::ExportRequest,
+        &mut buffer,
+        size_of::<SnpExportRequest>(),
+    )
+    .and_then(|response_len| {
+        if size_of::<SnpExportResponse>() > response_len {
+            return Err(SvsmReqError::invalid_request());
+        }
+        let response = SnpExportResponse::ref_from_bytes(&buffer)
+            .map_err(|_| SvsmReqError::invalid_parameter())?;
+        response.validate()?;
+        Ok(*response.guest_context())
+    });
+
+    buffer.fill(0);
+    result
+}
+
+fn send_import_request(
+    msg_type: SnpGuestRequestMsgType,
+    gctx_paddr: PhysAddr,
+    gctx: &SnpGuestContext,
+) -> Result<(), SvsmReqError> {
+    let request = SnpImportRequest::new(gctx_paddr, gctx);
+    let mut buffer = [0u8; size_of::<SnpImportRequest>()];
+    buffer.copy_from_slice(request.as_bytes());
+
+    let result = send_regular_guest_request(msg_type, &mut buffer, size_of::<SnpImportRequest>())
+        .and_then(|response_len| {
+            if size_of::<SnpImportResponse>() > response_len {
+                return Err(SvsmReqError::invalid_request());
+            }
+            let (response, _rest) = SnpImportResponse::ref_from_prefix(&buffer)
+                .map_err(|_| SvsmReqError::invalid_parameter())?;
+            response.validate()
+        });
+
+    buffer.fill(0);
+    result
+}
+
+/// Import a guest context exported with [`export_guest_context()`] into a
+/// guest launched to receive a migrated guest.
+///
+/// Sends a `MSG_IMPORT_REQ` command to the PSP. This is only permitted when
+/// the SVSM runs in the migration agent of the guest.
+///
+/// # Arguments
+///
+/// * `gctx_paddr`: System physical address of the guest context page of the
+///   receiving guest.
+/// * `gctx`: The exported guest context.
+///
+/// # Returns
+///
+/// `()` on success, [`SvsmReqError`] on failure.
+pub fn import_guest_context(
+    gctx_paddr: PhysAddr,
+    gctx: &SnpGuestContext,
+) -> Result<(), SvsmReqError> {
+    send_import_request(SnpGuestRequestMsgType::ImportRequest, gctx_paddr, gctx)
+}
+
+/// Absorb an exported guest context into a guest which was launched with
+/// its migration agent, completing the migration of the guest.
+///
+/// Sends a `MSG_ABSORB_REQ` command to the PSP. This is only permitted when
+/// the SVSM runs in the migration agent of the guest.
+///
+/// # Arguments
+///
+/// * `gctx_paddr`: System physical address of the guest context page of the
+///   receiving guest.
+/// * `gctx`: The exported guest context.
+///
+/// # Returns
+///
+/// `()` on success, [`SvsmReqError`] on failure.
+pub fn absorb_guest_context(
+    gctx_paddr: PhysAddr,
+    gctx: &SnpGuestContext,
+) -> Result<(), SvsmReqError> {
+    send_import_request(SnpGuestRequestMsgType::AbsorbRequest, gctx_paddr, gctx)
+}
+
 #[cfg(test)]
 mod tests {
     #[allow(unused)]
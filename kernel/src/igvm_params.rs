@@ -17,8 +17,13 @@ use crate::utils::MemoryRegion;
 use alloc::vec::Vec;
 use cpuarch::vmsa::VMSA;
 
+use bootlib::fs_archive::FsPublicKey;
 use bootlib::igvm_params::{IgvmGuestContext, IgvmParamBlock, IgvmParamPage};
 use bootlib::kernel_launch::LOWMEM_END;
+use bootlib::linux_boot::{
+    BootE820Entry, BOOT_PARAMS_E820_ENTRIES, BOOT_PARAMS_E820_TABLE, E820_MAX_ENTRIES,
+    E820_TYPE_RAM, E820_TYPE_RESERVED,
+};
 use core::mem::size_of;
 use core::slice;
 use igvm_defs::{IgvmEnvironmentInfo, MemoryMapEntryType, IGVM_VHS_MEMORY_MAP_ENTRY};
@@ -176,6 +181,12 @@ impl IgvmParams<'_> {
     }
 
     pub fn write_guest_memory_map(&self, map: &[MemoryRegion<PhysAddr>]) -> Result<(), SvsmError> {
+        // A directly booted Linux kernel finds the memory map in the E820
+        // table of its boot parameters.
+        if let Some(boot_params) = self.linux_boot_params() {
+            self.write_linux_e820_table(boot_params, map)?;
+        }
+
         // If the parameters do not include a guest memory map area, then no
         // work is required.
         let fw_info = &self.igvm_param_block.firmware;
@@ -268,6 +279,83 @@ impl IgvmParams<'_> {
         Ok(())
     }
 
+    fn write_linux_e820_table(
+        &self,
+        boot_params: PhysAddr,
+        map: &[MemoryRegion<PhysAddr>],
+    ) -> Result<(), SvsmError> {
+        let fw_info = &self.igvm_param_block.firmware;
+
+        // The pages shared between the kernel and the SVSM must not be used
+        // as RAM by the kernel, so they are reported as reserved.
+        let mut reserved = [fw_info.secrets_page, fw_info.caa_page, fw_info.cpuid_page]
+            .into_iter()
+            .filter(|page| *page != 0)
+            .map(|page| PhysAddr::from(u64::from(page)))
+            .collect::<Vec<_>>();
+        reserved.sort_unstable();
+
+        let e820_entry = |region: MemoryRegion<PhysAddr>, entry_type| BootE820Entry {
+            addr: u64::from(region.start()),
+            size: region.len() as u64,
+            entry_type,
+        };
+
+        let mut entries = Vec::new();
+        for region in map.iter() {
+            let mut start = region.start();
+            for page in reserved
+                .iter()
+                .copied()
+                .filter(|page| region.contains(*page))
+            {
+                if start < page {
+                    entries.push(e820_entry(
+                        MemoryRegion::from_addresses(start, page),
+                        E820_TYPE_RAM,
+                    ));
+                }
+                entries.push(e820_entry(
+                    MemoryRegion::new(page, PAGE_SIZE),
+                    E820_TYPE_RESERVED,
+                ));
+                start = page + PAGE_SIZE;
+            }
+            if start < region.end() {
+                entries.push(e820_entry(
+                    MemoryRegion::from_addresses(start, region.end()),
+                    E820_TYPE_RAM,
+                ));
+            }
+        }
+
+        if entries.len() > E820_MAX_ENTRIES {
+            log::error!("Guest memory map does not fit into the Linux E820 table");
+            return Err(SvsmError::Firmware);
+        }
+
+        log::info!(
+            "Filling Linux E820 table at {:#018x} with {} entries",
+            boot_params,
+            entries.len()
+        );
+
+        let guard = PerCPUPageMappingGuard::create_4k(boot_params)?;
+        let table = GuestPtr::<BootE820Entry>::new(guard.virt_addr() + BOOT_PARAMS_E820_TABLE);
+        for (i, entry) in entries.iter().enumerate() {
+            // SAFETY: the boot parameters page was mapped above and the E820
+            // table with E820_MAX_ENTRIES entries lies within it.
+            unsafe {
+                table.offset(i as isize).write(*entry)?;
+            }
+        }
+
+        let count = GuestPtr::<u8>::new(guard.virt_addr() + BOOT_PARAMS_E820_ENTRIES);
+        // SAFETY: the entry count lies within the boot parameters page mapped
+        // above.
+        unsafe { count.write(entries.len() as u8) }
+    }
+
     pub fn load_cpu_info(&self) -> Result<Option<Vec<ACPICPUInfo>>, SvsmError> {
         match self.igvm_madt {
             Some(madt_data) => {
@@ -360,6 +448,13 @@ impl IgvmParams<'_> {
         self.igvm_param_block.firmware.in_low_memory != 0
     }
 
+    pub fn linux_boot_params(&self) -> Option<PhysAddr> {
+        match self.igvm_param_block.firmware.linux_boot_params {
+            0 => None,
+            addr => Some(PhysAddr::from(u64::from(addr))),
+        }
+    }
+
     pub fn initialize_guest_vmsa(&self, vmsa: &mut VMSA) -> Result<(), SvsmError> {
         let Some(guest_context) = self.igvm_guest_context else {
             return Ok(());
@@ -410,7 +505,14 @@ impl IgvmParams<'_> {
         // segment attributes to be compatible with protected mode.
         if guest_context.data_selector != 0 {
             vmsa.ds.selector = guest_context.data_selector;
-            vmsa.ds.flags = 0xA93;
+            // Outside of long mode the data segments need the default size
+            // bit set, so that a 32-bit stack is used.
+            let efer_lma = EFERFlags::LMA;
+            if (vmsa.efer & efer_lma.bits()) != 0 {
+                vmsa.ds.flags = 0xA93;
+            } else {
+                vmsa.ds.flags = 0xC93;
+            }
             vmsa.ds.limit = 0xFFFFFFFF;
             vmsa.ss = vmsa.ds;
             vmsa.es = vmsa.ds;
@@ -438,4 +540,24 @@ impl IgvmParams<'_> {
     pub fn is_qemu(&self) -> bool {
         self.igvm_param_block.is_qemu != 0
     }
+
+    pub fn fs_public_key(&self) -> Option<FsPublicKey> {
+        let key = self.igvm_param_block.fs_public_key;
+        key.is_present().then_some(key)
+    }
+
+    pub fn fs_min_version(&self) -> u64 {
+        self.igvm_param_block.fs_min_version
+    }
+
+    pub fn vtpm_nv_min_generation(&self) -> u64 {
+        self.igvm_param_block.vtpm_nv_min_generation
+    }
+
+    pub fn svsm_pci_ecam(&self) -> Option<PhysAddr> {
+        match self.igvm_param_block.svsm_pci_ecam {
+            0 => None,
+            address => Some(PhysAddr::from(address)),
+        }
+    }
 }
@@ -716,6 +716,9 @@ impl DecodedInsnCtx {
                 DecodedInsn::Ins => self.emulate_ins_outs(mctx, true),
                 DecodedInsn::Outs => self.emulate_ins_outs(mctx, false),
                 DecodedInsn::Mov => self.emulate_mov(mctx),
+                DecodedInsn::Movs => self.emulate_movs(mctx),
+                DecodedInsn::Movsx => self.emulate_movx(mctx, true),
+                DecodedInsn::Movzx => self.emulate_movx(mctx, false),
                 _ => Err(InsnError::UnSupportedInsn),
             })
     }
@@ -743,6 +746,28 @@ impl DecodedInsnCtx {
             })
     }
 
+    /// Emulates MMIO instructions using the provided machine context.
+    ///
+    /// # Arguments
+    ///
+    /// * `mctx` - A mutable reference to an object implementing the
+    ///   `InsnMachineCtx` trait to provide the necessary machine context
+    ///   for emulation.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok(())` if emulation is successful or an `InsnError` otherwise.
+    pub fn emulate_mmio<I: InsnMachineCtx>(&self, mctx: &mut I) -> Result<(), InsnError> {
+        self.insn
+            .ok_or(InsnError::UnSupportedInsn)
+            .and_then(|insn| match insn {
+                DecodedInsn::Mov | DecodedInsn::Movs | DecodedInsn::Movsx | DecodedInsn::Movzx => {
+                    self.emulate(mctx)
+                }
+                _ => Err(InsnError::UnSupportedInsn),
+            })
+    }
+
     fn decode<I: InsnMachineCtx>(
         &mut self,
         bytes: &[u8; MAX_INSN_SIZE],
@@ -1143,6 +1168,15 @@ impl DecodedInsnCtx {
             OpCodeClass::Rdtscp => DecodedInsn::Rdtscp,
             OpCodeClass::Wrmsr => DecodedInsn::Wrmsr,
             OpCodeClass::Mov => DecodedInsn::Mov,
+            OpCodeClass::Movs => {
+                if self.prefix.contains(PrefixFlags::REPZ_P) {
+                    // Like for ins/outs, REPZ(F3h) represents REP for movs.
+                    self.repeat = read_reg(mctx, Register::Rcx, self.addrsize);
+                };
+                DecodedInsn::Movs
+            }
+            OpCodeClass::Movsx => DecodedInsn::Movsx,
+            OpCodeClass::Movzx => DecodedInsn::Movzx,
             _ => return Err(InsnError::UnSupportedInsn),
         })
     }
@@ -1480,9 +1514,10 @@ impl DecodedInsnCtx {
         mctx: &I,
         seg: SegRegister,
         ea: usize,
+        size: Bytes,
     ) -> Result<u64, InsnError> {
         mctx.translate_linear_addr(self.get_linear_addr(mctx, seg, ea, false)?, false, false)
-            .and_then(|(addr, shared)| mctx.handle_mmio_read(addr, shared, self.opsize))
+            .and_then(|(addr, shared)| mctx.handle_mmio_read(addr, shared, size))
     }
 
     #[inline]
@@ -1497,18 +1532,23 @@ impl DecodedInsnCtx {
             .and_then(|(addr, shared)| mctx.handle_mmio_write(addr, shared, self.opsize, data))
     }
 
-    fn emulate_mov<I: InsnMachineCtx>(&self, mctx: &mut I) -> Result<(), InsnError> {
-        if self.prefix.contains(PrefixFlags::REPZ_P) {
-            return Err(InsnError::UnSupportedInsn);
-        }
-
-        let seg = if let Some(s) = self.override_seg {
+    // Segment of the ModR/M or moffset memory operand
+    fn mem_operand_seg(&self) -> SegRegister {
+        if let Some(s) = self.override_seg {
             s
         } else if self.base_reg == Some(Register::Rsp) || self.base_reg == Some(Register::Rbp) {
             SegRegister::SS
         } else {
             SegRegister::DS
-        };
+        }
+    }
+
+    fn emulate_mov<I: InsnMachineCtx>(&self, mctx: &mut I) -> Result<(), InsnError> {
+        if self.prefix.contains(PrefixFlags::REPZ_P) {
+            return Err(InsnError::UnSupportedInsn);
+        }
+
+        let seg = self.mem_operand_seg();
         let ea = self.cal_effective_addr(mctx)?;
 
         match self.get_opdesc()?.code {
@@ -1532,7 +1572,7 @@ impl DecodedInsnCtx {
                 // MOV byte from mem (ModRM:r/m) to reg (ModRM:reg)
                 // 8A/r:	mov r8, r/m8
                 // REX + 8A/r:	mov r8, r/m8
-                let data = self.emulate_mmio_read(mctx, seg, ea)?;
+                let data = self.emulate_mmio_read(mctx, seg, ea, self.opsize)?;
                 let (reg, lhbr) = self.cal_modrm_bytereg()?;
                 write_bytereg(mctx, reg, lhbr, data as u8);
             }
@@ -1541,17 +1581,29 @@ impl DecodedInsnCtx {
                 // 8B/r:	mov r16, r/m16
                 // 8B/r:	mov r32, r/m32
                 // REX.W 8B/r:	mov r64, r/m64
-                let data = self.emulate_mmio_read(mctx, seg, ea)?;
+                let data = self.emulate_mmio_read(mctx, seg, ea, self.opsize)?;
                 write_reg(mctx, self.get_modrm_reg()?, data as usize, self.opsize);
             }
+            0xA0 => {
+                // MOV byte from seg:moffset to AL
+                // A0:		mov AL, moffs8
+                let data = self.emulate_mmio_read(mctx, seg, ea, self.opsize)?;
+                write_reg(mctx, Register::Rax, data as usize, self.opsize);
+            }
             0xA1 => {
                 // MOV from seg:moffset to AX/EAX/RAX
                 // A1:		mov AX, moffs16
                 // A1:		mov EAX, moffs32
                 // REX.W + A1:	mov RAX, moffs64
-                let data = self.emulate_mmio_read(mctx, seg, ea)?;
+                let data = self.emulate_mmio_read(mctx, seg, ea, self.opsize)?;
                 write_reg(mctx, Register::Rax, data as usize, self.opsize);
             }
+            0xA2 => {
+                // MOV byte from AL to seg:moffset
+                // A2:		mov moffs8, AL
+                let data = read_reg(mctx, Register::Rax, self.opsize);
+                self.emulate_mmio_write(mctx, seg, ea, data as u64)?;
+            }
             0xA3 => {
                 // MOV from AX/EAX/RAX to seg:moffset
                 // A3:		mov moffs16, AX
@@ -1575,4 +1627,135 @@ impl DecodedInsnCtx {
 
         Ok(())
     }
+
+    fn emulate_movx<I: InsnMachineCtx>(
+        &self,
+        mctx: &mut I,
+        sign_extend: bool,
+    ) -> Result<(), InsnError> {
+        // MOVZX/MOVSX from mem (ModRM:r/m) to reg (ModRM:reg)
+        // 0F B6/r:	movzx r16/r32/r64, r/m8
+        // 0F B7/r:	movzx r32/r64, r/m16
+        // 0F BE/r:	movsx r16/r32/r64, r/m8
+        // 0F BF/r:	movsx r32/r64, r/m16
+        let size = match self.get_opdesc()?.code {
+            0xB6 | 0xBE => Bytes::One,
+            0xB7 | 0xBF => Bytes::Two,
+            _ => return Err(InsnError::UnSupportedInsn),
+        };
+        let ea = self.cal_effective_addr(mctx)?;
+        let data = self.emulate_mmio_read(mctx, self.mem_operand_seg(), ea, size)?;
+        let data = match (sign_extend, size) {
+            (true, Bytes::One) => data as i8 as usize,
+            (true, Bytes::Two) => data as i16 as usize,
+            _ => data as usize,
+        };
+        write_reg(mctx, self.get_modrm_reg()?, data, self.opsize);
+
+        Ok(())
+    }
+
+    // Reads from the memory or MMIO location at the linear address `la`.
+    // Locations shared with the host are treated as MMIO.
+    fn read_mem_or_mmio<I: InsnMachineCtx>(&self, mctx: &I, la: usize) -> Result<u64, InsnError> {
+        let (pa, shared) = mctx.translate_linear_addr(la, false, false)?;
+        if shared {
+            return mctx.handle_mmio_read(pa, shared, self.opsize);
+        }
+
+        // Safety: The linear address is decoded from the instruction and checked. It can be
+        // remapped to a memory object with the read permission successfully, and the remapped
+        // memory size matches the operand size of the instruction.
+        unsafe {
+            Ok(match self.opsize {
+                Bytes::One => mctx.map_linear_addr::<u8>(la, false, false)?.mem_read()? as u64,
+                Bytes::Two => mctx.map_linear_addr::<u16>(la, false, false)?.mem_read()? as u64,
+                Bytes::Four => mctx.map_linear_addr::<u32>(la, false, false)?.mem_read()? as u64,
+                Bytes::Eight => mctx.map_linear_addr::<u64>(la, false, false)?.mem_read()?,
+                _ => return Err(InsnError::MemRead),
+            })
+        }
+    }
+
+    // Writes to the memory or MMIO location at the linear address `la`.
+    // Locations shared with the host are treated as MMIO.
+    fn write_mem_or_mmio<I: InsnMachineCtx>(
+        &self,
+        mctx: &mut I,
+        la: usize,
+        data: u64,
+    ) -> Result<(), InsnError> {
+        let (pa, shared) = mctx.translate_linear_addr(la, true, false)?;
+        if shared {
+            return mctx.handle_mmio_write(pa, shared, self.opsize, data);
+        }
+
+        // Safety: The linear address is decoded from the instruction and checked. It can be
+        // remapped to a memory object with the write permission successfully, and the remapped
+        // memory size matches the operand size of the instruction.
+        unsafe {
+            match self.opsize {
+                Bytes::One => mctx
+                    .map_linear_addr::<u8>(la, true, false)?
+                    .mem_write(data as u8),
+                Bytes::Two => mctx
+                    .map_linear_addr::<u16>(la, true, false)?
+                    .mem_write(data as u16),
+                Bytes::Four => mctx
+                    .map_linear_addr::<u32>(la, true, false)?
+                    .mem_write(data as u32),
+                Bytes::Eight => mctx
+                    .map_linear_addr::<u64>(la, true, false)?
+                    .mem_write(data),
+                _ => Err(InsnError::MemWrite),
+            }
+        }
+    }
+
+    fn emulate_movs<I: InsnMachineCtx>(&self, mctx: &mut I) -> Result<(), InsnError> {
+        // MOVS from DS:(E)SI or RSI to ES:(E)DI or RDI. The DS segment may
+        // be overridden with a segment override prefix. Either side may be
+        // MMIO.
+        // A4:		movs m8, m8
+        // A5:		movs m16, m16
+        // A5:		movs m32, m32
+        // REX.W + A5:	movs m64, m64
+        let src_seg = self.override_seg.unwrap_or(SegRegister::DS);
+        let src = self.get_linear_addr(
+            mctx,
+            src_seg,
+            read_reg(mctx, Register::Rsi, self.addrsize),
+            false,
+        )?;
+        let dst = self.get_linear_addr(
+            mctx,
+            SegRegister::ES,
+            read_reg(mctx, Register::Rdi, self.addrsize),
+            true,
+        )?;
+
+        let data = self.read_mem_or_mmio(mctx, src)?;
+        self.write_mem_or_mmio(mctx, dst, data)?;
+
+        let rflags = RFlags::from_bits_truncate(mctx.read_flags());
+        for reg in [Register::Rsi, Register::Rdi] {
+            let val = read_reg(mctx, reg, self.addrsize);
+            // The DF flag decides whether (E)SI/DI are decremented or
+            // incremented.
+            let val = if rflags.contains(RFlags::DF) {
+                val.wrapping_sub(self.opsize as usize)
+            } else {
+                val.wrapping_add(self.opsize as usize)
+            };
+            write_reg(mctx, reg, val, self.addrsize);
+        }
+
+        if self.repeat != 0 {
+            // Update the count register with the left count which are not
+            // emulated yet.
+            write_reg(mctx, Register::Rcx, self.repeat - 1, self.addrsize);
+        }
+
+        Ok(())
+    }
 }
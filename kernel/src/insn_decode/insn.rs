@@ -69,6 +69,9 @@ pub enum DecodedInsn {
     In(Operand, Bytes),
     Ins,
     Mov,
+    Movs,
+    Movsx,
+    Movzx,
     Out(Operand, Bytes),
     Outs,
     Wrmsr,
@@ -203,7 +206,7 @@ pub mod test_utils {
                 Register::Rax => self.rax,
                 Register::Rdx => self.rdx,
                 Register::Rcx => self.rcx,
-                Register::Rbx => self.rdx,
+                Register::Rbx => self.rbx,
                 Register::Rsp => self.rsp,
                 Register::Rbp => self.rbp,
                 Register::Rdi => self.rdi,
@@ -225,7 +228,7 @@ pub mod test_utils {
                 Register::Rax => self.rax = val,
                 Register::Rdx => self.rdx = val,
                 Register::Rcx => self.rcx = val,
-                Register::Rbx => self.rdx = val,
+                Register::Rbx => self.rbx = val,
                 Register::Rsp => self.rsp = val,
                 Register::Rbp => self.rbp = val,
                 Register::Rdi => self.rdi = val,
@@ -285,7 +288,8 @@ pub mod test_utils {
             _write: bool,
             _fetch: bool,
         ) -> Result<(usize, bool), InsnError> {
-            Ok((la, false))
+            // Only the emulated MMIO register is shared with the host.
+            Ok((la, la == &raw const self.mmio_reg as usize))
         }
 
         fn handle_mmio_read(
@@ -1349,6 +1353,173 @@ mod tests {
         assert_eq!(testctx.mmio_reg, 0x12345678);
     }
 
+    #[test]
+    fn test_decode_mov_moffset8() {
+        let mut raw_insn: [u8; MAX_INSN_SIZE] = [
+            0xA0, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut testctx = TestCtx {
+            rax: 0x1234,
+            mmio_reg: 0xab,
+            ..Default::default()
+        };
+        let addr = (&raw const testctx.mmio_reg as usize).to_le_bytes();
+        raw_insn[1..9].copy_from_slice(&addr);
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Mov);
+        assert_eq!(decoded.size(), 9);
+        assert_eq!(testctx.rax, 0x12ab);
+
+        raw_insn[0] = 0xA2;
+        let mut testctx = TestCtx {
+            rax: 0x1234,
+            ..Default::default()
+        };
+        let addr = (&raw const testctx.mmio_reg as usize).to_le_bytes();
+        raw_insn[1..9].copy_from_slice(&addr);
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Mov);
+        assert_eq!(decoded.size(), 9);
+        assert_eq!(testctx.mmio_reg, 0x34);
+    }
+
+    #[test]
+    fn test_decode_movzx() {
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0x0F, 0xB6, 0x07, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut testctx = TestCtx {
+            rax: usize::MAX,
+            mmio_reg: 0xfe,
+            ..Default::default()
+        };
+        testctx.rdi = &raw const testctx.mmio_reg as usize;
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate_mmio(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Movzx);
+        assert_eq!(decoded.size(), 3);
+        assert_eq!(testctx.rax, 0xfe);
+
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0x48, 0x0F, 0xB7, 0x1F, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut testctx = TestCtx {
+            rbx: usize::MAX,
+            mmio_reg: 0x12345678,
+            ..Default::default()
+        };
+        testctx.rdi = &raw const testctx.mmio_reg as usize;
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate_mmio(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Movzx);
+        assert_eq!(decoded.size(), 4);
+        assert_eq!(testctx.rbx, 0x5678);
+    }
+
+    #[test]
+    fn test_decode_movsx() {
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0x48, 0x0F, 0xBF, 0x07, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut testctx = TestCtx {
+            mmio_reg: 0x8000,
+            ..Default::default()
+        };
+        testctx.rdi = &raw const testctx.mmio_reg as usize;
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate_mmio(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Movsx);
+        assert_eq!(decoded.size(), 4);
+        assert_eq!(testctx.rax, 0xffff_ffff_ffff_8000);
+    }
+
+    #[test]
+    fn test_decode_movs() {
+        // movsq from MMIO to memory
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0x48, 0xA5, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut buffer = 0u64;
+        let mut testctx = TestCtx {
+            mmio_reg: 0x1234567890abcdef,
+            rdi: &raw mut buffer as usize,
+            ..Default::default()
+        };
+        testctx.rsi = &raw const testctx.mmio_reg as usize;
+        let (rsi, rdi) = (testctx.rsi, testctx.rdi);
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate_mmio(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Movs);
+        assert_eq!(decoded.size(), 2);
+        assert_eq!(buffer, 0x1234567890abcdef);
+        assert_eq!(testctx.rsi, rsi + 8);
+        assert_eq!(testctx.rdi, rdi + 8);
+
+        // rep movsw from memory to MMIO, moving backwards
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0x66, 0xF3, 0xA5, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let buffer = 0xabcdu16;
+        let mut testctx = TestCtx {
+            rcx: 2,
+            rsi: &raw const buffer as usize,
+            flags: RFlags::DF.bits(),
+            ..Default::default()
+        };
+        testctx.rdi = &raw const testctx.mmio_reg as usize;
+        let (rsi, rdi) = (testctx.rsi, testctx.rdi);
+
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+        decoded.emulate_mmio(&mut testctx).unwrap();
+
+        assert_eq!(decoded.insn().unwrap(), DecodedInsn::Movs);
+        // Not the last iteration, so the instruction is not skipped
+        assert_eq!(decoded.size(), 0);
+        assert_eq!(testctx.mmio_reg, 0xabcd);
+        assert_eq!(testctx.rcx, 1);
+        assert_eq!(testctx.rsi, rsi - 2);
+        assert_eq!(testctx.rdi, rdi - 2);
+    }
+
+    #[test]
+    fn test_emulate_mmio_rejects_ioio() {
+        let raw_insn: [u8; MAX_INSN_SIZE] = [
+            0xE4, 0xE0, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41,
+        ];
+
+        let mut testctx = TestCtx::default();
+        let decoded = Instruction::new(raw_insn).decode(&testctx).unwrap();
+
+        assert!(decoded.emulate_mmio(&mut testctx).is_err());
+    }
+
     #[test]
     fn test_decode_failed() {
         let raw_insn: [u8; MAX_INSN_SIZE] = [
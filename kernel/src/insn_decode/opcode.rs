@@ -43,6 +43,9 @@ pub enum OpCodeClass {
     In,
     Ins,
     Mov,
+    Movs,
+    Movsx,
+    Movzx,
     Out,
     Outs,
     Rdmsr,
@@ -109,16 +112,32 @@ static ONE_BYTE_TABLE: [Option<OpCodeDesc>; 256] = {
     table[0x8A] = opcode!(0x8A, OpCodeClass::Mov, OpCodeFlags::BYTE_OP.bits());
     table[0x89] = opcode!(0x89, OpCodeClass::Mov);
     table[0x8B] = opcode!(0x8B, OpCodeClass::Mov);
+    table[0xA0] = opcode!(
+        0xA0,
+        OpCodeClass::Mov,
+        OpCodeFlags::BYTE_OP.bits() | OpCodeFlags::MOFFSET.bits() | OpCodeFlags::NO_MODRM.bits()
+    );
     table[0xA1] = opcode!(
         0xA1,
         OpCodeClass::Mov,
         OpCodeFlags::MOFFSET.bits() | OpCodeFlags::NO_MODRM.bits()
     );
+    table[0xA2] = opcode!(
+        0xA2,
+        OpCodeClass::Mov,
+        OpCodeFlags::BYTE_OP.bits() | OpCodeFlags::MOFFSET.bits() | OpCodeFlags::NO_MODRM.bits()
+    );
     table[0xA3] = opcode!(
         0xA3,
         OpCodeClass::Mov,
         OpCodeFlags::MOFFSET.bits() | OpCodeFlags::NO_MODRM.bits()
     );
+    table[0xA4] = opcode!(
+        0xA4,
+        OpCodeClass::Movs,
+        OpCodeFlags::BYTE_OP.bits() | OpCodeFlags::NO_MODRM.bits()
+    );
+    table[0xA5] = opcode!(0xA5, OpCodeClass::Movs, OpCodeFlags::NO_MODRM.bits());
     table[0xC6] = opcode!(
         0xC6,
         OpCodeClass::Mov,
@@ -185,6 +204,10 @@ static TWO_BYTE_TABLE: [Option<OpCodeDesc>; 256] = {
     table[0x31] = opcode!(0x31, OpCodeClass::Rdtsc, OpCodeFlags::NO_MODRM.bits());
     table[0x32] = opcode!(0x32, OpCodeClass::Rdmsr, OpCodeFlags::NO_MODRM.bits());
     table[0xA2] = opcode!(0xA2, OpCodeClass::Cpuid, OpCodeFlags::NO_MODRM.bits());
+    table[0xB6] = opcode!(0xB6, OpCodeClass::Movzx);
+    table[0xB7] = opcode!(0xB7, OpCodeClass::Movzx);
+    table[0xBE] = opcode!(0xBE, OpCodeClass::Movsx);
+    table[0xBF] = opcode!(0xBF, OpCodeClass::Movsx);
 
     table
 };
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Message channels for communication between user-mode processes and the
+//! kernel.
+//!
+//! A channel consists of two connected [`Channel`] endpoints. Messages sent
+//! on one endpoint are received in order on the other one. Every endpoint has
+//! a bounded queue of incoming messages, senders never block but get
+//! [`ChannelError::QueueFull`] when the peer does not keep up. Receivers
+//! block until a message arrives or the peer endpoint is closed.
+
+extern crate alloc;
+
+use crate::error::SvsmError;
+use crate::fs::Buffer;
+use crate::locking::SpinLock;
+use crate::syscall::Obj;
+use crate::task::{current_task, schedule, wakeup_task, WaitQueue};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use syscall::CHANNEL_MSG_MAX;
+
+/// Maximum number of messages queued on a channel endpoint
+pub const CHANNEL_QUEUE_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelError {
+    /// The message exceeds [`CHANNEL_MSG_MAX`] bytes.
+    MessageTooLarge,
+    /// The receive buffer is too small for the next message.
+    BufferTooSmall,
+    /// The queue of the receiving endpoint is full.
+    QueueFull,
+    /// The peer endpoint has been closed.
+    PeerClosed,
+    /// Another task is already receiving on the endpoint.
+    RecvBusy,
+    /// No message arrived before the deadline of the receiver.
+    TimedOut,
+}
+
+impl From<ChannelError> for SvsmError {
+    fn from(err: ChannelError) -> Self {
+        Self::Channel(err)
+    }
+}
+
+/// Incoming message queue of one channel endpoint.
+#[derive(Debug, Default)]
+struct ChannelQueue {
+    messages: VecDeque<Vec<u8>>,
+    waiter: WaitQueue,
+    closed: bool,
+}
+
+impl ChannelQueue {
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(task) = self.waiter.wakeup() {
+            wakeup_task(task);
+        }
+    }
+}
+
+/// One endpoint of a message channel.
+#[derive(Debug)]
+pub struct Channel {
+    rx: Arc<SpinLock<ChannelQueue>>,
+    tx: Arc<SpinLock<ChannelQueue>>,
+}
+
+impl Channel {
+    /// Creates a new channel and returns its two connected endpoints.
+    pub fn new_pair() -> (Self, Self) {
+        let a = Arc::new(SpinLock::new(ChannelQueue::default()));
+        let b = Arc::new(SpinLock::new(ChannelQueue::default()));
+        (
+            Self {
+                rx: a.clone(),
+                tx: b.clone(),
+            },
+            Self { rx: b, tx: a },
+        )
+    }
+
+    /// Checks whether the peer endpoint has been closed.
+    pub fn is_peer_closed(&self) -> bool {
+        self.tx.lock().closed
+    }
+
+    /// Sends a message to the peer endpoint without blocking.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf`: [`Buffer`] containing the message.
+    ///
+    /// # Returns
+    ///
+    /// `()` on success, [`SvsmError`] on failure. The call fails with
+    /// [`ChannelError::QueueFull`] when the peer has too many pending
+    /// messages, and with [`ChannelError::PeerClosed`] when the peer endpoint
+    /// is gone.
+    pub fn send(&self, buf: &dyn Buffer) -> Result<(), SvsmError> {
+        let len = buf.size();
+        if len > CHANNEL_MSG_MAX {
+            return Err(ChannelError::MessageTooLarge.into());
+        }
+
+        let mut msg = vec![0u8; len];
+        buf.read_buffer(&mut msg, 0)?;
+
+        let mut tx = self.tx.lock();
+        if tx.closed {
+            return Err(ChannelError::PeerClosed.into());
+        }
+        if tx.messages.len() >= CHANNEL_QUEUE_LEN {
+            return Err(ChannelError::QueueFull.into());
+        }
+        tx.messages.push_back(msg);
+        let waiter = tx.waiter.wakeup();
+        drop(tx);
+
+        if let Some(task) = waiter {
+            wakeup_task(task);
+        }
+        Ok(())
+    }
+
+    /// Takes the next message from the queue when it fits into `size` bytes.
+    fn pop_message(&self, size: usize) -> Result<Option<Vec<u8>>, SvsmError> {
+        let mut rx = self.rx.lock();
+        match rx.messages.front() {
+            Some(msg) if msg.len() > size => Err(ChannelError::BufferTooSmall.into()),
+            Some(_) => Ok(rx.messages.pop_front()),
+            None if rx.closed => Err(ChannelError::PeerClosed.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Copies a message to `buf` and returns its size. A message that can
+    /// not be copied is put back to the front of the queue.
+    fn deliver(&self, msg: Vec<u8>, buf: &mut dyn Buffer) -> Result<usize, SvsmError> {
+        match buf.write_buffer(&msg, 0) {
+            Ok(len) => Ok(len),
+            Err(e) => {
+                self.rx.lock().messages.push_front(msg);
+                Err(e)
+            }
+        }
+    }
+
+    /// Receives the next message without blocking.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf`: [`Buffer`] to receive the message. It must be large enough
+    ///   to hold the complete message.
+    ///
+    /// # Returns
+    ///
+    /// The size of the received message, `None` if no message is pending, or
+    /// [`SvsmError`] on failure.
+    pub fn try_recv(&self, buf: &mut dyn Buffer) -> Result<Option<usize>, SvsmError> {
+        self.pop_message(buf.size())?
+            .map(|msg| self.deliver(msg, buf))
+            .transpose()
+    }
+
+    /// Receives the next message, blocking the current task until one
+    /// arrives. Only one task can wait on an endpoint at a time.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf`: [`Buffer`] to receive the message. It must be large enough
+    ///   to hold the complete message.
+    ///
+    /// # Returns
+    ///
+    /// The size of the received message on success, [`SvsmError`] on failure.
+    /// Pending messages are still delivered after the peer endpoint was
+    /// closed, afterwards the call fails with [`ChannelError::PeerClosed`].
+    pub fn recv(&self, buf: &mut dyn Buffer) -> Result<usize, SvsmError> {
+        loop {
+            if let Some(msg) = self.pop_message(buf.size())? {
+                return self.deliver(msg, buf);
+            }
+
+            let mut rx = self.rx.lock();
+            if !rx.messages.is_empty() || rx.closed {
+                continue;
+            }
+            if rx.waiter.is_waiting() {
+                return Err(ChannelError::RecvBusy.into());
+            }
+            rx.waiter.wait_for_event(current_task());
+            drop(rx);
+
+            schedule();
+        }
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.tx.lock().close();
+        self.rx.lock().close();
+    }
+}
+
+impl Obj for Channel {
+    fn as_channel(&self) -> Option<&Channel> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{SliceMutRefBuffer, SliceRefBuffer};
+
+    fn send(ch: &Channel, msg: &[u8]) -> Result<(), SvsmError> {
+        ch.send(&SliceRefBuffer::new(msg))
+    }
+
+    fn try_recv(ch: &Channel, buf: &mut [u8]) -> Result<Option<usize>, SvsmError> {
+        ch.try_recv(&mut SliceMutRefBuffer::new(buf))
+    }
+
+    #[test]
+    fn test_channel_send_recv() {
+        let (a, b) = Channel::new_pair();
+        let mut buf = [0u8; 16];
+
+        send(&a, b"hello").unwrap();
+        send(&a, b"world!").unwrap();
+        send(&b, b"reply").unwrap();
+
+        assert_eq!(try_recv(&b, &mut buf).unwrap(), Some(5));
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(try_recv(&b, &mut buf).unwrap(), Some(6));
+        assert_eq!(&buf[..6], b"world!");
+        assert_eq!(try_recv(&b, &mut buf).unwrap(), None);
+
+        assert_eq!(try_recv(&a, &mut buf).unwrap(), Some(5));
+        assert_eq!(&buf[..5], b"reply");
+    }
+
+    #[test]
+    fn test_channel_limits() {
+        let (a, b) = Channel::new_pair();
+        let large = vec![0u8; CHANNEL_MSG_MAX + 1];
+
+        assert!(matches!(
+            send(&a, &large),
+            Err(SvsmError::Channel(ChannelError::MessageTooLarge))
+        ));
+
+        for _ in 0..CHANNEL_QUEUE_LEN {
+            send(&a, b"message").unwrap();
+        }
+        assert!(matches!(
+            send(&a, b"message"),
+            Err(SvsmError::Channel(ChannelError::QueueFull))
+        ));
+
+        // A message which does not fit stays queued
+        let mut small = [0u8; 4];
+        assert!(matches!(
+            try_recv(&b, &mut small),
+            Err(SvsmError::Channel(ChannelError::BufferTooSmall))
+        ));
+        let mut buf = [0u8; 16];
+        assert_eq!(try_recv(&b, &mut buf).unwrap(), Some(7));
+    }
+
+    #[test]
+    fn test_channel_peer_closed() {
+        let (a, b) = Channel::new_pair();
+        let mut buf = [0u8; 16];
+
+        send(&a, b"last").unwrap();
+        drop(a);
+
+        assert!(matches!(
+            send(&b, b"message"),
+            Err(SvsmError::Channel(ChannelError::PeerClosed))
+        ));
+        assert_eq!(try_recv(&b, &mut buf).unwrap(), Some(4));
+        assert!(matches!(
+            try_recv(&b, &mut buf),
+            Err(SvsmError::Channel(ChannelError::PeerClosed))
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+pub mod channel;
+
+pub use channel::{Channel, ChannelError, CHANNEL_QUEUE_LEN};
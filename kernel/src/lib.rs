@@ -26,8 +26,11 @@ pub mod hyperv;
 pub mod igvm_params;
 pub mod insn_decode;
 pub mod io;
+pub mod ipc;
 pub mod kernel_region;
 pub mod locking;
+pub mod log_buffer;
+pub mod migration;
 pub mod mm;
 pub mod platform;
 pub mod protocols;
@@ -43,6 +46,7 @@ pub mod utils;
 #[cfg(feature = "virtio-drivers")]
 pub mod virtio;
 pub mod vmm;
+pub mod vsock;
 #[cfg(all(feature = "vtpm", not(test)))]
 pub mod vtpm;
 
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! In-memory ring buffer which retains the most recent log messages of the
+//! SVSM kernel, so that they are available when no serial console is
+//! attached. The buffer is readable as `/proc/log` and included in crash
+//! dumps.
+
+extern crate alloc;
+
+use crate::cpu::msr::rdtsc;
+use crate::cpu::percpu::try_this_cpu;
+use crate::locking::SpinLock;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Size of the log buffer in bytes
+pub const LOG_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+struct LogBuffer {
+    buf: Box<[u8]>,
+    /// Number of bytes written to the buffer since it was created
+    head: usize,
+}
+
+impl LogBuffer {
+    fn new(size: usize) -> Self {
+        Self {
+            buf: vec![0; size].into_boxed_slice(),
+            head: 0,
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        let size = self.buf.len();
+        // Only the tail of messages larger than the buffer is retained.
+        let data = &data[data.len().saturating_sub(size)..];
+        let pos = self.head % size;
+        let n = data.len().min(size - pos);
+        self.buf[pos..pos + n].copy_from_slice(&data[..n]);
+        self.buf[..data.len() - n].copy_from_slice(&data[n..]);
+        self.head += data.len();
+    }
+
+    /// Returns the retained log as two slices which are to be concatenated,
+    /// oldest messages first. After the buffer wrapped, the partially
+    /// overwritten oldest message is skipped.
+    fn contents(&self) -> (&[u8], &[u8]) {
+        let size = self.buf.len();
+        if self.head <= size {
+            return (&self.buf[..self.head], &[]);
+        }
+
+        let (newer, older) = self.buf.split_at(self.head % size);
+        match older.iter().position(|b| *b == b'\n') {
+            Some(i) => (&older[i + 1..], newer),
+            None => {
+                let i = newer
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .unwrap_or(newer.len());
+                (&newer[(i + 1).min(newer.len())..], &[])
+            }
+        }
+    }
+}
+
+impl fmt::Write for LogBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+static LOG_BUFFER: SpinLock<Option<LogBuffer>> = SpinLock::new(None);
+
+/// Allocates the log buffer. Messages logged before are only written to the
+/// console.
+pub fn init_log_buffer() {
+    let buffer = LogBuffer::new(LOG_BUFFER_SIZE);
+    LOG_BUFFER.lock().get_or_insert(buffer);
+}
+
+/// Records a log message with the current timestamp counter, CPU index and
+/// level in the log buffer.
+pub fn log_buffer_record(record: &log::Record<'_>) {
+    let timestamp = rdtsc();
+    let cpu_index = try_this_cpu().map(|cpu| cpu.get_cpu_index());
+    let level = record.metadata().level();
+
+    let mut guard = LOG_BUFFER.lock();
+    let Some(buffer) = guard.as_mut() else {
+        return;
+    };
+    let _ = match cpu_index {
+        Some(index) => fmt::write(buffer, format_args!("[{timestamp:>20}] [CPU {index}] ")),
+        None => fmt::write(buffer, format_args!("[{timestamp:>20}] [CPU ?] ")),
+    };
+    let _ = fmt::write(buffer, format_args!("{level}: {}\n", record.args()));
+}
+
+/// Returns a copy of the messages in the log buffer, oldest first.
+pub fn log_buffer_read() -> Vec<u8> {
+    let guard = LOG_BUFFER.lock();
+    let Some(buffer) = guard.as_ref() else {
+        return Vec::new();
+    };
+    let (older, newer) = buffer.contents();
+    [older, newer].concat()
+}
+
+/// Calls `f` with the contents of the log buffer, oldest messages first,
+/// without blocking. Used when the system crashed, possibly while the log
+/// buffer was locked.
+///
+/// # Returns
+///
+/// The return value of `f`, or `None` when the log buffer is locked or not
+/// initialized.
+pub fn try_with_log_buffer<R>(f: impl FnOnce(&[u8], &[u8]) -> R) -> Option<R> {
+    let guard = LOG_BUFFER.try_lock()?;
+    let (older, newer) = guard.as_ref()?.contents();
+    Some(f(older, newer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(buffer: &LogBuffer) -> Vec<u8> {
+        let (older, newer) = buffer.contents();
+        [older, newer].concat()
+    }
+
+    #[test]
+    fn test_log_buffer_wrap() {
+        let mut buffer = LogBuffer::new(16);
+        buffer.push(b"first\n");
+        buffer.push(b"second\n");
+        assert_eq!(contents(&buffer), b"first\nsecond\n");
+
+        // Overwrites the start of "first", which is dropped completely.
+        buffer.push(b"third\n");
+        assert_eq!(contents(&buffer), b"second\nthird\n");
+
+        buffer.push(b"0123456789abcdefgh\n");
+        assert_eq!(contents(&buffer), b"");
+        buffer.push(b"x\n");
+        assert_eq!(contents(&buffer), b"x\n");
+    }
+}
@@ -0,0 +1,607 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Serialization of SVSM state for live migration.
+//!
+//! A migration image carries the state the SVSM keeps for the guest, which
+//! is not part of guest memory: the VMSA and calling area of every vCPU and
+//! the state of the vTPM, including its volatile state. The image consists
+//! of a [`MigrationHeader`] followed by a sequence of records, encrypted with
+//! AES-256-GCM under a key from [`SvsmPlatform::derive_migration_key()`].
+//! The header is used as additional authenticated data.
+//!
+//! Export and import are driven by a user-mode process holding the
+//! [`ProcessCaps::MIGRATION`] capability, which talks to the migration agent
+//! over an attested channel. The guest has no access to them.
+//!
+//! Every image is bound to the nonce of the migration session. The nonce is
+//! generated by the SVSM on the destination, see [`migration_nonce()`], and
+//! is handed to the source by the migration agent. It is only valid for a
+//! single import, so an image of an earlier session can not be replayed to
+//! the destination. Images also carry a generation, which grows with every
+//! export and import, and an SVSM never imports an image with a generation
+//! below one it has seen before.
+//!
+//! [`ProcessCaps::MIGRATION`]: syscall::ProcessCaps::MIGRATION
+//! [`SvsmPlatform::derive_migration_key()`]: crate::platform::SvsmPlatform::derive_migration_key
+
+extern crate alloc;
+
+use crate::address::PhysAddr;
+use crate::cpu::percpu::PERCPU_AREAS;
+use crate::crypto::aead::{Aes256Gcm, Aes256GcmTrait, AUTHTAG_SIZE, IV_SIZE, KEY_SIZE};
+use crate::crypto::rng::fill_random;
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::mm::guestmem::{copy_slice_to_guest, read_bytes_from_guest};
+use crate::platform::SVSM_PLATFORM;
+use crate::protocols::core::create_vcpu;
+use crate::protocols::errors::SvsmReqError;
+use crate::types::PAGE_SIZE;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::{offset_of, size_of};
+use cpuarch::vmsa::VMSA;
+use syscall::MIGRATION_NONCE_SIZE;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Label for deriving the key which protects migration images.
+const MIGRATION_KEY_LABEL: &[u8] = b"svsm-migration-image";
+
+const MIGRATION_MAGIC: [u8; 8] = *b"SVSMMIGR";
+const MIGRATION_VERSION: u32 = 1;
+
+/// The vTPM record contains the saved volatile state of the TPM
+const VTPM_STATE_SAVED: u32 = 1 << 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The image is malformed or failed the integrity check.
+    InvalidImage,
+    /// The image contains state which is not supported by this SVSM.
+    UnsupportedRecord,
+    /// The image contains state for a CPU which does not exist.
+    UnknownCpu,
+    /// The image belongs to a different migration session, or no import
+    /// was started with [`migration_nonce()`].
+    SessionMismatch,
+    /// The image is older than an image exported or imported before.
+    Rollback,
+    /// A guest vCPU is still running.
+    VcpuRunning,
+}
+
+impl From<MigrationError> for SvsmError {
+    fn from(err: MigrationError) -> Self {
+        Self::Migration(err)
+    }
+}
+
+/// Header of a migration image.
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct MigrationHeader {
+    magic: [u8; 8],
+    version: u32,
+    /// Size of the unencrypted records
+    size: u32,
+    iv: [u8; IV_SIZE],
+    reserved: [u8; 4],
+    /// Generation of the image
+    generation: u64,
+    /// Nonce of the migration session
+    nonce: [u8; MIGRATION_NONCE_SIZE],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum RecordType {
+    Vcpu = 1,
+    Vtpm = 2,
+}
+
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct RecordHeader {
+    record_type: u32,
+    /// Size of the record data following the header
+    size: u32,
+}
+
+/// Record data of a vCPU. Followed by the contents of the VMSA page when
+/// `vmsa_gpa` is set.
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct VcpuRecord {
+    apic_id: u32,
+    reserved: u32,
+    /// Guest physical address of the VMSA, 0 if not set
+    vmsa_gpa: u64,
+    /// Guest physical address of the calling area, 0 if not set
+    caa_gpa: u64,
+}
+
+/// Record data of the vTPM, followed by the NV memory of the TPM.
+#[derive(Clone, Copy, Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct VtpmRecord {
+    flags: u32,
+    reserved: u32,
+}
+
+/// State of one guest vCPU
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VcpuState {
+    pub apic_id: u32,
+    pub vmsa_gpa: Option<PhysAddr>,
+    pub caa_gpa: Option<PhysAddr>,
+    /// Contents of the VMSA page, empty if there is no VMSA
+    pub vmsa: Vec<u8>,
+}
+
+/// State of the vTPM
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VtpmState {
+    /// NV memory of the TPM
+    pub nv_state: Vec<u8>,
+    /// Whether the NV memory holds the saved volatile state of the TPM
+    pub state_saved: bool,
+}
+
+/// SVSM state transferred in a migration image
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationState {
+    pub vcpus: Vec<VcpuState>,
+    pub vtpm: Option<VtpmState>,
+}
+
+fn push_record(out: &mut Vec<u8>, record_type: RecordType, data: &[&[u8]]) {
+    let size = data.iter().map(|d| d.len()).sum::<usize>();
+    let header = RecordHeader {
+        record_type: record_type as u32,
+        size: size as u32,
+    };
+    out.extend_from_slice(header.as_bytes());
+    for d in data {
+        out.extend_from_slice(d);
+    }
+}
+
+impl MigrationState {
+    /// Captures the current state of the SVSM. The guest vCPUs must be
+    /// stopped while the state is captured.
+    pub fn capture() -> Result<Self, SvsmError> {
+        let mut vcpus = Vec::new();
+        for cpu in PERCPU_AREAS.iter() {
+            let (vmsa_gpa, caa_gpa) = cpu.guest_vmsa_caa();
+            let vmsa = match vmsa_gpa {
+                Some(gpa) => read_bytes_from_guest(gpa, PAGE_SIZE)?,
+                None => Vec::new(),
+            };
+            vcpus.push(VcpuState {
+                apic_id: cpu.apic_id(),
+                vmsa_gpa,
+                caa_gpa,
+                vmsa,
+            });
+        }
+
+        #[cfg(feature = "vtpm")]
+        let vtpm = {
+            let (nv_state, state_saved) =
+                crate::vtpm::vtpm_get_migration_state().map_err(|_| SvsmError::NotSupported)?;
+            Some(VtpmState {
+                nv_state,
+                state_saved,
+            })
+        };
+        #[cfg(not(feature = "vtpm"))]
+        let vtpm = None;
+
+        Ok(Self { vcpus, vtpm })
+    }
+
+    /// Restores a captured state on the destination of a migration. The
+    /// guest memory must have been migrated already. The vCPUs are created
+    /// like with the SVSM_CORE_CREATE_VCPU call, including the checks of
+    /// their VMSAs. All VMSAs must have the SEV features of the first one.
+    ///
+    /// # Returns
+    ///
+    /// `()` on success. Fails with [`MigrationError::VcpuRunning`] when a
+    /// guest vCPU has not been deleted.
+    pub fn restore(&self) -> Result<(), SvsmError> {
+        if PERCPU_AREAS
+            .iter()
+            .any(|cpu| cpu.guest_vmsa_caa().0.is_some())
+        {
+            return Err(MigrationError::VcpuRunning.into());
+        }
+
+        let mut sev_features = None;
+        for vcpu in self.vcpus.iter() {
+            if PERCPU_AREAS.get_by_apic_id(vcpu.apic_id).is_none() {
+                return Err(MigrationError::UnknownCpu.into());
+            }
+            let (vmsa_gpa, caa_gpa) = match (vcpu.vmsa_gpa, vcpu.caa_gpa) {
+                (Some(vmsa_gpa), Some(caa_gpa)) => (vmsa_gpa, caa_gpa),
+                (None, _) => continue,
+                (Some(_), None) => return Err(MigrationError::InvalidImage.into()),
+            };
+
+            let features = *sev_features.get_or_insert_with(|| vmsa_sev_features(&vcpu.vmsa));
+            copy_slice_to_guest(&vcpu.vmsa, vmsa_gpa)?;
+            create_vcpu(vmsa_gpa, caa_gpa, vcpu.apic_id, features).map_err(|e| match e {
+                SvsmReqError::FatalError(e) => e,
+                SvsmReqError::RequestError(_) => MigrationError::InvalidImage.into(),
+            })?;
+        }
+
+        #[cfg(feature = "vtpm")]
+        if let Some(vtpm) = self.vtpm.as_ref() {
+            crate::vtpm::vtpm_resume(&vtpm.nv_state, vtpm.state_saved)
+                .map_err(|_| SvsmError::NotSupported)?;
+        }
+        #[cfg(not(feature = "vtpm"))]
+        if self.vtpm.is_some() {
+            return Err(MigrationError::UnsupportedRecord.into());
+        }
+
+        Ok(())
+    }
+
+    /// Serializes and encrypts the state into a migration image.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The migration key shared with the destination.
+    /// * `nonce` - The nonce of the migration session.
+    /// * `generation` - The generation of the image.
+    ///
+    /// # Returns
+    ///
+    /// The migration image on success, [`SvsmError`] on failure.
+    pub fn seal(
+        &self,
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; MIGRATION_NONCE_SIZE],
+        generation: u64,
+    ) -> Result<Vec<u8>, SvsmError> {
+        let mut records = Vec::new();
+        for vcpu in self.vcpus.iter() {
+            let record = VcpuRecord {
+                apic_id: vcpu.apic_id,
+                reserved: 0,
+                vmsa_gpa: vcpu.vmsa_gpa.map_or(0, u64::from),
+                caa_gpa: vcpu.caa_gpa.map_or(0, u64::from),
+            };
+            let vmsa: &[u8] = if vcpu.vmsa_gpa.is_some() {
+                &vcpu.vmsa
+            } else {
+                &[]
+            };
+            push_record(&mut records, RecordType::Vcpu, &[record.as_bytes(), vmsa]);
+        }
+        if let Some(vtpm) = self.vtpm.as_ref() {
+            let record = VtpmRecord {
+                flags: if vtpm.state_saved {
+                    VTPM_STATE_SAVED
+                } else {
+                    0
+                },
+                reserved: 0,
+            };
+            push_record(
+                &mut records,
+                RecordType::Vtpm,
+                &[record.as_bytes(), &vtpm.nv_state],
+            );
+        }
+
+        let mut header = MigrationHeader {
+            magic: MIGRATION_MAGIC,
+            version: MIGRATION_VERSION,
+            size: u32::try_from(records.len()).map_err(|_| SvsmError::Mem)?,
+            iv: [0; IV_SIZE],
+            reserved: [0; 4],
+            generation,
+            nonce: *nonce,
+        };
+        fill_random(&mut header.iv)?;
+
+        let header_size = size_of::<MigrationHeader>();
+        let mut image = vec![0u8; header_size + records.len() + AUTHTAG_SIZE];
+        image[..header_size].copy_from_slice(header.as_bytes());
+        Aes256Gcm::encrypt(
+            &header.iv,
+            key,
+            header.as_bytes(),
+            &records,
+            &mut image[header_size..],
+        )
+        .map_err(|_| SvsmError::Crypto)?;
+        records.fill(0);
+
+        Ok(image)
+    }
+
+    /// Decrypts and parses a migration image created with [`Self::seal()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The migration image.
+    /// * `key` - The migration key shared with the source.
+    /// * `nonce` - The nonce of the migration session.
+    /// * `min_generation` - The lowest acceptable generation of the image.
+    ///
+    /// # Returns
+    ///
+    /// The migration state and the generation of the image on success.
+    /// Fails with [`MigrationError::InvalidImage`] when the image is
+    /// malformed or was not created with the same key, with
+    /// [`MigrationError::SessionMismatch`] when it was not created for the
+    /// migration session with `nonce`, and with [`MigrationError::Rollback`]
+    /// when its generation is below `min_generation`.
+    pub fn unseal(
+        image: &[u8],
+        key: &[u8; KEY_SIZE],
+        nonce: &[u8; MIGRATION_NONCE_SIZE],
+        min_generation: u64,
+    ) -> Result<(Self, u64), SvsmError> {
+        let (header, ciphertext) =
+            MigrationHeader::read_from_prefix(image).map_err(|_| MigrationError::InvalidImage)?;
+        let size = header.size as usize;
+        if header.magic != MIGRATION_MAGIC
+            || header.version != MIGRATION_VERSION
+            || ciphertext.len() != size + AUTHTAG_SIZE
+        {
+            return Err(MigrationError::InvalidImage.into());
+        }
+        if header.nonce != *nonce {
+            return Err(MigrationError::SessionMismatch.into());
+        }
+
+        let mut records = vec![0u8; size];
+        Aes256Gcm::decrypt(&header.iv, key, header.as_bytes(), ciphertext, &mut records)
+            .map_err(|_| MigrationError::InvalidImage)?;
+        // The generation is only trusted once the header is authenticated
+        if header.generation < min_generation {
+            records.fill(0);
+            return Err(MigrationError::Rollback.into());
+        }
+
+        let result = Self::parse(&records);
+        records.fill(0);
+        result.map(|state| (state, header.generation))
+    }
+
+    fn parse(mut records: &[u8]) -> Result<Self, SvsmError> {
+        let mut state = Self::default();
+
+        while !records.is_empty() {
+            let (header, rest) = RecordHeader::read_from_prefix(records)
+                .map_err(|_| MigrationError::InvalidImage)?;
+            let size = header.size as usize;
+            if rest.len() < size {
+                return Err(MigrationError::InvalidImage.into());
+            }
+            let (data, rest) = rest.split_at(size);
+            records = rest;
+
+            match header.record_type {
+                t if t == RecordType::Vcpu as u32 => {
+                    let (record, vmsa) = VcpuRecord::read_from_prefix(data)
+                        .map_err(|_| MigrationError::InvalidImage)?;
+                    let vmsa_gpa = (record.vmsa_gpa != 0).then(|| PhysAddr::from(record.vmsa_gpa));
+                    let expected = if vmsa_gpa.is_some() { PAGE_SIZE } else { 0 };
+                    if vmsa.len() != expected {
+                        return Err(MigrationError::InvalidImage.into());
+                    }
+                    state.vcpus.push(VcpuState {
+                        apic_id: record.apic_id,
+                        vmsa_gpa,
+                        caa_gpa: (record.caa_gpa != 0).then(|| PhysAddr::from(record.caa_gpa)),
+                        vmsa: vmsa.to_vec(),
+                    });
+                }
+                t if t == RecordType::Vtpm as u32 => {
+                    let (record, nv_state) = VtpmRecord::read_from_prefix(data)
+                        .map_err(|_| MigrationError::InvalidImage)?;
+                    if record.flags & !VTPM_STATE_SAVED != 0 {
+                        return Err(MigrationError::UnsupportedRecord.into());
+                    }
+                    state.vtpm = Some(VtpmState {
+                        nv_state: nv_state.to_vec(),
+                        state_saved: record.flags & VTPM_STATE_SAVED != 0,
+                    });
+                }
+                _ => return Err(MigrationError::UnsupportedRecord.into()),
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+/// Returns the SEV features of the VMSA page in `vmsa`.
+fn vmsa_sev_features(vmsa: &[u8]) -> u64 {
+    let offset = offset_of!(VMSA, sev_features);
+    vmsa.get(offset..offset + size_of::<u64>())
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, u64::from_le_bytes)
+}
+
+/// Migration state of this SVSM
+#[derive(Debug)]
+struct MigrationSession {
+    /// Nonce of the import started by [`migration_nonce()`]
+    import_nonce: Option<[u8; MIGRATION_NONCE_SIZE]>,
+    /// Generation of the last image exported or imported
+    generation: u64,
+}
+
+static MIGRATION_SESSION: SpinLock<MigrationSession> = SpinLock::new(MigrationSession {
+    import_nonce: None,
+    generation: 0,
+});
+
+/// Starts the import of a migration image on the destination of a live
+/// migration.
+///
+/// # Returns
+///
+/// A new random nonce for the migration session, which the source has to
+/// pass to [`migration_export()`]. It replaces the nonce of an earlier
+/// import which was not completed.
+pub fn migration_nonce() -> Result<[u8; MIGRATION_NONCE_SIZE], SvsmError> {
+    let mut nonce = [0u8; MIGRATION_NONCE_SIZE];
+    fill_random(&mut nonce)?;
+    MIGRATION_SESSION.lock().import_nonce = Some(nonce);
+    Ok(nonce)
+}
+
+/// Captures the SVSM state and seals it into a migration image for the
+/// destination of a live migration. The guest vCPUs must be stopped.
+///
+/// # Arguments
+///
+/// * `nonce` - The nonce of the migration session, generated by the
+///   destination with [`migration_nonce()`].
+pub fn migration_export(nonce: &[u8; MIGRATION_NONCE_SIZE]) -> Result<Vec<u8>, SvsmError> {
+    let key = SVSM_PLATFORM.derive_migration_key(MIGRATION_KEY_LABEL)?;
+    let mut session = MIGRATION_SESSION.lock();
+    let generation = session.generation.checked_add(1).ok_or(SvsmError::Mem)?;
+    let image = MigrationState::capture()?.seal(&key, nonce, generation)?;
+    session.generation = generation;
+    Ok(image)
+}
+
+/// Restores the SVSM state from a migration image created by
+/// [`migration_export()`] on the source of a live migration. The import
+/// must have been started with [`migration_nonce()`], which is consumed by
+/// this call, and all guest vCPUs must have been deleted.
+///
+/// # Arguments
+///
+/// * `image` - The migration image.
+pub fn migration_import(image: &[u8]) -> Result<(), SvsmError> {
+    let key = SVSM_PLATFORM.derive_migration_key(MIGRATION_KEY_LABEL)?;
+    let mut session = MIGRATION_SESSION.lock();
+    let nonce = session
+        .import_nonce
+        .take()
+        .ok_or(MigrationError::SessionMismatch)?;
+    let min_generation = session.generation.checked_add(1).ok_or(SvsmError::Mem)?;
+    let (state, generation) = MigrationState::unseal(image, &key, &nonce, min_generation)?;
+    state.restore()?;
+    session.generation = generation;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = [0x17; KEY_SIZE];
+    const NONCE: [u8; MIGRATION_NONCE_SIZE] = [0x23; MIGRATION_NONCE_SIZE];
+
+    fn test_state() -> MigrationState {
+        MigrationState {
+            vcpus: vec![
+                VcpuState {
+                    apic_id: 0,
+                    vmsa_gpa: Some(PhysAddr::from(0x10000u64)),
+                    caa_gpa: Some(PhysAddr::from(0x20000u64)),
+                    vmsa: vec![0xaa; PAGE_SIZE],
+                },
+                VcpuState {
+                    apic_id: 1,
+                    ..Default::default()
+                },
+            ],
+            vtpm: Some(VtpmState {
+                nv_state: vec![0x55; 128],
+                state_saved: true,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_migration_seal_unseal() {
+        let state = test_state();
+        let image = state.seal(&KEY, &NONCE, 1).unwrap();
+        assert_eq!(
+            MigrationState::unseal(&image, &KEY, &NONCE, 1).unwrap(),
+            (state, 1)
+        );
+
+        assert!(matches!(
+            MigrationState::unseal(&image, &[0x18; KEY_SIZE], &NONCE, 0),
+            Err(SvsmError::Migration(MigrationError::InvalidImage))
+        ));
+        assert!(matches!(
+            MigrationState::unseal(&image[..image.len() - 1], &KEY, &NONCE, 0),
+            Err(SvsmError::Migration(MigrationError::InvalidImage))
+        ));
+    }
+
+    #[test]
+    fn test_migration_tampered_image() {
+        let mut image = test_state().seal(&KEY, &NONCE, 1).unwrap();
+        // Flip a bit in the header, which is authenticated as well
+        image[offset_of!(MigrationHeader, reserved)] ^= 1;
+        assert!(matches!(
+            MigrationState::unseal(&image, &KEY, &NONCE, 0),
+            Err(SvsmError::Migration(MigrationError::InvalidImage))
+        ));
+    }
+
+    #[test]
+    fn test_migration_session_nonce() {
+        let image = test_state().seal(&KEY, &NONCE, 1).unwrap();
+        assert!(matches!(
+            MigrationState::unseal(&image, &KEY, &[0x24; MIGRATION_NONCE_SIZE], 0),
+            Err(SvsmError::Migration(MigrationError::SessionMismatch))
+        ));
+
+        // Rewriting the nonce in the header breaks the authentication
+        let mut image = image;
+        let offset = offset_of!(MigrationHeader, nonce);
+        image[offset..offset + MIGRATION_NONCE_SIZE].fill(0x24);
+        assert!(matches!(
+            MigrationState::unseal(&image, &KEY, &[0x24; MIGRATION_NONCE_SIZE], 0),
+            Err(SvsmError::Migration(MigrationError::InvalidImage))
+        ));
+    }
+
+    #[test]
+    fn test_migration_generation() {
+        let image = test_state().seal(&KEY, &NONCE, 5).unwrap();
+        assert!(MigrationState::unseal(&image, &KEY, &NONCE, 5).is_ok());
+        assert!(matches!(
+            MigrationState::unseal(&image, &KEY, &NONCE, 6),
+            Err(SvsmError::Migration(MigrationError::Rollback))
+        ));
+
+        // Lowering the generation in the header breaks the authentication
+        let mut image = image;
+        let offset = offset_of!(MigrationHeader, generation);
+        image[offset..offset + size_of::<u64>()].copy_from_slice(&7u64.to_le_bytes());
+        assert!(matches!(
+            MigrationState::unseal(&image, &KEY, &NONCE, 6),
+            Err(SvsmError::Migration(MigrationError::InvalidImage))
+        ));
+    }
+
+    #[test]
+    fn test_vmsa_sev_features() {
+        let mut vmsa = vec![0u8; PAGE_SIZE];
+        let offset = offset_of!(VMSA, sev_features);
+        vmsa[offset..offset + size_of::<u64>()].copy_from_slice(&0x21u64.to_le_bytes());
+        assert_eq!(vmsa_sev_features(&vmsa), 0x21);
+        assert_eq!(vmsa_sev_features(&[]), 0);
+    }
+}
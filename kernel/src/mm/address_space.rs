@@ -5,6 +5,8 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use crate::address::{PhysAddr, VirtAddr};
+#[cfg(target_os = "none")]
+use crate::mm::alloc::deposited_phys_to_virt;
 use crate::mm::pagetable::{PageFrame, PageTable};
 use crate::utils::immut_after_init::ImmutAfterInitCell;
 
@@ -92,6 +94,9 @@ pub fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
             return addr;
         }
     }
+    if let Some(addr) = deposited_phys_to_virt(paddr) {
+        return addr;
+    }
 
     panic!("Invalid physical address {:#018x}", paddr);
 }
@@ -152,6 +157,15 @@ pub const SVSM_GLOBAL_MAPPING_END: VirtAddr = SVSM_GLOBAL_MAPPING_BASE.const_add
 /// Mapping address for Hyper-V hypercall page.
 pub const SVSM_HYPERCALL_CODE_PAGE: VirtAddr = SVSM_GLOBAL_MAPPING_BASE.const_sub(PAGE_SIZE);
 
+/// Size of the region for memory deposited by the guest
+pub const SVSM_DEPOSIT_SIZE: usize = 64 * SIZE_1G;
+
+/// Start of the region for memory deposited by the guest
+pub const SVSM_DEPOSIT_BASE: VirtAddr = SVSM_GLOBAL_BASE.const_add(384 * SIZE_1G);
+
+/// End of the region for memory deposited by the guest
+pub const SVSM_DEPOSIT_END: VirtAddr = SVSM_DEPOSIT_BASE.const_add(SVSM_DEPOSIT_SIZE);
+
 /// PerCPU mappings level 3 index
 pub const PGTABLE_LVL3_IDX_PERCPU: usize = 510;
 
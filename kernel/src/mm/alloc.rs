@@ -4,16 +4,24 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
+extern crate alloc;
+
 use crate::address::{Address, PhysAddr, VirtAddr};
+use crate::cpu::flush_tlb_global_sync;
 use crate::cpu::mem::{unsafe_copy_bytes, write_bytes};
+use crate::cpu::percpu::this_cpu;
 use crate::error::SvsmError;
 use crate::fs::Buffer;
-use crate::locking::SpinLock;
-use crate::mm::virt_to_phys;
-use crate::types::{PAGE_SHIFT, PAGE_SIZE};
+use crate::locking::{RWLock, SpinLock};
+use crate::mm::pagetable::PTEntryFlags;
+use crate::mm::{virt_to_phys, SVSM_DEPOSIT_BASE, SVSM_DEPOSIT_END, SVSM_DEPOSIT_SIZE};
+use crate::types::{PageSize, PAGE_SHIFT, PAGE_SIZE};
 use crate::utils::{align_down, align_up, zero_mem_region};
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
 use core::alloc::{GlobalAlloc, Layout};
 use core::mem::size_of;
+use core::ops::Range;
 use core::{cmp, ptr, slice};
 
 #[cfg(any(test, fuzzing))]
@@ -369,6 +377,10 @@ pub struct MemInfo {
     free_pages: [usize; MAX_ORDER],
 }
 
+/// Number of pages in a slot of a slotted [`MemoryRegion`]. The first page of
+/// each slot holds the page information for all pages of the slot.
+const SLOT_PAGES: usize = PAGE_SIZE / size_of::<PageStorageType>();
+
 /// Memory region with its physical/virtual addresses, page count, as well
 /// as other details.
 #[derive(Debug, Default)]
@@ -379,6 +391,10 @@ struct MemoryRegion {
     nr_pages: [usize; MAX_ORDER],
     next_page: [usize; MAX_ORDER],
     free_pages: [usize; MAX_ORDER],
+    /// Whether page information is stored at the start of every slot of
+    /// [`SLOT_PAGES`] pages instead of at the start of the region. Slotted
+    /// regions do not need to be backed by memory contiguously.
+    slotted: bool,
 }
 
 impl MemoryRegion {
@@ -391,6 +407,32 @@ impl MemoryRegion {
             nr_pages: [0; MAX_ORDER],
             next_page: [0; MAX_ORDER],
             free_pages: [0; MAX_ORDER],
+            slotted: false,
+        }
+    }
+
+    /// Creates a new slotted [`MemoryRegion`] covering `page_count` pages at
+    /// `start_virt`. All pages are initially considered as not present.
+    const fn new_slotted(start_virt: VirtAddr, page_count: usize) -> Self {
+        Self {
+            start_phys: PhysAddr::null(),
+            start_virt,
+            page_count,
+            nr_pages: [0; MAX_ORDER],
+            next_page: [0; MAX_ORDER],
+            free_pages: [0; MAX_ORDER],
+            slotted: true,
+        }
+    }
+
+    /// Calculates the index of the page information for a given page frame
+    /// number, relative to the start of the region.
+    const fn page_info_index(&self, pfn: usize) -> usize {
+        if self.slotted {
+            // The information page of a slot holds exactly SLOT_PAGES entries
+            (pfn & !(SLOT_PAGES - 1)) * SLOT_PAGES + (pfn & (SLOT_PAGES - 1))
+        } else {
+            pfn
         }
     }
 
@@ -429,7 +471,8 @@ impl MemoryRegion {
     /// undefined, as the compiler is allowed to optimize assuming there will
     /// be no arithmetic overflows.
     unsafe fn page_info_mut_ptr(&mut self, pfn: usize) -> *mut PageStorageType {
-        unsafe { self.start_virt.as_mut_ptr::<PageStorageType>().add(pfn) }
+        let index = self.page_info_index(pfn);
+        unsafe { self.start_virt.as_mut_ptr::<PageStorageType>().add(index) }
     }
 
     /// Gets a pointer to the page information for a given page frame number.
@@ -440,7 +483,8 @@ impl MemoryRegion {
     /// undefined, as the compiler is allowed to optimize assuming there will
     /// be no arithmetic overflows.
     unsafe fn page_info_ptr(&self, pfn: usize) -> *const PageStorageType {
-        unsafe { self.start_virt.as_ptr::<PageStorageType>().add(pfn) }
+        let index = self.page_info_index(pfn);
+        unsafe { self.start_virt.as_ptr::<PageStorageType>().add(index) }
     }
 
     /// Checks if a page frame number is valid.
@@ -907,6 +951,93 @@ impl MemoryRegion {
             }
         }
     }
+
+    /// Initializes the page information of a slot in a slotted region after
+    /// its first page has been made present. All pages of the slot are
+    /// marked as reserved, meaning not present.
+    fn init_slot(&mut self, slot: usize) {
+        assert!(self.slotted);
+        let first = slot * SLOT_PAGES;
+        for pfn in first..first + SLOT_PAGES {
+            self.write_page_info(pfn, PageInfo::Reserved(ReservedInfo));
+        }
+    }
+
+    /// Finds a page within a slot which is not present yet.
+    fn find_absent_page(&self, slot: usize) -> Option<usize> {
+        let first = slot * SLOT_PAGES;
+        // The first page of the slot always holds the page information.
+        (first + 1..first + SLOT_PAGES)
+            .find(|pfn| matches!(self.read_page_info(*pfn), PageInfo::Reserved(_)))
+    }
+
+    /// Makes a page which has just become present available for allocation.
+    fn add_page(&mut self, pfn: usize) {
+        let pg = PageInfo::Allocated(AllocatedInfo { order: 0 });
+        self.write_page_info(pfn, pg);
+        self.nr_pages[0] += 1;
+        self.free_page_order(pfn, 0);
+    }
+
+    /// Checks whether a page is free, either on its own or as part of a
+    /// free compound page.
+    fn page_is_free(&self, pfn: usize) -> bool {
+        match self.read_page_info(pfn) {
+            PageInfo::Free(_) => true,
+            PageInfo::Compound(ci) => {
+                let head = pfn & !((1usize << ci.order) - 1);
+                matches!(self.read_page_info(head), PageInfo::Free(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes a single free page out of the allocator and marks it as not
+    /// present. A free compound page containing the page is split as
+    /// needed.
+    fn remove_page(&mut self, pfn: usize) -> Result<(), AllocError> {
+        let (mut head, mut order) = match self.read_page_info(pfn) {
+            PageInfo::Free(fi) => (pfn, fi.order),
+            PageInfo::Compound(ci) => (pfn & !((1usize << ci.order) - 1), ci.order),
+            _ => return Err(AllocError::InvalidPfn(pfn)),
+        };
+
+        let PageInfo::Free(fi) = self.read_page_info(head) else {
+            return Err(AllocError::InvalidPfn(pfn));
+        };
+        if fi.order != order {
+            return Err(AllocError::InvalidPageOrder(fi.order));
+        }
+
+        self.allocate_pfn(head, order)?;
+        while order > 0 {
+            self.split_page(head, order)?;
+            order -= 1;
+            if pfn >= head + (1usize << order) {
+                head += 1usize << order;
+            }
+            self.allocate_pfn(head, order)?;
+        }
+
+        self.nr_pages[0] -= 1;
+        self.write_page_info(pfn, PageInfo::Reserved(ReservedInfo));
+
+        Ok(())
+    }
+
+    /// Takes a range of free pages out of the allocator. Either all pages
+    /// of the range are removed or, on failure, none of them.
+    fn remove_pages(&mut self, pfns: Range<usize>) -> Result<(), AllocError> {
+        for pfn in pfns.clone() {
+            if let Err(e) = self.remove_page(pfn) {
+                for removed in pfns.start..pfn {
+                    self.add_page(removed);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Represents a reference to a memory page, holding both virtual and
@@ -915,6 +1046,8 @@ impl MemoryRegion {
 pub struct PageRef {
     virt_addr: VirtAddr,
     phys_addr: PhysAddr,
+    /// Whether the page is owned by the page allocator and reference-counted.
+    refcounted: bool,
 }
 
 impl PageRef {
@@ -926,9 +1059,27 @@ impl PageRef {
         Ok(Self {
             virt_addr,
             phys_addr,
+            refcounted: true,
         })
     }
 
+    /// Create a reference to a page which is not managed by the page
+    /// allocator, like a page of the boot filesystem archive. References
+    /// to such pages are not counted.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure that `virt_addr` maps the page at
+    /// `phys_addr` and that both the mapping and the page contents stay
+    /// valid for the remaining lifetime of the SVSM.
+    pub unsafe fn new_static(virt_addr: VirtAddr, phys_addr: PhysAddr) -> Self {
+        Self {
+            virt_addr,
+            phys_addr,
+            refcounted: false,
+        }
+    }
+
     /// Returns the virtual address of the memory page.
     pub fn virt_addr(&self) -> VirtAddr {
         self.virt_addr
@@ -953,6 +1104,7 @@ impl PageRef {
         Ok(PageRef {
             virt_addr,
             phys_addr: virt_to_phys(virt_addr),
+            refcounted: true,
         })
     }
 
@@ -1063,10 +1215,13 @@ impl PageRef {
 impl Clone for PageRef {
     /// Clones the [`PageRef`] instance, obtaining a new reference to the same memory page.
     fn clone(&self) -> Self {
-        get_file_page(self.virt_addr).expect("Failed to get page reference");
+        if self.refcounted {
+            get_file_page(self.virt_addr).expect("Failed to get page reference");
+        }
         PageRef {
             virt_addr: self.virt_addr,
             phys_addr: self.phys_addr,
+            refcounted: self.refcounted,
         }
     }
 }
@@ -1075,7 +1230,9 @@ impl Drop for PageRef {
     /// Drops the [`PageRef`] instance, decreasing the reference count for
     /// the associated memory page.
     fn drop(&mut self) {
-        put_file_page(self.virt_addr).expect("Failed to drop page reference");
+        if self.refcounted {
+            put_file_page(self.virt_addr).expect("Failed to drop page reference");
+        }
     }
 }
 
@@ -1111,6 +1268,35 @@ pub fn print_memory_info(info: &MemInfo) {
 /// root memory region.
 static ROOT_MEM: SpinLock<MemoryRegion> = SpinLock::new(MemoryRegion::new());
 
+/// Static spinlock-protected instance of the slotted [`MemoryRegion`] which
+/// manages memory deposited by the guest.
+static DEPOSIT_MEM: SpinLock<MemoryRegion> = SpinLock::new(MemoryRegion::new_slotted(
+    SVSM_DEPOSIT_BASE,
+    SVSM_DEPOSIT_SIZE / PAGE_SIZE,
+));
+
+/// Returns the memory region managing the given virtual address.
+fn mem_region(vaddr: VirtAddr) -> &'static SpinLock<MemoryRegion> {
+    if (SVSM_DEPOSIT_BASE..SVSM_DEPOSIT_END).contains(&vaddr) {
+        &DEPOSIT_MEM
+    } else {
+        &ROOT_MEM
+    }
+}
+
+/// Runs an allocation function on the root memory region and falls back to
+/// deposited memory when the root memory region is exhausted.
+fn allocate_with<F>(f: F) -> Result<VirtAddr, AllocError>
+where
+    F: Fn(&mut MemoryRegion) -> Result<VirtAddr, AllocError>,
+{
+    let result = f(&mut ROOT_MEM.lock());
+    match result {
+        Err(AllocError::OutOfMemory) => f(&mut DEPOSIT_MEM.lock()),
+        _ => result,
+    }
+}
+
 /// Allocates a single memory page from the root memory region.
 ///
 /// # Returns
@@ -1118,7 +1304,7 @@ static ROOT_MEM: SpinLock<MemoryRegion> = SpinLock::new(MemoryRegion::new());
 /// Result containing the virtual address of the allocated page or an
 /// `SvsmError` if allocation fails.
 pub fn allocate_page() -> Result<VirtAddr, SvsmError> {
-    Ok(ROOT_MEM.lock().allocate_page()?)
+    Ok(allocate_with(|mem| mem.allocate_page())?)
 }
 
 /// Allocates multiple memory pages with a specified order from the root
@@ -1133,7 +1319,7 @@ pub fn allocate_page() -> Result<VirtAddr, SvsmError> {
 /// Result containing the virtual address of the allocated pages or an
 /// `SvsmError` if allocation fails.
 pub fn allocate_pages(order: usize) -> Result<VirtAddr, SvsmError> {
-    Ok(ROOT_MEM.lock().allocate_pages(order)?)
+    Ok(allocate_with(|mem| mem.allocate_pages(order))?)
 }
 
 /// Allocate a slab page.
@@ -1147,7 +1333,7 @@ pub fn allocate_pages(order: usize) -> Result<VirtAddr, SvsmError> {
 /// Result containing the virtual address of the allocated slab page or an
 /// `SvsmError` if allocation fails.
 pub fn allocate_slab_page(item_size: u16) -> Result<VirtAddr, SvsmError> {
-    Ok(ROOT_MEM.lock().allocate_slab_page(item_size)?)
+    Ok(allocate_with(|mem| mem.allocate_slab_page(item_size))?)
 }
 
 /// Allocate a zeroed page.
@@ -1157,7 +1343,7 @@ pub fn allocate_slab_page(item_size: u16) -> Result<VirtAddr, SvsmError> {
 /// Result containing the virtual address of the allocated zeroed page or an
 /// `SvsmError` if allocation fails.
 pub fn allocate_zeroed_page() -> Result<VirtAddr, SvsmError> {
-    Ok(ROOT_MEM.lock().allocate_zeroed_page()?)
+    Ok(allocate_with(|mem| mem.allocate_zeroed_page())?)
 }
 
 /// Allocate a file page.
@@ -1167,7 +1353,7 @@ pub fn allocate_zeroed_page() -> Result<VirtAddr, SvsmError> {
 /// Result containing the virtual address of the allocated file page or an
 /// `SvsmError` if allocation fails.
 pub fn allocate_file_page() -> Result<VirtAddr, SvsmError> {
-    let vaddr = ROOT_MEM.lock().allocate_file_page()?;
+    let vaddr = allocate_with(|mem| mem.allocate_file_page())?;
 
     // SAFETY: we trust allocate_file_page() to return a pointer to a valid
     // page. vaddr + PAGE_SIZE also correctly points to the end of the
@@ -1180,21 +1366,299 @@ pub fn allocate_file_page() -> Result<VirtAddr, SvsmError> {
 }
 
 fn get_file_page(vaddr: VirtAddr) -> Result<(), SvsmError> {
-    Ok(ROOT_MEM.lock().get_file_page(vaddr)?)
+    Ok(mem_region(vaddr).lock().get_file_page(vaddr)?)
 }
 
 fn put_file_page(vaddr: VirtAddr) -> Result<(), SvsmError> {
-    Ok(ROOT_MEM.lock().put_file_page(vaddr)?)
+    Ok(mem_region(vaddr).lock().put_file_page(vaddr)?)
 }
 
 /// Free the page at the given virtual address.
 pub fn free_page(vaddr: VirtAddr) {
-    ROOT_MEM.lock().free_page(vaddr)
+    mem_region(vaddr).lock().free_page(vaddr)
 }
 
-/// Retrieve information about the root memory
+/// Retrieve information about the root memory and deposited memory
 pub fn memory_info() -> MemInfo {
-    ROOT_MEM.lock().memory_info()
+    let mut info = ROOT_MEM.lock().memory_info();
+    let deposited = DEPOSIT_MEM.lock().memory_info();
+    for i in 0..MAX_ORDER {
+        info.total_pages[i] += deposited.total_pages[i];
+        info.free_pages[i] += deposited.free_pages[i];
+    }
+    info
+}
+
+/// State of a slot in the deposited memory region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DepositSlot {
+    /// The slot was never populated and can take 4KiB pages or a 2MiB page.
+    Empty,
+    /// The slot is populated with the given number of 4KiB pages, including
+    /// the page holding the page information of the slot.
+    Small(usize),
+    /// The slot is populated with a single 2MiB page.
+    Huge,
+}
+
+/// Slots of the deposited memory region. The lock also serializes deposit and
+/// withdraw operations.
+static DEPOSIT_SLOTS: SpinLock<Vec<DepositSlot>> = SpinLock::new(Vec::new());
+
+/// Physical start address, virtual address and size of each deposited page.
+static DEPOSIT_PHYS: RWLock<BTreeMap<PhysAddr, (VirtAddr, PageSize)>> =
+    RWLock::new(BTreeMap::new());
+
+/// Returns the virtual address of a page in the deposited memory region.
+fn deposit_vaddr(pfn: usize) -> VirtAddr {
+    SVSM_DEPOSIT_BASE + pfn * PAGE_SIZE
+}
+
+/// Finds a slot for a newly deposited page, preferring partially populated
+/// slots over empty ones.
+fn find_deposit_slot(slots: &mut Vec<DepositSlot>, size: PageSize) -> Result<usize, SvsmError> {
+    let slot = match size {
+        PageSize::Regular => slots
+            .iter()
+            .position(|s| matches!(s, DepositSlot::Small(n) if *n > 0 && *n < SLOT_PAGES))
+            .or_else(|| slots.iter().position(|s| *s == DepositSlot::Small(0)))
+            .or_else(|| slots.iter().position(|s| *s == DepositSlot::Empty)),
+        PageSize::Huge => slots.iter().position(|s| *s == DepositSlot::Empty),
+    };
+
+    if let Some(slot) = slot {
+        return Ok(slot);
+    }
+
+    if slots.len() >= SVSM_DEPOSIT_SIZE / (SLOT_PAGES * PAGE_SIZE) {
+        return Err(AllocError::OutOfMemory.into());
+    }
+    slots.try_reserve(1).map_err(|_| AllocError::OutOfMemory)?;
+    slots.push(DepositSlot::Empty);
+
+    Ok(slots.len() - 1)
+}
+
+/// Adds a page deposited by the guest to the allocator. The page must be
+/// validated and must not be accessible by any lower VMPL.
+///
+/// # Arguments
+///
+/// * `paddr` - Physical address of the page, aligned to `size`.
+/// * `size` - Size of the deposited page.
+///
+/// # Returns
+///
+/// `Ok(())` on success, or an [`SvsmError`] if the page could not be added
+/// to the deposited memory region.
+pub fn deposit_page(paddr: PhysAddr, size: PageSize) -> Result<(), SvsmError> {
+    let mut slots = DEPOSIT_SLOTS.lock();
+    let slot = find_deposit_slot(&mut slots, size)?;
+    let first = slot * SLOT_PAGES;
+
+    match (size, slots[slot]) {
+        (PageSize::Huge, _) => {
+            let vaddr = deposit_vaddr(first);
+            this_cpu()
+                .get_pgtable()
+                .map_2m(vaddr, paddr, PTEntryFlags::data())?;
+            DEPOSIT_PHYS.lock_write().insert(paddr, (vaddr, size));
+
+            let mut mem = DEPOSIT_MEM.lock();
+            mem.init_slot(slot);
+            for pfn in first + 1..first + SLOT_PAGES {
+                mem.add_page(pfn);
+            }
+            slots[slot] = DepositSlot::Huge;
+        }
+        (PageSize::Regular, DepositSlot::Small(n)) if n > 0 => {
+            let pfn = DEPOSIT_MEM
+                .lock()
+                .find_absent_page(slot)
+                .expect("No absent page in partially populated deposit slot");
+            let vaddr = deposit_vaddr(pfn);
+            this_cpu()
+                .get_pgtable()
+                .map_4k(vaddr, paddr, PTEntryFlags::data())?;
+            DEPOSIT_PHYS.lock_write().insert(paddr, (vaddr, size));
+
+            DEPOSIT_MEM.lock().add_page(pfn);
+            slots[slot] = DepositSlot::Small(n + 1);
+        }
+        (PageSize::Regular, _) => {
+            // The first page of a slot holds its page information
+            let vaddr = deposit_vaddr(first);
+            this_cpu()
+                .get_pgtable()
+                .map_4k(vaddr, paddr, PTEntryFlags::data())?;
+            DEPOSIT_PHYS.lock_write().insert(paddr, (vaddr, size));
+
+            DEPOSIT_MEM.lock().init_slot(slot);
+            slots[slot] = DepositSlot::Small(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Clears and releases a single page which has already been taken out of the
+/// deposited memory region, then unmaps it.
+fn withdraw_one<F>(vaddr: VirtAddr, size: PageSize, release: &mut F) -> Result<(), SvsmError>
+where
+    F: FnMut(VirtAddr, PhysAddr, PageSize) -> Result<(), SvsmError>,
+{
+    let paddr = virt_to_phys(vaddr);
+
+    // SAFETY: the page is mapped at vaddr with the given size and is no
+    // longer used by the allocator.
+    unsafe {
+        zero_mem_region(vaddr, vaddr + usize::from(size));
+    }
+
+    release(vaddr, paddr, size)?;
+
+    match size {
+        PageSize::Regular => this_cpu().get_pgtable().unmap_4k(vaddr),
+        PageSize::Huge => this_cpu().get_pgtable().unmap_2m(vaddr),
+    }
+    DEPOSIT_PHYS.lock_write().remove(&paddr);
+
+    Ok(())
+}
+
+/// Takes free pages out of the deposited memory region so they can be
+/// returned to the guest.
+///
+/// # Arguments
+///
+/// * `max` - Maximum number of pages to withdraw.
+/// * `release` - Called for every withdrawn page with its virtual address in
+///   the deposited memory region, its physical address and its size. The page
+///   is zeroed and still mapped when `release` is called. When `release`
+///   fails, the page is given back to the allocator and withdrawal stops.
+///
+/// # Returns
+///
+/// The number of withdrawn pages, or the error returned by `release`.
+pub fn withdraw_pages<F>(max: usize, mut release: F) -> Result<usize, SvsmError>
+where
+    F: FnMut(VirtAddr, PhysAddr, PageSize) -> Result<(), SvsmError>,
+{
+    let mut slots = DEPOSIT_SLOTS.lock();
+    let mut count = 0;
+    let mut result = Ok(());
+
+    'slots: for slot in 0..slots.len() {
+        if count >= max {
+            break;
+        }
+
+        let first = slot * SLOT_PAGES;
+        match slots[slot] {
+            DepositSlot::Huge => {
+                {
+                    let mut mem = DEPOSIT_MEM.lock();
+                    if !(first + 1..first + SLOT_PAGES).all(|pfn| mem.page_is_free(pfn)) {
+                        continue;
+                    }
+                    if let Err(e) = mem.remove_pages(first + 1..first + SLOT_PAGES) {
+                        result = Err(e.into());
+                        break;
+                    }
+                }
+
+                if let Err(e) = withdraw_one(deposit_vaddr(first), PageSize::Huge, &mut release) {
+                    let mut mem = DEPOSIT_MEM.lock();
+                    for pfn in first + 1..first + SLOT_PAGES {
+                        mem.add_page(pfn);
+                    }
+                    result = Err(e);
+                    break;
+                }
+
+                slots[slot] = DepositSlot::Empty;
+                count += 1;
+            }
+            DepositSlot::Small(mut n) if n > 0 => {
+                for pfn in first + 1..first + SLOT_PAGES {
+                    if count >= max || n == 1 {
+                        break;
+                    }
+
+                    {
+                        let mut mem = DEPOSIT_MEM.lock();
+                        if !mem.page_is_free(pfn) {
+                            continue;
+                        }
+                        if let Err(e) = mem.remove_page(pfn) {
+                            result = Err(e.into());
+                            break;
+                        }
+                    }
+
+                    if let Err(e) =
+                        withdraw_one(deposit_vaddr(pfn), PageSize::Regular, &mut release)
+                    {
+                        DEPOSIT_MEM.lock().add_page(pfn);
+                        slots[slot] = DepositSlot::Small(n);
+                        result = Err(e);
+                        break 'slots;
+                    }
+
+                    n -= 1;
+                    count += 1;
+                }
+
+                // Release the page information page once it is the last page
+                // left in the slot.
+                if result.is_ok() && n == 1 && count < max {
+                    match withdraw_one(deposit_vaddr(first), PageSize::Regular, &mut release) {
+                        Ok(()) => {
+                            n = 0;
+                            count += 1;
+                        }
+                        Err(e) => result = Err(e),
+                    }
+                }
+
+                slots[slot] = DepositSlot::Small(n);
+                if result.is_err() {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Make sure no stale translations of withdrawn pages survive
+    flush_tlb_global_sync();
+
+    result.map(|_| count)
+}
+
+/// Returns the number of free pages in the deposited memory region.
+pub fn deposited_free_pages() -> usize {
+    let info = DEPOSIT_MEM.lock().memory_info();
+    (0..MAX_ORDER).map(|i| info.free_pages[i] << i).sum()
+}
+
+/// Translates the physical address of a deposited page into its virtual
+/// address in the deposited memory region.
+pub fn deposited_phys_to_virt(paddr: PhysAddr) -> Option<VirtAddr> {
+    let map = DEPOSIT_PHYS.lock_read();
+    let (start, (vaddr, size)) = map.range(..=paddr).next_back()?;
+    let offset = paddr - *start;
+    (offset < usize::from(*size)).then(|| *vaddr + offset)
+}
+
+/// Checks whether a physical address range overlaps with memory deposited
+/// by the guest.
+pub fn is_deposited_range(paddr: PhysAddr, size: usize) -> bool {
+    let map = DEPOSIT_PHYS.lock_read();
+    let Some((start, (_, psize))) = map.range(..paddr + size).next_back() else {
+        return false;
+    };
+    *start + usize::from(*psize) > paddr
 }
 
 /// Represents a slab memory page, used for efficient allocation of
@@ -1224,7 +1688,7 @@ impl<const N: u16> SlabPage<N> {
         if !self.vaddr.is_null() {
             return Ok(());
         }
-        let vaddr = ROOT_MEM.lock().allocate_slab_page(N)?;
+        let vaddr = allocate_with(|mem| mem.allocate_slab_page(N))?;
         self.vaddr = vaddr;
         self.free = self.get_capacity();
 
@@ -1674,7 +2138,7 @@ unsafe impl GlobalAlloc for SvsmAllocator {
         let size = layout.size();
 
         let info = {
-            let mem = ROOT_MEM.lock();
+            let mem = mem_region(virt_addr).lock();
             let pfn = mem.get_pfn(virt_addr).expect("Freeing unknown memory");
             mem.read_page_info(pfn)
         };
@@ -1736,9 +2200,9 @@ pub fn layout_from_size(size: usize) -> Result<Layout, AllocError> {
 pub fn layout_from_ptr(ptr: *mut u8) -> Option<Layout> {
     let va = VirtAddr::from(ptr);
 
-    let root = ROOT_MEM.lock();
-    let pfn = root.get_pfn(va).ok()?;
-    let info = root.read_page_info(pfn);
+    let mem = mem_region(va).lock();
+    let pfn = mem.get_pfn(va).ok()?;
+    let info = mem.read_page_info(pfn);
 
     match info {
         PageInfo::Allocated(ai) => {
@@ -2181,4 +2645,62 @@ mod test {
         // value that items were cloned out of.
         assert_eq!(DROPPED.load(Ordering::Relaxed), NUM_ITEMS.get() + 1);
     }
+
+    #[test]
+    fn test_slotted_region() {
+        extern crate alloc;
+        use alloc::alloc::{alloc, dealloc};
+
+        const SLOTS: usize = 2;
+        let page_count = SLOTS * SLOT_PAGES;
+        let layout = Layout::from_size_align(page_count * PAGE_SIZE, PAGE_SIZE).unwrap();
+        let ptr = unsafe { alloc(layout) };
+        assert!(!ptr.is_null());
+
+        let free_pages = |region: &MemoryRegion| -> usize {
+            let info = region.memory_info();
+            (0..MAX_ORDER).map(|o| info.free_pages[o] << o).sum()
+        };
+
+        let mut region = MemoryRegion::new_slotted(VirtAddr::from(ptr), page_count);
+
+        // Populate the second slot, its first page holds the page information
+        region.init_slot(1);
+        let mut added = 0;
+        while let Some(pfn) = region.find_absent_page(1) {
+            region.add_page(pfn);
+            added += 1;
+        }
+        assert_eq!(added, SLOT_PAGES - 1);
+        assert_eq!(free_pages(&region), SLOT_PAGES - 1);
+
+        // Pages of the slot can be allocated and freed again
+        let vaddr = region.allocate_pages(2).unwrap();
+        let pfn = region.get_pfn(vaddr).unwrap();
+        assert!(pfn > SLOT_PAGES && pfn < 2 * SLOT_PAGES);
+        assert!(!region.page_is_free(pfn));
+        region.free_page(vaddr);
+        assert!(region.page_is_free(pfn));
+
+        // Removing a range with a page in use leaves the region untouched
+        let vaddr = region.allocate_page().unwrap();
+        let used = region.get_pfn(vaddr).unwrap();
+        assert!(region.remove_pages(SLOT_PAGES + 1..2 * SLOT_PAGES).is_err());
+        assert_eq!(free_pages(&region), SLOT_PAGES - 2);
+        region.free_page(vaddr);
+        assert!(region.page_is_free(used));
+        assert_eq!(free_pages(&region), SLOT_PAGES - 1);
+
+        // Remove all pages again, splitting any compound pages on the way
+        for pfn in SLOT_PAGES + 1..2 * SLOT_PAGES {
+            assert!(region.page_is_free(pfn));
+            region.remove_page(pfn).unwrap();
+        }
+        assert_eq!(free_pages(&region), 0);
+        assert_eq!(region.memory_info().total_pages[0], 0);
+        assert!(region.allocate_page().is_err());
+        assert_eq!(region.find_absent_page(1), Some(SLOT_PAGES + 1));
+
+        unsafe { dealloc(ptr, layout) };
+    }
 }
@@ -70,6 +70,9 @@ pub struct GlobalRangeGuard {
     pages: usize,
     huge: bool,
     shared: bool,
+    /// Number of bytes mapped with 4KiB pages if a range of 2MiB pages is
+    /// mapped only partially.
+    size_4k: Option<usize>,
 }
 
 impl GlobalRangeGuard {
@@ -92,6 +95,7 @@ impl GlobalRangeGuard {
             pages,
             huge,
             shared,
+            size_4k: None,
         }
     }
 
@@ -111,11 +115,11 @@ impl GlobalRangeGuard {
     /// Length of the global mapping in bytes.
     pub fn size(&self) -> usize {
         let page_size = if self.huge { PAGE_SIZE_2M } else { PAGE_SIZE };
-        self.pages * page_size
+        self.size_4k.unwrap_or(self.pages * page_size)
     }
 
     fn map(&self, paddr: PhysAddr, flags: PTEntryFlags) -> Result<(), SvsmError> {
-        if self.huge {
+        if self.huge && self.size_4k.is_none() {
             this_cpu()
                 .get_pgtable()
                 .map_region_2m(self.region(), paddr, flags, self.shared)
@@ -127,7 +131,7 @@ impl GlobalRangeGuard {
     }
 
     fn unmap(&self) {
-        if self.huge {
+        if self.huge && self.size_4k.is_none() {
             this_cpu().get_pgtable().unmap_region_2m(self.region());
         } else {
             this_cpu().get_pgtable().unmap_region_4k(self.region());
@@ -140,8 +144,7 @@ impl GlobalRangeGuard {
     ///
     /// The global mapped region as an instance of [`MemoryRegion`].
     pub fn region(&self) -> MemoryRegion<VirtAddr> {
-        let page_size = if self.huge { PAGE_SIZE_2M } else { PAGE_SIZE };
-        MemoryRegion::new(self.vstart, self.pages * page_size)
+        MemoryRegion::new(self.vstart, self.size())
     }
 }
 
@@ -240,6 +243,41 @@ pub fn map_global_range_4k_shared(
     map_global_range(pstart, size, flags, false, true)
 }
 
+/// Create a private mapping using of physical addresses into the global shared
+/// address range using 4KiB pages. Unlike [`map_global_range_4k_private`], the
+/// virtual addresses are taken from the range for 2MiB pages, so that large
+/// regions can be mapped without mapping any memory around them.
+///
+/// # Arguments
+///
+/// * `pstart`: Start physical to map, must be aligned to 4KiB.
+/// * `size`: Number of bytes to map. Will be aligned up to 4KiB.
+/// * `flages`: Page-table flags to use for mapping.
+///
+/// # Returns
+///
+/// A Result with a [`GlobalRangeGuard`] on success or [`SvsmError`] on failure.
+pub fn map_global_range_4k_private_large(
+    pstart: PhysAddr,
+    size: usize,
+    flags: PTEntryFlags,
+) -> Result<GlobalRangeGuard, SvsmError> {
+    assert!(pstart.is_page_aligned());
+
+    let size_aligned = align_up(size, PAGE_SIZE);
+    if size_aligned == 0 {
+        return Err(SvsmError::Mem);
+    }
+
+    let pages = align_up(size_aligned, PAGE_SIZE_2M) / PAGE_SIZE_2M;
+
+    let mut guard = GLOBAL_RANGES.lock().alloc(pages, true, false)?;
+    guard.size_4k = Some(size_aligned);
+    guard.map(pstart, flags)?;
+
+    Ok(guard)
+}
+
 /// Create a private mapping using of physical addresses into the global shared
 /// address range using 2MiB pages.
 ///
@@ -85,6 +85,10 @@ pub fn munmap_user(addr: VirtAddr) -> Result<(), SvsmError> {
     current_task().munmap_user(addr)
 }
 
+pub fn mprotect_user(addr: VirtAddr, flags: VMFileMappingFlags) -> Result<(), SvsmError> {
+    current_task().mprotect_user(addr, flags)
+}
+
 pub fn munmap_kernel(addr: VirtAddr) -> Result<(), SvsmError> {
     current_task().munmap_kernel(addr)
 }
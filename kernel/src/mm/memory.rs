@@ -11,6 +11,7 @@ use crate::config::SvsmConfig;
 use crate::cpu::percpu::PERCPU_VMSAS;
 use crate::error::SvsmError;
 use crate::locking::RWLock;
+use crate::mm::alloc::is_deposited_range;
 use crate::types::PAGE_SIZE;
 use crate::utils::MemoryRegion;
 use alloc::vec::Vec;
@@ -21,35 +22,18 @@ use super::pagetable::LAUNCH_VMSA_ADDR;
 /// Global memory map containing various memory regions.
 static MEMORY_MAP: RWLock<Vec<MemoryRegion<PhysAddr>>> = RWLock::new(Vec::new());
 
-/// Initializes the global memory map based on the provided configuration
-/// and kernel launch information.
-///
-/// # Arguments
-///
-/// * `config` - A reference to the [`SvsmConfig`] containing memory region
-///   information.
-/// * `launch_info` - A reference to the [`KernelLaunchInfo`] containing
-///   information about the kernel region.
-///
-/// # Returns
-///
-/// Returns `Ok(())` if the memory map is successfully initialized, otherwise
-/// returns an error of type `SvsmError`.
-pub fn init_memory_map(
-    config: &SvsmConfig<'_>,
-    launch_info: &KernelLaunchInfo,
-) -> Result<(), SvsmError> {
-    let mut regions = config.get_memory_regions()?;
-    let kernel_start = PhysAddr::from(launch_info.kernel_region_phys_start);
-    let kernel_end = PhysAddr::from(launch_info.kernel_region_phys_end);
-    let kernel_region = MemoryRegion::from_addresses(kernel_start, kernel_end);
+/// End of the highest memory region of the configuration, including SVSM
+/// memory
+static MEMORY_END: RWLock<PhysAddr> = RWLock::new(PhysAddr::new(0));
 
-    // Remove SVSM memory from guest memory map
+/// Removes `excluded` from the list of memory regions, splitting regions
+/// which partially overlap with it.
+fn remove_region(regions: &mut Vec<MemoryRegion<PhysAddr>>, excluded: MemoryRegion<PhysAddr>) {
     let mut i = 0;
     while i < regions.len() {
-        // Check if the region overlaps with SVSM memory.
+        // Check if the region overlaps with the excluded region.
         let region = regions[i];
-        if !region.overlap(&kernel_region) {
+        if !region.overlap(&excluded) {
             // Check the next region.
             i += 1;
             continue;
@@ -58,9 +42,9 @@ pub fn init_memory_map(
         // 1. Remove the region.
         regions.remove(i);
 
-        // 2. Insert a region up until the start of SVSM memory (if non-empty).
+        // 2. Insert a region up until the start of the excluded region (if non-empty).
         let region_before_start = region.start();
-        let region_before_end = kernel_region.start();
+        let region_before_end = excluded.start();
         if region_before_start < region_before_end {
             regions.insert(
                 i,
@@ -69,8 +53,8 @@ pub fn init_memory_map(
             i += 1;
         }
 
-        // 3. Insert a region up after the end of SVSM memory (if non-empty).
-        let region_after_start = kernel_region.end();
+        // 3. Insert a region up after the end of the excluded region (if non-empty).
+        let region_after_start = excluded.end();
         let region_after_end = region.end();
         if region_after_start < region_after_end {
             regions.insert(
@@ -80,6 +64,48 @@ pub fn init_memory_map(
             i += 1;
         }
     }
+}
+
+/// Initializes the global memory map based on the provided configuration
+/// and kernel launch information.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the [`SvsmConfig`] containing memory region
+///   information.
+/// * `launch_info` - A reference to the [`KernelLaunchInfo`] containing
+///   information about the kernel region.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the memory map is successfully initialized, otherwise
+/// returns an error of type `SvsmError`.
+pub fn init_memory_map(
+    config: &SvsmConfig<'_>,
+    launch_info: &KernelLaunchInfo,
+) -> Result<(), SvsmError> {
+    let mut regions = config.get_memory_regions()?;
+    let kernel_start = PhysAddr::from(launch_info.kernel_region_phys_start);
+    let kernel_end = PhysAddr::from(launch_info.kernel_region_phys_end);
+    let kernel_region = MemoryRegion::from_addresses(kernel_start, kernel_end);
+    let fs_end = PhysAddr::from(launch_info.kernel_fs_end);
+    let memory_end = regions
+        .iter()
+        .map(|r| r.end())
+        .chain([kernel_end, fs_end.page_align_up()])
+        .max()
+        .unwrap();
+
+    // Remove SVSM memory from guest memory map
+    remove_region(&mut regions, kernel_region);
+
+    // The filesystem archive is served from memory for the lifetime of the
+    // SVSM, so it must not be handed to the guest either.
+    let fs_start = PhysAddr::from(launch_info.kernel_fs_start);
+    if fs_start < fs_end {
+        let fs_region = MemoryRegion::from_addresses(fs_start.page_align(), fs_end.page_align_up());
+        remove_region(&mut regions, fs_region);
+    }
 
     log::info!("Guest Memory Regions:");
     for r in regions.iter() {
@@ -88,6 +114,7 @@ pub fn init_memory_map(
 
     let mut map = MEMORY_MAP.lock_write();
     *map = regions;
+    *MEMORY_END.lock_write() = memory_end;
 
     Ok(())
 }
@@ -97,6 +124,17 @@ pub fn write_guest_memory_map(config: &SvsmConfig<'_>) -> Result<(), SvsmError>
     config.write_guest_memory_map(&MEMORY_MAP.lock_read())
 }
 
+/// Returns a copy of the guest memory map, which excludes SVSM memory.
+pub fn guest_memory_regions() -> Vec<MemoryRegion<PhysAddr>> {
+    MEMORY_MAP.lock_read().clone()
+}
+
+/// Returns the end of guest and SVSM memory. Addresses above it are free for
+/// MMIO.
+pub fn memory_end() -> PhysAddr {
+    *MEMORY_END.lock_read()
+}
+
 /// Returns `true` if the provided physical address `paddr` is valid, i.e.
 /// it is within the configured memory regions, otherwise returns `false`.
 pub fn valid_phys_address(paddr: PhysAddr) -> bool {
@@ -108,6 +146,9 @@ pub fn valid_phys_address(paddr: PhysAddr) -> bool {
     if page_addr == LAUNCH_VMSA_ADDR {
         return false;
     }
+    if is_deposited_range(page_addr, PAGE_SIZE) {
+        return false;
+    }
 
     MEMORY_MAP
         .lock_read()
@@ -126,6 +167,9 @@ pub fn valid_phys_region(region: &MemoryRegion<PhysAddr>) -> bool {
     if region.overlap(&MemoryRegion::new(LAUNCH_VMSA_ADDR, PAGE_SIZE)) {
         return false;
     }
+    if is_deposited_range(region.start(), region.len()) {
+        return false;
+    }
 
     MEMORY_MAP
         .lock_read()
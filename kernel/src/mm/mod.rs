@@ -30,6 +30,9 @@ pub use alloc::{allocate_file_page, PageRef};
 
 pub use global_memory::{
     map_global_range, map_global_range_2m_private, map_global_range_2m_shared,
-    map_global_range_4k_private, map_global_range_4k_shared, GlobalRangeGuard,
+    map_global_range_4k_private, map_global_range_4k_private_large, map_global_range_4k_shared,
+    GlobalRangeGuard,
+};
+pub use mappings::{
+    mmap_kernel, mmap_user, mprotect_user, munmap_kernel, munmap_user, VMMappingGuard,
 };
-pub use mappings::{mmap_kernel, mmap_user, munmap_kernel, munmap_user, VMMappingGuard};
@@ -245,7 +245,7 @@ unsafe impl<T> Sync for SharedBox<T> where T: Sync {}
 impl<T> Drop for SharedBox<T> {
     fn drop(&mut self) {
         // Re-encrypt the pages.
-        let res = (0..size_of::<Self>())
+        let res = (0..size_of::<T>())
             .step_by(PAGE_SIZE)
             .try_for_each(|offset| unsafe { make_page_private(self.addr() + offset) });
 
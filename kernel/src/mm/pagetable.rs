@@ -350,6 +350,12 @@ impl PTEntry {
         self.0 = PhysAddr::from(addr | supported_flags(flags).bits());
     }
 
+    /// Check whether the entry maps memory shared with the host.
+    pub fn shared(&self) -> bool {
+        let raw = self.0.bits();
+        raw & shared_pte_mask() == shared_pte_mask() && raw & private_pte_mask() == 0
+    }
+
     /// Get the address from the page table entry, excluding the C bit.
     pub fn address(&self) -> PhysAddr {
         let addr = PhysAddr::from(self.0.bits() & 0x000f_ffff_ffff_f000);
@@ -651,6 +657,23 @@ impl PageTable {
     /// Some(PageFrame) if the virtual address is valid.
     /// None if the virtual address is not valid.
     pub fn virt_to_frame(vaddr: VirtAddr) -> Option<PageFrame> {
+        Self::walk_self_map(vaddr).map(|(frame, _)| frame)
+    }
+
+    /// Perform a virtual to physical translation using the self-map and
+    /// report whether the page is shared with the host.
+    ///
+    /// # Parameters
+    /// - `vaddr': The virtual address to translate.
+    ///
+    /// # Returns
+    /// Some((PageFrame, shared)) if the virtual address is valid.
+    /// None if the virtual address is not valid.
+    pub fn virt_to_frame_shared(vaddr: VirtAddr) -> Option<(PageFrame, bool)> {
+        Self::walk_self_map(vaddr).map(|(frame, entry)| (frame, entry.shared()))
+    }
+
+    fn walk_self_map(vaddr: VirtAddr) -> Option<(PageFrame, PTEntry)> {
         // Calculate the virtual addresses of each level of the paging
         // hierarchy in the self-map.
         let pte_addr = Self::get_pte_address(vaddr);
@@ -678,7 +701,7 @@ impl PageTable {
         }
         if pdpe.huge() {
             let pa = pdpe.address() + (usize::from(vaddr) & 0x3FFF_FFFF);
-            return Some(PageFrame::Size1G(pa));
+            return Some((PageFrame::Size1G(pa), pdpe));
         }
 
         let pde = unsafe { PTEntry::read_pte(pde_addr) };
@@ -687,13 +710,13 @@ impl PageTable {
         }
         if pde.huge() {
             let pa = pde.address() + (usize::from(vaddr) & 0x001F_FFFF);
-            return Some(PageFrame::Size2M(pa));
+            return Some((PageFrame::Size2M(pa), pde));
         }
 
         let pte = unsafe { PTEntry::read_pte(pte_addr) };
         if pte.present() {
             let pa = pte.address() + (usize::from(vaddr) & 0xFFF);
-            Some(PageFrame::Size4K(pa))
+            Some((PageFrame::Size4K(pa), pte))
         } else {
             None
         }
@@ -8,7 +8,7 @@ use crate::address::{PhysAddr, VirtAddr};
 use crate::error::SvsmError;
 use crate::locking::{RWLock, ReadLockGuard, WriteLockGuard};
 use crate::mm::pagetable::PTEntryFlags;
-use crate::mm::vm::VMR;
+use crate::mm::vm::{VMFileMappingFlags, VMR};
 use crate::types::{PageSize, PAGE_SHIFT};
 
 use intrusive_collections::rbtree::AtomicLink;
@@ -134,6 +134,23 @@ pub trait VirtualMapping: core::fmt::Debug {
     ) -> Result<VMPageFaultResolution, SvsmError> {
         Err(SvsmError::Mem)
     }
+
+    /// Change the access permissions of this virtual mapping. The new
+    /// permissions take effect once the mapping is re-mapped into the
+    /// page-table. Implementing `set_flags()` is optional.
+    ///
+    /// # Arguments
+    ///
+    /// * `_flags` - The new access permissions for the mapping. Only the
+    ///   `Read`, `Write` and `Execute` flags are evaluated.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success, `Err(SvsmError::NotSupported)` if the mapping
+    /// does not support changing its permissions.
+    fn set_flags(&mut self, _flags: VMFileMappingFlags) -> Result<(), SvsmError> {
+        Err(SvsmError::NotSupported)
+    }
 }
 
 #[derive(Debug)]
@@ -47,6 +47,9 @@ pub struct VMFileMapping {
 
     /// A vec containing references to mapped pages within the file
     pages: Vec<PageRef>,
+
+    /// Whether the file handle used to create the mapping allows writes
+    file_writable: bool,
 }
 
 impl VMFileMapping {
@@ -112,6 +115,7 @@ impl VMFileMapping {
             size: page_size,
             flags,
             pages,
+            file_writable: file.writable(),
         })
     }
 }
@@ -170,13 +174,28 @@ impl VirtualMapping for VMFileMapping {
     ) -> Result<VMPageFaultResolution, SvsmError> {
         Err(SvsmError::Mem)
     }
+
+    fn set_flags(&mut self, flags: VMFileMappingFlags) -> Result<(), SvsmError> {
+        // Shared writable mappings need a file handle which allows writes
+        if flags.contains(VMFileMappingFlags::Write)
+            && !self.flags.contains(VMFileMappingFlags::Private)
+            && !self.file_writable
+        {
+            return Err(SvsmError::FileSystem(FsError::read_only()));
+        }
+
+        let access =
+            VMFileMappingFlags::Read | VMFileMappingFlags::Write | VMFileMappingFlags::Execute;
+        self.flags = (self.flags - access) | (flags & access);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        fs::{create, open_rw, unlink, TestFileSystemGuard},
+        fs::{create, open, open_rw, unlink, TestFileSystemGuard},
         mm::alloc::{TestRootMem, DEFAULT_TEST_MEMORY_SIZE},
         types::PAGE_SIZE,
     };
@@ -395,4 +414,53 @@ mod tests {
     fn test_map_non_zero_offset_readwrite() {
         test_map_non_zero_offset(VMFileMappingFlags::Write)
     }
+
+    #[test]
+    fn test_set_flags() {
+        let _test_mem = TestRootMem::setup(DEFAULT_TEST_MEMORY_SIZE);
+        let _test_fs = TestFileSystemGuard::setup();
+
+        let (fh, name) = create_512b_test_file();
+        let mut vm = VMFileMapping::new(&fh, 0, 512, VMFileMappingFlags::Read)
+            .expect("Failed to create new VMFileMapping");
+        assert!(!vm.pt_flags(0).contains(PTEntryFlags::WRITABLE));
+        assert!(vm.pt_flags(0).contains(PTEntryFlags::NX));
+
+        vm.set_flags(VMFileMappingFlags::Read | VMFileMappingFlags::Write)
+            .expect("Failed to make mapping writable");
+        assert!(vm.pt_flags(0).contains(PTEntryFlags::WRITABLE));
+
+        vm.set_flags(VMFileMappingFlags::Read | VMFileMappingFlags::Execute)
+            .expect("Failed to make mapping executable");
+        assert!(!vm.pt_flags(0).contains(PTEntryFlags::WRITABLE));
+        assert!(!vm.pt_flags(0).contains(PTEntryFlags::NX));
+        unlink(name).unwrap();
+    }
+
+    #[test]
+    fn test_set_flags_readonly_file() {
+        let _test_mem = TestRootMem::setup(DEFAULT_TEST_MEMORY_SIZE);
+        let _test_fs = TestFileSystemGuard::setup();
+
+        let (_, name) = create_512b_test_file();
+        let fh = open(name, true, false).unwrap();
+        let mut vm = VMFileMapping::new(&fh, 0, 512, VMFileMappingFlags::Read)
+            .expect("Failed to create new VMFileMapping");
+        assert!(vm
+            .set_flags(VMFileMappingFlags::Read | VMFileMappingFlags::Write)
+            .is_err());
+
+        // Writing to a private copy of the file pages is allowed
+        let mut vm = VMFileMapping::new(
+            &fh,
+            0,
+            512,
+            VMFileMappingFlags::Read | VMFileMappingFlags::Private,
+        )
+        .expect("Failed to create new VMFileMapping");
+        vm.set_flags(VMFileMappingFlags::Read | VMFileMappingFlags::Write)
+            .expect("Failed to make private mapping writable");
+        assert!(vm.flags.contains(VMFileMappingFlags::Private));
+        unlink(name).unwrap();
+    }
 }
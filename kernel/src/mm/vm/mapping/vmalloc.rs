@@ -36,19 +36,25 @@ impl VMalloc {
     pub fn new(size: usize, flags: VMFileMappingFlags) -> Result<Self, SvsmError> {
         let mut vmalloc = VMalloc {
             alloc: RawAllocMapping::new(size),
-            flags: PTEntryFlags::ACCESSED,
+            flags: Self::pt_flags_from(flags),
         };
 
+        vmalloc.alloc_pages()?;
+        Ok(vmalloc)
+    }
+
+    fn pt_flags_from(flags: VMFileMappingFlags) -> PTEntryFlags {
+        let mut pt_flags = PTEntryFlags::ACCESSED;
+
         if flags.contains(VMFileMappingFlags::Write) {
-            vmalloc.flags |= PTEntryFlags::WRITABLE | PTEntryFlags::DIRTY;
+            pt_flags |= PTEntryFlags::WRITABLE | PTEntryFlags::DIRTY;
         }
 
         if !flags.contains(VMFileMappingFlags::Execute) {
-            vmalloc.flags |= PTEntryFlags::NX;
+            pt_flags |= PTEntryFlags::NX;
         }
 
-        vmalloc.alloc_pages()?;
-        Ok(vmalloc)
+        pt_flags
     }
 
     /// Create a new [`Mapping`] of [`VMalloc`] and allocate backing memory
@@ -85,4 +91,9 @@ impl VirtualMapping for VMalloc {
     fn pt_flags(&self, _offset: usize) -> PTEntryFlags {
         self.flags
     }
+
+    fn set_flags(&mut self, flags: VMFileMappingFlags) -> Result<(), SvsmError> {
+        self.flags = Self::pt_flags_from(flags);
+        Ok(())
+    }
 }
@@ -18,7 +18,7 @@ use core::cmp::max;
 use intrusive_collections::rbtree::{CursorMut, RBTree};
 use intrusive_collections::Bound;
 
-use super::{Mapping, VMMAdapter, VMM};
+use super::{Mapping, VMFileMappingFlags, VMMAdapter, VMM};
 
 extern crate alloc;
 use alloc::boxed::Box;
@@ -473,6 +473,35 @@ impl VMR {
         cursor.remove().ok_or(SvsmError::Mem)
     }
 
+    /// Changes the access permissions of the mapping at a given base address
+    /// and updates the page-table entries of the mapping accordingly.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - Virtual base address of the [`VMM`] to change
+    /// * `flags` - New access permissions for the mapping
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success, SvsmError::Mem if no mapping starts at `base`, or
+    /// the error returned by the mapping if it rejects the new permissions.
+    pub fn protect(&self, base: VirtAddr, flags: VMFileMappingFlags) -> Result<(), SvsmError> {
+        let tree = self.tree.lock_read();
+        let cursor = tree.find(&base.pfn());
+        let node = cursor.get().ok_or(SvsmError::Mem)?;
+
+        node.get_mapping_mut().set_flags(flags)?;
+
+        self.unmap_vmm(node);
+        let result = self.map_vmm(node);
+        if self.per_cpu {
+            flush_tlb_global_percpu();
+        } else {
+            flush_tlb_global_sync();
+        }
+        result
+    }
+
     /// Dump all [`VMM`] mappings in the RBTree. This function is included for
     /// debugging purposes. And should not be called in production code.
     pub fn dump_ranges(&self) {
@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Platform-neutral interface to the attestation evidence of the hardware.
+
+extern crate alloc;
+
+use crate::protocols::errors::SvsmReqError;
+use alloc::vec::Vec;
+
+/// Size of the caller-provided data which is bound into the attestation
+/// evidence. Both SEV-SNP and TDX use 64 bytes of REPORT_DATA.
+pub const REPORT_DATA_SIZE: usize = 64;
+
+/// Backend which produces attestation evidence for the SVSM.
+pub trait AttestationBackend {
+    /// Produces attestation evidence which covers the launch measurement of
+    /// the SVSM and `report_data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `report_data` - Data to include in the evidence, usually a hash
+    ///   over a nonce and a services manifest.
+    ///
+    /// # Returns
+    ///
+    /// The evidence in the platform format: an attestation report on
+    /// SEV-SNP, a quote on TDX.
+    fn attestation_report(
+        &self,
+        report_data: &[u8; REPORT_DATA_SIZE],
+    ) -> Result<Vec<u8>, SvsmReqError>;
+}
@@ -4,6 +4,7 @@
 //
 // Author: Jon Lange <jlange@microsoft.com>
 
+pub mod attestation;
 pub mod capabilities;
 pub mod guest_cpu;
 pub mod native;
@@ -13,6 +14,7 @@ pub mod tdp;
 mod snp_fw;
 pub use snp_fw::{parse_fw_meta_data, SevFWMetaData};
 
+use attestation::AttestationBackend;
 use capabilities::Caps;
 use native::NativePlatform;
 use snp::SnpPlatform;
@@ -27,9 +29,11 @@ use crate::cpu::cpuid::CpuidResult;
 use crate::cpu::percpu::PerCpu;
 use crate::cpu::shadow_stack::determine_cet_support_from_cpuid;
 use crate::cpu::tlb::{flush_tlb, TlbFlushScope};
+use crate::crypto::aead::KEY_SIZE;
 use crate::error::SvsmError;
 use crate::hyperv;
 use crate::io::IOPort;
+use crate::tdx::rtmr::RTMR_DIGEST_SIZE;
 use crate::types::PageSize;
 use crate::utils::immut_after_init::ImmutAfterInitCell;
 use crate::utils::MemoryRegion;
@@ -212,6 +216,39 @@ pub trait SvsmPlatform: Sync {
         false
     }
 
+    /// Derives a key from platform secrets, which is bound to the platform
+    /// and to the launch measurement of the SVSM. The same label always
+    /// yields the same key, so it can be used to protect data stored
+    /// outside of the guest. Data sealed by one SVSM image can not be
+    /// unsealed by an updated image with a different measurement.
+    fn derive_sealing_key(&self, _label: &[u8]) -> Result<[u8; KEY_SIZE], SvsmError> {
+        Err(SvsmError::NotSupported)
+    }
+
+    /// Returns the backend which produces attestation evidence on this
+    /// platform, if the platform supports attestation.
+    fn attestation(&self) -> Option<&dyn AttestationBackend> {
+        None
+    }
+
+    /// Extends runtime measurement register `index` with `digest`.
+    /// Callers should use [`rtmr_measure`](crate::tdx::rtmr::rtmr_measure),
+    /// which also records the extension in the event log.
+    fn extend_rtmr(
+        &self,
+        _index: usize,
+        _digest: &[u8; RTMR_DIGEST_SIZE],
+    ) -> Result<(), SvsmError> {
+        Err(SvsmError::NotSupported)
+    }
+
+    /// Derives a key which is shared between the source and the
+    /// destination of a live migration. The same label always yields the
+    /// same key.
+    fn derive_migration_key(&self, _label: &[u8]) -> Result<[u8; KEY_SIZE], SvsmError> {
+        Err(SvsmError::NotSupported)
+    }
+
     /// Perfrom a write to a memory-mapped IO area
     ///
     /// # Safety
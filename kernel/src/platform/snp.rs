@@ -4,9 +4,11 @@
 //
 // Author: Jon Lange <jlange@microsoft.com>
 
+use super::attestation::{AttestationBackend, REPORT_DATA_SIZE};
 use super::capabilities::Caps;
 use super::snp_fw::{
-    copy_tables_to_fw, launch_fw, prepare_fw_launch, print_fw_meta, validate_fw, validate_fw_memory,
+    copy_tables_to_fw, launch_fw, prepare_fw_launch, print_fw_meta, validate_fw,
+    validate_fw_memory, validate_linux_memory,
 };
 use super::{PageEncryptionMasks, PageStateChangeOp, PageValidateOp, SvsmPlatform};
 use crate::address::{Address, PhysAddr, VirtAddr};
@@ -16,13 +18,19 @@ use crate::cpu::cpuid::{cpuid_table, CpuidResult};
 use crate::cpu::percpu::{current_ghcb, this_cpu, PerCpu};
 use crate::cpu::tlb::TlbFlushScope;
 use crate::cpu::x86::{apic_enable, apic_initialize, apic_sw_enable};
+use crate::crypto::aead::KEY_SIZE;
+use crate::crypto::kdf::{HkdfSha512, HkdfSha512Trait};
 use crate::error::ApicError::Registration;
 use crate::error::SvsmError;
 use crate::greq::driver::guest_request_driver_init;
+use crate::greq::pld_key::{GuestFieldSelect, RootKeySelect, SnpKeyRequest};
+use crate::greq::pld_report::{SnpReportRequest, SnpReportResponse};
+use crate::greq::services::{get_derived_key, get_regular_report};
 use crate::hyperv;
 use crate::io::IOPort;
 use crate::mm::memory::write_guest_memory_map;
 use crate::mm::{PerCPUPageMappingGuard, PAGE_SIZE, PAGE_SIZE_2M};
+use crate::protocols::errors::SvsmReqError;
 use crate::sev::ghcb::GHCBIOSize;
 use crate::sev::msr_protocol::{
     hypervisor_ghcb_features, request_termination_msr, verify_ghcb_version, GHCBHvFeatures,
@@ -37,7 +45,10 @@ use crate::types::PageSize;
 use crate::utils::immut_after_init::ImmutAfterInitCell;
 use crate::utils::MemoryRegion;
 use syscall::GlobalFeatureFlags;
+use zerocopy::{FromBytes, FromZeros, IntoBytes};
 
+extern crate alloc;
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicU32, Ordering};
 
 #[cfg(test)]
@@ -133,6 +144,9 @@ impl SvsmPlatform for SnpPlatform {
             print_fw_meta(fw_meta);
             write_guest_memory_map(config)?;
             validate_fw_memory(config, fw_meta, &kernel_region)?;
+            if config.linux_boot_params().is_some() {
+                validate_linux_memory(config, fw_meta, &kernel_region)?;
+            }
             copy_tables_to_fw(fw_meta, &kernel_region)?;
             validate_fw(config, &kernel_region)?;
             prepare_fw_launch(fw_meta)?;
@@ -376,6 +390,31 @@ impl SvsmPlatform for SnpPlatform {
         true
     }
 
+    fn attestation(&self) -> Option<&dyn AttestationBackend> {
+        Some(self)
+    }
+
+    fn derive_sealing_key(&self, label: &[u8]) -> Result<[u8; KEY_SIZE], SvsmError> {
+        // Bind the key to the chip, the guest policy and the launch
+        // measurement, so that only the same SVSM image can unseal data.
+        let request = SnpKeyRequest::builder()
+            .guest_fields(GuestFieldSelect::GUEST_POLICY | GuestFieldSelect::MEASUREMENT)
+            .build()
+            .map_err(|_| SvsmError::Crypto)?;
+        derive_psp_key(&request, label)
+    }
+
+    fn derive_migration_key(&self, label: &[u8]) -> Result<[u8; KEY_SIZE], SvsmError> {
+        // The VM root key is shared by the source and the destination of a
+        // migration through the migration agent.
+        let request = SnpKeyRequest::builder()
+            .root_key(RootKeySelect::Vmrk)
+            .guest_fields(GuestFieldSelect::GUEST_POLICY)
+            .build()
+            .map_err(|_| SvsmError::Crypto)?;
+        derive_psp_key(&request, label)
+    }
+
     /// Perfrom a write to a memory-mapped IO area
     ///
     /// # Safety
@@ -399,6 +438,47 @@ impl SvsmPlatform for SnpPlatform {
     }
 }
 
+impl AttestationBackend for SnpPlatform {
+    fn attestation_report(
+        &self,
+        report_data: &[u8; REPORT_DATA_SIZE],
+    ) -> Result<Vec<u8>, SvsmReqError> {
+        let mut resp = SnpReportResponse::new_box_zeroed()
+            .map_err(|_| SvsmReqError::FatalError(SvsmError::Mem))?;
+        let resp_buffer = resp.as_mut_bytes();
+        // Cast error is infallibly discarded.
+        let (report_req, _) = SnpReportRequest::mut_from_prefix(resp_buffer)
+            .map_err(|_| SvsmReqError::invalid_parameter())?;
+        // Zero initialized, so
+        // vmpl=0
+        // flags=0: Use VLEK if installed, otherwise VCEK.
+        report_req.user_data = *report_data;
+        let _response_size = get_regular_report(resp_buffer)?;
+
+        Ok(resp.report.as_bytes().to_vec())
+    }
+}
+
+/// Prefix of the HKDF info of keys derived from PSP keys.
+const PSP_KEY_INFO_PREFIX: &[u8] = b"coconut-svsm psp-key ";
+
+/// Requests a key from the PSP and derives a separate key for `label` from
+/// it with HKDF-SHA512. The label is prefixed with [`PSP_KEY_INFO_PREFIX`],
+/// so that the derived keys never collide with keys other software derives
+/// from the same PSP key.
+fn derive_psp_key(request: &SnpKeyRequest, label: &[u8]) -> Result<[u8; KEY_SIZE], SvsmError> {
+    let mut root_key = get_derived_key(request).map_err(|e| {
+        log::error!("Failed to get derived key from the PSP: {e:?}");
+        SvsmError::Crypto
+    })?;
+
+    let info = [PSP_KEY_INFO_PREFIX, label].concat();
+    let mut key = [0u8; KEY_SIZE];
+    let result = HkdfSha512::derive(&root_key, None, &info, &mut key);
+    root_key.fill(0);
+    result.map(|_| key)
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct GHCBIOPort {}
 
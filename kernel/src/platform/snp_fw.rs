@@ -11,6 +11,7 @@ use crate::config::SvsmConfig;
 use crate::cpu::cpuid::copy_cpuid_table_to;
 use crate::cpu::percpu::{current_ghcb, this_cpu, this_cpu_shared};
 use crate::error::SvsmError;
+use crate::mm::memory::guest_memory_regions;
 use crate::mm::PerCPUPageMappingGuard;
 use crate::platform::PageStateChangeOp;
 use crate::sev::{pvalidate, rmp_adjust, secrets_page, PvalidateOp, RMPFlags};
@@ -264,6 +265,58 @@ pub fn validate_fw_memory(
     validate_fw_memory_vec(config, regions)
 }
 
+/// Removes the parts of `regions` that overlap with any of the sorted
+/// regions in `excluded`.
+fn exclude_regions(
+    regions: &[MemoryRegion<PhysAddr>],
+    excluded: &[MemoryRegion<PhysAddr>],
+) -> Vec<MemoryRegion<PhysAddr>> {
+    let mut result = Vec::new();
+
+    for region in regions.iter() {
+        let mut start = region.start();
+        for hole in excluded.iter().filter(|hole| hole.overlap(region)) {
+            if start < hole.start() {
+                result.push(MemoryRegion::from_addresses(start, hole.start()));
+            }
+            start = start.max(hole.end());
+        }
+        if start < region.end() {
+            result.push(MemoryRegion::from_addresses(start, region.end()));
+        }
+    }
+
+    result
+}
+
+/// Validates all guest memory for a directly booted Linux kernel, which
+/// expects every page in its E820 table to be validated. The firmware
+/// regions and the pages described by the firmware metadata are already
+/// validated and are skipped.
+pub fn validate_linux_memory(
+    config: &SvsmConfig<'_>,
+    fw_meta: &SevFWMetaData,
+    kernel_region: &MemoryRegion<PhysAddr>,
+) -> Result<(), SvsmError> {
+    let mut excluded = config.get_fw_regions(kernel_region);
+    if excluded.iter().any(|region| region.overlap(kernel_region)) {
+        log::error!("Linux kernel image overlaps with SVSM kernel");
+        return Err(SvsmError::Firmware);
+    }
+
+    excluded.extend_from_slice(&fw_meta.valid_mem);
+    for page in [fw_meta.cpuid_page, fw_meta.secrets_page, fw_meta.caa_page]
+        .into_iter()
+        .flatten()
+    {
+        excluded.push(MemoryRegion::new(page, PAGE_SIZE));
+    }
+    excluded.sort_unstable_by_key(|a| a.start());
+
+    let regions = exclude_regions(&guest_memory_regions(), &excluded);
+    validate_fw_memory_vec(config, regions)
+}
+
 pub fn print_fw_meta(fw_meta: &SevFWMetaData) {
     log::info!("FW Meta Data");
 
@@ -4,6 +4,7 @@
 //
 // Author: Peter Fang <peter.fang@intel.com>
 
+use super::attestation::{AttestationBackend, REPORT_DATA_SIZE};
 use super::capabilities::Caps;
 use super::{PageEncryptionMasks, PageStateChangeOp, PageValidateOp, SvsmPlatform};
 use crate::address::{Address, PhysAddr, VirtAddr};
@@ -17,15 +18,21 @@ use crate::hyperv;
 use crate::hyperv::{hyperv_start_cpu, IS_HYPERV};
 use crate::io::IOPort;
 use crate::mm::PerCPUPageMappingGuard;
+use crate::protocols::errors::SvsmReqError;
 use crate::tdx::apic::TDX_APIC_ACCESSOR;
+use crate::tdx::quote::td_quote;
+use crate::tdx::rtmr::{td_extend_rtmr, RTMR_DIGEST_SIZE};
 use crate::tdx::tdcall::{
     td_accept_physical_memory, td_accept_virtual_memory, tdcall_vm_read, tdvmcall_halt,
     tdvmcall_hyperv_hypercall, tdvmcall_io_read, tdvmcall_io_write, tdvmcall_map_gpa,
-    tdvmcall_wrmsr, MD_TDCS_NUM_L2_VMS,
+    tdvmcall_mmio_read, tdvmcall_mmio_write, tdvmcall_wrmsr, MD_TDCS_NUM_L2_VMS,
 };
-use crate::types::{PageSize, PAGE_SIZE};
+use crate::types::{Bytes, PageSize, PAGE_SIZE};
 use crate::utils::immut_after_init::ImmutAfterInitCell;
 use crate::utils::{is_aligned, MemoryRegion};
+
+extern crate alloc;
+use alloc::vec::Vec;
 use bootlib::kernel_launch::{ApStartContext, SIPI_STUB_GPA};
 use core::{mem, ptr};
 use syscall::GlobalFeatureFlags;
@@ -270,12 +277,40 @@ impl SvsmPlatform for TdpPlatform {
         Ok(())
     }
 
-    unsafe fn mmio_write(&self, _paddr: PhysAddr, _data: &[u8]) -> Result<(), SvsmError> {
-        unimplemented!()
+    fn attestation(&self) -> Option<&dyn AttestationBackend> {
+        Some(self)
+    }
+
+    fn extend_rtmr(&self, index: usize, digest: &[u8; RTMR_DIGEST_SIZE]) -> Result<(), SvsmError> {
+        td_extend_rtmr(index, digest)
+    }
+
+    unsafe fn mmio_write(&self, paddr: PhysAddr, data: &[u8]) -> Result<(), SvsmError> {
+        let size = Bytes::try_from(data.len())?;
+        let mut value = [0u8; 8];
+        value[..data.len()].copy_from_slice(data);
+        // MMIO is emulated by the host, so the request targets the shared
+        // alias of the address.
+        let gpa = u64::from(paddr) | *VTOM as u64;
+        tdvmcall_mmio_write(gpa, size as usize, u64::from_le_bytes(value))?;
+        Ok(())
     }
 
-    unsafe fn mmio_read(&self, _paddr: PhysAddr, _data: &mut [u8]) -> Result<(), SvsmError> {
-        unimplemented!()
+    unsafe fn mmio_read(&self, paddr: PhysAddr, data: &mut [u8]) -> Result<(), SvsmError> {
+        let size = Bytes::try_from(data.len())?;
+        let gpa = u64::from(paddr) | *VTOM as u64;
+        let value = tdvmcall_mmio_read(gpa, size as usize)?.to_le_bytes();
+        data.copy_from_slice(&value[..data.len()]);
+        Ok(())
+    }
+}
+
+impl AttestationBackend for TdpPlatform {
+    fn attestation_report(
+        &self,
+        report_data: &[u8; REPORT_DATA_SIZE],
+    ) -> Result<Vec<u8>, SvsmReqError> {
+        Ok(td_quote(report_data)?)
     }
 }
 
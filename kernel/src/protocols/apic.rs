@@ -33,6 +33,10 @@ fn apic_configure(params: &RequestParams) -> Result<(), SvsmReqError> {
         }
 
         0b01 => {
+            // Deregistration cannot be undone, so make sure that the current
+            // CPU can hand off APIC emulation to the host before deregistering.
+            this_cpu().check_apic_handoff()?;
+
             // Deregister APIC emulation if possible, noting whether it is now
             // disabled for the platform.  This cannot fail.
             SVSM_PLATFORM.change_apic_registration_state(false).unwrap()
@@ -52,7 +56,7 @@ fn apic_configure(params: &RequestParams) -> Result<(), SvsmReqError> {
 
     // Disable APIC emulation on the current CPU if required.
     if !enabled {
-        this_cpu().disable_apic_emulation();
+        this_cpu().disable_apic_emulation()?;
     }
 
     Ok(())
@@ -11,19 +11,18 @@ extern crate alloc;
 use crate::address::{Address, PhysAddr};
 use crate::crypto::digest::{Algorithm, Sha512};
 use crate::error::{AttestError, SvsmError};
-use crate::greq::{
-    pld_report::{SnpReportRequest, SnpReportResponse},
-    services::get_regular_report,
-};
 use crate::mm::guestmem::{copy_slice_to_guest, read_bytes_from_guest, read_from_guest};
+use crate::platform::attestation::REPORT_DATA_SIZE;
+use crate::platform::SVSM_PLATFORM;
 use crate::protocols::{errors::SvsmReqError, RequestParams};
+use crate::tdx::rtmr::rtmr_event_log;
 use crate::utils::MemoryRegion;
 #[cfg(all(feature = "vtpm", not(test)))]
 use crate::vtpm::vtpm_get_manifest;
 
-use alloc::{boxed::Box, vec::Vec};
+use alloc::vec::Vec;
 use uuid::{uuid, Uuid};
-use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
 pub const ATTEST_PROTOCOL_VERSION_MIN: u32 = 1;
 pub const ATTEST_PROTOCOL_VERSION_MAX: u32 = 1;
@@ -33,6 +32,10 @@ const GUID_HEADER_ENTRY_SIZE: usize = 24;
 const SVSM_ATTEST_SERVICES: u32 = 0;
 const SVSM_ATTEST_SINGLE_SERVICE: u32 = 1;
 
+// Service whose manifest is the event log of the RTMR extensions done by
+// the SVSM, in the TCG crypto-agile format.
+const SVSM_ATTEST_RTMR_LOG_GUID: Uuid = uuid!("bb3c8ace-b1f1-4a55-8bfe-7dc58cca4290");
+
 #[cfg(all(feature = "vtpm", not(test)))]
 const SVSM_ATTEST_VTPM_GUID: Uuid = uuid!("c476f1eb-0123-45a5-9641-b4e7dde5bfe3");
 
@@ -239,22 +242,16 @@ impl AttestSingleServiceOp {
     }
 }
 
-fn get_attestation_report(nonce: &[u8]) -> Result<Box<SnpReportResponse>, SvsmReqError> {
-    let mut resp = SnpReportResponse::new_box_zeroed()
-        .map_err(|_| SvsmReqError::FatalError(SvsmError::Mem))?;
-    let resp_buffer = resp.as_mut_bytes();
-    // Cast error is infallibly discarded.
-    let (report_req, _) = SnpReportRequest::mut_from_prefix(resp_buffer)
-        .map_err(|_| SvsmReqError::invalid_parameter())?;
-    // Zero initialized, so
-    // vmpl=0
-    // flags=0: Use VLEK if installed, otherwise VCEK.
-    report_req.user_data = nonce
+/// Gets the attestation evidence of the platform with `report_data` as
+/// REPORT_DATA. This is an attestation report on SEV-SNP and a quote on TDX.
+fn get_attestation_report(report_data: &[u8]) -> Result<Vec<u8>, SvsmReqError> {
+    let report_data: &[u8; REPORT_DATA_SIZE] = report_data
         .try_into()
         .map_err(|_| SvsmReqError::invalid_parameter())?;
-    let _response_size = get_regular_report(resp_buffer)?;
-
-    Ok(resp)
+    let backend = SVSM_PLATFORM
+        .attestation()
+        .ok_or_else(SvsmReqError::unsupported_protocol)?;
+    backend.attestation_report(report_data)
 }
 
 fn write_report_and_manifest(
@@ -313,7 +310,6 @@ fn write_report_and_manifest(
     Ok(())
 }
 
-#[allow(dead_code)]
 fn attest_single_service(
     manifest: &[u8],
     params: &mut RequestParams,
@@ -326,10 +322,10 @@ fn attest_single_service(
     let nonce_and_manifest = [&nonce[..], manifest].concat();
     let hash = Sha512::digest(&nonce_and_manifest);
 
-    // Get attestation report from PSP with Sha512(nonce||manifest) as REPORT_DATA.
-    let resp = get_attestation_report(hash.as_slice())?;
+    // Get attestation report with Sha512(nonce||manifest) as REPORT_DATA.
+    let report = get_attestation_report(hash.as_slice())?;
 
-    write_report_and_manifest(manifest, params, &ops.op, resp.report.as_bytes())
+    write_report_and_manifest(manifest, params, &ops.op, &report)
 }
 
 #[cfg(all(feature = "vtpm", not(test)))]
@@ -349,9 +345,15 @@ fn attest_multiple_services(params: &mut RequestParams) -> Result<(), SvsmReqErr
     // Attest multiple services is expected to return a GUID table (mixed endian ordering) of the
     // enumerated active services' attestation manifest. A service that does not have its own
     // manifest is still enumerated, but with an empty data blob.
-    #[allow(unused_mut)]
     let mut services = GuidTable::new();
 
+    // The RTMR event log is only enumerated when the SVSM measured events,
+    // which requires a platform with RTMRs.
+    let rtmr_log = rtmr_event_log();
+    if !rtmr_log.is_empty() {
+        services.push(SVSM_ATTEST_RTMR_LOG_GUID, rtmr_log);
+    }
+
     #[cfg(all(feature = "vtpm", not(test)))]
     services.push(SVSM_ATTEST_VTPM_GUID, vtpm_get_manifest()?);
 
@@ -363,15 +365,10 @@ fn attest_multiple_services(params: &mut RequestParams) -> Result<(), SvsmReqErr
     // "Secure VM Service Module for SEV-SNP Guests 58019 Rev. 1.00".
     let hash = Sha512::digest(&nonce_and_manifest);
 
-    // Get attestation report from PSP with Sha512(nonce||manifest) as REPORT_DATA.
-    let resp = get_attestation_report(hash.as_slice())?;
+    // Get attestation report with Sha512(nonce||manifest) as REPORT_DATA.
+    let report = get_attestation_report(hash.as_slice())?;
 
-    write_report_and_manifest(
-        manifest.as_slice(),
-        params,
-        &attest_op,
-        resp.report.as_bytes(),
-    )
+    write_report_and_manifest(manifest.as_slice(), params, &attest_op, &report)
 }
 
 #[allow(clippy::needless_pass_by_ref_mut)]
@@ -384,10 +381,13 @@ fn attest_single_service_handler(params: &mut RequestParams) -> Result<(), SvsmR
 
     // Extract the GUID from the Attest Single Service Operation structure.
     // The GUID is used to determine the specific service to be attested.
-    // Currently, only the VTPM service with the GUID 0xebf176c4_2301a545_9641b4e7_dde5bfe3
-    // is supported, see 8.3.1 of the spec "Secure VM Service Module for SEV-SNP Guests
-    // 58019 Rev. 1.00" for more details.
+    // The VTPM service with the GUID 0xebf176c4_2301a545_9641b4e7_dde5bfe3 is defined in 8.3.1
+    // of the spec "Secure VM Service Module for SEV-SNP Guests 58019 Rev. 1.00". The RTMR
+    // event log service is specific to the SVSM.
     match attest_op.get_guid() {
+        SVSM_ATTEST_RTMR_LOG_GUID => {
+            attest_single_service(rtmr_event_log().as_slice(), params, &attest_op)
+        }
         #[cfg(all(feature = "vtpm", not(test)))]
         SVSM_ATTEST_VTPM_GUID => attest_single_vtpm(params, &attest_op),
         _ => Err(SvsmReqError::unsupported_protocol()),
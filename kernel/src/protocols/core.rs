@@ -10,14 +10,18 @@ use crate::cpu::percpu::{this_cpu, this_cpu_shared, PERCPU_AREAS, PERCPU_VMSAS};
 use crate::cpu::vmsa::{vmsa_mut_ref_from_vaddr, vmsa_ref_from_vaddr};
 use crate::error::SvsmError;
 use crate::locking::RWLock;
+use crate::mm::alloc::{deposit_page, deposited_free_pages, withdraw_pages};
+use crate::mm::memory::valid_phys_region;
 use crate::mm::virtualrange::{VIRT_ALIGN_2M, VIRT_ALIGN_4K};
 use crate::mm::PerCPUPageMappingGuard;
 use crate::mm::{valid_phys_address, writable_phys_addr, GuestPtr};
 use crate::protocols::apic::{APIC_PROTOCOL_VERSION_MAX, APIC_PROTOCOL_VERSION_MIN};
 use crate::protocols::attest::{ATTEST_PROTOCOL_VERSION_MAX, ATTEST_PROTOCOL_VERSION_MIN};
 use crate::protocols::errors::SvsmReqError;
+use crate::protocols::migration::{MIGRATION_PROTOCOL_VERSION_MAX, MIGRATION_PROTOCOL_VERSION_MIN};
 use crate::protocols::{
     RequestParams, SVSM_APIC_PROTOCOL, SVSM_ATTEST_PROTOCOL, SVSM_CORE_PROTOCOL,
+    SVSM_MIGRATION_PROTOCOL,
 };
 use crate::requests::SvsmCaa;
 use crate::sev::utils::{
@@ -26,7 +30,7 @@ use crate::sev::utils::{
 };
 use crate::sev::vmsa::VMSAControl;
 use crate::types::{PageSize, PAGE_SIZE, PAGE_SIZE_2M};
-use crate::utils::zero_mem_region;
+use crate::utils::{zero_mem_region, MemoryRegion};
 use cpuarch::vmsa::VMSA;
 
 const SVSM_REQ_CORE_REMAP_CA: u32 = 0;
@@ -41,13 +45,16 @@ const SVSM_REQ_CORE_CONFIGURE_VTOM: u32 = 7;
 pub const CORE_PROTOCOL_VERSION_MIN: u32 = 1;
 pub const CORE_PROTOCOL_VERSION_MAX: u32 = 1;
 
-// This lock prevents races around PVALIDATE and CREATE_VCPU
+// This lock prevents races around PVALIDATE, CREATE_VCPU, DEPOSIT_MEM and
+// WITHDRAW_MEM
 //
 // Without the lock there is a possible attack where the error path of
-// core_create_vcpu() could give the guest OS access to a SVSM page.
+// core_create_vcpu() could give the guest OS access to a SVSM page. The same
+// applies to pages which change ownership in core_deposit_mem() and
+// core_withdraw_mem().
 //
-// The PValidate path will take the lock for read, the create_vcpu path takes
-// the lock for write.
+// The PValidate path will take the lock for read, the create_vcpu and the
+// deposit and withdraw paths take the lock for write.
 static PVALIDATE_LOCK: RWLock<()> = RWLock::new(());
 
 #[repr(C, packed)]
@@ -87,12 +94,15 @@ fn check_vmsa(new: &VMSA, sev_features: u64, svme_mask: u64) -> bool {
         && new.sev_features == sev_features
 }
 
-/// per-cpu request mapping area size (1GB)
-fn core_create_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
-    let paddr = PhysAddr::from(params.rcx);
-    let pcaa = PhysAddr::from(params.rdx);
-    let apic_id: u32 = (params.r8 & 0xffff_ffff) as u32;
-
+/// Turns the guest page at `paddr` into the VMSA of the guest vCPU with
+/// `apic_id` and sets the calling area of the vCPU to `pcaa`. The VMSA must
+/// pass the checks of the SVSM specification for `sev_features`.
+pub fn create_vcpu(
+    paddr: PhysAddr,
+    pcaa: PhysAddr,
+    apic_id: u32,
+    sev_features: u64,
+) -> Result<(), SvsmReqError> {
     // Check VMSA address
     if !valid_phys_address(paddr) || !paddr.is_page_aligned() {
         return Err(SvsmReqError::invalid_address());
@@ -143,7 +153,7 @@ fn core_create_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
     let svme_mask: u64 = 1u64 << 12;
 
     // VMSA validity checks according to SVSM spec
-    if !check_vmsa(new_vmsa, params.sev_features, svme_mask) {
+    if !check_vmsa(new_vmsa, sev_features, svme_mask) {
         // SAFETY: this address has already been validated as a guest-owned
         // address.
         unsafe {
@@ -168,6 +178,15 @@ fn core_create_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
     Ok(())
 }
 
+/// per-cpu request mapping area size (1GB)
+fn core_create_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let paddr = PhysAddr::from(params.rcx);
+    let pcaa = PhysAddr::from(params.rdx);
+    let apic_id: u32 = (params.r8 & 0xffff_ffff) as u32;
+
+    create_vcpu(paddr, pcaa, apic_id, params.sev_features)
+}
+
 fn core_delete_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
     let paddr = PhysAddr::from(params.rcx);
 
@@ -197,14 +216,237 @@ fn core_delete_vcpu(params: &RequestParams) -> Result<(), SvsmReqError> {
     res
 }
 
-fn core_deposit_mem(_params: &RequestParams) -> Result<(), SvsmReqError> {
-    log::info!("Request SVSM_REQ_CORE_DEPOSIT_MEM not yet supported");
-    Err(SvsmReqError::unsupported_call())
+/// Reflects in the `mem_available` flag of the calling area whether the SVSM
+/// holds deposited memory which the guest can withdraw.
+fn core_update_mem_available() -> Result<(), SvsmReqError> {
+    let Some(caa_addr) = this_cpu().guest_vmsa_ref().caa_addr() else {
+        return Ok(());
+    };
+
+    let calling_area = GuestPtr::<SvsmCaa>::new(caa_addr);
+    let available = u8::from(deposited_free_pages() != 0);
+
+    // SAFETY: guest vmsa and ca are always validated before being updated
+    // (core_remap_ca(), core_create_vcpu() or prepare_fw_launch()) so they're
+    // safe to use.
+    unsafe {
+        let caa = calling_area.read()?;
+        calling_area.write(caa.update_mem_available(available))?;
+    }
+
+    Ok(())
+}
+
+/// Decodes an entry of a DEPOSIT_MEM or WITHDRAW_MEM page list.
+fn core_mem_list_entry(entry: u64) -> Result<(PhysAddr, PageSize), SvsmReqError> {
+    let size = match entry & 3 {
+        0 => PageSize::Regular,
+        1 => PageSize::Huge,
+        _ => return Err(SvsmReqError::invalid_parameter()),
+    };
+
+    // Bits 11:2 are reserved
+    if entry & 0xffc != 0 {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let paddr = PhysAddr::from(entry).page_align();
+    if !paddr.is_aligned(usize::from(size)) {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    Ok((paddr, size))
+}
+
+/// Encodes a page for a WITHDRAW_MEM page list.
+fn core_mem_list_encode(paddr: PhysAddr, size: PageSize) -> u64 {
+    let size_bits = match size {
+        PageSize::Regular => 0,
+        PageSize::Huge => 1,
+    };
+    u64::from(paddr) | size_bits
+}
+
+fn core_deposit_one(entry: u64) -> Result<(), SvsmReqError> {
+    let (paddr, size) = core_mem_list_entry(entry)?;
+    let (page_size_bytes, valign) = match size {
+        PageSize::Regular => (PAGE_SIZE, VIRT_ALIGN_4K),
+        PageSize::Huge => (PAGE_SIZE_2M, VIRT_ALIGN_2M),
+    };
+
+    // Take lock to prevent PVALIDATE and CREATE_VCPU calls from using the
+    // page while its ownership changes
+    let lock = PVALIDATE_LOCK.lock_write();
+
+    // The SVSM writes to deposited memory, so read-only ranges are rejected.
+    let region = MemoryRegion::new(paddr, page_size_bytes);
+    if !valid_phys_region(&region) || !region.iter_pages(PageSize::Regular).all(writable_phys_addr)
+    {
+        log::debug!("Invalid deposit address: {:#x}", paddr);
+        return Err(SvsmReqError::invalid_address());
+    }
+
+    let guard = PerCPUPageMappingGuard::create(paddr, paddr + page_size_bytes, valign)?;
+    let vaddr = guard.virt_addr();
+
+    // A page which is still validated might be in use by the guest, so only
+    // invalid pages are accepted.
+    // SAFETY: the physical address was guaranteed to be a guest address and
+    // cannot affect memory safety.
+    unsafe {
+        pvalidate(vaddr, size, PvalidateOp::Valid)?;
+    }
+
+    // Make sure no lower VMPL keeps access to the page before handing it to
+    // the allocator.
+    let result = rmp_revoke_guest_access(vaddr, size).and_then(|_| deposit_page(paddr, size));
+    if let Err(err) = result {
+        // SAFETY: the page was validated above and is not used by the SVSM.
+        if let Err(e) = unsafe { pvalidate(vaddr, size, PvalidateOp::Invalid) } {
+            log::error!("Failed to invalidate page {:#x}: {:?}", paddr, e);
+        }
+        return Err(match err {
+            SvsmError::SevSnp(_) => err.into(),
+            _ => SvsmReqError::invalid_request(),
+        });
+    }
+
+    drop(guard);
+    drop(lock);
+
+    Ok(())
+}
+
+fn core_deposit_mem(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let gpa = PhysAddr::from(params.rcx);
+
+    if !gpa.is_aligned(8) || !valid_phys_address(gpa) {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let paddr = gpa.page_align();
+    let offset = gpa.page_offset();
+
+    let guard = PerCPUPageMappingGuard::create_4k(paddr)?;
+    let start = guard.virt_addr();
+
+    // The deposit list uses the same format as the PVALIDATE list.
+    let guest_page = GuestPtr::<PValidateRequest>::new(start + offset);
+    // SAFETY: start is a new mapped page address, thus valid.
+    // offset can't exceed a page size, so guest_page belongs to mapped memory.
+    let mut request = unsafe { guest_page.read()? };
+
+    let entries = request.entries;
+    let next = request.next;
+
+    // Each entry is 8 bytes in size, 8 bytes for the request header
+    let max_entries: u16 = ((PAGE_SIZE - offset - 8) / 8).try_into().unwrap();
+
+    if entries == 0 || entries > max_entries || entries <= next {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let mut loop_result = Ok(());
+
+    let guest_entries = guest_page.offset(1).cast::<u64>();
+    for i in next..entries {
+        let index = i as isize;
+        // SAFETY: guest_entries comes from guest_page which is a new mapped
+        // page. index is between [next, entries) and both values have been
+        // validated.
+        let entry = match unsafe { guest_entries.offset(index).read() } {
+            Ok(v) => v,
+            Err(e) => {
+                loop_result = Err(e.into());
+                break;
+            }
+        };
+
+        loop_result = core_deposit_one(entry);
+        match loop_result {
+            Ok(()) => request.next += 1,
+            Err(SvsmReqError::RequestError(..)) => break,
+            Err(SvsmReqError::FatalError(..)) => return loop_result,
+        }
+    }
+
+    // SAFETY: guest_page is obtained from a guest-provided physical address
+    // which has been validated by valid_phys_address() above.
+    if let Err(e) = unsafe { guest_page.write_ref(&request) } {
+        loop_result = Err(e.into());
+    }
+
+    core_update_mem_available()?;
+
+    loop_result
 }
 
-fn core_withdraw_mem(_params: &RequestParams) -> Result<(), SvsmReqError> {
-    log::info!("Request SVSM_REQ_CORE_WITHDRAW_MEM not yet supported");
-    Err(SvsmReqError::unsupported_call())
+fn core_withdraw_mem(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let gpa = PhysAddr::from(params.rcx);
+
+    if !gpa.is_aligned(8) || !valid_phys_address(gpa) {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let paddr = gpa.page_align();
+    let offset = gpa.page_offset();
+
+    let guard = PerCPUPageMappingGuard::create_4k(paddr)?;
+    let start = guard.virt_addr();
+
+    // The withdraw list uses the same format as the PVALIDATE list. The guest
+    // passes the number of entries the list can hold, the SVSM returns the
+    // number of entries it filled in.
+    let guest_page = GuestPtr::<PValidateRequest>::new(start + offset);
+    // SAFETY: start is a new mapped page address, thus valid.
+    // offset can't exceed a page size, so guest_page belongs to mapped memory.
+    let mut request = unsafe { guest_page.read()? };
+
+    let entries = request.entries;
+
+    // Each entry is 8 bytes in size, 8 bytes for the request header
+    let max_entries: u16 = ((PAGE_SIZE - offset - 8) / 8).try_into().unwrap();
+
+    if entries == 0 || entries > max_entries || request.next != 0 {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let guest_entries = guest_page.offset(1).cast::<u64>();
+    let mut filled: u16 = 0;
+
+    // Take lock to prevent PVALIDATE calls on pages while they are returned
+    // to the guest
+    let lock = PVALIDATE_LOCK.lock_write();
+
+    let result = withdraw_pages(entries.into(), |vaddr, paddr, size| {
+        // SAFETY: guest_entries comes from guest_page which is a new mapped
+        // page, and filled stays below the validated number of entries.
+        unsafe {
+            guest_entries
+                .offset(filled as isize)
+                .write(core_mem_list_encode(paddr, size))?;
+        }
+        // The guest needs to validate the page with a PVALIDATE request before
+        // it can use it again.
+        // SAFETY: the page is no longer used by the SVSM.
+        unsafe {
+            pvalidate(vaddr, size, PvalidateOp::Invalid)?;
+        }
+        filled += 1;
+        Ok(())
+    });
+
+    drop(lock);
+
+    request.entries = filled;
+
+    // SAFETY: guest_page is obtained from a guest-provided physical address
+    // which has been validated by valid_phys_address() above.
+    unsafe { guest_page.write_ref(&request)? };
+
+    core_update_mem_available()?;
+
+    result.map(|_| ()).map_err(SvsmReqError::from)
 }
 
 fn protocol_supported(version: u32, version_min: u32, version_max: u32) -> u64 {
@@ -247,6 +489,11 @@ fn core_query_protocol(params: &mut RequestParams) -> Result<(), SvsmReqError> {
             ATTEST_PROTOCOL_VERSION_MIN,
             ATTEST_PROTOCOL_VERSION_MAX,
         ),
+        SVSM_MIGRATION_PROTOCOL => protocol_supported(
+            version,
+            MIGRATION_PROTOCOL_VERSION_MIN,
+            MIGRATION_PROTOCOL_VERSION_MAX,
+        ),
         _ => 0,
     };
 
@@ -286,6 +533,11 @@ fn core_pvalidate_one(entry: u64, flush: &mut bool) -> Result<(), SvsmReqError>
         return Err(SvsmReqError::invalid_parameter());
     }
 
+    // Take lock to prevent races with CREATE_VCPU and DEPOSIT_MEM calls. The
+    // address must be checked with the lock held, as a DEPOSIT_MEM call might
+    // just be taking ownership of the page.
+    let lock = PVALIDATE_LOCK.lock_read();
+
     if !valid_phys_address(paddr) {
         log::debug!("Invalid phys address: {:#x}", paddr);
         return Err(SvsmReqError::invalid_address());
@@ -294,9 +546,6 @@ fn core_pvalidate_one(entry: u64, flush: &mut bool) -> Result<(), SvsmReqError>
     let guard = PerCPUPageMappingGuard::create(paddr, paddr + page_size_bytes, valign)?;
     let vaddr = guard.virt_addr();
 
-    // Take lock to prevent races with CREATE_VCPU calls
-    let lock = PVALIDATE_LOCK.lock_read();
-
     if valid == PvalidateOp::Invalid {
         *flush |= true;
         rmp_revoke_guest_access(vaddr, huge)?;
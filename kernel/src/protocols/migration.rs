@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Migration protocol implementation
+//!
+//! The guest uses this protocol to export, import and absorb guest contexts
+//! through the PSP when the SVSM runs in the migration agent (MA) of a
+//! guest. The state of the SVSM itself is not accessible to the guest, it
+//! is moved to the destination of a live migration by a user-mode process
+//! talking to the MA, see [`crate::migration`].
+
+use crate::address::{Address, PhysAddr};
+use crate::greq::pld_migration::SnpGuestContext;
+use crate::greq::services::{absorb_guest_context, export_guest_context, import_guest_context};
+use crate::mm::guestmem::{copy_slice_to_guest, read_from_guest};
+use crate::protocols::{errors::SvsmReqError, RequestParams};
+use zerocopy::IntoBytes;
+
+pub const MIGRATION_PROTOCOL_VERSION_MIN: u32 = 1;
+pub const MIGRATION_PROTOCOL_VERSION_MAX: u32 = 1;
+
+const SVSM_REQ_MIGRATION_EXPORT_CONTEXT: u32 = 0;
+const SVSM_REQ_MIGRATION_IMPORT_CONTEXT: u32 = 1;
+const SVSM_REQ_MIGRATION_ABSORB_CONTEXT: u32 = 2;
+
+/// Export the guest context for the import of an incoming migration image
+const MIGRATION_EXPORT_IMI_EN: u64 = 1 << 0;
+
+/// RCX: system physical address of the guest context page to export.
+/// RDX: guest physical address of the page receiving the guest context.
+/// R8: flags, see [`MIGRATION_EXPORT_IMI_EN`].
+fn migration_export_context(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let gpa = PhysAddr::from(params.rdx);
+    if params.r8 & !MIGRATION_EXPORT_IMI_EN != 0 || !gpa.is_page_aligned() {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+
+    let imi_en = params.r8 & MIGRATION_EXPORT_IMI_EN != 0;
+    let gctx = export_guest_context(PhysAddr::from(params.rcx), imi_en)?;
+    copy_slice_to_guest(gctx.as_bytes(), gpa)?;
+    Ok(())
+}
+
+/// RCX: system physical address of the guest context page of the receiving
+/// guest. RDX: guest physical address of the page holding the exported
+/// guest context.
+fn read_guest_context(params: &RequestParams) -> Result<(PhysAddr, SnpGuestContext), SvsmReqError> {
+    let gpa = PhysAddr::from(params.rdx);
+    if !gpa.is_page_aligned() {
+        return Err(SvsmReqError::invalid_parameter());
+    }
+    let gctx =
+        read_from_guest::<SnpGuestContext>(gpa).map_err(|_| SvsmReqError::invalid_parameter())?;
+    Ok((PhysAddr::from(params.rcx), gctx))
+}
+
+fn migration_import_context(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let (gctx_paddr, gctx) = read_guest_context(params)?;
+    import_guest_context(gctx_paddr, &gctx)
+}
+
+fn migration_absorb_context(params: &RequestParams) -> Result<(), SvsmReqError> {
+    let (gctx_paddr, gctx) = read_guest_context(params)?;
+    absorb_guest_context(gctx_paddr, &gctx)
+}
+
+pub fn migration_protocol_request(
+    request: u32,
+    params: &mut RequestParams,
+) -> Result<(), SvsmReqError> {
+    match request {
+        SVSM_REQ_MIGRATION_EXPORT_CONTEXT => migration_export_context(params),
+        SVSM_REQ_MIGRATION_IMPORT_CONTEXT => migration_import_context(params),
+        SVSM_REQ_MIGRATION_ABSORB_CONTEXT => migration_absorb_context(params),
+        _ => Err(SvsmReqError::unsupported_call()),
+    }
+}
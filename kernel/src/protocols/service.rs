@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Forwarding of SVSM protocol requests to user-mode service processes.
+//!
+//! A user-mode process claims an SVSM protocol number by registering one
+//! endpoint of a [`Channel`] for it. Requests for that protocol are sent as a
+//! [`ServiceRequest`] message on the endpoint, followed by the contents of
+//! the guest buffer of the request when the service asked for it. The
+//! service answers with a [`ServiceReply`] message with the identifier of the
+//! request, which carries the result code and the new register values for
+//! the guest. Only processes with the [`ProcessCaps::PROTOCOL_SERVICE`]
+//! capability can register services.
+//!
+//! [`ProcessCaps::PROTOCOL_SERVICE`]: syscall::ProcessCaps::PROTOCOL_SERVICE
+
+extern crate alloc;
+
+use super::errors::{SvsmReqError, SvsmResultCode};
+use super::RequestParams;
+use super::{
+    SVSM_APIC_PROTOCOL, SVSM_ATTEST_PROTOCOL, SVSM_CORE_PROTOCOL, SVSM_MIGRATION_PROTOCOL,
+    SVSM_VTPM_PROTOCOL,
+};
+use crate::address::PhysAddr;
+use crate::cpu::msr::rdtsc;
+use crate::cpu::x86::{apic_timer_counts_to_tsc, apic_timer_frequency};
+use crate::error::SvsmError;
+use crate::fs::{SliceMutRefBuffer, SliceRefBuffer};
+use crate::ipc::{Channel, ChannelError};
+use crate::locking::RWLock;
+use crate::mm::guestmem::{copy_slice_to_guest, read_bytes_from_guest};
+use crate::syscall::{Obj, ObjError};
+use crate::task::schedule;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use syscall::{ProtocolFlags, ServiceReply, ServiceRequest, CHANNEL_MSG_MAX, SERVICE_BUFFER_MAX};
+use zerocopy::{FromBytes, IntoBytes};
+
+/// A user-mode service handling an SVSM protocol.
+#[derive(Debug)]
+struct ProtocolService {
+    /// Kernel endpoint of the channel to the service
+    endpoint: Arc<dyn Obj>,
+    flags: ProtocolFlags,
+    /// Set while a request is forwarded to the service
+    busy: AtomicBool,
+    /// Identifier of the next request
+    next_id: AtomicU64,
+}
+
+impl ProtocolService {
+    fn channel(&self) -> &Channel {
+        self.endpoint.as_channel().unwrap()
+    }
+}
+
+/// Time a service gets to reply to a request
+const SERVICE_REPLY_TIMEOUT_SECONDS: u64 = 5;
+/// Maximum number of polls for a reply when the TSC is not calibrated and
+/// the timeout can not be measured
+const SERVICE_REPLY_MAX_POLLS: u64 = 1 << 24;
+
+static SERVICES: RWLock<BTreeMap<u32, Arc<ProtocolService>>> = RWLock::new(BTreeMap::new());
+
+/// Protocols handled by the kernel itself, which can not be claimed by a
+/// service.
+const KERNEL_PROTOCOLS: [u32; 5] = [
+    SVSM_CORE_PROTOCOL,
+    SVSM_ATTEST_PROTOCOL,
+    SVSM_VTPM_PROTOCOL,
+    SVSM_APIC_PROTOCOL,
+    SVSM_MIGRATION_PROTOCOL,
+];
+
+/// Registers a user-mode service for an SVSM protocol. A protocol can be
+/// claimed again once the service which registered it has closed its
+/// channel endpoint.
+///
+/// # Arguments
+///
+/// * `protocol` - The SVSM protocol number to claim.
+/// * `endpoint` - Channel endpoint used to forward requests to the service.
+/// * `flags` - [`ProtocolFlags`] describing the requests of the protocol.
+///
+/// # Returns
+///
+/// `()` on success, [`SvsmError`] on failure. [`ObjError::Busy`] is
+/// returned when the protocol is already handled by the kernel or by
+/// another service.
+pub fn register_protocol_service(
+    protocol: u32,
+    endpoint: Arc<dyn Obj>,
+    flags: ProtocolFlags,
+) -> Result<(), SvsmError> {
+    if endpoint.as_channel().is_none() {
+        return Err(SvsmError::NotSupported);
+    }
+    if KERNEL_PROTOCOLS.contains(&protocol) {
+        return Err(ObjError::Busy.into());
+    }
+
+    let mut services = SERVICES.lock_write();
+    if services
+        .get(&protocol)
+        .is_some_and(|service| !service.channel().is_peer_closed())
+    {
+        return Err(ObjError::Busy.into());
+    }
+
+    let service = ProtocolService {
+        endpoint,
+        flags,
+        busy: AtomicBool::new(false),
+        next_id: AtomicU64::new(0),
+    };
+    services.insert(protocol, Arc::new(service));
+    log::info!("Registered user-mode service for SVSM protocol {protocol}");
+    Ok(())
+}
+
+/// Waits until the service replies to the request `id`, giving other tasks
+/// the CPU in between. Replies to earlier requests, which were abandoned
+/// because forwarding them failed or timed out, are discarded.
+///
+/// # Returns
+///
+/// The reply header and the data following it on success, [`SvsmError`] if
+/// receiving from the channel failed. The call fails with
+/// [`ChannelError::TimedOut`] when the service did not reply within
+/// [`SERVICE_REPLY_TIMEOUT_SECONDS`].
+fn service_wait_reply(channel: &Channel, id: u64) -> Result<(ServiceReply, Vec<u8>), SvsmError> {
+    let timeout = apic_timer_counts_to_tsc(apic_timer_frequency() * SERVICE_REPLY_TIMEOUT_SECONDS);
+    let start = rdtsc();
+    let mut polls = 0u64;
+    let mut buf = vec![0u8; CHANNEL_MSG_MAX];
+    loop {
+        let Some(len) = channel.try_recv(&mut SliceMutRefBuffer::new(&mut buf))? else {
+            polls += 1;
+            let timed_out = match timeout {
+                Some(ticks) => rdtsc().wrapping_sub(start) > ticks,
+                None => polls > SERVICE_REPLY_MAX_POLLS,
+            };
+            if timed_out {
+                return Err(ChannelError::TimedOut.into());
+            }
+            schedule();
+            continue;
+        };
+        let Ok((header, data)) = ServiceReply::read_from_prefix(&buf[..len]) else {
+            log::warn!("Discarding malformed protocol service reply");
+            continue;
+        };
+        if header.id != id {
+            log::warn!("Discarding stale protocol service reply {}", header.id);
+            continue;
+        }
+        return Ok((header, data.to_vec()));
+    }
+}
+
+fn service_call(
+    service: &ProtocolService,
+    protocol: u32,
+    request: u32,
+    params: &mut RequestParams,
+) -> Result<(), SvsmReqError> {
+    let guest_buffer = service.flags.contains(ProtocolFlags::GUEST_BUFFER);
+    let buffer = if guest_buffer {
+        let size = usize::try_from(params.rdx).map_err(|_| SvsmReqError::invalid_parameter())?;
+        if size > SERVICE_BUFFER_MAX {
+            return Err(SvsmReqError::invalid_parameter());
+        }
+        read_bytes_from_guest(PhysAddr::from(params.rcx), size)
+            .map_err(|_| SvsmReqError::invalid_address())?
+    } else {
+        vec![]
+    };
+
+    let id = service.next_id.fetch_add(1, Ordering::Relaxed);
+    let header = ServiceRequest {
+        id,
+        protocol,
+        request,
+        rcx: params.rcx,
+        rdx: params.rdx,
+        r8: params.r8,
+        buffer_size: buffer.len() as u64,
+    };
+    let mut msg = vec![];
+    msg.extend_from_slice(header.as_bytes());
+    msg.extend_from_slice(&buffer);
+
+    let channel = service.channel();
+    let (header, data) = channel
+        .send(&SliceRefBuffer::new(&msg))
+        .and_then(|_| service_wait_reply(channel, id))
+        .map_err(|e| {
+            log::error!("Failed to forward SVSM protocol {protocol} request {request}: {e:?}");
+            match e {
+                SvsmError::Channel(ChannelError::PeerClosed) => {
+                    SvsmReqError::unsupported_protocol()
+                }
+                _ => SvsmReqError::busy(),
+            }
+        })?;
+
+    let data = data
+        .get(..header.buffer_size as usize)
+        .ok_or(SvsmReqError::invalid_request())?;
+    if !data.is_empty() {
+        if !guest_buffer || data.len() > buffer.len() {
+            return Err(SvsmReqError::invalid_request());
+        }
+        copy_slice_to_guest(data, PhysAddr::from(params.rcx))
+            .map_err(|_| SvsmReqError::invalid_address())?;
+    }
+
+    params.rcx = header.rcx;
+    params.rdx = header.rdx;
+    params.r8 = header.r8;
+
+    match SvsmResultCode::from(header.result) {
+        SvsmResultCode::SUCCESS => Ok(()),
+        code => Err(SvsmReqError::RequestError(code)),
+    }
+}
+
+/// Forwards an SVSM protocol request to the user-mode service registered
+/// for the protocol and waits for its reply.
+///
+/// # Returns
+///
+/// `Ok(())` when the service completed the request successfully, or the
+/// [`SvsmReqError`] to return to the guest. Requests for protocols without a
+/// registered service fail with an unsupported-protocol error, and requests
+/// arriving while the service is busy with a request from another CPU, or
+/// which the service does not answer in time, fail with a busy error.
+pub fn service_protocol_request(
+    protocol: u32,
+    request: u32,
+    params: &mut RequestParams,
+) -> Result<(), SvsmReqError> {
+    let service = SERVICES
+        .lock_read()
+        .get(&protocol)
+        .cloned()
+        .ok_or(SvsmReqError::unsupported_protocol())?;
+
+    if service.busy.swap(true, Ordering::Acquire) {
+        return Err(SvsmReqError::busy());
+    }
+    let result = service_call(&service, protocol, request, params);
+    service.busy.store(false, Ordering::Release);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_protocol_service() {
+        let (a, b) = Channel::new_pair();
+        let endpoint: Arc<dyn Obj> = Arc::new(a);
+
+        assert!(matches!(
+            register_protocol_service(SVSM_CORE_PROTOCOL, endpoint.clone(), ProtocolFlags::empty()),
+            Err(SvsmError::Obj(ObjError::Busy))
+        ));
+
+        let protocol = 0x1000;
+        register_protocol_service(protocol, endpoint.clone(), ProtocolFlags::empty()).unwrap();
+        assert!(matches!(
+            register_protocol_service(protocol, endpoint.clone(), ProtocolFlags::empty()),
+            Err(SvsmError::Obj(ObjError::Busy))
+        ));
+
+        // The protocol is released when the service closes its endpoint
+        drop(b);
+        register_protocol_service(protocol, endpoint, ProtocolFlags::empty()).unwrap();
+    }
+}
@@ -16,7 +16,7 @@ use crate::{
     mm::guestmem::{copy_slice_to_guest, read_bytes_from_guest, read_from_guest},
     protocols::{errors::SvsmReqError, RequestParams},
     types::PAGE_SIZE,
-    vtpm::{vtpm_get_locked, TcgTpmSimulatorInterface, VtpmProtocolInterface},
+    vtpm::{vtpm_get_locked, vtpm_sync_nv, TcgTpmSimulatorInterface, VtpmProtocolInterface},
 };
 
 /// vTPM platform commands (SVSM spec, section 8.1 - SVSM_VTPM_QUERY)
@@ -114,6 +114,10 @@ impl TpmSendCommandRequest {
 
         let vtpm = vtpm_get_locked();
         let response = vtpm.send_tpm_command(tpm_cmd, self.locality)?;
+        // The guest must not rely on the effects of a command which could
+        // not be made persistent, so a failure to store the NV state fails
+        // the request.
+        vtpm_sync_nv(&vtpm)?;
 
         Ok(response)
     }
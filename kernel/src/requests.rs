@@ -11,7 +11,11 @@ use crate::cpu::percpu::{this_cpu, PERCPU_AREAS};
 use crate::protocols::apic::apic_protocol_request;
 use crate::protocols::core::core_protocol_request;
 use crate::protocols::errors::{SvsmReqError, SvsmResultCode};
-use crate::task::{go_idle, set_affinity, start_kernel_task};
+use crate::protocols::migration::migration_protocol_request;
+use crate::protocols::service::service_protocol_request;
+use crate::task::{
+    current_task, go_idle, preempt_point, set_affinity, start_kernel_task, TaskPriority,
+};
 use crate::vmm::{enter_guest, GuestExitMessage, GuestRegister};
 
 use crate::protocols::attest::attest_protocol_request;
@@ -19,6 +23,7 @@ use crate::protocols::attest::attest_protocol_request;
 use crate::protocols::{vtpm::vtpm_protocol_request, SVSM_VTPM_PROTOCOL};
 use crate::protocols::{
     RequestParams, SVSM_APIC_PROTOCOL, SVSM_ATTEST_PROTOCOL, SVSM_CORE_PROTOCOL,
+    SVSM_MIGRATION_PROTOCOL,
 };
 
 use alloc::format;
@@ -59,6 +64,15 @@ impl SvsmCaa {
         }
     }
 
+    /// Returns a copy of the this CAA with the `mem_available` flag updated
+    #[inline]
+    pub const fn update_mem_available(self, mem_available: u8) -> Self {
+        Self {
+            mem_available,
+            ..self
+        }
+    }
+
     /// A CAA with all of its fields set to zero.
     #[inline]
     pub const fn zeroed() -> Self {
@@ -84,13 +98,17 @@ fn request_loop_once(
         #[cfg(all(feature = "vtpm", not(test)))]
         SVSM_VTPM_PROTOCOL => vtpm_protocol_request(request, params),
         SVSM_APIC_PROTOCOL => apic_protocol_request(request, params),
-        _ => Err(SvsmReqError::unsupported_protocol()),
+        SVSM_MIGRATION_PROTOCOL => migration_protocol_request(request, params),
+        _ => service_protocol_request(protocol, request, params),
     }
 }
 
 pub extern "C" fn request_loop_main(cpu_index: usize) {
     log::info!("Launching request-processing task on CPU {}", cpu_index);
 
+    // Guest requests take precedence over all other work in the SVSM
+    current_task().set_priority(TaskPriority::High);
+
     if cpu_index != 0 {
         // Send this task to the correct CPU.
         set_affinity(cpu_index);
@@ -114,6 +132,9 @@ pub extern "C" fn request_loop_main(cpu_index: usize) {
     let mut guest_regs = Vec::<GuestRegister>::new();
 
     loop {
+        // Let other tasks run if this one used up its time slice.
+        preempt_point();
+
         // Attempt to enter the guest.  Once registers have been set, reset the
         // vector so they are not set again.
         let msg = enter_guest(guest_regs.as_slice());
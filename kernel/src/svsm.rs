@@ -28,13 +28,15 @@ use svsm::cpu::shadow_stack::{
 };
 use svsm::cpu::smp::start_secondary_cpus;
 use svsm::cpu::sse::sse_init;
+use svsm::debug::crashdump::{crash_dump_init, write_crash_dump};
 use svsm::debug::gdbstub::svsm_gdbstub::{debug_break, gdbstub_start};
 use svsm::debug::stacktrace::print_stack;
 use svsm::enable_shadow_stacks;
-use svsm::fs::{initialize_fs, opendir, populate_ram_fs};
+use svsm::fs::{initialize_fs, mount_fs_archive, mount_proc_fs, opendir};
 use svsm::hyperv::hyperv_setup;
 use svsm::igvm_params::IgvmParams;
 use svsm::kernel_region::new_kernel_region;
+use svsm::log_buffer::init_log_buffer;
 use svsm::mm::alloc::{memory_info, print_memory_info, root_mem_init};
 use svsm::mm::memory::init_memory_map;
 use svsm::mm::pagetable::paging_init;
@@ -49,6 +51,9 @@ use svsm::task::schedule_init;
 use svsm::task::{exec_user, start_kernel_task};
 use svsm::types::PAGE_SIZE;
 use svsm::utils::{immut_after_init::ImmutAfterInitCell, zero_mem_region, MemoryRegion};
+#[cfg(feature = "virtio-drivers")]
+use svsm::virtio::pci_init;
+use svsm::vsock::vsock_init;
 #[cfg(all(feature = "vtpm", not(test)))]
 use svsm::vtpm::vtpm_init;
 
@@ -56,6 +61,7 @@ use svsm::mm::validate::{init_valid_bitmap_ptr, migrate_valid_bitmap};
 
 use alloc::string::String;
 use release::COCONUT_VERSION;
+use syscall::ProcessCaps;
 
 extern "C" {
     static bsp_stack: u8;
@@ -216,6 +222,7 @@ extern "C" fn svsm_start(li: &KernelLaunchInfo, vb_addr: usize) -> ! {
         .expect("Early environment setup failed");
 
     memory_init(&launch_info);
+    init_log_buffer();
     migrate_valid_bitmap().expect("Failed to migrate valid-bitmap");
 
     let kernel_elf_len = (launch_info.kernel_elf_stage2_virt_end
@@ -264,6 +271,7 @@ extern "C" fn svsm_start(li: &KernelLaunchInfo, vb_addr: usize) -> ! {
     }
 
     initialize_fs();
+    mount_proc_fs().expect("Failed to mount /proc");
 
     // Idle task must be allocated after PerCPU data is mapped
     bsp_percpu
@@ -330,11 +338,20 @@ pub extern "C" fn svsm_main(cpu_index: usize) {
 
     init_memory_map(&config, &LAUNCH_INFO).expect("Failed to init guest memory map");
 
-    populate_ram_fs(LAUNCH_INFO.kernel_fs_start, LAUNCH_INFO.kernel_fs_end)
-        .expect("Failed to unpack FS archive");
+    mount_fs_archive(
+        LAUNCH_INFO.kernel_fs_start,
+        LAUNCH_INFO.kernel_fs_end,
+        config.fs_public_key(),
+        config.fs_min_version(),
+    )
+    .expect("Failed to mount FS archive");
 
     init_capabilities();
 
+    if let Err(e) = crash_dump_init() {
+        log::warn!("Failed to configure crash dumps: {e:?}");
+    }
+
     let cpus = config.load_cpu_info().expect("Failed to load ACPI tables");
 
     start_secondary_cpus(&**SVSM_PLATFORM, &cpus);
@@ -346,8 +363,17 @@ pub extern "C" fn svsm_main(cpu_index: usize) {
         panic!("Failed to prepare guest FW: {e:#?}");
     }
 
+    #[cfg(feature = "virtio-drivers")]
+    if let Err(e) = pci_init(config.svsm_pci_ecam()) {
+        log::warn!("PCI devices not available: {e:?}");
+    }
+
     #[cfg(all(feature = "vtpm", not(test)))]
-    vtpm_init().expect("vTPM failed to initialize");
+    vtpm_init(config.vtpm_nv_min_generation()).expect("vTPM failed to initialize");
+
+    if let Err(e) = vsock_init() {
+        log::info!("vsock not available: {e:?}");
+    }
 
     virt_log_usage();
 
@@ -363,7 +389,12 @@ pub extern "C" fn svsm_main(cpu_index: usize) {
         crate::test_main();
     }
 
-    match exec_user("/init", opendir("/").expect("Failed to find FS root")) {
+    match exec_user(
+        "/init",
+        opendir("/").expect("Failed to find FS root"),
+        &[],
+        ProcessCaps::all(),
+    ) {
         Ok(_) => (),
         Err(e) => log::info!("Failed to launch /init: {e:?}"),
     }
@@ -397,6 +428,8 @@ fn panic(info: &PanicInfo<'_>) -> ! {
 
     print_stack(3);
 
+    write_crash_dump(info);
+
     loop {
         debug_break();
         platform::halt();
@@ -150,14 +150,8 @@ pub fn invalidate_early_boot_memory(
         );
         invalidate_boot_memory_region(platform, config, kernel_elf_region)?;
 
-        let kernel_fs_size = launch_info.kernel_fs_end - launch_info.kernel_fs_start;
-        if kernel_fs_size > 0 {
-            let kernel_fs_region = MemoryRegion::new(
-                PhysAddr::new(launch_info.kernel_fs_start.try_into().unwrap()),
-                kernel_fs_size.try_into().unwrap(),
-            );
-            invalidate_boot_memory_region(platform, config, kernel_fs_region)?;
-        }
+        // The filesystem archive is not invalidated, as its files are served
+        // directly from the archive pages.
 
         if launch_info.stage2_igvm_params_size > 0 {
             let igvm_params_region = MemoryRegion::new(
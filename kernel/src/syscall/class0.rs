@@ -4,40 +4,192 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
-use super::obj::obj_close;
-use crate::address::VirtAddr;
+extern crate alloc;
+
+use super::obj::{obj_close, obj_get};
+use crate::address::{Address, VirtAddr};
 use crate::cpu::percpu::current_task;
 use crate::fs::find_dir;
 use crate::mm::guestmem::UserPtr;
-use crate::task::{current_task_terminated, exec_user, schedule};
+use crate::mm::vm::VMFileMappingFlags;
+use crate::mm::{mmap_user, mprotect_user, munmap_user, USER_MEM_END, USER_MEM_START};
+use crate::task::{current_task_exit, exec_user, schedule, start_user_thread};
+use crate::types::PAGE_SIZE;
+use crate::utils::is_aligned;
+use alloc::vec::Vec;
 use core::ffi::c_char;
-use syscall::SysCallError;
+use syscall::{MMapFlags, ProcessCaps, SysCallError, EXEC_HANDLES_MAX};
 
+/// Terminates the calling thread. The resources of the process are released
+/// when its last thread has terminated.
 pub fn sys_exit(exit_code: u32) -> ! {
     log::info!(
         "Terminating task {}, exit_code {exit_code}",
         current_task().get_task_name()
     );
-    current_task_terminated();
+    current_task_exit(exit_code);
     schedule();
     unreachable!("schedule() returned in sys_exit()");
 }
 
-pub fn sys_exec(file: usize, root: usize, _flags: usize) -> Result<u64, SysCallError> {
+pub fn sys_thread_create(entry: usize, stack: usize, arg: usize) -> Result<u64, SysCallError> {
+    let user_range = USER_MEM_START.bits()..USER_MEM_END.bits();
+    if !user_range.contains(&entry)
+        || !user_range.contains(&stack.wrapping_sub(1))
+        || !is_aligned(stack, 16)
+    {
+        return Err(SysCallError::EINVAL);
+    }
+
+    let task = start_user_thread(entry, stack, arg)?;
+    Ok(task.get_task_id().into())
+}
+
+pub fn sys_thread_join(tid: u32) -> Result<u64, SysCallError> {
+    let exit_code = current_task().join_thread(tid)?;
+    Ok(exit_code.into())
+}
+
+pub fn sys_thread_detach(tid: u32) -> Result<u64, SysCallError> {
+    current_task().detach_thread(tid)?;
+    Ok(0)
+}
+
+/// Starts a new process. The objects referenced by the `nr_handles` object
+/// handles in the array at `handles` are passed to the new process, starting
+/// at object handle [`EXEC_HANDLE_BASE`](syscall::EXEC_HANDLE_BASE).
+pub fn sys_exec(
+    file: usize,
+    root: usize,
+    flags: usize,
+    handles: usize,
+    nr_handles: usize,
+) -> Result<u64, SysCallError> {
+    if nr_handles > EXEC_HANDLES_MAX {
+        return Err(SysCallError::EINVAL);
+    }
+    let caps = u32::try_from(flags)
+        .ok()
+        .and_then(ProcessCaps::from_bits)
+        .ok_or(SysCallError::EINVAL)?;
+
     let user_file_ptr = UserPtr::<c_char>::new(VirtAddr::from(file));
     let user_root_ptr = UserPtr::<c_char>::new(VirtAddr::from(root));
+    let user_handles_ptr = UserPtr::<u32>::new(VirtAddr::from(handles));
 
     let file_str = user_file_ptr.read_c_string()?;
     let root_str = user_root_ptr.read_c_string()?;
+    let objs = (0..nr_handles)
+        .map(|i| obj_get(user_handles_ptr.offset(i as isize).read()?.into()))
+        .collect::<Result<Vec<_>, _>>()?;
     let real_root = find_dir(current_task().rootdir(), &root_str)?;
-    let tid = exec_user(&file_str, real_root)?;
+    let tid = exec_user(&file_str, real_root, &objs, caps)?;
 
     Ok(tid.into())
 }
 
+/// Waits for the termination of a child process and returns its process
+/// ID. A `tid` of zero waits for any child. The exit code of the child is
+/// written to `status` unless it is zero.
+pub fn sys_wait(tid: u32, status: usize) -> Result<u64, SysCallError> {
+    let pid = (tid != 0).then_some(tid);
+    let pid = current_task().wait_child(pid, |_, exit_code| {
+        if status != 0 {
+            UserPtr::<u32>::new(VirtAddr::from(status)).write(exit_code)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(pid.into())
+}
+
 pub fn sys_close(obj_id: u32) -> Result<u64, SysCallError> {
     // According to syscall ABI/API spec, close always returns 0 even
     // if called with an invalid handle
     let _ = obj_close(obj_id.into());
     Ok(0)
 }
+
+/// Translates the flags passed to the MMap and MProtect system calls into
+/// the flags used by the virtual memory mappings. Mapped pages are always
+/// readable, as x86 page-tables have no way to express write-only or
+/// execute-only pages.
+fn mmap_vm_flags(flags: MMapFlags) -> VMFileMappingFlags {
+    let mut vm_flags = VMFileMappingFlags::Read;
+
+    if flags.contains(MMapFlags::WRITE) {
+        vm_flags |= VMFileMappingFlags::Write;
+    }
+    if flags.contains(MMapFlags::EXEC) {
+        vm_flags |= VMFileMappingFlags::Execute;
+    }
+    if flags.contains(MMapFlags::PRIVATE) {
+        vm_flags |= VMFileMappingFlags::Private;
+    }
+    if flags.contains(MMapFlags::FIXED) {
+        vm_flags |= VMFileMappingFlags::Fixed;
+    }
+
+    vm_flags
+}
+
+pub fn sys_mmap(
+    obj_id: u32,
+    offset: usize,
+    addr: usize,
+    size: usize,
+    flags: usize,
+) -> Result<u64, SysCallError> {
+    let mmap_flags = MMapFlags::from_bits(flags).ok_or(SysCallError::EINVAL)?;
+    if size == 0
+        || !is_aligned(addr, PAGE_SIZE)
+        || !is_aligned(size, PAGE_SIZE)
+        || !is_aligned(offset, PAGE_SIZE)
+    {
+        return Err(SysCallError::EINVAL);
+    }
+
+    let vm_flags = mmap_vm_flags(mmap_flags);
+    let vaddr = VirtAddr::from(addr);
+
+    let start = if mmap_flags.contains(MMapFlags::ANONYMOUS) {
+        mmap_user(vaddr, None, 0, size, vm_flags)?
+    } else {
+        let obj = obj_get(obj_id.into())?;
+        let fh = obj.as_fs().ok_or(SysCallError::ENOTSUPP)?.file_handle()?;
+
+        if !fh.readable() {
+            return Err(SysCallError::EWRONLY);
+        }
+        // Writes to shared file pages end up in the file
+        if mmap_flags.contains(MMapFlags::WRITE)
+            && !mmap_flags.contains(MMapFlags::PRIVATE)
+            && !fh.writable()
+        {
+            return Err(SysCallError::ERDONLY);
+        }
+
+        mmap_user(vaddr, Some(fh), offset, size, vm_flags)?
+    };
+
+    Ok(start.bits() as u64)
+}
+
+pub fn sys_munmap(addr: usize) -> Result<u64, SysCallError> {
+    if !is_aligned(addr, PAGE_SIZE) {
+        return Err(SysCallError::EINVAL);
+    }
+
+    munmap_user(VirtAddr::from(addr))?;
+    Ok(0)
+}
+
+pub fn sys_mprotect(addr: usize, flags: usize) -> Result<u64, SysCallError> {
+    let mmap_flags = MMapFlags::from_bits(flags).ok_or(SysCallError::EINVAL)?;
+    if !MMapFlags::PROT_MASK.contains(mmap_flags) || !is_aligned(addr, PAGE_SIZE) {
+        return Err(SysCallError::EINVAL);
+    }
+
+    mprotect_user(VirtAddr::from(addr), mmap_vm_flags(mmap_flags))?;
+    Ok(0)
+}
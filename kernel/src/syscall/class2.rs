@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+extern crate alloc;
+
+use super::obj::{obj_add, obj_close, obj_get};
+use crate::address::VirtAddr;
+use crate::fs::UserBuffer;
+use crate::ipc::Channel;
+use crate::mm::guestmem::UserPtr;
+use crate::protocols::service::register_protocol_service;
+use crate::task::current_task;
+use crate::vsock::VsockStream;
+use alloc::sync::Arc;
+use syscall::SysCallError::{self, ENOTSUPP};
+use syscall::{ProcessCaps, ProtocolFlags};
+
+/// Creates a new channel and writes the object handles of its two
+/// endpoints to the `[u32; 2]` array at `handles`.
+pub fn sys_channel_create(handles: usize) -> Result<u64, SysCallError> {
+    let (a, b) = Channel::new_pair();
+    let id_a = obj_add(Arc::new(a))?;
+    let id_b = match obj_add(Arc::new(b)) {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = obj_close(id_a);
+            return Err(e.into());
+        }
+    };
+
+    let ids = [u32::from(id_a), u32::from(id_b)];
+    if let Err(e) = UserPtr::<[u32; 2]>::new(VirtAddr::from(handles)).write(ids) {
+        let _ = obj_close(id_a);
+        let _ = obj_close(id_b);
+        return Err(e.into());
+    }
+
+    Ok(0)
+}
+
+pub fn sys_channel_send(obj_id: u32, user_addr: usize, bytes: usize) -> Result<u64, SysCallError> {
+    let obj = obj_get(obj_id.into())?;
+    let channel = obj.as_channel().ok_or(ENOTSUPP)?;
+
+    let buffer = UserBuffer::new(VirtAddr::from(user_addr), bytes);
+    channel.send(&buffer)?;
+    Ok(0)
+}
+
+pub fn sys_channel_recv(obj_id: u32, user_addr: usize, bytes: usize) -> Result<u64, SysCallError> {
+    let obj = obj_get(obj_id.into())?;
+    let channel = obj.as_channel().ok_or(ENOTSUPP)?;
+
+    let mut buffer = UserBuffer::new(VirtAddr::from(user_addr), bytes);
+    channel
+        .recv(&mut buffer)
+        .map(|b| b as u64)
+        .map_err(SysCallError::from)
+}
+
+/// Claims an SVSM protocol for the calling process. Guest requests for the
+/// protocol are forwarded on the channel endpoint `obj_id`, whose peer
+/// endpoint is used by the process to receive them. Requires the
+/// [`ProcessCaps::PROTOCOL_SERVICE`] capability.
+pub fn sys_protocol_register(
+    protocol: u32,
+    obj_id: u32,
+    flags: usize,
+) -> Result<u64, SysCallError> {
+    if !current_task()
+        .caps()
+        .contains(ProcessCaps::PROTOCOL_SERVICE)
+    {
+        return Err(SysCallError::EPERM);
+    }
+    let flags = ProtocolFlags::from_bits(flags).ok_or(SysCallError::EINVAL)?;
+    let obj = obj_get(obj_id.into())?;
+    register_protocol_service(protocol, obj, flags)?;
+    Ok(0)
+}
+
+/// Connects a stream socket to port `port` of the VM or host with context
+/// ID `cid` and returns the object handle of the socket.
+pub fn sys_vsock_connect(cid: u64, port: u32) -> Result<u64, SysCallError> {
+    let stream = VsockStream::connect(cid, port)?;
+    let id = obj_add(Arc::new(stream))?;
+    Ok(u32::from(id).into())
+}
+
+pub fn sys_vsock_send(obj_id: u32, user_addr: usize, bytes: usize) -> Result<u64, SysCallError> {
+    let obj = obj_get(obj_id.into())?;
+    let stream = obj.as_vsock().ok_or(ENOTSUPP)?;
+
+    let buffer = UserBuffer::new(VirtAddr::from(user_addr), bytes);
+    stream
+        .send_buffer(&buffer)
+        .map(|b| b as u64)
+        .map_err(SysCallError::from)
+}
+
+pub fn sys_vsock_recv(obj_id: u32, user_addr: usize, bytes: usize) -> Result<u64, SysCallError> {
+    let obj = obj_get(obj_id.into())?;
+    let stream = obj.as_vsock().ok_or(ENOTSUPP)?;
+
+    let mut buffer = UserBuffer::new(VirtAddr::from(user_addr), bytes);
+    stream
+        .recv_buffer(&mut buffer)
+        .map(|b| b as u64)
+        .map_err(SysCallError::from)
+}
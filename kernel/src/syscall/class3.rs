@@ -4,9 +4,20 @@
 //
 // Author: Chuanxiao Dong <chuanxiao.dong@intel.com>
 
+extern crate alloc;
+
+use crate::address::VirtAddr;
+use crate::migration::{migration_export, migration_import, migration_nonce};
+use crate::mm::{copy_from_user, copy_to_user};
 use crate::platform::capabilities::Cap;
 use crate::platform::CAPS;
-use syscall::SysCallError;
+use crate::task::current_task;
+use crate::tdx::rtmr::{rtmr_measure, EV_NO_ACTION};
+use alloc::vec;
+use syscall::{
+    ProcessCaps, SysCallError, MIGRATION_IMAGE_MAX, MIGRATION_NONCE_SIZE, RTMR_COUNT,
+    RTMR_EVENT_DATA_MAX,
+};
 
 pub fn sys_capabilities(index: u32) -> Result<u64, SysCallError> {
     let cap = match index {
@@ -16,3 +27,76 @@ pub fn sys_capabilities(index: u32) -> Result<u64, SysCallError> {
     };
     Ok(CAPS.get(cap))
 }
+
+/// Measures the `len` bytes of event data at `data` into runtime
+/// measurement register `index` and records the event in the RTMR event
+/// log. Requires the [`ProcessCaps::RTMR_EXTEND`] capability.
+/// `EV_NO_ACTION` events are rejected, since verifiers do not replay them
+/// and the log would no longer match the RTMR.
+pub fn sys_rtmr_extend(
+    index: u32,
+    event_type: u32,
+    data: usize,
+    len: usize,
+) -> Result<u64, SysCallError> {
+    if !current_task().caps().contains(ProcessCaps::RTMR_EXTEND) {
+        return Err(SysCallError::EPERM);
+    }
+    if index as usize >= RTMR_COUNT || event_type == EV_NO_ACTION || len > RTMR_EVENT_DATA_MAX {
+        return Err(SysCallError::EINVAL);
+    }
+
+    let mut event_data = vec![0u8; len];
+    copy_from_user(VirtAddr::from(data), &mut event_data)?;
+    rtmr_measure(index as usize, event_type, &event_data)?;
+    Ok(0)
+}
+
+fn check_migration_cap() -> Result<(), SysCallError> {
+    if !current_task().caps().contains(ProcessCaps::MIGRATION) {
+        return Err(SysCallError::EPERM);
+    }
+    Ok(())
+}
+
+/// Starts the import of a migration image and writes the nonce of the
+/// migration session to `nonce`. Requires the [`ProcessCaps::MIGRATION`]
+/// capability.
+pub fn sys_migration_nonce(nonce: usize) -> Result<u64, SysCallError> {
+    check_migration_cap()?;
+    copy_to_user(&migration_nonce()?, VirtAddr::from(nonce))?;
+    Ok(0)
+}
+
+/// Exports the SVSM state into a migration image for the session with the
+/// nonce at `nonce`. The image is written to the buffer at `image` when it
+/// is large enough. Requires the [`ProcessCaps::MIGRATION`] capability.
+///
+/// # Returns
+///
+/// The size of the migration image.
+pub fn sys_migration_export(nonce: usize, image: usize, len: usize) -> Result<u64, SysCallError> {
+    check_migration_cap()?;
+    let mut session_nonce = [0u8; MIGRATION_NONCE_SIZE];
+    copy_from_user(VirtAddr::from(nonce), &mut session_nonce)?;
+
+    let data = migration_export(&session_nonce)?;
+    if data.len() <= len {
+        copy_to_user(&data, VirtAddr::from(image))?;
+    }
+    Ok(data.len() as u64)
+}
+
+/// Restores the SVSM state from the `len` bytes of the migration image at
+/// `image`. Requires the [`ProcessCaps::MIGRATION`] capability.
+pub fn sys_migration_import(image: usize, len: usize) -> Result<u64, SysCallError> {
+    check_migration_cap()?;
+    if len > MIGRATION_IMAGE_MAX {
+        return Err(SysCallError::EINVAL);
+    }
+
+    let mut data = vec![0u8; len];
+    copy_from_user(VirtAddr::from(image), &mut data)?;
+    migration_import(&data)?;
+    Ok(0)
+}
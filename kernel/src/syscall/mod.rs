@@ -6,10 +6,12 @@
 
 mod class0;
 mod class1;
+mod class2;
 mod class3;
 mod obj;
 
 pub use class0::*;
 pub use class1::*;
+pub use class2::*;
 pub use class3::*;
 pub use obj::{Obj, ObjError, ObjHandle};
@@ -11,11 +11,13 @@ use crate::error::SvsmError;
 use crate::fs::{open_read, Directory};
 use crate::mm::vm::VMFileMappingFlags;
 use crate::mm::USER_MEM_END;
-use crate::task::{create_user_task, current_task, finish_user_task, schedule};
+use crate::syscall::{Obj, ObjHandle};
+use crate::task::{create_user_task, current_task, finish_user_task, schedule, TaskModule};
 use crate::types::PAGE_SIZE;
 use crate::utils::align_up;
 use alloc::sync::Arc;
 use elf::{Elf64File, Elf64PhdrFlags};
+use syscall::{ProcessCaps, EXEC_HANDLE_BASE};
 
 use alloc::string::String;
 
@@ -48,15 +50,30 @@ fn task_name(binary: &str) -> String {
 /// # Arguments
 ///
 /// * binary: Path to file in the file-system
+/// * root: Root directory of the new process
+/// * objs: Objects passed to the new process, which are assigned object
+///   handles starting at [`EXEC_HANDLE_BASE`]
+/// * caps: Capabilities of the new process. A user process can only grant
+///   capabilities it has itself.
 ///
 /// # Returns
 ///
 /// [`Ok(tid)`] on success, [`Err(SvsmError)`] on failure.
-pub fn exec_user(binary: &str, root: Arc<dyn Directory>) -> Result<u32, SvsmError> {
+pub fn exec_user(
+    binary: &str,
+    root: Arc<dyn Directory>,
+    objs: &[Arc<dyn Obj>],
+    caps: ProcessCaps,
+) -> Result<u32, SvsmError> {
     let fh = open_read(binary)?;
     let file_size = fh.size();
 
     let current_task = current_task();
+    let caps = if current_task.is_user_task() {
+        caps & current_task.caps()
+    } else {
+        caps
+    };
     let vstart = current_task.mmap_kernel_guard(
         VirtAddr::new(0),
         Some(&fh),
@@ -73,7 +90,11 @@ pub fn exec_user(binary: &str, root: Arc<dyn Directory>) -> Result<u32, SvsmErro
     let virt_base = alloc_info.range.vaddr_begin;
     let entry = elf_bin.get_entry(virt_base);
 
-    let new_task = create_user_task(entry.try_into().unwrap(), root, task_name(binary))?;
+    let new_task = create_user_task(entry.try_into().unwrap(), root, task_name(binary), caps)?;
+    new_task.add_module(TaskModule {
+        path: String::from(binary),
+        load_bias: entry.wrapping_sub(elf_bin.elf_hdr.e_entry) as usize,
+    });
 
     for seg in elf_bin.image_load_segment_iter(virt_base) {
         let virt_start = VirtAddr::from(seg.vaddr_range.vaddr_begin);
@@ -114,6 +135,12 @@ pub fn exec_user(binary: &str, root: Arc<dyn Directory>) -> Result<u32, SvsmErro
     let stack_addr = USER_MEM_END - user_stack_size;
     new_task.mmap_user(stack_addr, None, 0, user_stack_size, stack_flags)?;
 
+    for (handle, obj) in (EXEC_HANDLE_BASE..).zip(objs) {
+        new_task.add_obj_at(obj.clone(), ObjHandle::new(handle))?;
+    }
+
+    // The calling process can now wait for the termination of the new one
+    new_task.register_child();
     finish_user_task(new_task.clone());
     schedule();
 
@@ -10,14 +10,15 @@ mod tasks;
 mod waiting;
 
 pub use schedule::{
-    create_user_task, current_task, current_task_terminated, finish_user_task, go_idle,
-    is_current_task, schedule, schedule_init, schedule_task, set_affinity, start_kernel_task,
-    terminate, RunQueue, TASKLIST,
+    create_user_task, current_task, current_task_exit, current_task_terminated, finish_user_task,
+    go_idle, is_current_task, preempt_point, schedule, schedule_init, schedule_task, set_affinity,
+    start_kernel_task, start_user_thread, terminate, wakeup_task, RunQueue, SchedTimer, TASKLIST,
 };
 
 pub use tasks::{
-    is_task_fault, Task, TaskContext, TaskError, TaskListAdapter, TaskPointer, TaskRunListAdapter,
-    TaskState, INITIAL_TASK_ID, TASK_FLAG_SHARE_PT,
+    is_task_fault, ChildProcesses, Task, TaskContext, TaskError, TaskExitStatus, TaskListAdapter,
+    TaskModule, TaskPointer, TaskPriority, TaskRunListAdapter, TaskState, DEFAULT_TIME_SLICE,
+    EXIT_CODE_KILLED, INITIAL_TASK_ID, TASK_FLAG_SHARE_PT,
 };
 
 pub use exec::exec_user;
@@ -6,23 +6,30 @@
 
 //! Round-Robin scheduler implementation for COCONUT-SVSM
 //!
-//! This module implements a round-robin scheduler for cooperative multi-tasking.
-//! It works by assigning a single owner for each struct [`Task`]. The owner
-//! depends on the state of the task:
+//! This module implements a priority-based round-robin scheduler. It works by
+//! assigning a single owner for each struct [`Task`]. The owner depends on the
+//! state of the task:
 //!
 //! * [`RUNNING`] A task in running state is owned by the [`RunQueue`] and either
-//!   stored in the `run_list` (when the task is not actively running) or in
+//!   stored in the `run_lists` (when the task is not actively running) or in
 //!   `current_task` when it is scheduled on the CPU.
 //! * [`BLOCKED`] A task in this state is waiting for an event to become runnable
 //!   again. It is owned by a wait object when in this state.
 //! * [`TERMINATED`] The task is about to be destroyed and owned by the [`RunQueue`].
 //!
-//! The scheduler is cooperative. A task runs until it voluntarily calls the
-//! [`schedule()`] function.
+//! A task runs until it voluntarily calls the [`schedule()`] function, or until
+//! it is preempted. Runnable tasks are picked by their [`TaskPriority`], tasks
+//! of the same priority share the CPU round-robin. When the platform uses
+//! interrupts, a per-CPU APIC timer accounts the time slices of the running
+//! tasks. A task is preempted when its time slice has expired or a task of a
+//! higher priority became runnable, but only at safe points: before returning
+//! to user mode, or when kernel code calls [`preempt_point()`].
 //!
 //! Only when a task is in [`RUNNING`] or [`TERMINATED`] state it is assigned to a
 //! specific CPU. Tasks in the [`BLOCKED`] state have no CPU assigned and will run
 //! on the CPU where their event is triggered that makes them [`RUNNING`] again.
+//! Events triggered by other tasks use [`wakeup_task()`], which waits until the
+//! woken task has completely switched out on its previous CPU.
 //!
 //! [`RUNNING`]: super::tasks::TaskState::RUNNING
 //! [`BLOCKED`]: super::tasks::TaskState::BLOCKED
@@ -30,15 +37,21 @@
 
 extern crate alloc;
 
-use super::INITIAL_TASK_ID;
-use super::{Task, TaskListAdapter, TaskPointer, TaskRunListAdapter};
+use super::{Task, TaskListAdapter, TaskPointer, TaskPriority, TaskRunListAdapter};
+use super::{EXIT_CODE_KILLED, INITIAL_TASK_ID};
 use crate::address::{Address, VirtAddr};
+use crate::cpu::debug_regs::sync_debug_regs;
+use crate::cpu::idt::common::TIMER_VECTOR;
 use crate::cpu::ipi::{send_multicast_ipi, IpiMessage, IpiTarget};
-use crate::cpu::irq_state::raw_get_tpr;
-use crate::cpu::msr::write_msr;
+use crate::cpu::irq_state::{raw_get_tpr, raw_irqs_disable, raw_irqs_enable};
+use crate::cpu::irqs_disabled;
+use crate::cpu::msr::{rdtsc, write_msr};
 use crate::cpu::percpu::{irq_nesting_count, this_cpu};
 use crate::cpu::shadow_stack::{is_cet_ss_supported, IS_CET_SUPPORTED, PL0_SSP};
 use crate::cpu::sse::{sse_restore_context, sse_save_context};
+use crate::cpu::x86::{
+    apic_timer_counts_to_tsc, apic_timer_frequency, apic_timer_start, tsc_to_apic_timer_counts,
+};
 use crate::cpu::IrqGuard;
 use crate::error::SvsmError;
 use crate::fs::Directory;
@@ -48,16 +61,39 @@ use crate::platform::SVSM_PLATFORM;
 use alloc::string::String;
 use alloc::sync::Arc;
 use core::arch::{asm, global_asm};
+use core::cell::Cell;
 use core::mem::offset_of;
 use core::ptr::null_mut;
+use core::sync::atomic::{AtomicU32, Ordering};
 use intrusive_collections::LinkedList;
+use syscall::ProcessCaps;
+
+/// Number of scheduler timer ticks per second, a tick every 10ms.
+const SCHED_TICKS_PER_SECOND: u64 = 100;
+
+/// Interval of the scheduler timer in APIC timer counts, 0 until it has been
+/// computed by [`sched_timer_interval()`].
+static SCHED_TIMER_INTERVAL: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the interval of the scheduler timer in APIC timer counts.
+fn sched_timer_interval() -> u32 {
+    match SCHED_TIMER_INTERVAL.load(Ordering::Relaxed) {
+        0 => {
+            let counts = apic_timer_frequency() / SCHED_TICKS_PER_SECOND;
+            let interval = u32::try_from(counts).unwrap_or(u32::MAX).max(1);
+            SCHED_TIMER_INTERVAL.store(interval, Ordering::Relaxed);
+            interval
+        }
+        interval => interval,
+    }
+}
 
-/// A RunQueue implementation that uses an RBTree to efficiently sort the priority
-/// of tasks within the queue.
+/// A RunQueue implementation that keeps one list of runnable tasks per
+/// [`TaskPriority`].
 #[derive(Debug, Default)]
 pub struct RunQueue {
-    /// Linked list with runable tasks
-    run_list: LinkedList<TaskRunListAdapter>,
+    /// Linked lists with runable tasks, indexed by task priority
+    run_lists: [LinkedList<TaskRunListAdapter>; TaskPriority::COUNT],
 
     /// Pointer to currently running task
     current_task: Option<TaskPointer>,
@@ -68,12 +104,20 @@ pub struct RunQueue {
     /// Temporary storage for tasks which are about to be terminated
     terminated_task: Option<TaskPointer>,
 
+    /// Task which was switched out by the last task switch and is not yet
+    /// marked as being off the CPU
+    prev_task: Option<TaskPointer>,
+
     /// Pointer to a task that should be woken when returning from idle
     wake_from_idle: Option<TaskPointer>,
 
     /// Pointer to a task that is requesting an affinity change to another
     /// processor, along with the CPU index describing the new affinity..
     set_affinity: Option<(TaskPointer, usize)>,
+
+    /// Whether the current task should be switched out at the next safe
+    /// point
+    need_resched: bool,
 }
 
 impl RunQueue {
@@ -82,17 +126,20 @@ impl RunQueue {
     /// determine the affinity of tasks.
     pub fn new() -> Self {
         Self {
-            run_list: LinkedList::new(TaskRunListAdapter::new()),
+            run_lists: core::array::from_fn(|_| LinkedList::new(TaskRunListAdapter::new())),
             current_task: None,
             idle_task: None,
             terminated_task: None,
+            prev_task: None,
             wake_from_idle: None,
             set_affinity: None,
+            need_resched: false,
         }
     }
 
     /// Find the next task to run, which is either the task at the front of the
-    /// run_list or the idle task, if the run_list is empty.
+    /// run list with the highest priority or the idle task, if all run lists
+    /// are empty.
     ///
     /// # Returns
     ///
@@ -103,18 +150,39 @@ impl RunQueue {
     /// Panics if there are no tasks to run and no idle task has been
     /// allocated via [`set_idle_task()`](Self::set_idle_task).
     fn get_next_task(&mut self) -> TaskPointer {
-        self.run_list
-            .pop_front()
+        self.run_lists
+            .iter_mut()
+            .rev()
+            .find_map(|list| list.pop_front())
             .unwrap_or_else(|| self.idle_task.clone().unwrap())
     }
 
+    /// Index of the highest priority with runnable tasks.
+    ///
+    /// # Returns
+    ///
+    /// `None` when there are no runnable tasks.
+    fn highest_runnable_priority(&self) -> Option<usize> {
+        self.run_lists.iter().rposition(|list| !list.is_empty())
+    }
+
+    fn is_idle_task(&self, task: &TaskPointer) -> bool {
+        self.idle_task.as_ref() == Some(task)
+    }
+
     /// Update state before a task is scheduled out. Non-idle tasks in RUNNING
-    /// state will be put at the end of the run_list. Terminated tasks will be
-    /// stored in the terminated_task field of the RunQueue and be destroyed
-    /// after the task-switch.
+    /// state will be put at the end of the run list for their priority. When
+    /// such a task has a higher priority than the current task, a task switch
+    /// is requested. Terminated tasks will be stored in the terminated_task
+    /// field of the RunQueue and be destroyed after the task-switch.
     fn handle_task(&mut self, task: TaskPointer) {
         if task.is_running() && !task.is_idle_task() {
-            self.run_list.push_back(task);
+            if let Some(current) = self.current_task.as_ref() {
+                if self.is_idle_task(current) || task.priority() > current.priority() {
+                    self.need_resched = true;
+                }
+            }
+            self.run_lists[task.priority().index()].push_back(task);
         } else if task.is_terminated() {
             self.terminated_task = Some(task);
         }
@@ -129,6 +197,7 @@ impl RunQueue {
     /// [TaskPointer] to the first task to run
     pub fn schedule_init(&mut self) -> TaskPointer {
         let task = self.get_next_task();
+        task.set_on_cpu(true);
         self.current_task = Some(task.clone());
         task
     }
@@ -156,15 +225,52 @@ impl RunQueue {
         // Get next task and update current_task state
         let next = self.get_next_task();
         self.current_task = Some(next.clone());
+        self.need_resched = false;
+        next.reset_time_slice();
 
         // Check if task switch is needed
         if current != next {
+            next.set_on_cpu(true);
+            self.prev_task = Some(current.clone());
             Some((current, next))
         } else {
             None
         }
     }
 
+    /// Accounts a scheduler timer tick to the current task. A task switch is
+    /// requested when the time slice of the current task has expired and
+    /// another task with the same or a higher priority is runnable.
+    pub fn timer_tick(&mut self) {
+        let Some(current) = self.current_task.as_ref() else {
+            return;
+        };
+
+        // The idle task schedules on its own after every wakeup
+        if self.is_idle_task(current) || !current.tick_time_slice() {
+            return;
+        }
+
+        if self
+            .highest_runnable_priority()
+            .is_some_and(|prio| prio >= current.priority().index())
+        {
+            self.need_resched = true;
+        } else {
+            // Nothing else to run, start a new time slice right away
+            current.reset_time_slice();
+        }
+    }
+
+    /// Checks whether the current task should be switched out.
+    ///
+    /// # Returns
+    ///
+    /// `true` when a task switch was requested, `false` otherwise.
+    pub fn need_resched(&self) -> bool {
+        self.need_resched
+    }
+
     pub fn current_task_id(&self) -> u32 {
         self.current_task
             .as_ref()
@@ -285,6 +391,7 @@ pub fn start_kernel_task(
 /// # Arguments
 ///
 /// * user_entry: The user-space entry point.
+/// * caps: The capabilities of the new process.
 ///
 /// # Returns
 ///
@@ -293,9 +400,12 @@ pub fn create_user_task(
     user_entry: usize,
     root: Arc<dyn Directory>,
     name: String,
+    caps: ProcessCaps,
 ) -> Result<TaskPointer, SvsmError> {
     let cpu = this_cpu();
-    Task::create_user(cpu, user_entry, root, name)
+    let current = cpu.current_task();
+    let parent = current.is_user_task().then_some(current.as_ref());
+    Task::create_user(cpu, parent, user_entry, root, name, caps)
 }
 
 /// Finished user-space task creation by putting the task on the global
@@ -312,6 +422,37 @@ pub fn finish_user_task(task: TaskPointer) {
     this_cpu().runqueue().lock_write().handle_task(task);
 }
 
+/// Creates a new thread in the process of the current task and puts it on
+/// the run-queue of this CPU. The thread starts running the next time the
+/// scheduler selects it.
+///
+/// # Arguments
+///
+/// * user_entry: The user-space entry point of the thread.
+/// * user_stack: The top of the user-mode stack of the thread.
+/// * start_parameter: Value passed to the thread in the first argument
+///   register.
+///
+/// # Returns
+///
+/// A new instance of [`TaskPointer`] on success, [`SvsmError`] on failure.
+pub fn start_user_thread(
+    user_entry: usize,
+    user_stack: usize,
+    start_parameter: usize,
+) -> Result<TaskPointer, SvsmError> {
+    let cpu = this_cpu();
+    let task = Task::create_user_thread(
+        cpu,
+        &cpu.current_task(),
+        user_entry,
+        user_stack,
+        start_parameter,
+    )?;
+    finish_user_task(task.clone());
+    Ok(task)
+}
+
 pub fn current_task() -> TaskPointer {
     this_cpu().current_task()
 }
@@ -339,8 +480,28 @@ pub fn current_task_terminated() {
     TASKLIST.lock().terminate(task_node.clone());
 }
 
-pub fn terminate() {
+/// Records the exit code of the current task and terminates it. Tasks
+/// waiting for the termination of the current task or its process are put
+/// back on the run-queue.
+///
+/// # Panic
+///
+/// This function must only be called after scheduling is initialized, otherwise it will panic.
+pub fn current_task_exit(exit_code: u32) {
+    let task = current_task();
+    let waiters = [task.set_exit_code(exit_code), task.notify_parent(exit_code)];
+    drop(task);
+
+    for waiter in waiters.into_iter().flatten() {
+        wakeup_task(waiter);
+    }
     current_task_terminated();
+}
+
+/// Terminates the current task on behalf of the kernel, reporting
+/// [`EXIT_CODE_KILLED`] as its exit code.
+pub fn terminate() {
+    current_task_exit(EXIT_CODE_KILLED);
     schedule();
 }
 
@@ -418,6 +579,82 @@ unsafe fn switch_to(prev: *const Task, next: *const Task) {
     }
 }
 
+/// Per-CPU state of the scheduler timer. The timer can be made to fire
+/// earlier than its regular interval, e.g. when the emulated APIC timer of
+/// the guest expires before the next scheduler tick. Those early interrupts
+/// are only accounted as scheduler ticks when about a full interval has
+/// passed since the last tick.
+#[derive(Debug, Default)]
+pub struct SchedTimer {
+    /// TSC value of the last scheduler tick
+    last_tick: Cell<u64>,
+    /// TSC value at which the timer fires next
+    next_expiry: Cell<u64>,
+    /// Whether the timer runs with a shortened interval
+    early: Cell<bool>,
+}
+
+impl SchedTimer {
+    pub const fn new() -> Self {
+        Self {
+            last_tick: Cell::new(0),
+            next_expiry: Cell::new(0),
+            early: Cell::new(false),
+        }
+    }
+
+    /// Makes the timer fire no later than when the TSC reaches `deadline`.
+    /// Nothing happens when the TSC has not been calibrated against the
+    /// APIC timer, or when the platform does not use interrupts.
+    pub fn expire_by(&self, deadline: u64) {
+        let next_expiry = self.next_expiry.get();
+        if !SVSM_PLATFORM.use_interrupts() || (next_expiry != 0 && deadline >= next_expiry) {
+            return;
+        }
+        let Some(counts) = tsc_to_apic_timer_counts(deadline.saturating_sub(rdtsc())) else {
+            return;
+        };
+
+        let counts = counts.clamp(1, u64::from(sched_timer_interval()));
+        apic_timer_start(TIMER_VECTOR as u8, counts as u32);
+        self.next_expiry.set(deadline);
+        self.early.set(true);
+    }
+
+    /// Handles an interrupt of the timer and restores the regular interval
+    /// after an early interrupt.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the interrupt is to be accounted as a scheduler tick,
+    /// `false` otherwise.
+    pub fn handle_interrupt(&self) -> bool {
+        let Some(interval) = apic_timer_counts_to_tsc(sched_timer_interval().into()) else {
+            return true;
+        };
+
+        if self.early.replace(false) {
+            apic_timer_start(TIMER_VECTOR as u8, sched_timer_interval());
+        }
+        let now = rdtsc();
+        self.next_expiry.set(now.saturating_add(interval));
+
+        let elapsed = now.wrapping_sub(self.last_tick.get());
+        if elapsed < interval / 2 {
+            return false;
+        }
+        // Advance by whole intervals so that early interrupts don't shift
+        // the ticks, unless the timer was stopped for a longer time.
+        let last_tick = if elapsed < interval * 2 {
+            self.last_tick.get().wrapping_add(interval)
+        } else {
+            now
+        };
+        self.last_tick.set(last_tick);
+        true
+    }
+}
+
 /// Initializes the [RunQueue] on the current CPU. It will switch to the idle
 /// task and initialize the current_task field of the RunQueue. After this
 /// function has ran it is safe to call [`schedule()`] on the current CPU.
@@ -429,6 +666,12 @@ unsafe fn switch_to(prev: *const Task, next: *const Task) {
 /// calculation of task pointers can be incorrect.
 pub unsafe fn schedule_init() {
     let guard = IrqGuard::new();
+
+    // Start the timer which drives preemption on this CPU
+    if SVSM_PLATFORM.use_interrupts() {
+        apic_timer_start(TIMER_VECTOR as u8, sched_timer_interval());
+    }
+
     // SAFETY: The caller guarantees that there is no current task, and the
     // pointer obtained for the next task will always be correct, thus
     // providing a guarantee that the task switch will be safe.
@@ -503,6 +746,37 @@ pub fn schedule() {
     let _ = this_cpu().runqueue().lock_write().terminated_task.take();
 }
 
+/// Switches to another task if the scheduler requested to preempt the current
+/// task, either because its time slice expired or because a task with a higher
+/// priority became runnable. Long-running kernel code calls this function at
+/// points where a task switch is safe. Nothing happens when called with IRQs
+/// disabled or a raised TPR.
+pub fn preempt_point() {
+    if irqs_disabled() || irq_nesting_count() != 0 || raw_get_tpr() != 0 {
+        return;
+    }
+
+    if this_cpu().runqueue().lock_read().need_resched() {
+        schedule();
+    }
+}
+
+/// Called with IRQs disabled on every return to user mode, which is always a
+/// safe point for preemption.
+#[no_mangle]
+extern "C" fn preempt_user_return() {
+    if irq_nesting_count() != 0 {
+        return;
+    }
+
+    // User mode always runs with IRQs enabled, so they can be enabled while
+    // the task might be switched out. Switching tasks with IRQs disabled
+    // would leave them disabled for the next task.
+    raw_irqs_enable();
+    preempt_point();
+    raw_irqs_disable();
+}
+
 struct SetAffinityMessage {
     task: TaskPointer,
 }
@@ -522,13 +796,23 @@ unsafe impl IpiMessage for SetAffinityMessage {
 }
 
 pub fn after_task_switch() {
+    // Pick up breakpoint changes which could not be sent by IPI.
+    sync_debug_regs();
+
     // Determine whether any task is pending an affinity change.  This must be
     // done with the run queue locked, but the actual affinity change must
     // happen without holding the run queue lock.
     let mut runqueue = this_cpu().runqueue().lock_write();
     let set_affinity = runqueue.set_affinity.take();
+    let prev_task = runqueue.prev_task.take();
     drop(runqueue);
 
+    // The context of the previous task is saved, so it can now be woken up
+    // on other CPUs.
+    if let Some(task) = prev_task {
+        task.set_on_cpu(false);
+    }
+
     if let Some((task, cpu_index)) = set_affinity {
         // Send an IPI to the target processor indicating which task it should
         // take.
@@ -542,6 +826,37 @@ fn enqueue_task(task: TaskPointer) {
     this_cpu().runqueue().lock_write().handle_task(task);
 }
 
+/// Puts a task which was taken from a wait object back on the run-queue. A
+/// task blocked on another CPU is moved to this CPU once it has completely
+/// switched out there. On this CPU the task might still be the current task
+/// when it has not yet scheduled out after blocking. It is only marked as
+/// running in this case and will be put back on the run-queue by
+/// [`schedule()`].
+///
+/// # Arguments
+///
+/// * `task` - The blocked task to wake up.
+pub fn wakeup_task(task: TaskPointer) {
+    let cpu = this_cpu();
+    if task.cpu_index() != cpu.get_cpu_index() {
+        // The task is still on its way into schedule() or its context is
+        // being saved. It can not be made runnable here before that is done.
+        while task.is_on_cpu() {
+            core::hint::spin_loop();
+        }
+    }
+
+    let mut runqueue = cpu.runqueue().lock_write();
+    if task.is_running() {
+        return;
+    }
+
+    task.set_task_running();
+    if runqueue.current_task.as_ref() != Some(&task) {
+        runqueue.handle_task(task);
+    }
+}
+
 pub fn schedule_task(task: TaskPointer) {
     enqueue_task(task);
     schedule();
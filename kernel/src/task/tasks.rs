@@ -7,12 +7,14 @@
 extern crate alloc;
 
 use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::btree_set::BTreeSet;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt;
 use core::mem::size_of;
 use core::num::NonZeroUsize;
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 
 use crate::address::{Address, VirtAddr};
 use crate::cpu::idt::svsm::return_new_task;
@@ -40,8 +42,10 @@ use crate::types::{SVSM_USER_CS, SVSM_USER_DS};
 use crate::utils::bitmap_allocator::{BitmapAllocator, BitmapAllocator1024};
 use crate::utils::{is_aligned, MemoryRegion};
 use intrusive_collections::{intrusive_adapter, LinkedListAtomicLink};
+use syscall::ProcessCaps;
 
 use super::schedule::{after_task_switch, current_task_terminated, schedule};
+use super::waiting::WaitQueue;
 
 pub static KTASK_VADDR_BITMAP: SpinLock<BitmapAllocator1024> =
     SpinLock::new(BitmapAllocator1024::new_empty());
@@ -92,6 +96,14 @@ pub enum TaskError {
     NotTerminated,
     // A closed task could not be removed from the task list
     CloseFailed,
+    // The task is not a joinable thread of the current process
+    NotJoinable,
+    // Another thread is already waiting to join the task
+    JoinBusy,
+    // The process has no child process to wait for
+    NoChild,
+    // Another thread of the process is already waiting for a child
+    WaitBusy,
 }
 
 impl From<TaskError> for SvsmError {
@@ -102,6 +114,42 @@ impl From<TaskError> for SvsmError {
 
 pub const TASK_FLAG_SHARE_PT: u16 = 0x01;
 
+/// Default length of a task time slice in scheduler timer ticks
+pub const DEFAULT_TIME_SLICE: u32 = 5;
+
+/// Scheduling priority of a task. Runnable tasks with a higher priority are
+/// always scheduled before tasks with a lower priority, tasks with the same
+/// priority share the CPU in a round-robin fashion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum TaskPriority {
+    /// Background work, used for user-mode tasks
+    Low = 0,
+    /// Default priority of kernel tasks
+    #[default]
+    Normal = 1,
+    /// Processing of guest requests
+    High = 2,
+}
+
+impl TaskPriority {
+    /// Number of distinct task priorities
+    pub const COUNT: usize = 3;
+
+    /// Index of the priority, with `0` being the lowest priority.
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    const fn from_index(index: u8) -> Self {
+        match index {
+            0 => Self::Low,
+            1 => Self::Normal,
+            _ => Self::High,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct TaskIDAllocator {
     next_id: AtomicU32,
@@ -126,6 +174,67 @@ impl TaskIDAllocator {
 
 static TASK_ID_ALLOCATOR: TaskIDAllocator = TaskIDAllocator::new();
 
+/// Exit status of a task, kept until it has been collected by another task.
+#[derive(Debug, Default)]
+pub struct TaskExitStatus {
+    /// Exit code of the task, set once the task has terminated
+    exit_code: Option<u32>,
+
+    /// Task waiting for the termination of the task
+    waiter: WaitQueue,
+}
+
+impl TaskExitStatus {
+    pub fn exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+}
+
+/// Exit status of the joinable threads of a process, indexed by task ID
+type ThreadMap = BTreeMap<u32, Arc<SpinLock<TaskExitStatus>>>;
+
+/// Exit code reported for tasks which have been terminated by the kernel,
+/// e.g. because of an unhandled exception.
+pub const EXIT_CODE_KILLED: u32 = !0;
+
+/// Exit state shared among the threads of a process. A process terminates
+/// when its last thread exits.
+#[derive(Debug, Default)]
+struct ProcessExit {
+    /// Number of threads of the process which have not exited yet
+    live_threads: usize,
+
+    /// Exit code of the initial task of the process, once it has exited
+    exit_code: Option<u32>,
+}
+
+/// Child processes of a process which have not been waited for yet. A
+/// process terminates when its last thread exits, terminated children are
+/// kept as zombies until their exit code has been collected.
+#[derive(Debug, Default)]
+pub struct ChildProcesses {
+    /// Process IDs of running children
+    running: BTreeSet<u32>,
+
+    /// Exit codes of terminated children, indexed by process ID
+    zombies: BTreeMap<u32, u32>,
+
+    /// Task waiting for a child to terminate
+    waiter: WaitQueue,
+}
+
+/// An ELF module loaded into the address space of a user process, as
+/// reported to debuggers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskModule {
+    /// Path of the ELF file
+    pub path: String,
+
+    /// Difference between the load addresses and the link addresses of the
+    /// module
+    pub load_bias: usize,
+}
+
 #[repr(C, packed)]
 #[derive(Default, Debug, Clone, Copy)]
 pub struct TaskContext {
@@ -145,6 +254,10 @@ struct TaskSchedState {
 
     /// CPU this task is currently assigned to
     cpu_index: usize,
+
+    /// Whether the context of the task is loaded on its CPU. This stays set
+    /// until the task switch away from the task has completed.
+    on_cpu: bool,
 }
 
 impl TaskSchedState {
@@ -179,8 +292,9 @@ pub struct Task {
     /// Task virtual memory range for use at CPL 0
     vm_kernel_range: VMR,
 
-    /// Task virtual memory range for use at CPL 3 - None for kernel tasks
-    vm_user_range: Option<VMR>,
+    /// Task virtual memory range for use at CPL 3 - None for kernel tasks.
+    /// Shared among threads within the same process.
+    vm_user_range: Option<Arc<VMR>>,
 
     /// State relevant for scheduler
     sched_state: RWLock<TaskSchedState>,
@@ -191,6 +305,10 @@ pub struct Task {
     /// ID of the task
     id: u32,
 
+    /// ID of the process the task belongs to, which is the ID of the first
+    /// task of the process
+    process_id: u32,
+
     /// Root directory for this task
     rootdir: Arc<dyn Directory>,
 
@@ -202,6 +320,37 @@ pub struct Task {
 
     /// Objects shared among threads within the same process
     objs: Arc<RWLock<BTreeMap<ObjHandle, Arc<dyn Obj>>>>,
+
+    /// Joinable threads shared among threads within the same process
+    threads: Arc<SpinLock<ThreadMap>>,
+
+    /// Exit status of the task
+    exit_status: Arc<SpinLock<TaskExitStatus>>,
+
+    /// Exit state shared among threads within the same process
+    process_exit: Arc<SpinLock<ProcessExit>>,
+
+    /// Child processes shared among threads within the same process
+    children: Arc<SpinLock<ChildProcesses>>,
+
+    /// Child processes of the parent process - None for tasks without a
+    /// user-mode parent
+    parent: Option<Arc<SpinLock<ChildProcesses>>>,
+
+    /// ELF modules loaded into the process
+    modules: Arc<SpinLock<Vec<TaskModule>>>,
+
+    /// Scheduling priority, stored as [`TaskPriority::index()`]
+    priority: AtomicU8,
+
+    /// Capabilities of the process
+    caps: ProcessCaps,
+
+    /// Length of the time slice in scheduler timer ticks
+    time_slice: AtomicU32,
+
+    /// Scheduler timer ticks left in the current time slice
+    ticks_left: AtomicU32,
 }
 
 // SAFETY: Send + Sync is required for Arc<Task> to implement Send. All members
@@ -234,27 +383,48 @@ impl fmt::Debug for Task {
     }
 }
 
-struct CreateTaskArguments {
+struct CreateTaskArguments<'a> {
     // The entry point of the task.  For user tasks, this is a user-mode
     // address, and for kernel tasks, it is a kernel address,
     entry: usize,
 
-    // A start parameter for kernel tasks.
+    // A start parameter for the task, passed in the first argument register.
     start_parameter: usize,
 
+    // For a user task, supplies the top of the user-mode stack.
+    user_stack: usize,
+
     // The name of the task.
     name: String,
 
     // For a user task, supplies the `VMR` that will represent the user-mode
     // address space.
-    vm_user_range: Option<VMR>,
+    vm_user_range: Option<Arc<VMR>>,
+
+    // For a user thread, supplies the task whose process the new task joins.
+    process: Option<&'a Task>,
+
+    // For a user process, supplies a task of the parent process.
+    parent: Option<&'a Task>,
 
     // The root directory that will be associated with this task.
     rootdir: Arc<dyn Directory>,
+
+    // The scheduling priority of the task.
+    priority: TaskPriority,
+
+    // The length of the time slice of the task in scheduler timer ticks.
+    time_slice: u32,
+
+    // The capabilities of a user process.
+    caps: ProcessCaps,
 }
 
 impl Task {
-    fn create_common(cpu: &PerCpu, args: CreateTaskArguments) -> Result<TaskPointer, SvsmError> {
+    fn create_common(
+        cpu: &PerCpu,
+        args: CreateTaskArguments<'_>,
+    ) -> Result<TaskPointer, SvsmError> {
         let mut pgtable = cpu.get_pgtable().clone_shared()?;
 
         cpu.populate_page_table(&mut pgtable);
@@ -308,7 +478,13 @@ impl Task {
 
         // Call the correct stack creation routine for this task.
         let (stack, raw_bounds, rsp_offset) = if args.vm_user_range.is_some() {
-            Self::allocate_utask_stack(cpu, args.entry, xsa_addr)?
+            Self::allocate_utask_stack(
+                cpu,
+                args.entry,
+                args.user_stack,
+                args.start_parameter,
+                xsa_addr,
+            )?
         } else {
             Self::allocate_ktask_stack(cpu, args.entry, xsa_addr, args.start_parameter)?
         };
@@ -322,6 +498,32 @@ impl Task {
         // Stack frames should be 16b-aligned
         debug_assert!(bounds.end().is_aligned(16));
 
+        let id = TASK_ID_ALLOCATOR.next_id();
+        let (process_id, objs, threads, process_exit, children, parent, modules) =
+            match args.process {
+                Some(task) => (
+                    task.process_id,
+                    task.objs.clone(),
+                    task.threads.clone(),
+                    task.process_exit.clone(),
+                    task.children.clone(),
+                    task.parent.clone(),
+                    task.modules.clone(),
+                ),
+                None => (
+                    id,
+                    Arc::new(RWLock::new(BTreeMap::new())),
+                    Arc::new(SpinLock::new(BTreeMap::new())),
+                    Arc::new(SpinLock::new(ProcessExit::default())),
+                    Arc::new(SpinLock::new(ChildProcesses::default())),
+                    args.parent.map(|task| task.children.clone()),
+                    Arc::new(SpinLock::new(Vec::new())),
+                ),
+            };
+        // Nothing can fail anymore, the task counts as a thread of the
+        // process from now on.
+        process_exit.lock().live_threads += 1;
+
         Ok(Arc::new(Task {
             rsp: bounds
                 .end()
@@ -340,13 +542,25 @@ impl Task {
                 idle_task: false,
                 state: TaskState::RUNNING,
                 cpu_index: cpu.get_cpu_index(),
+                on_cpu: false,
             }),
             name: args.name,
-            id: TASK_ID_ALLOCATOR.next_id(),
+            id,
+            process_id,
             rootdir: args.rootdir,
             list_link: LinkedListAtomicLink::default(),
             runlist_link: LinkedListAtomicLink::default(),
-            objs: Arc::new(RWLock::new(BTreeMap::new())),
+            objs,
+            threads,
+            exit_status: Arc::new(SpinLock::new(TaskExitStatus::default())),
+            process_exit,
+            children,
+            parent,
+            modules,
+            priority: AtomicU8::new(args.priority.index() as u8),
+            caps: args.caps,
+            time_slice: AtomicU32::new(args.time_slice),
+            ticks_left: AtomicU32::new(args.time_slice),
         }))
     }
 
@@ -359,18 +573,26 @@ impl Task {
         let create_args = CreateTaskArguments {
             entry: entry as usize,
             start_parameter,
+            user_stack: 0,
             name,
             vm_user_range: None,
+            process: None,
+            parent: None,
             rootdir: opendir("/")?,
+            priority: TaskPriority::Normal,
+            time_slice: DEFAULT_TIME_SLICE,
+            caps: ProcessCaps::empty(),
         };
         Self::create_common(cpu, create_args)
     }
 
     pub fn create_user(
         cpu: &PerCpu,
+        parent: Option<&Task>,
         user_entry: usize,
         root: Arc<dyn Directory>,
         name: String,
+        caps: ProcessCaps,
     ) -> Result<TaskPointer, SvsmError> {
         let vm_user_range = VMR::new(USER_MEM_START, USER_MEM_END, PTEntryFlags::USER);
         // SAFETY: the user address range is fully aligned to top-level paging
@@ -381,13 +603,64 @@ impl Task {
         let create_args = CreateTaskArguments {
             entry: user_entry,
             start_parameter: 0,
+            user_stack: USER_MEM_END.bits(),
             name,
-            vm_user_range: Some(vm_user_range),
+            vm_user_range: Some(Arc::new(vm_user_range)),
+            process: None,
+            parent,
             rootdir: root,
+            priority: TaskPriority::Low,
+            time_slice: DEFAULT_TIME_SLICE,
+            caps,
         };
         Self::create_common(cpu, create_args)
     }
 
+    /// Creates a new thread in the process of an existing user task. The
+    /// thread shares the user-mode address space, the objects and the root
+    /// directory of the process.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - The CPU to create the thread on.
+    /// * `process` - A task of the process the thread is created in.
+    /// * `user_entry` - The user-space entry point of the thread.
+    /// * `user_stack` - The top of the user-mode stack of the thread.
+    /// * `start_parameter` - Value passed in the first argument register.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of [`TaskPointer`] on success, [`SvsmError`] on
+    /// failure.
+    pub fn create_user_thread(
+        cpu: &PerCpu,
+        process: &Task,
+        user_entry: usize,
+        user_stack: usize,
+        start_parameter: usize,
+    ) -> Result<TaskPointer, SvsmError> {
+        let vm_user_range = process.vm_user_range.clone().ok_or(SvsmError::Mem)?;
+        let create_args = CreateTaskArguments {
+            entry: user_entry,
+            start_parameter,
+            user_stack,
+            name: process.name.clone(),
+            vm_user_range: Some(vm_user_range),
+            process: Some(process),
+            parent: None,
+            rootdir: process.rootdir(),
+            priority: process.priority(),
+            time_slice: process.time_slice(),
+            caps: process.caps,
+        };
+        let thread = Self::create_common(cpu, create_args)?;
+        process
+            .threads
+            .lock()
+            .insert(thread.id, thread.exit_status.clone());
+        Ok(thread)
+    }
+
     pub fn stack_bounds(&self) -> MemoryRegion<VirtAddr> {
         self.stack_bounds
     }
@@ -400,6 +673,202 @@ impl Task {
         self.id
     }
 
+    pub fn get_process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the capabilities of the process of this task.
+    pub fn caps(&self) -> ProcessCaps {
+        self.caps
+    }
+
+    pub fn is_user_task(&self) -> bool {
+        self.vm_user_range.is_some()
+    }
+
+    /// Records an ELF module loaded into the process of this task.
+    pub fn add_module(&self, module: TaskModule) {
+        self.modules.lock().push(module);
+    }
+
+    /// Returns the ELF modules loaded into the process of this task.
+    pub fn modules(&self) -> Vec<TaskModule> {
+        self.modules.lock().clone()
+    }
+
+    /// Returns the address of the user-mode register frame on the kernel
+    /// stack of this task. It is only accessible while the page table of
+    /// the task is loaded.
+    pub fn user_context_addr(&self) -> Option<VirtAddr> {
+        self.is_user_task()
+            .then(|| self.stack_bounds.end() - size_of::<X86ExceptionContext>())
+    }
+
+    /// Records the exit code of the task.
+    ///
+    /// # Returns
+    ///
+    /// The task waiting for the termination of this task, if any. The caller
+    /// is responsible for waking it up.
+    pub fn set_exit_code(&self, exit_code: u32) -> Option<TaskPointer> {
+        let mut exit_status = self.exit_status.lock();
+        exit_status.exit_code = Some(exit_code);
+        exit_status.waiter.wakeup()
+    }
+
+    /// Registers the process of this task as a running child with its parent
+    /// process, so that the parent can wait for its termination.
+    pub fn register_child(&self) {
+        if let Some(parent) = &self.parent {
+            parent.lock().running.insert(self.process_id);
+        }
+    }
+
+    /// Accounts the exit of this task to its process and reports the
+    /// termination of the process to its parent process when this task is
+    /// the last thread of the process. The exit code of the process is the
+    /// exit code of its initial task.
+    ///
+    /// # Returns
+    ///
+    /// The task waiting for a child of the parent process to terminate, if
+    /// any. The caller is responsible for waking it up.
+    pub fn notify_parent(&self, exit_code: u32) -> Option<TaskPointer> {
+        let exit_code = {
+            let mut process_exit = self.process_exit.lock();
+            if self.process_id == self.id {
+                process_exit.exit_code = Some(exit_code);
+            }
+            process_exit.live_threads -= 1;
+            if process_exit.live_threads != 0 {
+                return None;
+            }
+            process_exit.exit_code.unwrap_or(exit_code)
+        };
+
+        let mut children = self.parent.as_ref()?.lock();
+        if !children.running.remove(&self.process_id) {
+            return None;
+        }
+        children.zombies.insert(self.process_id, exit_code);
+        children.waiter.wakeup()
+    }
+
+    /// Waits until a child process of the process of this task has
+    /// terminated and collects its exit code. Must be called from the
+    /// context of this task.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - Process ID of the child to wait for, or `None` to wait for
+    ///   any child.
+    /// * `collect` - Called with the process ID and exit code of the
+    ///   terminated child before it is reaped. The child is only reaped when
+    ///   `collect` succeeds, otherwise it can be waited for again.
+    ///
+    /// # Returns
+    ///
+    /// The process ID of the terminated child on success. The error of
+    /// `collect`, [`TaskError::NoChild`] if there is no matching child, or
+    /// [`TaskError::WaitBusy`] if another thread of the process is already
+    /// waiting.
+    pub fn wait_child<F>(self: &Arc<Self>, pid: Option<u32>, collect: F) -> Result<u32, SvsmError>
+    where
+        F: Fn(u32, u32) -> Result<(), SvsmError>,
+    {
+        loop {
+            let mut children = self.children.lock();
+            let zombie = match pid {
+                Some(pid) => children.zombies.get_key_value(&pid),
+                None => children.zombies.first_key_value(),
+            };
+            if let Some((&zombie_pid, &exit_code)) = zombie {
+                // Do not hold the lock while collecting, which may access
+                // user memory.
+                drop(children);
+                collect(zombie_pid, exit_code)?;
+                // Another thread may have reaped the child in the meantime
+                if self.children.lock().zombies.remove(&zombie_pid).is_some() {
+                    return Ok(zombie_pid);
+                }
+                continue;
+            }
+
+            let running = match pid {
+                Some(pid) => children.running.contains(&pid),
+                None => !children.running.is_empty(),
+            };
+            if !running {
+                return Err(TaskError::NoChild.into());
+            }
+
+            if children.waiter.is_waiting() {
+                return Err(TaskError::WaitBusy.into());
+            }
+            children.waiter.wait_for_event(self.clone());
+            drop(children);
+
+            schedule();
+        }
+    }
+
+    /// Waits until the joinable thread `tid` of the process of this task has
+    /// terminated. Must be called from the context of this task.
+    ///
+    /// # Returns
+    ///
+    /// The exit code of the thread on success. [`TaskError::NotJoinable`] if
+    /// `tid` is not a joinable thread of the process, or
+    /// [`TaskError::JoinBusy`] if another task is already joining it.
+    pub fn join_thread(self: &Arc<Self>, tid: u32) -> Result<u32, SvsmError> {
+        if tid == self.id {
+            return Err(TaskError::NotJoinable.into());
+        }
+
+        let exit_status = self
+            .threads
+            .lock()
+            .get(&tid)
+            .cloned()
+            .ok_or(TaskError::NotJoinable)?;
+
+        loop {
+            let mut status = exit_status.lock();
+            if let Some(exit_code) = status.exit_code {
+                drop(status);
+                self.threads.lock().remove(&tid);
+                return Ok(exit_code);
+            }
+
+            if status.waiter.is_waiting() {
+                return Err(TaskError::JoinBusy.into());
+            }
+            status.waiter.wait_for_event(self.clone());
+            drop(status);
+
+            schedule();
+        }
+    }
+
+    /// Detaches the joinable thread `tid` of the process of this task. The
+    /// exit status of a detached thread is released together with the
+    /// thread.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success, [`TaskError::NotJoinable`] if `tid` is not a
+    /// joinable thread of the process, or [`TaskError::JoinBusy`] if another
+    /// task is already joining it.
+    pub fn detach_thread(&self, tid: u32) -> Result<(), SvsmError> {
+        let mut threads = self.threads.lock();
+        let exit_status = threads.get(&tid).ok_or(TaskError::NotJoinable)?;
+        if exit_status.lock().waiter.is_waiting() {
+            return Err(TaskError::JoinBusy.into());
+        }
+        threads.remove(&tid);
+        Ok(())
+    }
+
     pub fn rootdir(&self) -> Arc<dyn Directory> {
         self.rootdir.clone()
     }
@@ -438,6 +907,78 @@ impl Task {
         self.sched_state.lock_read().idle_task
     }
 
+    pub fn priority(&self) -> TaskPriority {
+        TaskPriority::from_index(self.priority.load(Ordering::Relaxed))
+    }
+
+    /// Changes the scheduling priority of the task. The new priority takes
+    /// effect the next time the task is put on a run queue.
+    pub fn set_priority(&self, priority: TaskPriority) {
+        self.priority
+            .store(priority.index() as u8, Ordering::Relaxed);
+    }
+
+    pub fn time_slice(&self) -> u32 {
+        self.time_slice.load(Ordering::Relaxed)
+    }
+
+    /// Changes the length of the time slice of the task. The new length takes
+    /// effect when the next time slice of the task starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticks` - Length of the time slice in scheduler timer ticks, at
+    ///   least one tick is always granted.
+    pub fn set_time_slice(&self, ticks: u32) {
+        self.time_slice.store(ticks.max(1), Ordering::Relaxed);
+    }
+
+    /// Starts a new time slice for the task.
+    pub fn reset_time_slice(&self) {
+        self.ticks_left.store(self.time_slice(), Ordering::Relaxed);
+    }
+
+    /// Accounts a scheduler timer tick to the current time slice of the task.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the time slice of the task has expired, `false`
+    /// otherwise.
+    pub fn tick_time_slice(&self) -> bool {
+        let left = self.ticks_left.load(Ordering::Relaxed).saturating_sub(1);
+        self.ticks_left.store(left, Ordering::Relaxed);
+        left == 0
+    }
+
+    /// Returns the index of the CPU this task was last scheduled on.
+    pub fn cpu_index(&self) -> usize {
+        self.sched_state.lock_read().cpu_index
+    }
+
+    pub fn set_on_cpu(&self, on_cpu: bool) {
+        self.sched_state.lock_write().on_cpu = on_cpu;
+    }
+
+    /// Checks whether the context of the task is still loaded on a CPU.
+    pub fn is_on_cpu(&self) -> bool {
+        self.sched_state.lock_read().on_cpu
+    }
+
+    /// Calls `f` when the context of the task is not loaded on any CPU. The
+    /// task can not be switched to until `f` returns, so `f` can access the
+    /// saved context of the task.
+    ///
+    /// # Returns
+    ///
+    /// The return value of `f`, or `None` when the task is running on a CPU.
+    pub fn with_saved_context<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        let state = self.sched_state.lock_read();
+        if state.on_cpu {
+            return None;
+        }
+        Some(f())
+    }
+
     pub fn update_cpu(&self, new_cpu_index: usize) -> usize {
         let mut state = self.sched_state.lock_write();
         let old_cpu_index = state.cpu_index;
@@ -524,6 +1065,8 @@ impl Task {
     fn allocate_utask_stack(
         cpu: &PerCpu,
         user_entry: usize,
+        user_stack: usize,
+        start_parameter: usize,
         xsa_addr: usize,
     ) -> Result<(Arc<Mapping>, MemoryRegion<VirtAddr>, usize), SvsmError> {
         let (mapping, bounds) = Task::allocate_stack_common()?;
@@ -559,7 +1102,8 @@ impl Task {
             iret_frame.frame.rip = user_entry;
             iret_frame.frame.cs = (SVSM_USER_CS | 3).into();
             iret_frame.frame.flags = iret_rflags;
-            iret_frame.frame.rsp = (USER_MEM_END - 8).into();
+            iret_frame.frame.rsp = user_stack - 8;
+            iret_frame.regs.rdi = start_parameter;
             iret_frame.frame.ss = (SVSM_USER_DS | 3).into();
             debug_assert!(is_aligned(iret_frame.frame.rsp + 8, 16));
 
@@ -670,6 +1214,18 @@ impl Task {
         Ok(())
     }
 
+    pub fn mprotect_user(
+        &self,
+        addr: VirtAddr,
+        flags: VMFileMappingFlags,
+    ) -> Result<(), SvsmError> {
+        if self.vm_user_range.is_none() {
+            return Err(SvsmError::Mem);
+        }
+
+        self.vm_user_range.as_ref().unwrap().protect(addr, flags)
+    }
+
     /// Adds an object to the current task.
     ///
     /// # Arguments
@@ -806,7 +1362,13 @@ unsafe fn setup_user_task(xsa_addr: u64) {
         // Needs to be the first function called here.
         setup_new_task_common(xsa_addr);
     }
-    task_attach_console();
+
+    // Threads share the objects of their process, which already has a
+    // console attached.
+    let task = current_task();
+    if task.get_process_id() == task.get_task_id() {
+        task_attach_console();
+    }
 }
 
 unsafe fn setup_new_task_common(xsa_addr: u64) {
@@ -23,6 +23,10 @@ impl WaitQueue {
         self.waiter = Some(current_task);
     }
 
+    pub fn is_waiting(&self) -> bool {
+        self.waiter.is_some()
+    }
+
     pub fn wakeup(&mut self) -> Option<TaskPointer> {
         self.waiter.take()
     }
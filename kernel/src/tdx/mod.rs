@@ -6,6 +6,8 @@
 
 pub mod apic;
 pub mod error;
+pub mod quote;
+pub mod rtmr;
 pub mod tdcall;
 pub mod ve;
 
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! TDREPORT generation and quote retrieval through the GetQuote TDVMCALL
+//! (GHCI spec. section 3.3).
+
+extern crate alloc;
+
+use super::tdcall::{tdcall_mr_report, tdvmcall_get_quote};
+use crate::cpu::irqs_disabled;
+use crate::cpu::mem::unsafe_copy_bytes;
+use crate::cpu::msr::rdtsc;
+use crate::cpu::x86::{apic_timer_counts_to_tsc, apic_timer_frequency};
+use crate::error::{AttestError, SvsmError};
+use crate::mm::page_visibility::SharedBox;
+use crate::mm::{virt_to_phys, PageBox};
+use crate::platform::attestation::REPORT_DATA_SIZE;
+use crate::platform::SVSM_PLATFORM;
+use crate::task::schedule;
+use crate::types::PAGE_SIZE;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::sync::atomic::{fence, Ordering};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Size of a TDREPORT_STRUCT
+pub const TDREPORT_SIZE: usize = 1024;
+
+/// Size of the buffer shared with the host for quote generation. It must be
+/// large enough for the quote including its certification data.
+const QUOTE_BUFFER_SIZE: usize = 8 * PAGE_SIZE;
+
+const GET_QUOTE_VERSION: u64 = 1;
+const GET_QUOTE_SUCCESS: u64 = 0;
+const GET_QUOTE_IN_FLIGHT: u64 = u64::MAX;
+
+/// Time the host gets to complete a GetQuote request
+const GET_QUOTE_TIMEOUT_SECONDS: u64 = 30;
+/// Maximum number of status polls when the TSC is not calibrated and the
+/// timeout can not be measured
+const GET_QUOTE_MAX_POLLS: u64 = 1 << 24;
+
+/// Buffer for TDG.MR.REPORT, which requires a 1024-byte aligned report and
+/// a 64-byte aligned REPORTDATA.
+#[repr(C, align(1024))]
+#[derive(Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+struct TdReportBuffer {
+    report: [u8; TDREPORT_SIZE],
+    report_data: [u8; REPORT_DATA_SIZE],
+    _pad: [u8; TDREPORT_SIZE - REPORT_DATA_SIZE],
+}
+
+/// Header of the GetQuote buffer (GHCI spec. table 3-11)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, FromBytes, KnownLayout, Immutable, IntoBytes)]
+struct QuoteHeader {
+    /// Version of the buffer format, must be 1
+    version: u64,
+    /// Status code set by the host
+    status: u64,
+    /// Length of the TDREPORT following the header
+    in_len: u32,
+    /// Length of the quote following the header, set by the host
+    out_len: u32,
+}
+
+const QUOTE_DATA_SIZE: usize = QUOTE_BUFFER_SIZE - size_of::<QuoteHeader>();
+
+/// Generates a TDREPORT_STRUCT for the current TD.
+///
+/// # Arguments
+///
+/// * `report_data` - Data to include in the REPORTDATA field of the report.
+///
+/// # Returns
+///
+/// The TDREPORT_STRUCT generated by the TDX module.
+pub fn td_report(report_data: &[u8; REPORT_DATA_SIZE]) -> Result<[u8; TDREPORT_SIZE], SvsmError> {
+    let mut buffer = PageBox::<TdReportBuffer>::try_new_zeroed()?;
+    buffer.report_data = *report_data;
+
+    let report_pa = virt_to_phys(buffer.vaddr());
+    let report_data_pa = report_pa + TDREPORT_SIZE;
+    // SAFETY: both addresses point into `buffer`, which is private memory
+    // owned by this function and satisfies the alignment requirements.
+    unsafe { tdcall_mr_report(report_pa, report_data_pa)? };
+
+    Ok(buffer.report)
+}
+
+/// Generates a quote over a TDREPORT which includes `report_data`. The
+/// quote is produced by the quoting enclave on the host, and verifiers
+/// check it against the certification chain of the platform.
+///
+/// # Arguments
+///
+/// * `report_data` - Data to include in the REPORTDATA field of the report.
+///
+/// # Returns
+///
+/// The quote returned by the host.
+pub fn td_quote(report_data: &[u8; REPORT_DATA_SIZE]) -> Result<Vec<u8>, SvsmError> {
+    let report = td_report(report_data)?;
+
+    let buffer = SharedBox::<[u8; QUOTE_BUFFER_SIZE]>::try_new_zeroed()?;
+    let header = QuoteHeader {
+        version: GET_QUOTE_VERSION,
+        status: GET_QUOTE_SUCCESS,
+        in_len: TDREPORT_SIZE as u32,
+        out_len: 0,
+    };
+    let header_ptr = buffer.addr().as_mut_ptr::<QuoteHeader>();
+    // SAFETY: the shared buffer is valid and large enough for the header
+    // followed by the report.
+    unsafe {
+        header_ptr.write_volatile(header);
+        unsafe_copy_bytes(
+            report.as_ptr(),
+            header_ptr.add(1).cast::<u8>(),
+            TDREPORT_SIZE,
+        );
+    }
+
+    let gpa = u64::from(virt_to_phys(buffer.addr()))
+        | SVSM_PLATFORM.get_page_encryption_masks().shared_pte_mask as u64;
+    tdvmcall_get_quote(gpa, QUOTE_BUFFER_SIZE as u64).map_err(|e| {
+        log::error!("GetQuote TDVMCALL failed: {e:?}");
+        SvsmError::Attestation(AttestError::Report)
+    })?;
+
+    // The host completes the request asynchronously. No notification
+    // interrupt is registered, so poll the status until the request is no
+    // longer in flight, giving other tasks the CPU in between. The host may
+    // never complete the request, so give up after a timeout.
+    let timeout = apic_timer_counts_to_tsc(apic_timer_frequency() * GET_QUOTE_TIMEOUT_SECONDS);
+    let start = rdtsc();
+    let mut polls = 0u64;
+    let header = loop {
+        // SAFETY: the shared buffer is valid for the lifetime of `buffer`.
+        // The host may modify it at any time, hence the volatile read.
+        let header = unsafe { header_ptr.read_volatile() };
+        if header.status != GET_QUOTE_IN_FLIGHT {
+            break header;
+        }
+
+        polls += 1;
+        let timed_out = match timeout {
+            Some(ticks) => rdtsc().wrapping_sub(start) > ticks,
+            None => polls > GET_QUOTE_MAX_POLLS,
+        };
+        if timed_out {
+            log::error!("Quote generation timed out");
+            return Err(SvsmError::Attestation(AttestError::Report));
+        }
+
+        if irqs_disabled() {
+            core::hint::spin_loop();
+        } else {
+            schedule();
+        }
+    };
+    fence(Ordering::Acquire);
+
+    // The contents of the buffer are untrusted, the quote itself is
+    // verified by the relying party.
+    let out_len = header.out_len as usize;
+    if header.status != GET_QUOTE_SUCCESS || out_len == 0 || out_len > QUOTE_DATA_SIZE {
+        log::error!(
+            "Quote generation failed, status={:#x} length={:#x}",
+            header.status,
+            out_len
+        );
+        return Err(SvsmError::Attestation(AttestError::Report));
+    }
+
+    let mut data = vec![0u8; size_of::<QuoteHeader>() + out_len];
+    buffer
+        .copy_to_slice(&mut data)
+        .map_err(|_| SvsmError::Attestation(AttestError::Report))?;
+    Ok(data.split_off(size_of::<QuoteHeader>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{align_of, offset_of};
+
+    #[test]
+    fn test_quote_buffer_layout() {
+        assert_eq!(offset_of!(QuoteHeader, status), 0x8);
+        assert_eq!(offset_of!(QuoteHeader, in_len), 0x10);
+        assert_eq!(offset_of!(QuoteHeader, out_len), 0x14);
+        assert_eq!(size_of::<QuoteHeader>(), 0x18);
+
+        assert_eq!(offset_of!(TdReportBuffer, report_data), TDREPORT_SIZE);
+        assert_eq!(align_of::<TdReportBuffer>(), TDREPORT_SIZE);
+        assert!(size_of::<TdReportBuffer>() <= PAGE_SIZE);
+    }
+}
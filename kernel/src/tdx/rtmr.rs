@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Runtime measurement registers (RTMRs) of a TD and the event log which
+//! records their extensions.
+//!
+//! The event log uses the TCG crypto-agile format with SHA-384 digests, as
+//! the CC event log (CCEL) of the firmware does. Like in the CCEL, events
+//! for RTMR `n` are logged with MR index `n + 1`, since index 0 refers to
+//! MRTD.
+//!
+//! The event log is exposed as `/proc/rtmr_log` and to the guest as a
+//! service of the attestation protocol. It is limited to
+//! [`RTMR_EVENT_LOG_MAX`] bytes; once it is full, further measurements fail
+//! without extending an RTMR, so that the log always matches the RTMRs.
+
+extern crate alloc;
+
+use super::tdcall::tdcall_mr_rtmr_extend;
+use crate::crypto::digest::{Algorithm, Sha384};
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::mm::alloc::AllocError;
+use crate::mm::{virt_to_phys, PageBox};
+use crate::platform::SVSM_PLATFORM;
+use alloc::vec::Vec;
+
+/// Size of an RTMR and of the digests it is extended with (SHA-384)
+pub const RTMR_DIGEST_SIZE: usize = 48;
+
+/// Maximum size of the event log
+pub const RTMR_EVENT_LOG_MAX: usize = 256 * 1024;
+
+const TPM_ALG_SHA384: u16 = 0x000c;
+/// TCG event type of events which are logged without extending a register
+pub const EV_NO_ACTION: u32 = 0x3;
+
+static EVENT_LOG: SpinLock<Vec<u8>> = SpinLock::new(Vec::new());
+
+/// Builds the Spec ID event which starts every crypto-agile event log. It is
+/// logged in the SHA-1 log format (TCG PC Client PFP spec. section 10.4.5.1).
+fn spec_id_event() -> Vec<u8> {
+    let mut spec_id = Vec::new();
+    spec_id.extend_from_slice(b"Spec ID Event03\0");
+    // platformClass
+    spec_id.extend_from_slice(&0u32.to_le_bytes());
+    // specVersionMinor, specVersionMajor, specErrata, uintnSize
+    spec_id.extend_from_slice(&[0, 2, 0, 2]);
+    // numberOfAlgorithms, followed by the algorithm list
+    spec_id.extend_from_slice(&1u32.to_le_bytes());
+    spec_id.extend_from_slice(&TPM_ALG_SHA384.to_le_bytes());
+    spec_id.extend_from_slice(&(RTMR_DIGEST_SIZE as u16).to_le_bytes());
+    // vendorInfoSize
+    spec_id.push(0);
+
+    let mut event = Vec::new();
+    event.extend_from_slice(&0u32.to_le_bytes());
+    event.extend_from_slice(&EV_NO_ACTION.to_le_bytes());
+    event.extend_from_slice(&[0u8; 20]);
+    event.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+    event.extend_from_slice(&spec_id);
+    event
+}
+
+/// Size of a TCG_PCR_EVENT2 structure with `data_len` bytes of event data
+fn event_size(data_len: usize) -> usize {
+    // pcrIndex, eventType, digest count, hash algorithm, digest, eventSize
+    4 + 4 + 4 + 2 + RTMR_DIGEST_SIZE + 4 + data_len
+}
+
+/// Appends a TCG_PCR_EVENT2 structure to `log`.
+fn log_event(
+    log: &mut Vec<u8>,
+    index: usize,
+    event_type: u32,
+    digest: &[u8; RTMR_DIGEST_SIZE],
+    event_data: &[u8],
+) {
+    if log.is_empty() {
+        log.extend_from_slice(&spec_id_event());
+    }
+    log.extend_from_slice(&(index as u32 + 1).to_le_bytes());
+    log.extend_from_slice(&event_type.to_le_bytes());
+    log.extend_from_slice(&1u32.to_le_bytes());
+    log.extend_from_slice(&TPM_ALG_SHA384.to_le_bytes());
+    log.extend_from_slice(digest);
+    log.extend_from_slice(&(event_data.len() as u32).to_le_bytes());
+    log.extend_from_slice(event_data);
+}
+
+/// Extends an RTMR with a digest through the TDX module.
+///
+/// # Arguments
+///
+/// * `index` - Index of the RTMR to extend.
+/// * `digest` - The SHA-384 digest to extend the RTMR with.
+pub fn td_extend_rtmr(index: usize, digest: &[u8; RTMR_DIGEST_SIZE]) -> Result<(), SvsmError> {
+    // The TDX module requires the digest in 64-byte aligned private memory.
+    let mut buffer = PageBox::<[u8; RTMR_DIGEST_SIZE]>::try_new_zeroed()?;
+    *buffer = *digest;
+    // SAFETY: the buffer is private memory owned by this function.
+    unsafe { tdcall_mr_rtmr_extend(virt_to_phys(buffer.vaddr()), index as u64)? };
+    Ok(())
+}
+
+/// Measures an event into an RTMR and records it in the event log.
+///
+/// # Arguments
+///
+/// * `index` - Index of the RTMR to extend, below
+///   [`RTMR_COUNT`](syscall::RTMR_COUNT).
+/// * `event_type` - TCG event type recorded in the event log.
+/// * `event_data` - Data describing the event. The RTMR is extended with
+///   its SHA-384 digest.
+///
+/// # Returns
+///
+/// `()` on success, [`SvsmError::NotSupported`] when the platform has no
+/// RTMRs, [`AllocError::OutOfMemory`] when the event log is full.
+pub fn rtmr_measure(index: usize, event_type: u32, event_data: &[u8]) -> Result<(), SvsmError> {
+    let mut digest = [0u8; RTMR_DIGEST_SIZE];
+    digest.copy_from_slice(&Sha384::digest(event_data));
+
+    // Keep the log locked while extending, so that the order of the events
+    // in the log matches the order of the extensions.
+    let mut log = EVENT_LOG.lock();
+    let header_size = if log.is_empty() {
+        spec_id_event().len()
+    } else {
+        0
+    };
+    if log.len() + header_size + event_size(event_data.len()) > RTMR_EVENT_LOG_MAX {
+        log::warn!("RTMR event log is full, not measuring event {event_type:#x}");
+        return Err(SvsmError::Alloc(AllocError::OutOfMemory));
+    }
+    SVSM_PLATFORM.extend_rtmr(index, &digest)?;
+    log_event(&mut log, index, event_type, &digest, event_data);
+    Ok(())
+}
+
+/// Returns a copy of the event log of all RTMR extensions done by the SVSM,
+/// which a verifier replays to check the RTMR values. The log is empty when
+/// no event was measured.
+pub fn rtmr_event_log() -> Vec<u8> {
+    EVENT_LOG.lock().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtmr_event_log_format() {
+        let mut log = Vec::new();
+        let digest = [0xaau8; RTMR_DIGEST_SIZE];
+        log_event(&mut log, 2, 0xd, &digest, b"event");
+
+        let spec_id = spec_id_event();
+        assert_eq!(&spec_id[32..48], b"Spec ID Event03\0");
+        assert_eq!(spec_id.len(), 32 + 33);
+        assert_eq!(&log[..spec_id.len()], &spec_id[..]);
+
+        let event = &log[spec_id.len()..];
+        assert_eq!(event[0..4], 3u32.to_le_bytes());
+        assert_eq!(event[4..8], 0xdu32.to_le_bytes());
+        assert_eq!(event[8..12], 1u32.to_le_bytes());
+        assert_eq!(event[12..14], TPM_ALG_SHA384.to_le_bytes());
+        assert_eq!(event[14..62], digest);
+        assert_eq!(event[62..66], 5u32.to_le_bytes());
+        assert_eq!(&event[66..], b"event");
+        assert_eq!(event.len(), event_size(5));
+    }
+}
@@ -19,16 +19,20 @@ use bitfield_struct::bitfield;
 use core::arch::asm;
 
 const TDG_VP_TDVMCALL: u32 = 0;
+const TDG_MR_RTMR_EXTEND: u32 = 2;
 const TDG_VP_VEINFO_GET: u32 = 3;
+const TDG_MR_REPORT: u32 = 4;
 const TDG_MEM_PAGE_ACCEPT: u32 = 6;
 const TDG_VM_RD: u32 = 7;
 
 const TDVMCALL_CPUID: u32 = 10;
 const TDVMCALL_HLT: u32 = 12;
 const TDVMCALL_IO: u32 = 30;
+const TDVMCALL_MMIO: u32 = 48;
 const TDVMCALL_RDMSR: u32 = 31;
 const TDVMCALL_WRMSR: u32 = 32;
 const TDVMCALL_MAP_GPA: u32 = 0x10001;
+const TDVMCALL_GET_QUOTE: u32 = 0x10002;
 
 pub const MD_TDCS_NUM_L2_VMS: u64 = 0x9010_0001_0000_0005;
 
@@ -326,6 +330,74 @@ pub fn tdcall_vm_read(field: u64) -> u64 {
     val
 }
 
+/// Extends a runtime measurement register of the current TD.
+///
+/// # Arguments
+///
+/// * `data` - Physical address of the 64-byte aligned, 48-byte SHA-384
+///   digest to extend the register with.
+/// * `index` - Index of the RTMR to extend.
+///
+/// # Safety
+///
+/// The caller must ensure that `data` refers to private memory which is
+/// readable by the TDX module.
+pub unsafe fn tdcall_mr_rtmr_extend(data: PhysAddr, index: u64) -> Result<(), TdxError> {
+    let err = loop {
+        // SAFETY: executing TDCALL requires the use of assembly.  The caller
+        // takes responsibility for correctness of the parameters.
+        let err = unsafe {
+            let mut ret: u64;
+            asm!("tdcall",
+                 in("rax") TDG_MR_RTMR_EXTEND,
+                 in("rcx") u64::from(data),
+                 in("rdx") index,
+                 lateout("rax") ret,
+                 options(att_syntax));
+            ret
+        };
+        if !tdx_recoverable_error(err) {
+            break err;
+        }
+    };
+    tdx_result(err).map(|_| ())
+}
+
+/// Generates a TDREPORT_STRUCT for the current TD.
+///
+/// # Arguments
+///
+/// * `report` - Physical address of the 1024-byte aligned buffer which
+///   receives the 1024-byte TDREPORT_STRUCT.
+/// * `report_data` - Physical address of the 64-byte aligned REPORTDATA to
+///   include in the report.
+///
+/// # Safety
+///
+/// The caller must ensure that both addresses refer to private memory which
+/// is owned by the caller, as the TDX module writes to `report`.
+pub unsafe fn tdcall_mr_report(report: PhysAddr, report_data: PhysAddr) -> Result<(), TdxError> {
+    let err = loop {
+        // SAFETY: executing TDCALL requires the use of assembly.  The caller
+        // takes responsibility for correctness of the parameters.
+        let err = unsafe {
+            let mut ret: u64;
+            asm!("tdcall",
+                 in("rax") TDG_MR_REPORT,
+                 in("rcx") u64::from(report),
+                 in("rdx") u64::from(report_data),
+                 in("r8") 0,
+                 lateout("rax") ret,
+                 options(att_syntax));
+            ret
+        };
+        if !tdx_recoverable_error(err) {
+            break err;
+        }
+    };
+    tdx_result(err).map(|_| ())
+}
+
 pub fn tdvmcall_map_gpa(mut gpa: u64, size: u64) -> Result<(), TdxError> {
     let pass_regs = (1 << 10) | (1 << 11) | (1 << 12) | (1 << 13);
     let end = gpa + size;
@@ -362,6 +434,36 @@ pub fn tdvmcall_map_gpa(mut gpa: u64, size: u64) -> Result<(), TdxError> {
     }
 }
 
+/// Asks the host to convert the TDREPORT in a shared buffer into a quote.
+/// The host completes the request asynchronously by updating the status
+/// field of the buffer.
+///
+/// # Arguments
+///
+/// * `gpa` - Shared GPA of the quote buffer, including the shared bit.
+/// * `size` - Size of the quote buffer in bytes.
+pub fn tdvmcall_get_quote(gpa: u64, size: u64) -> Result<(), TdxError> {
+    let pass_regs = (1 << 10) | (1 << 11) | (1 << 12) | (1 << 13);
+    let mut ret: u64;
+    let mut vmcall_ret: u64;
+    // SAFETY: executing TDCALL requires the use of assembly.
+    unsafe {
+        asm!("tdcall",
+             in("rax") TDG_VP_TDVMCALL,
+             in("rcx") pass_regs,
+             in("r10") 0,
+             in("r11") TDVMCALL_GET_QUOTE,
+             in("r12") gpa,
+             in("r13") size,
+             lateout("rax") ret,
+             lateout("r10") vmcall_ret,
+             lateout("r11") _,
+             options(att_syntax));
+    }
+    debug_assert!(tdx_result(ret).is_ok());
+    tdvmcall_result(vmcall_ret)
+}
+
 pub fn tdvmcall_cpuid(cpuid_fn: u32, cpuid_subfn: u32) -> CpuidResult {
     let pass_regs = (1 << 10) | (1 << 11) | (1 << 12) | (1 << 13) | (1 << 14) | (1 << 15);
     let mut ret: u64;
@@ -523,6 +625,60 @@ pub fn tdvmcall_io_read<T>(port: u16) -> u32 {
     tdvmcall_io(port, 0, size_of::<T>(), false)
 }
 
+fn tdvmcall_mmio(gpa: u64, size: usize, write: bool, data: u64) -> Result<u64, TdxError> {
+    let pass_regs = (1 << 10) | (1 << 11) | (1 << 12) | (1 << 13) | (1 << 14) | (1 << 15);
+    let mut ret: u64;
+    let mut vmcall_ret: u64;
+    let mut output: u64;
+    // SAFETY: executing TDCALL requires the use of assembly.
+    unsafe {
+        asm!("tdcall",
+             in("rax") TDG_VP_TDVMCALL,
+             in("rcx") pass_regs,
+             in("r10") 0,
+             in("r11") TDVMCALL_MMIO,
+             in("r12") size,
+             in("r13") write as u64,
+             in("r14") gpa,
+             in("r15") data,
+             lateout("rax") ret,
+             lateout("r10") vmcall_ret,
+             lateout("r11") output,
+             lateout("r12") _,
+             lateout("r13") _,
+             lateout("r14") _,
+             lateout("r15") _,
+             options(att_syntax));
+    }
+    debug_assert!(tdx_result(ret).is_ok());
+    tdvmcall_result(vmcall_ret).map(|_| output)
+}
+
+/// Asks the host to emulate a read from a memory-mapped IO register.
+///
+/// # Arguments
+///
+/// * `gpa` - Shared GPA of the register, including the shared bit.
+/// * `size` - Size of the access in bytes (1, 2, 4 or 8).
+///
+/// # Returns
+///
+/// The value read, zero-extended to 64 bits.
+pub fn tdvmcall_mmio_read(gpa: u64, size: usize) -> Result<u64, TdxError> {
+    tdvmcall_mmio(gpa, size, false, 0)
+}
+
+/// Asks the host to emulate a write to a memory-mapped IO register.
+///
+/// # Arguments
+///
+/// * `gpa` - Shared GPA of the register, including the shared bit.
+/// * `size` - Size of the access in bytes (1, 2, 4 or 8).
+/// * `data` - The value to write in the low `size` bytes.
+pub fn tdvmcall_mmio_write(gpa: u64, size: usize, data: u64) -> Result<(), TdxError> {
+    tdvmcall_mmio(gpa, size, true, data).map(|_| ())
+}
+
 pub fn tdvmcall_hyperv_hypercall(regs: &mut X86GeneralRegs) {
     let pass_regs = (1 << 2) | (1 << 8) | (1 << 10) | (1 << 11);
     let mut ret: u64;
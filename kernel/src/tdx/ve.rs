@@ -6,20 +6,25 @@
 
 use super::tdcall::{tdcall_get_ve_info, tdvmcall_cpuid};
 use super::TdxError;
+use crate::address::VirtAddr;
 use crate::cpu::idt::common::X86ExceptionContext;
 use crate::error::SvsmError;
+use crate::insn_decode::{Instruction, MAX_INSN_SIZE};
+use crate::mm::GuestPtr;
 
 const VMX_EXIT_REASON_CPUID: u32 = 10;
+const VMX_EXIT_REASON_EPT_VIOLATION: u32 = 48;
 
 pub fn handle_virtualization_exception(ctx: &mut X86ExceptionContext) -> Result<(), SvsmError> {
     let veinfo = tdcall_get_ve_info().expect("Failed to get #VE info");
 
-    match veinfo.exit_reason {
-        VMX_EXIT_REASON_CPUID => handle_cpuid(ctx),
+    let insn_len = match veinfo.exit_reason {
+        VMX_EXIT_REASON_CPUID => handle_cpuid(ctx).map(|_| veinfo.exit_instruction_length as usize),
+        VMX_EXIT_REASON_EPT_VIOLATION => handle_mmio(ctx),
         _ => Err(TdxError::Unknown(veinfo.exit_reason.into()).into()),
     }?;
 
-    let new_rip = ctx.frame.rip + veinfo.exit_instruction_length as usize;
+    let new_rip = ctx.frame.rip + insn_len;
     // SAFETY: we are advancing the instruction pointer by the size of the exit
     // instruction.
     unsafe {
@@ -36,3 +41,21 @@ fn handle_cpuid(ctx: &mut X86ExceptionContext) -> Result<(), SvsmError> {
     ctx.regs.rdx = cpuidinfo.edx as usize;
     Ok(())
 }
+
+/// Emulates an access to emulated MMIO, which raises a #VE exception for an
+/// EPT violation, through TDVMCALL MMIO requests. The exit instruction
+/// length is not valid for EPT violations, so the instruction is decoded.
+///
+/// # Returns
+///
+/// The number of bytes to advance the instruction pointer by.
+fn handle_mmio(ctx: &mut X86ExceptionContext) -> Result<usize, SvsmError> {
+    let rip: GuestPtr<[u8; MAX_INSN_SIZE]> = GuestPtr::new(VirtAddr::from(ctx.frame.rip));
+    // SAFETY: RIP points to the instruction that raised the #VE. GuestPtr
+    // uses the exception table to handle faults while fetching.
+    let insn_raw = unsafe { rip.read()? };
+
+    let insn_ctx = Instruction::new(insn_raw).decode(ctx)?;
+    insn_ctx.emulate_mmio(ctx)?;
+    Ok(insn_ctx.size())
+}
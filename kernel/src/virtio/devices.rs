@@ -10,20 +10,41 @@ extern crate alloc;
 use alloc::boxed::Box;
 use core::ptr::NonNull;
 use virtio_drivers::device::blk::VirtIOBlk;
+use virtio_drivers::device::socket::{VirtIOSocket, VsockConnectionManager};
 use virtio_drivers::transport::mmio::{MmioError, MmioTransport};
-use virtio_drivers::transport::{DeviceType, Transport};
+use virtio_drivers::transport::{DeviceType, SomeTransport, Transport};
 use virtio_drivers::PAGE_SIZE;
 
 use super::error::*;
+use super::pci::{pci_transport, PciBars};
+use super::VirtioDeviceAddress;
 use crate::address::PhysAddr;
 use crate::error::SvsmError;
 use crate::locking::SpinLock;
 use crate::mm::global_memory::{map_global_range_4k_shared, GlobalRangeGuard};
 use crate::mm::pagetable::PTEntryFlags;
 
+/// Resources of a VirtIO device which are kept while the device is used
+#[derive(Debug)]
+enum DeviceResources {
+    /// Mapping of the registers of a virtio-mmio device
+    Mmio { _mapping: GlobalRangeGuard },
+    /// BARs of a virtio-pci function
+    Pci(PciBars),
+}
+
+impl DeviceResources {
+    fn check(&self) -> Result<(), SvsmError> {
+        match self {
+            Self::Mmio { .. } => Ok(()),
+            Self::Pci(bars) => bars.check(),
+        }
+    }
+}
+
 pub struct VirtIOBlkDevice {
-    pub device: SpinLock<VirtIOBlk<SvsmHal, MmioTransport<SvsmHal>>>,
-    _mmio_space: GlobalRangeGuard,
+    pub device: SpinLock<VirtIOBlk<SvsmHal, SomeTransport<SvsmHal>>>,
+    resources: DeviceResources,
 }
 
 impl core::fmt::Debug for VirtIOBlkDevice {
@@ -32,34 +53,112 @@ impl core::fmt::Debug for VirtIOBlkDevice {
     }
 }
 
-impl VirtIOBlkDevice {
-    pub fn new(mmio_base: PhysAddr) -> Result<Box<Self>, SvsmError> {
-        virtio_init();
-
-        let mem = map_global_range_4k_shared(mmio_base, PAGE_SIZE, PTEntryFlags::data())?;
-
-        // Not expected to fail, because mem exists.
-        let header = NonNull::new(mem.addr().as_mut_ptr()).unwrap();
-
-        // SAFETY: `header` is the MMIO config area; we have to trust the content is valid.
-        let transport = unsafe {
-            // TODO: Use more detailed error types ?
-            MmioTransport::<SvsmHal>::new(header).map_err(|e| match e {
-                MmioError::BadMagic(_) => VirtioError::InvalidDevice,
-                MmioError::UnsupportedVersion(_) => VirtioError::InvalidDevice,
-                MmioError::ZeroDeviceId => VirtioError::InvalidDevice,
-            })?
-        };
-
-        if transport.device_type() != DeviceType::Block {
-            return Err(VirtioError::InvalidDeviceType)?;
+/// Maps the MMIO configuration area of the VirtIO device at `mmio_base`
+/// and creates a transport for it.
+///
+/// # Returns
+///
+/// The transport and the guard of the mapping, or
+/// [`VirtioError::InvalidDeviceType`] when the device is not of type
+/// `device_type`.
+fn mmio_transport(
+    mmio_base: PhysAddr,
+    device_type: DeviceType,
+) -> Result<(MmioTransport<SvsmHal>, GlobalRangeGuard), SvsmError> {
+    virtio_init();
+
+    let mem = map_global_range_4k_shared(mmio_base, PAGE_SIZE, PTEntryFlags::data())?;
+
+    // Not expected to fail, because mem exists.
+    let header = NonNull::new(mem.addr().as_mut_ptr()).unwrap();
+
+    // SAFETY: `header` is the MMIO config area; we have to trust the content is valid.
+    let transport = unsafe {
+        // TODO: Use more detailed error types ?
+        MmioTransport::<SvsmHal>::new(header).map_err(|e| match e {
+            MmioError::BadMagic(_) => VirtioError::InvalidDevice,
+            MmioError::UnsupportedVersion(_) => VirtioError::InvalidDevice,
+            MmioError::ZeroDeviceId => VirtioError::InvalidDevice,
+        })?
+    };
+
+    if transport.device_type() != device_type {
+        return Err(VirtioError::InvalidDeviceType.into());
+    }
+
+    Ok((transport, mem))
+}
+
+/// Creates a transport for the VirtIO device at `address`.
+///
+/// # Returns
+///
+/// The transport and the resources of the device. Fails with
+/// [`VirtioError::InvalidDeviceType`] when the device is not of type
+/// `device_type`.
+fn transport(
+    address: VirtioDeviceAddress,
+    device_type: DeviceType,
+) -> Result<(SomeTransport<SvsmHal>, DeviceResources), SvsmError> {
+    match address {
+        VirtioDeviceAddress::Mmio(mmio_base) => {
+            let (transport, mem) = mmio_transport(mmio_base, device_type)?;
+            Ok((transport.into(), DeviceResources::Mmio { _mapping: mem }))
+        }
+        VirtioDeviceAddress::Pci(device_function) => {
+            virtio_init();
+            let (transport, bars) = pci_transport(device_function, device_type)?;
+            Ok((transport.into(), DeviceResources::Pci(bars)))
         }
+    }
+}
+
+impl VirtIOBlkDevice {
+    pub fn new(address: VirtioDeviceAddress) -> Result<Box<Self>, SvsmError> {
+        let (transport, resources) = transport(address, DeviceType::Block)?;
 
         let blk = VirtIOBlk::new(transport).map_err(|_| VirtioError::InvalidDevice)?;
 
         Ok(Box::new(VirtIOBlkDevice {
             device: SpinLock::new(blk),
-            _mmio_space: mem,
+            resources,
+        }))
+    }
+
+    /// Checks that the device can be reached through its mappings. Must be
+    /// called before the device is used.
+    pub fn check(&self) -> Result<(), SvsmError> {
+        self.resources.check()
+    }
+}
+
+pub struct VirtIOVsockDevice {
+    pub device: SpinLock<VsockConnectionManager<SvsmHal, SomeTransport<SvsmHal>>>,
+    resources: DeviceResources,
+}
+
+impl core::fmt::Debug for VirtIOVsockDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VirtIOVsockDevice").finish()
+    }
+}
+
+impl VirtIOVsockDevice {
+    pub fn new(address: VirtioDeviceAddress, buffer_capacity: u32) -> Result<Box<Self>, SvsmError> {
+        let (transport, resources) = transport(address, DeviceType::Socket)?;
+
+        let socket = VirtIOSocket::new(transport).map_err(|_| VirtioError::InvalidDevice)?;
+        let manager = VsockConnectionManager::new_with_capacity(socket, buffer_capacity);
+
+        Ok(Box::new(VirtIOVsockDevice {
+            device: SpinLock::new(manager),
+            resources,
         }))
     }
+
+    /// Checks that the device can be reached through its mappings. Must be
+    /// called before the device is used.
+    pub fn check(&self) -> Result<(), SvsmError> {
+        self.resources.check()
+    }
 }
@@ -14,9 +14,14 @@ use core::{
 use zerocopy::{FromBytes, Immutable, IntoBytes};
 
 use crate::{
-    address::{PhysAddr, VirtAddr},
+    address::{Address, PhysAddr, VirtAddr},
     cpu::percpu::this_cpu,
-    mm::{page_visibility::*, *},
+    mm::{
+        global_memory::{map_global_range_4k_shared, GlobalRangeGuard},
+        page_visibility::*,
+        pagetable::PTEntryFlags,
+        *,
+    },
 };
 
 struct PageStore {
@@ -43,6 +48,10 @@ impl PageStore {
 
 static SHARED_MEM: SpinLock<OnceCell<PageStore>> = SpinLock::new(OnceCell::new());
 
+/// Mappings created by `mmio_phys_to_virt()`. Transports never unmap their
+/// registers, so the mappings are kept for the lifetime of the SVSM.
+static MMIO_MAPPINGS: SpinLock<Vec<GlobalRangeGuard>> = SpinLock::new(Vec::new());
+
 pub fn virtio_init() {
     SHARED_MEM.lock().get_or_init(PageStore::new);
 }
@@ -106,9 +115,23 @@ unsafe impl virtio_drivers::Hal for SvsmHal {
     }
 
     /// Converts a physical address used for MMIO to a virtual address.
-    /// NOT IMPLEMENTED - the cut-down virtio driver for SVSM does not use this.
-    unsafe fn mmio_phys_to_virt(_paddr: virtio_drivers::PhysAddr, _size: usize) -> NonNull<u8> {
-        unimplemented!()
+    /// Used by the PCI transport to map the VirtIO structures in the BARs of
+    /// a device. The range is mapped shared into the global address space.
+    ///
+    /// # Safety
+    ///
+    /// The `paddr` and `size` must describe a valid MMIO region.
+    unsafe fn mmio_phys_to_virt(paddr: virtio_drivers::PhysAddr, size: usize) -> NonNull<u8> {
+        let paddr = PhysAddr::from(paddr);
+        let offset = paddr.page_offset();
+        let mem =
+            map_global_range_4k_shared(paddr.page_align(), offset + size, PTEntryFlags::data())
+                .expect("Failed to map VirtIO MMIO region");
+        let vaddr = mem.addr() + offset;
+
+        MMIO_MAPPINGS.lock().push(mem);
+
+        NonNull::new(vaddr.as_mut_ptr()).unwrap()
     }
 
     /// Shares the given memory range with the device, and returns the physical address that the
@@ -4,8 +4,47 @@
 //
 // Author: Oliver Steffen <osteffen@redhat.com>
 
+extern crate alloc;
+
 pub mod devices;
 pub mod error;
 mod hal;
+mod pci;
 
 pub use error::VirtioError;
+pub use pci::pci_init;
+pub use virtio_drivers::transport::pci::bus::DeviceFunction;
+
+use crate::address::PhysAddr;
+use crate::fw_cfg::FwCfg;
+use crate::platform::SVSM_PLATFORM;
+use alloc::vec::Vec;
+
+/// Location of a VirtIO device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VirtioDeviceAddress {
+    /// A virtio-mmio device with its registers at the given address
+    Mmio(PhysAddr),
+    /// A virtio-pci function
+    Pci(DeviceFunction),
+}
+
+/// Finds the VirtIO devices of the SVSM: the VirtIO functions on the PCI
+/// host bridge reserved for the SVSM, followed by the virtio-mmio devices in the hardware-info list
+/// of the fw_cfg interface.
+pub fn virtio_devices() -> Vec<VirtioDeviceAddress> {
+    let mut devices: Vec<_> = pci::pci_virtio_devices()
+        .into_iter()
+        .map(VirtioDeviceAddress::Pci)
+        .collect();
+
+    let cfg = FwCfg::new(SVSM_PLATFORM.get_io_port());
+    devices.extend(
+        cfg.get_virtio_mmio_addresses()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| VirtioDeviceAddress::Mmio(PhysAddr::from(a))),
+    );
+
+    devices
+}
@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Discovery of VirtIO devices on the PCI bus.
+//!
+//! The SVSM only drives PCI functions behind a host bridge which is reserved
+//! for it. The VMM describes the host bridge to the guest neither in ACPI
+//! nor in the firmware configuration, so guest firmware and drivers do not
+//! find the functions and can not race with the SVSM for them. The ECAM
+//! region of the host bridge is passed in the IGVM parameters, which makes
+//! it part of the launch measurement. Without such a host bridge, the SVSM
+//! uses no PCI functions.
+//!
+//! The SVSM runs before the guest firmware assigns PCI resources, so the
+//! 64-bit memory BARs of the functions are placed into a window above guest
+//! and SVSM memory. Only bus 0 is scanned, because bridges have no bus
+//! numbers assigned. The assignment is checked with [`PciBars::check()`]
+//! before each use of a device and restored when it was changed.
+
+extern crate alloc;
+
+use super::hal::SvsmHal;
+use super::VirtioError;
+use crate::address::{Address, PhysAddr};
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::mm::memory::{memory_end, valid_phys_address};
+use crate::mm::pagetable::max_phys_addr;
+use crate::platform::SVSM_PLATFORM;
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+use virtio_drivers::transport::pci::bus::{
+    BarInfo, Command, ConfigurationAccess, DeviceFunction, MemoryBarType, PciRoot,
+};
+use virtio_drivers::transport::pci::{virtio_device_type, PciTransport};
+use virtio_drivers::transport::DeviceType;
+
+/// Size of the ECAM region of a single bus
+const PCI_ECAM_BUS_SIZE: usize = 1 << 20;
+
+/// Number of BARs in a standard configuration space header
+const PCI_NUM_BARS: u8 = 6;
+/// Offset of the first BAR in configuration space
+const PCI_BAR_OFFSET: u8 = 0x10;
+
+/// Preferred start of the window for memory BARs assigned by the SVSM. It
+/// is above the memory of most guests and below the 40-bit physical address
+/// limit of the default QEMU CPU models.
+const PCI_BAR_WINDOW_START: u64 = 0x80_0000_0000;
+/// Size of the window for memory BARs assigned by the SVSM
+const PCI_BAR_WINDOW_SIZE: u64 = 0x80_0000_0000;
+/// Alignment of the window when it has to be moved above guest memory
+const PCI_BAR_WINDOW_ALIGN: u64 = 1 << 30;
+
+/// Next free address in the BAR window, `None` before the first allocation
+static NEXT_BAR_ADDRESS: SpinLock<Option<u64>> = SpinLock::new(None);
+
+/// ECAM region of bus 0 of the host bridge reserved for the SVSM, `None`
+/// when there is none
+static SVSM_PCI_ECAM: SpinLock<Option<PhysAddr>> = SpinLock::new(None);
+
+/// Sets up access to the PCI host bridge reserved for the SVSM.
+///
+/// # Arguments
+///
+/// * `ecam` - Address of the ECAM region of the host bridge from the IGVM
+///   parameters, if any.
+///
+/// # Returns
+///
+/// `()` on success, or [`SvsmError::InvalidAddress`] when the ECAM region
+/// is not aligned or overlaps guest memory.
+pub fn pci_init(ecam: Option<PhysAddr>) -> Result<(), SvsmError> {
+    let Some(ecam) = ecam else {
+        log::info!("PCI: no host bridge reserved for the SVSM");
+        return Ok(());
+    };
+    let end = ecam
+        .checked_add(PCI_ECAM_BUS_SIZE)
+        .ok_or(SvsmError::InvalidAddress)?;
+    if !ecam.is_aligned(PCI_ECAM_BUS_SIZE)
+        || valid_phys_address(ecam)
+        || valid_phys_address(end - 1)
+    {
+        log::warn!("PCI: invalid ECAM region {ecam:#x} of the SVSM host bridge");
+        return Err(SvsmError::InvalidAddress);
+    }
+    *SVSM_PCI_ECAM.lock() = Some(ecam);
+    Ok(())
+}
+
+/// Configuration space access to bus 0 of the host bridge reserved for the
+/// SVSM, through `SvsmPlatform::mmio_read()` and `SvsmPlatform::mmio_write()`.
+#[derive(Clone, Copy, Debug)]
+struct SvsmConfigurationAccess {
+    ecam: PhysAddr,
+}
+
+impl SvsmConfigurationAccess {
+    fn new() -> Option<Self> {
+        let ecam = (*SVSM_PCI_ECAM.lock())?;
+        Some(Self { ecam })
+    }
+
+    fn ecam_address(
+        &self,
+        device_function: DeviceFunction,
+        register_offset: u8,
+    ) -> Option<PhysAddr> {
+        if device_function.bus != 0 {
+            return None;
+        }
+        let offset = (usize::from(device_function.device) << 15)
+            | (usize::from(device_function.function) << 12)
+            | usize::from(register_offset & 0xfc);
+        Some(self.ecam + offset)
+    }
+}
+
+impl ConfigurationAccess for SvsmConfigurationAccess {
+    fn read_word(&self, device_function: DeviceFunction, register_offset: u8) -> u32 {
+        // Reads outside of bus 0 return all ones, like reads of
+        // non-existent functions.
+        let Some(paddr) = self.ecam_address(device_function, register_offset) else {
+            return u32::MAX;
+        };
+        let mut data = [0u8; 4];
+        // SAFETY: `paddr` is a 4-byte aligned address within the ECAM region
+        // of the SVSM host bridge, which is not guest memory.
+        match unsafe { SVSM_PLATFORM.mmio_read(paddr, &mut data) } {
+            Ok(()) => u32::from_le_bytes(data),
+            Err(_) => u32::MAX,
+        }
+    }
+
+    fn write_word(&mut self, device_function: DeviceFunction, register_offset: u8, data: u32) {
+        let Some(paddr) = self.ecam_address(device_function, register_offset) else {
+            return;
+        };
+        // SAFETY: `paddr` is a 4-byte aligned address within the ECAM region
+        // of the SVSM host bridge, which is not guest memory.
+        if unsafe { SVSM_PLATFORM.mmio_write(paddr, &data.to_le_bytes()) }.is_err() {
+            log::warn!("PCI: failed to write configuration space of {device_function}");
+        }
+    }
+}
+
+/// Returns the window for memory BARs assigned by the SVSM as a pair of
+/// start and end address. The window starts at [`PCI_BAR_WINDOW_START`], or
+/// above guest and SVSM memory when memory extends beyond that, and ends
+/// below the physical address limit.
+fn bar_window(memory_end: u64, max_phys_addr: u64) -> Option<(u64, u64)> {
+    let start = max(
+        PCI_BAR_WINDOW_START,
+        memory_end.checked_next_multiple_of(PCI_BAR_WINDOW_ALIGN)?,
+    );
+    let end = min(start.saturating_add(PCI_BAR_WINDOW_SIZE), max_phys_addr);
+    (start < end).then_some((start, end))
+}
+
+/// Allocates a naturally aligned range of `size` bytes from the BAR window.
+fn allocate_bar(size: u64) -> Result<u64, SvsmError> {
+    let (start, end) =
+        bar_window(u64::from(memory_end()), u64::from(max_phys_addr())).ok_or_else(|| {
+            log::warn!("PCI: no room for a BAR window above guest memory");
+            SvsmError::Mem
+        })?;
+
+    let mut next = NEXT_BAR_ADDRESS.lock();
+    let address = next
+        .unwrap_or(start)
+        .checked_next_multiple_of(size)
+        .ok_or(SvsmError::Mem)?;
+    let bar_end = address
+        .checked_add(size)
+        .filter(|bar_end| *bar_end <= end)
+        .ok_or(SvsmError::Mem)?;
+    *next = Some(bar_end);
+    Ok(address)
+}
+
+/// Reads the address of the 64-bit memory BAR `bar_index` without sizing
+/// it.
+fn read_bar_64(
+    root: &PciRoot<SvsmConfigurationAccess>,
+    device_function: DeviceFunction,
+    bar_index: u8,
+) -> u64 {
+    let offset = PCI_BAR_OFFSET + 4 * bar_index;
+    let low = root.config_read_word(device_function, offset) & !0xf;
+    let high = root.config_read_word(device_function, offset + 4);
+    (u64::from(high) << 32) | u64::from(low)
+}
+
+/// The memory BARs of a function used by the SVSM.
+#[derive(Debug)]
+pub struct PciBars {
+    device_function: DeviceFunction,
+    /// Addresses of the 64-bit memory BARs, indexed by BAR number
+    bars: [Option<u64>; PCI_NUM_BARS as usize],
+}
+
+impl PciBars {
+    fn assigned(&self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        self.bars
+            .iter()
+            .enumerate()
+            .filter_map(|(index, address)| Some((index as u8, (*address)?)))
+    }
+
+    /// Checks that the BARs of the function are still at the addresses the
+    /// SVSM mapped and that memory decoding is enabled. A changed
+    /// configuration is restored.
+    ///
+    /// # Returns
+    ///
+    /// `()` when the function decodes the BARs at the mapped addresses, or
+    /// [`VirtioError::InvalidDevice`] when the configuration can not be
+    /// restored.
+    pub fn check(&self) -> Result<(), SvsmError> {
+        let access = SvsmConfigurationAccess::new().ok_or(VirtioError::InvalidDevice)?;
+        let mut root = PciRoot::new(access);
+        let device_function = self.device_function;
+        let (_, command) = root.get_status_command(device_function);
+        let enabled = Command::MEMORY_SPACE | Command::BUS_MASTER;
+        if command.contains(enabled)
+            && self
+                .assigned()
+                .all(|(index, address)| read_bar_64(&root, device_function, index) == address)
+        {
+            return Ok(());
+        }
+
+        log::warn!("PCI: {device_function} was reconfigured, restoring its BARs");
+        root.set_command(
+            device_function,
+            command - (Command::IO_SPACE | Command::MEMORY_SPACE),
+        );
+        for (index, address) in self.assigned() {
+            root.set_bar_64(device_function, index, address);
+        }
+        root.set_command(device_function, command | enabled);
+
+        let (_, command) = root.get_status_command(device_function);
+        if !command.contains(enabled)
+            || self
+                .assigned()
+                .any(|(index, address)| read_bar_64(&root, device_function, index) != address)
+        {
+            log::warn!("PCI: failed to restore the BARs of {device_function}");
+            return Err(VirtioError::InvalidDevice.into());
+        }
+        Ok(())
+    }
+}
+
+/// Assigns addresses from the BAR window to the 64-bit memory BARs of a
+/// function, including BARs which already have an address. Sizing BARs
+/// temporarily writes all ones to them, so decoding is disabled and left
+/// disabled for the caller to enable.
+fn assign_bars(
+    root: &mut PciRoot<SvsmConfigurationAccess>,
+    device_function: DeviceFunction,
+    command: Command,
+) -> Result<PciBars, SvsmError> {
+    root.set_command(
+        device_function,
+        command - (Command::IO_SPACE | Command::MEMORY_SPACE),
+    );
+
+    let mut bars = PciBars {
+        device_function,
+        bars: [None; PCI_NUM_BARS as usize],
+    };
+    let mut bar_index = 0;
+    while bar_index < PCI_NUM_BARS {
+        let bar = root
+            .bar_info(device_function, bar_index)
+            .map_err(|_| VirtioError::InvalidDevice)?;
+        match bar {
+            Some(BarInfo::Memory {
+                address_type: MemoryBarType::Width64,
+                size,
+                ..
+            }) => {
+                let address = allocate_bar(size)?;
+                root.set_bar_64(device_function, bar_index, address);
+                bars.bars[usize::from(bar_index)] = Some(address);
+                log::info!(
+                    "PCI: {device_function} BAR{bar_index} assigned to {address:#x}, size {size:#x}"
+                );
+            }
+            Some(BarInfo::Memory { address: 0, .. }) => {
+                log::warn!("PCI: {device_function} BAR{bar_index} is unassigned");
+            }
+            _ => {}
+        }
+        bar_index += match bar {
+            Some(bar) if bar.takes_two_entries() => 2,
+            _ => 1,
+        };
+    }
+
+    Ok(bars)
+}
+
+/// Walks bus 0 of the host bridge reserved for the SVSM for VirtIO devices.
+///
+/// # Returns
+///
+/// The bus, device and function numbers of all VirtIO functions found,
+/// which is none without a host bridge for the SVSM.
+pub fn pci_virtio_devices() -> Vec<DeviceFunction> {
+    let Some(access) = SvsmConfigurationAccess::new() else {
+        return Vec::new();
+    };
+    let root = PciRoot::new(access);
+    root.enumerate_bus(0)
+        .filter(|(_, info)| virtio_device_type(info).is_some())
+        .map(|(device_function, _)| device_function)
+        .collect()
+}
+
+/// Creates a transport for the VirtIO function `device_function` on the host
+/// bridge reserved for the SVSM and assigns its BARs.
+///
+/// # Returns
+///
+/// The transport and the BARs to check before each use of the device, or
+/// [`VirtioError::InvalidDeviceType`] when the device is not of type
+/// `device_type`.
+pub fn pci_transport(
+    device_function: DeviceFunction,
+    device_type: DeviceType,
+) -> Result<(PciTransport<SvsmHal>, PciBars), SvsmError> {
+    let access = SvsmConfigurationAccess::new().ok_or(VirtioError::InvalidDevice)?;
+    let mut root = PciRoot::new(access);
+    let info = root
+        .device_function_info(device_function)
+        .ok_or(VirtioError::InvalidDevice)?;
+    if virtio_device_type(&info) != Some(device_type) {
+        return Err(VirtioError::InvalidDeviceType.into());
+    }
+
+    let (_, command) = root.get_status_command(device_function);
+    let bars = assign_bars(&mut root, device_function, command)?;
+
+    let transport = PciTransport::new(&mut root, device_function);
+    root.set_command(
+        device_function,
+        command | Command::MEMORY_SPACE | Command::BUS_MASTER,
+    );
+
+    let transport = transport.map_err(|e| {
+        log::warn!("PCI: no VirtIO transport for {device_function}: {e}");
+        VirtioError::InvalidDevice
+    })?;
+    Ok((transport, bars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_window() {
+        const GIB: u64 = 1 << 30;
+        assert_eq!(
+            bar_window(4 * GIB, 1 << 48),
+            Some((
+                PCI_BAR_WINDOW_START,
+                PCI_BAR_WINDOW_START + PCI_BAR_WINDOW_SIZE
+            ))
+        );
+        // Moved above large guests
+        assert_eq!(
+            bar_window(PCI_BAR_WINDOW_START + 1, 1 << 48),
+            Some((
+                PCI_BAR_WINDOW_START + GIB,
+                PCI_BAR_WINDOW_START + GIB + PCI_BAR_WINDOW_SIZE
+            ))
+        );
+        // Limited by the physical address width
+        assert_eq!(
+            bar_window(4 * GIB, 1 << 40),
+            Some((PCI_BAR_WINDOW_START, 1 << 40))
+        );
+        assert_eq!(bar_window(1 << 40, 1 << 40), None);
+        assert_eq!(bar_window(u64::MAX, 1 << 48), None);
+    }
+}
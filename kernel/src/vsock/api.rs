@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use crate::error::SvsmError;
+
+/// Address of a vsock endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VsockAddr {
+    /// Context ID of the VM or of the host.
+    pub cid: u64,
+    /// Port number.
+    pub port: u32,
+}
+
+/// Stream socket operations of a vsock device. A connection is identified
+/// by the address of the remote endpoint and the local port. All operations
+/// block until they complete, or fail with
+/// [`VsockError::TimedOut`](super::VsockError::TimedOut) when the peer does
+/// not answer in time.
+pub trait VsockDriver: Send + Sync + core::fmt::Debug {
+    /// Returns the context ID of the SVSM.
+    fn guest_cid(&self) -> u64;
+
+    /// Connects `local_port` to `remote` and waits until the peer accepted
+    /// or refused the connection.
+    fn connect(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError>;
+
+    /// Receives data on the connection. Waits until at least one byte is
+    /// available and returns 0 when the peer closed the connection.
+    fn recv(&self, remote: VsockAddr, local_port: u32, buf: &mut [u8]) -> Result<usize, SvsmError>;
+
+    /// Sends all of `buf` on the connection, waiting for the peer to free
+    /// buffer space when necessary.
+    fn send(&self, remote: VsockAddr, local_port: u32, buf: &[u8]) -> Result<usize, SvsmError>;
+
+    /// Shuts down both directions of the connection.
+    fn shutdown(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError>;
+}
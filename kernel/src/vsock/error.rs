@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsockError {
+    /// No vsock device has been found.
+    NoDevice,
+    /// The peer refused the connection request.
+    ConnectionRefused,
+    /// The connection has been closed.
+    NotConnected,
+    /// No free local port for a connection to the peer.
+    AddressInUse,
+    /// The device failed to process a request.
+    Failed,
+    /// The peer did not answer in time.
+    TimedOut,
+}
@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Stream sockets over a virtio-vsock device, used to communicate with the
+//! host without emulated serial ports.
+//!
+//! The first virtio-vsock device found on the PCI bus or in the
+//! hardware-info list of the fw_cfg interface is used. Connections are only
+//! initiated by the SVSM, incoming connection requests from the host are
+//! refused. The device has no interrupt support, so blocking operations poll
+//! it and yield the CPU to other tasks while waiting.
+
+extern crate alloc;
+
+pub mod api;
+pub mod error;
+#[cfg(feature = "virtio-drivers")]
+pub mod virtio_vsock;
+
+pub use api::{VsockAddr, VsockDriver};
+pub use error::VsockError;
+
+use crate::error::SvsmError;
+use crate::fs::Buffer;
+use crate::locking::SpinLock;
+use crate::syscall::Obj;
+use crate::utils::immut_after_init::ImmutAfterInitCell;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use core::cmp::min;
+
+/// Well-known context ID of the host
+pub const VSOCK_CID_HOST: u64 = 2;
+
+/// First local port assigned to outgoing connections
+const VSOCK_FIRST_LOCAL_PORT: u32 = 1024;
+
+/// Number of local ports tried for a connection when the device still
+/// knows a closed connection with the same address
+const VSOCK_CONNECT_ATTEMPTS: usize = 8;
+
+/// Maximum number of bytes copied through a kernel buffer at once
+const VSOCK_COPY_SIZE: usize = 4096;
+
+static VSOCK_DEVICE: ImmutAfterInitCell<Box<dyn VsockDriver>> = ImmutAfterInitCell::uninit();
+
+static LOCAL_PORTS: SpinLock<LocalPorts> = SpinLock::new(LocalPorts::new());
+
+/// Local ports of the open connections
+#[derive(Debug)]
+struct LocalPorts {
+    /// Port tried first by the next allocation
+    next: u32,
+    used: BTreeSet<u32>,
+}
+
+impl LocalPorts {
+    const fn new() -> Self {
+        Self {
+            next: VSOCK_FIRST_LOCAL_PORT,
+            used: BTreeSet::new(),
+        }
+    }
+
+    /// Allocates a local port which is not used by another connection.
+    fn alloc(&mut self) -> Result<u32, SvsmError> {
+        // One of the next `used.len() + 1` ports is free.
+        for _ in 0..=self.used.len() {
+            let port = self.next;
+            self.next = port.checked_add(1).unwrap_or(VSOCK_FIRST_LOCAL_PORT);
+            if self.used.insert(port) {
+                return Ok(port);
+            }
+        }
+        Err(VsockError::AddressInUse.into())
+    }
+
+    fn free(&mut self, port: u32) {
+        self.used.remove(&port);
+    }
+}
+
+impl From<VsockError> for SvsmError {
+    fn from(err: VsockError) -> Self {
+        Self::Vsock(err)
+    }
+}
+
+#[cfg(feature = "virtio-drivers")]
+fn vsock_device() -> Result<Box<dyn VsockDriver>, SvsmError> {
+    use crate::virtio::virtio_devices;
+    use virtio_vsock::VirtIOVsockDriver;
+
+    virtio_devices()
+        .into_iter()
+        .find_map(|a| VirtIOVsockDriver::new(a).ok())
+        .map(|dev| Box::new(dev) as Box<dyn VsockDriver>)
+        .ok_or(VsockError::NoDevice.into())
+}
+
+#[cfg(not(feature = "virtio-drivers"))]
+fn vsock_device() -> Result<Box<dyn VsockDriver>, SvsmError> {
+    Err(VsockError::NoDevice.into())
+}
+
+/// Initializes the first virtio-vsock device found.
+///
+/// # Returns
+///
+/// `()` on success, or [`VsockError::NoDevice`] when no vsock device is
+/// available.
+pub fn vsock_init() -> Result<(), SvsmError> {
+    let device = vsock_device()?;
+    log::info!("vsock: using device with guest CID {}", device.guest_cid());
+    VSOCK_DEVICE
+        .init(device)
+        .map_err(|_| SvsmError::Vsock(VsockError::Failed))
+}
+
+/// A connected stream socket. The connection is shut down when the socket
+/// is dropped.
+#[derive(Debug)]
+pub struct VsockStream {
+    remote: VsockAddr,
+    local_port: u32,
+    device: &'static dyn VsockDriver,
+}
+
+impl VsockStream {
+    /// Connects to port `port` of the VM or host with context ID `cid`.
+    ///
+    /// # Returns
+    ///
+    /// The connected socket, or [`VsockError::NoDevice`] when no vsock
+    /// device is available, [`VsockError::ConnectionRefused`] when the
+    /// peer refused the connection and [`VsockError::AddressInUse`] when no
+    /// local port is available.
+    pub fn connect(cid: u64, port: u32) -> Result<Self, SvsmError> {
+        let device = VSOCK_DEVICE
+            .try_get_inner()
+            .map_err(|_| SvsmError::Vsock(VsockError::NoDevice))?;
+        let remote = VsockAddr { cid, port };
+
+        // A port of a dropped stream can still be in use by the device until
+        // the peer acknowledged the shutdown, so try the next port then.
+        for _ in 0..VSOCK_CONNECT_ATTEMPTS {
+            let local_port = LOCAL_PORTS.lock().alloc()?;
+            match device.connect(remote, local_port) {
+                Ok(()) => {
+                    return Ok(Self {
+                        remote,
+                        local_port,
+                        device: &**device,
+                    })
+                }
+                Err(e) => {
+                    LOCAL_PORTS.lock().free(local_port);
+                    if !matches!(e, SvsmError::Vsock(VsockError::AddressInUse)) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Err(VsockError::AddressInUse.into())
+    }
+
+    /// Returns the address of the remote endpoint.
+    pub fn remote(&self) -> VsockAddr {
+        self.remote
+    }
+
+    /// Returns the local port of the connection.
+    pub fn local_port(&self) -> u32 {
+        self.local_port
+    }
+
+    /// Receives data from the peer. Blocks until data is available.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes received, 0 when the peer closed the connection.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize, SvsmError> {
+        self.device.recv(self.remote, self.local_port, buf)
+    }
+
+    /// Sends all of `buf` to the peer.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes sent, or [`VsockError::NotConnected`] when the
+    /// connection has been closed.
+    pub fn send(&self, buf: &[u8]) -> Result<usize, SvsmError> {
+        self.device.send(self.remote, self.local_port, buf)
+    }
+
+    /// Receives data from the peer into a [`Buffer`], e.g. a buffer in
+    /// user-mode memory.
+    pub fn recv_buffer(&self, buffer: &mut dyn Buffer) -> Result<usize, SvsmError> {
+        let mut data = vec![0u8; min(buffer.size(), VSOCK_COPY_SIZE)];
+        let len = self.recv(&mut data)?;
+        buffer.write_buffer(&data[..len], 0)
+    }
+
+    /// Sends the contents of a [`Buffer`] to the peer.
+    pub fn send_buffer(&self, buffer: &dyn Buffer) -> Result<usize, SvsmError> {
+        let size = buffer.size();
+        let mut data = vec![0u8; min(size, VSOCK_COPY_SIZE)];
+        let mut offset = 0;
+        while offset < size {
+            let len = buffer.read_buffer(&mut data, offset)?;
+            self.send(&data[..len])?;
+            offset += len;
+        }
+        Ok(size)
+    }
+}
+
+impl Drop for VsockStream {
+    fn drop(&mut self) {
+        // Fails when the peer already closed the connection.
+        let _ = self.device.shutdown(self.remote, self.local_port);
+        LOCAL_PORTS.lock().free(self.local_port);
+    }
+}
+
+impl Obj for VsockStream {
+    fn as_vsock(&self) -> Option<&VsockStream> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_ports() {
+        let mut ports = LocalPorts::new();
+        assert_eq!(ports.alloc().unwrap(), VSOCK_FIRST_LOCAL_PORT);
+        assert_eq!(ports.alloc().unwrap(), VSOCK_FIRST_LOCAL_PORT + 1);
+
+        // Wrap around and skip the ports which are still in use.
+        ports.next = u32::MAX;
+        assert_eq!(ports.alloc().unwrap(), u32::MAX);
+        assert_eq!(ports.alloc().unwrap(), VSOCK_FIRST_LOCAL_PORT + 2);
+
+        ports.free(VSOCK_FIRST_LOCAL_PORT);
+        ports.next = VSOCK_FIRST_LOCAL_PORT;
+        assert_eq!(ports.alloc().unwrap(), VSOCK_FIRST_LOCAL_PORT);
+        assert_eq!(ports.alloc().unwrap(), VSOCK_FIRST_LOCAL_PORT + 3);
+    }
+}
@@ -0,0 +1,468 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+extern crate alloc;
+
+use super::api::{VsockAddr, VsockDriver};
+use super::VsockError;
+use crate::cpu::msr::rdtsc;
+use crate::cpu::x86::{apic_timer_counts_to_tsc, apic_timer_frequency};
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::task::schedule;
+use crate::types::PAGE_SIZE;
+use crate::virtio::devices::VirtIOVsockDevice;
+use crate::virtio::VirtioDeviceAddress;
+use alloc::boxed::Box;
+use virtio_drivers::device::socket::{self, SocketError, VsockConnectionManager};
+use virtio_drivers::transport::Transport;
+use virtio_drivers::{Error, Hal};
+
+/// Receive buffer space of each connection in bytes
+const VSOCK_BUFFER_CAPACITY: u32 = 64 * 1024;
+
+/// Time in seconds an operation waits for the peer before it fails
+const VSOCK_TIMEOUT_SECONDS: u64 = 30;
+
+/// Number of polls an operation waits for the peer when the TSC frequency
+/// is unknown
+const VSOCK_MAX_POLLS: u64 = 1 << 24;
+
+pub struct VirtIOVsockDriver(Box<VirtIOVsockDevice>);
+
+impl core::fmt::Debug for VirtIOVsockDriver {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VirtIOVsockDriver").finish()
+    }
+}
+
+impl VirtIOVsockDriver {
+    pub fn new(address: VirtioDeviceAddress) -> Result<Self, SvsmError> {
+        Ok(VirtIOVsockDriver(VirtIOVsockDevice::new(
+            address,
+            VSOCK_BUFFER_CAPACITY,
+        )?))
+    }
+}
+
+fn vsock_error(err: Error) -> SvsmError {
+    match err {
+        Error::SocketDeviceError(SocketError::NotConnected) => VsockError::NotConnected.into(),
+        Error::SocketDeviceError(SocketError::ConnectionExists) => VsockError::AddressInUse.into(),
+        _ => VsockError::Failed.into(),
+    }
+}
+
+fn socket_addr(addr: VsockAddr) -> socket::VsockAddr {
+    socket::VsockAddr {
+        cid: addr.cid,
+        port: addr.port,
+    }
+}
+
+/// Processes all pending events of the device. Events for other connections
+/// are buffered by the connection manager.
+fn poll_events<H: Hal, T: Transport>(dev: &mut VsockConnectionManager<H, T>) {
+    loop {
+        match dev.poll() {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(e) => log::warn!("vsock: failed to process event: {e}"),
+        }
+    }
+}
+
+/// Tracks how long an operation has been waiting for the peer.
+struct Deadline {
+    ticks: Option<u64>,
+    start: u64,
+    polls: u64,
+}
+
+impl Deadline {
+    fn new(ticks: Option<u64>) -> Self {
+        Self {
+            ticks,
+            start: rdtsc(),
+            polls: 0,
+        }
+    }
+
+    /// Returns whether the operation waited for too long. Without a TSC
+    /// timeout, the number of polls is limited instead.
+    fn expired(&mut self) -> bool {
+        self.polls += 1;
+        match self.ticks {
+            Some(ticks) => rdtsc().wrapping_sub(self.start) > ticks,
+            None => self.polls > VSOCK_MAX_POLLS,
+        }
+    }
+}
+
+/// Blocking stream socket operations on the connections of a vsock device.
+/// Operations fail with [`VsockError::TimedOut`] when the peer does not
+/// answer in time.
+struct Connections<'a, H: Hal, T: Transport> {
+    device: &'a SpinLock<VsockConnectionManager<H, T>>,
+    /// Called while an operation waits for the device or the peer
+    wait: fn(),
+    /// TSC ticks an operation waits for the peer, if known
+    timeout: Option<u64>,
+}
+
+impl<H: Hal, T: Transport> Connections<'_, H, T>
+where
+    VsockConnectionManager<H, T>: Send,
+{
+    fn connect(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError> {
+        let peer = socket_addr(remote);
+        self.device
+            .lock()
+            .connect(peer, local_port)
+            .map_err(vsock_error)?;
+
+        let mut deadline = Deadline::new(self.timeout);
+        loop {
+            let established = self.device.locked_do(|dev| {
+                poll_events(dev);
+                dev.is_established(peer, local_port)
+            });
+            match established {
+                Ok(true) => return Ok(()),
+                Ok(false) if deadline.expired() => {
+                    // Reset the pending connection to free the local port.
+                    let _ = self.device.lock().force_close(peer, local_port);
+                    return Err(VsockError::TimedOut.into());
+                }
+                Ok(false) => (self.wait)(),
+                Err(_) => return Err(VsockError::ConnectionRefused.into()),
+            }
+        }
+    }
+
+    fn recv(&self, remote: VsockAddr, local_port: u32, buf: &mut [u8]) -> Result<usize, SvsmError> {
+        let peer = socket_addr(remote);
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut deadline = Deadline::new(self.timeout);
+        loop {
+            let received = self.device.locked_do(|dev| {
+                poll_events(dev);
+                match dev.recv_buffer_available_bytes(peer, local_port) {
+                    // The connection is gone after the peer closed it and all
+                    // data was read.
+                    Err(_) => Some(Ok(0)),
+                    Ok(0) => None,
+                    Ok(_) => {
+                        let len = dev.recv(peer, local_port, buf).map_err(vsock_error);
+                        // Tell the peer about the freed buffer space. This
+                        // fails when the connection was closed by the read.
+                        let _ = dev.update_credit(peer, local_port);
+                        Some(len)
+                    }
+                }
+            });
+            match received {
+                Some(result) => return result,
+                None if deadline.expired() => return Err(VsockError::TimedOut.into()),
+                None => (self.wait)(),
+            }
+        }
+    }
+
+    fn send(&self, remote: VsockAddr, local_port: u32, buf: &[u8]) -> Result<usize, SvsmError> {
+        let peer = socket_addr(remote);
+
+        // Buffers passed to the device are limited to one page.
+        for chunk in buf.chunks(PAGE_SIZE) {
+            let mut deadline = Deadline::new(self.timeout);
+            loop {
+                let result = self.device.locked_do(|dev| {
+                    poll_events(dev);
+                    dev.send(peer, local_port, chunk)
+                });
+                match result {
+                    Ok(()) => break,
+                    Err(Error::SocketDeviceError(SocketError::InsufficientBufferSpaceInPeer)) => {
+                        if deadline.expired() {
+                            return Err(VsockError::TimedOut.into());
+                        }
+                        (self.wait)()
+                    }
+                    Err(e) => return Err(vsock_error(e)),
+                }
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn shutdown(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError> {
+        self.device
+            .lock()
+            .shutdown(socket_addr(remote), local_port)
+            .map_err(vsock_error)
+    }
+}
+
+impl VirtIOVsockDriver {
+    fn connections(&self) -> Connections<'_, impl Hal, impl Transport> {
+        Connections {
+            device: &self.0.device,
+            wait: schedule,
+            timeout: apic_timer_counts_to_tsc(apic_timer_frequency() * VSOCK_TIMEOUT_SECONDS),
+        }
+    }
+}
+
+impl VsockDriver for VirtIOVsockDriver {
+    fn guest_cid(&self) -> u64 {
+        self.0.device.lock().guest_cid()
+    }
+
+    fn connect(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError> {
+        self.0.check()?;
+        self.connections().connect(remote, local_port)
+    }
+
+    fn recv(&self, remote: VsockAddr, local_port: u32, buf: &mut [u8]) -> Result<usize, SvsmError> {
+        self.0.check()?;
+        self.connections().recv(remote, local_port, buf)
+    }
+
+    fn send(&self, remote: VsockAddr, local_port: u32, buf: &[u8]) -> Result<usize, SvsmError> {
+        self.0.check()?;
+        self.connections().send(remote, local_port, buf)
+    }
+
+    fn shutdown(&self, remote: VsockAddr, local_port: u32) -> Result<(), SvsmError> {
+        self.0.check()?;
+        self.connections().shutdown(remote, local_port)
+    }
+}
+
+#[cfg(all(test, not(test_in_svsm)))]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::ptr::NonNull;
+    use std::sync::Mutex;
+    use std::thread;
+    use virtio_drivers::device::socket::{
+        SocketType, VirtIOSocket, VirtioVsockConfig, VirtioVsockHdr, VirtioVsockOp, QUEUE_SIZE,
+        RX_QUEUE_IDX, TX_QUEUE_IDX,
+    };
+    use virtio_drivers::transport::fake::{FakeTransport, QueueStatus, State};
+    use virtio_drivers::transport::DeviceType;
+    use virtio_drivers::{FakeHal, ReadOnly};
+    use zerocopy::{FromBytes, IntoBytes};
+
+    const GUEST_CID: u64 = 66;
+    const HOST: VsockAddr = VsockAddr { cid: 2, port: 1234 };
+    const LOCAL_PORT: u32 = 4321;
+
+    type FakeManager = VsockConnectionManager<FakeHal, FakeTransport<VirtioVsockConfig>>;
+
+    /// Creates a connection manager on a fake device. The returned state is
+    /// used to play the host side of the device.
+    fn fake_device(config: &mut VirtioVsockConfig) -> (SpinLock<FakeManager>, Arc<Mutex<State>>) {
+        let state = Arc::new(Mutex::new(State {
+            queues: vec![
+                QueueStatus::default(),
+                QueueStatus::default(),
+                QueueStatus::default(),
+            ],
+            ..Default::default()
+        }));
+        let transport = FakeTransport {
+            device_type: DeviceType::Socket,
+            max_queue_size: 32,
+            device_features: 0,
+            config_space: NonNull::from(config),
+            state: state.clone(),
+        };
+        let socket = VirtIOSocket::new(transport).unwrap();
+        (SpinLock::new(VsockConnectionManager::new(socket)), state)
+    }
+
+    fn connections(
+        device: &SpinLock<FakeManager>,
+    ) -> Connections<'_, FakeHal, FakeTransport<VirtioVsockConfig>> {
+        Connections {
+            device,
+            wait: thread::yield_now,
+            timeout: None,
+        }
+    }
+
+    /// Waits for the next packet of the guest.
+    fn host_recv(state: &Mutex<State>) -> (VirtioVsockHdr, Vec<u8>) {
+        State::wait_until_queue_notified(state, TX_QUEUE_IDX);
+        let packet = state
+            .lock()
+            .unwrap()
+            .read_from_queue::<QUEUE_SIZE>(TX_QUEUE_IDX);
+        let (hdr, data) = VirtioVsockHdr::read_from_prefix(&packet).unwrap();
+        assert_eq!(hdr.source().port, LOCAL_PORT);
+        assert_eq!(hdr.destination().cid, HOST.cid);
+        assert_eq!(hdr.destination().port, HOST.port);
+        (hdr, data.to_vec())
+    }
+
+    fn host_send(state: &Mutex<State>, op: VirtioVsockOp, data: &[u8]) {
+        let hdr = VirtioVsockHdr {
+            src_cid: HOST.cid.into(),
+            dst_cid: GUEST_CID.into(),
+            src_port: HOST.port.into(),
+            dst_port: LOCAL_PORT.into(),
+            len: (data.len() as u32).into(),
+            socket_type: SocketType::Stream.into(),
+            op: op.into(),
+            buf_alloc: 4096.into(),
+            ..Default::default()
+        };
+        let mut packet = Vec::from(hdr.as_bytes());
+        packet.extend_from_slice(data);
+        state
+            .lock()
+            .unwrap()
+            .write_to_queue::<QUEUE_SIZE>(RX_QUEUE_IDX, &packet);
+    }
+
+    #[test]
+    fn test_vsock_connect_send_recv() {
+        let mut config = VirtioVsockConfig {
+            guest_cid_low: ReadOnly::new(GUEST_CID as u32),
+            guest_cid_high: ReadOnly::new(0),
+        };
+        let (device, state) = fake_device(&mut config);
+
+        let host = thread::spawn(move || {
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Request);
+            host_send(&state, VirtioVsockOp::Response, &[]);
+
+            let (hdr, data) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Rw);
+            assert_eq!(data, b"ping");
+            host_send(&state, VirtioVsockOp::Rw, b"pong");
+
+            // The guest returns the credit after reading the data.
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::CreditUpdate);
+            assert_eq!(u32::from(hdr.fwd_cnt), 4);
+
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Shutdown);
+        });
+
+        let conns = connections(&device);
+        conns.connect(HOST, LOCAL_PORT).unwrap();
+        // The local port is taken by the connection.
+        assert!(matches!(
+            conns.connect(HOST, LOCAL_PORT),
+            Err(SvsmError::Vsock(VsockError::AddressInUse))
+        ));
+
+        assert_eq!(conns.send(HOST, LOCAL_PORT, b"ping").unwrap(), 4);
+        let mut buf = [0u8; 16];
+        let len = conns.recv(HOST, LOCAL_PORT, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"pong");
+        assert_eq!(conns.recv(HOST, LOCAL_PORT, &mut []).unwrap(), 0);
+
+        conns.shutdown(HOST, LOCAL_PORT).unwrap();
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn test_vsock_connection_refused() {
+        let mut config = VirtioVsockConfig {
+            guest_cid_low: ReadOnly::new(GUEST_CID as u32),
+            guest_cid_high: ReadOnly::new(0),
+        };
+        let (device, state) = fake_device(&mut config);
+
+        let host = thread::spawn(move || {
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Request);
+            host_send(&state, VirtioVsockOp::Rst, &[]);
+        });
+
+        let conns = connections(&device);
+        assert!(matches!(
+            conns.connect(HOST, LOCAL_PORT),
+            Err(SvsmError::Vsock(VsockError::ConnectionRefused))
+        ));
+        assert!(matches!(
+            conns.send(HOST, LOCAL_PORT, b"ping"),
+            Err(SvsmError::Vsock(VsockError::NotConnected))
+        ));
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn test_vsock_recv_after_peer_shutdown() {
+        let mut config = VirtioVsockConfig {
+            guest_cid_low: ReadOnly::new(GUEST_CID as u32),
+            guest_cid_high: ReadOnly::new(0),
+        };
+        let (device, state) = fake_device(&mut config);
+
+        let host = thread::spawn(move || {
+            host_recv(&state);
+            host_send(&state, VirtioVsockOp::Response, &[]);
+            host_send(&state, VirtioVsockOp::Rw, b"bye");
+            host_send(&state, VirtioVsockOp::Shutdown, &[]);
+            // Credit update of the guest after reading the data
+            host_recv(&state);
+        });
+
+        let conns = connections(&device);
+        conns.connect(HOST, LOCAL_PORT).unwrap();
+        let mut buf = [0u8; 16];
+        let len = conns.recv(HOST, LOCAL_PORT, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"bye");
+        assert_eq!(conns.recv(HOST, LOCAL_PORT, &mut buf).unwrap(), 0);
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn test_vsock_connect_timeout() {
+        let mut config = VirtioVsockConfig {
+            guest_cid_low: ReadOnly::new(GUEST_CID as u32),
+            guest_cid_high: ReadOnly::new(0),
+        };
+        let (device, state) = fake_device(&mut config);
+
+        let host = thread::spawn(move || {
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Request);
+            // The host never answers, so the guest resets the connection.
+            let (hdr, _) = host_recv(&state);
+            assert_eq!(hdr.op().unwrap(), VirtioVsockOp::Rst);
+        });
+
+        let conns = Connections {
+            device: &device,
+            wait: thread::yield_now,
+            timeout: Some(1_000_000),
+        };
+        assert!(matches!(
+            conns.connect(HOST, LOCAL_PORT),
+            Err(SvsmError::Vsock(VsockError::TimedOut))
+        ));
+        host.join().unwrap();
+
+        // The pending connection was removed.
+        assert!(matches!(conns.recv(HOST, LOCAL_PORT, &mut [0u8; 4]), Ok(0)));
+    }
+}
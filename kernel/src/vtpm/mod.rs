@@ -7,6 +7,8 @@
 //! This crate defines the Virtual TPM interfaces and shows what
 //! TPM backends are supported
 
+/// Persistent storage of the NV state
+pub mod nvstore;
 /// TPM 2.0 Reference Implementation
 pub mod tcgtpm;
 
@@ -14,7 +16,9 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 
-use crate::vtpm::tcgtpm::TcgTpm as Vtpm;
+use crate::error::SvsmError;
+use crate::vtpm::nvstore::{nv_store_init, nv_store_update};
+use crate::vtpm::tcgtpm::{TcgTpm as Vtpm, TPM_NV_MEMORY_SIZE};
 use crate::{locking::LockGuard, protocols::vtpm::TpmPlatformCommand};
 use crate::{locking::SpinLock, protocols::errors::SvsmReqError};
 
@@ -85,9 +89,40 @@ pub trait VtpmInterface: TcgTpmSimulatorInterface {
     /// Check if the TPM is powered on.
     fn is_powered_on(&self) -> bool;
 
-    /// Prepare the TPM to be used for the first time. At this stage,
-    /// the TPM is manufactured.
-    fn init(&mut self) -> Result<(), SvsmReqError>;
+    /// Prepare the TPM to be used. The TPM is manufactured, unless a
+    /// previously saved NV state is provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `nv_state`: NV state obtained from [`Self::read_nv_state()`] before
+    ///   the last reboot, if any.
+    fn init(&mut self, nv_state: Option<&[u8]>) -> Result<(), SvsmReqError>;
+
+    /// Returns a copy of the NV memory of the TPM.
+    fn read_nv_state(&self) -> Result<Vec<u8>, SvsmReqError>;
+
+    /// Replaces the NV memory of the TPM and powers it on again, e.g. to
+    /// take over the state of a migrated TPM.
+    ///
+    /// # Arguments
+    ///
+    /// * `nv_state`: NV state obtained from [`Self::read_nv_state()`].
+    fn restore(&mut self, nv_state: &[u8]) -> Result<(), SvsmReqError>;
+
+    /// Saves the volatile state of the TPM, e.g. the PCRs, into its NV
+    /// memory, like an orderly shutdown with TPM2_Shutdown(TPM_SU_STATE).
+    /// The TPM keeps running.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the state was saved, `false` when the TPM was not started
+    /// yet and has no volatile state.
+    fn save_state(&self) -> Result<bool, SvsmReqError>;
+
+    /// Resumes the TPM from the volatile state saved with
+    /// [`Self::save_state()`] after its NV memory was restored with
+    /// [`Self::restore()`].
+    fn resume(&mut self) -> Result<(), SvsmReqError>;
 
     /// Returns the cached EK public key if it exists, otherwise it returns an error indicating
     /// that the EK public key does not exist.
@@ -98,14 +133,62 @@ pub trait VtpmInterface: TcgTpmSimulatorInterface {
 static VTPM: SpinLock<Vtpm> = SpinLock::new(Vtpm::new());
 
 /// Initialize the TPM by calling the init() implementation of the
-/// [`VtpmInterface`]
-pub fn vtpm_init() -> Result<(), SvsmReqError> {
+/// [`VtpmInterface`]. The NV state is restored from persistent storage when
+/// the platform provides it.
+///
+/// # Arguments
+///
+/// * `nv_min_generation` - Lowest acceptable generation of the persistent
+///   NV state.
+pub fn vtpm_init(nv_min_generation: u64) -> Result<(), SvsmReqError> {
     let mut vtpm = VTPM.lock();
     if vtpm.is_powered_on() {
         return Ok(());
     }
-    vtpm.init()?;
-    Ok(())
+
+    let nv_state = match nv_store_init(TPM_NV_MEMORY_SIZE, nv_min_generation) {
+        Ok(nv_state) => nv_state,
+        Err(SvsmError::NotSupported) => {
+            log::warn!("VTPM: no persistent NV storage, NV state is lost on reboot");
+            None
+        }
+        Err(e) => {
+            log::error!("VTPM: failed to load NV state: {e:?}");
+            return Err(SvsmReqError::incomplete());
+        }
+    };
+
+    vtpm.init(nv_state.as_deref())?;
+    vtpm_sync_nv(&vtpm)
+}
+
+/// Returns the state of the TPM for a migration: its NV memory including the
+/// volatile state. The second value tells whether the volatile state was
+/// saved, which is not the case when the TPM was never started.
+pub fn vtpm_get_migration_state() -> Result<(Vec<u8>, bool), SvsmReqError> {
+    let vtpm = VTPM.lock();
+    let state_saved = vtpm.save_state()?;
+    Ok((vtpm.read_nv_state()?, state_saved))
+}
+
+/// Replaces the state of the TPM with the state of a migrated TPM obtained
+/// from [`vtpm_get_migration_state()`] and resumes it.
+pub fn vtpm_resume(nv_state: &[u8], state_saved: bool) -> Result<(), SvsmReqError> {
+    let mut vtpm = VTPM.lock();
+    vtpm.restore(nv_state)?;
+    if state_saved {
+        vtpm.resume()?;
+    }
+    vtpm_sync_nv(&vtpm)
+}
+
+/// Writes the NV state of the TPM to persistent storage if it changed. This
+/// must be called after each command sent to the TPM.
+pub fn vtpm_sync_nv(vtpm: &Vtpm) -> Result<(), SvsmReqError> {
+    nv_store_update(&vtpm.read_nv_state()?).map_err(|e| {
+        log::error!("VTPM: failed to store NV state: {e:?}");
+        SvsmReqError::incomplete()
+    })
 }
 
 pub fn vtpm_get_locked<'a>() -> LockGuard<'a, Vtpm> {
@@ -116,5 +199,7 @@ pub fn vtpm_get_locked<'a>() -> LockGuard<'a, Vtpm> {
 /// [`VtpmInterface`]
 pub fn vtpm_get_manifest() -> Result<Vec<u8>, SvsmReqError> {
     let mut vtpm = VTPM.lock();
-    vtpm.get_ekpub()
+    let ekpub = vtpm.get_ekpub()?;
+    vtpm_sync_nv(&vtpm)?;
+    Ok(ekpub)
 }
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+//! Persistent storage of the vTPM NV state.
+//!
+//! The NV memory of the TPM is kept in a [`SealedStore`] on a block device,
+//! sealed with a key derived from platform secrets. Without a block device
+//! or a sealing key the vTPM runs with NV state which is lost on reboot.
+//!
+//! The sealing key is bound to the launch measurement of the SVSM, so an
+//! updated SVSM image can not unseal the state of the previous one. The
+//! state has to be carried over with a migration image before the update.
+//!
+//! The host controls the block device and can restore an older copy of the
+//! whole device, which the store itself can not detect. The lowest
+//! acceptable generation of the state is therefore taken from the IGVM
+//! parameters, which are part of the launch measurement, and older state is
+//! refused.
+
+extern crate alloc;
+
+use crate::block::api::BlockDriver;
+use crate::block::sealed::SealedStore;
+use crate::error::SvsmError;
+use crate::locking::SpinLock;
+use crate::platform::SVSM_PLATFORM;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Label for deriving the key which seals the NV state.
+const NV_SEALING_KEY_LABEL: &[u8] = b"svsm-vtpm-nv-state";
+
+#[derive(Debug)]
+struct NvStore {
+    store: SealedStore,
+    /// NV state last written to the store
+    state: Vec<u8>,
+}
+
+static NV_STORE: SpinLock<Option<NvStore>> = SpinLock::new(None);
+
+#[cfg(feature = "virtio-drivers")]
+fn nv_block_device() -> Result<Box<dyn BlockDriver + Send>, SvsmError> {
+    use crate::block::virtio_blk::VirtIOBlkDriver;
+    use crate::virtio::virtio_devices;
+
+    virtio_devices()
+        .into_iter()
+        .find_map(|a| VirtIOBlkDriver::new(a).ok())
+        .map(|dev| Box::new(dev) as Box<dyn BlockDriver + Send>)
+        .ok_or(SvsmError::NotSupported)
+}
+
+#[cfg(not(feature = "virtio-drivers"))]
+fn nv_block_device() -> Result<Box<dyn BlockDriver + Send>, SvsmError> {
+    Err(SvsmError::NotSupported)
+}
+
+/// Opens the persistent storage for the NV state.
+///
+/// # Arguments
+///
+/// * `size` - Size of the NV memory of the TPM.
+/// * `min_generation` - Lowest acceptable generation of the saved state.
+///
+/// # Returns
+///
+/// The saved NV state, or `None` if there is no saved state yet. Fails with
+/// [`SvsmError::NotSupported`] when the platform provides no storage for
+/// the NV state, and with [`BlockDeviceError::Rollback`] when the saved
+/// state is older than `min_generation`.
+///
+/// [`BlockDeviceError::Rollback`]: crate::block::BlockDeviceError::Rollback
+pub fn nv_store_init(size: usize, min_generation: u64) -> Result<Option<Vec<u8>>, SvsmError> {
+    let key = SVSM_PLATFORM
+        .derive_sealing_key(NV_SEALING_KEY_LABEL)
+        .inspect_err(|_| log::info!("VTPM: platform provides no sealing key"))?;
+    let dev = nv_block_device().inspect_err(|_| log::info!("VTPM: no NV block device"))?;
+    let mut store = SealedStore::new(dev, &key, size)?;
+    let state = store.load(min_generation)?;
+
+    *NV_STORE.lock() = Some(NvStore {
+        store,
+        state: state.clone().unwrap_or_default(),
+    });
+    Ok(state)
+}
+
+/// Writes the NV state to persistent storage if it changed since the last
+/// call. Does nothing when there is no persistent storage.
+pub fn nv_store_update(state: &[u8]) -> Result<(), SvsmError> {
+    let mut guard = NV_STORE.lock();
+    let Some(nv) = guard.as_mut() else {
+        return Ok(());
+    };
+    if nv.state == state {
+        return Ok(());
+    }
+
+    nv.store.store(state)?;
+    nv.state.clear();
+    nv.state.extend_from_slice(state);
+    Ok(())
+}
@@ -16,12 +16,14 @@ mod tss;
 
 extern crate alloc;
 
+use alloc::vec;
 use alloc::vec::Vec;
 
 use core::ffi::c_void;
 use libtcgtpm::bindings::{
     TPM_Manufacture, TPM_TearDown, _plat__LocalitySet, _plat__NVDisable, _plat__NVEnable,
-    _plat__RunCommand, _plat__SetNvAvail, _plat__Signal_PowerOn, _plat__Signal_Reset,
+    _plat__NvCommit, _plat__NvMemoryRead, _plat__NvMemoryWrite, _plat__RunCommand,
+    _plat__SetNvAvail, _plat__Signal_PowerOff, _plat__Signal_PowerOn, _plat__Signal_Reset,
 };
 
 use crate::{
@@ -29,8 +31,8 @@ use crate::{
     protocols::{errors::SvsmReqError, vtpm::TpmPlatformCommand},
     types::PAGE_SIZE,
     vtpm::{
-        tcgtpm::ek_templates::DEFAULT_PUBLIC_AREA, TcgTpmSimulatorInterface, VtpmInterface,
-        VtpmProtocolInterface,
+        tcgtpm::ek_templates::DEFAULT_PUBLIC_AREA, SvsmVTpmError, TcgTpmSimulatorInterface,
+        VtpmInterface, VtpmProtocolInterface,
     },
 };
 
@@ -75,8 +77,32 @@ impl TcgTpm {
             }
         }
     }
+
+    fn restore_nv(&self, nv_state: &[u8]) -> Result<(), SvsmReqError> {
+        if nv_state.len() != TPM_NV_MEMORY_SIZE {
+            log::error!("VTPM: invalid NV state size {}", nv_state.len());
+            return Err(SvsmReqError::invalid_parameter());
+        }
+        // _plat__NvMemoryWrite() only reads from the buffer, but does not
+        // declare it `const`.
+        let data_p = nv_state.as_ptr() as *mut c_void;
+        // SAFETY: FFI calls. The buffer is valid for the size of the NV
+        // memory and return values are checked.
+        let ok = unsafe {
+            _plat__NvMemoryWrite(0, TPM_NV_MEMORY_SIZE as u32, data_p) != 0
+                && _plat__NvCommit() == 0
+        };
+        if !ok {
+            log::error!("VTPM: failed to restore NV state");
+            return Err(SvsmReqError::incomplete());
+        }
+        Ok(())
+    }
 }
 
+/// Size of the NV memory of the TPM (`NV_MEMORY_SIZE` in `TpmProfile_Misc.h`)
+pub const TPM_NV_MEMORY_SIZE: usize = 16384;
+
 const TPM_CMDS_SUPPORTED: &[TpmPlatformCommand] = &[TpmPlatformCommand::SendCommand];
 
 impl VtpmProtocolInterface for TcgTpm {
@@ -176,8 +202,63 @@ impl VtpmInterface for TcgTpm {
         self.is_powered_on
     }
 
-    fn init(&mut self) -> Result<(), SvsmReqError> {
-        // Initialize the TPM TCG following the same steps done in the Simulator:
+    fn read_nv_state(&self) -> Result<Vec<u8>, SvsmReqError> {
+        let mut nv_state = vec![0u8; TPM_NV_MEMORY_SIZE];
+        // SAFETY: FFI call. The buffer is valid for the size of the NV
+        // memory and the return value is checked.
+        let ok = unsafe {
+            _plat__NvMemoryRead(
+                0,
+                TPM_NV_MEMORY_SIZE as u32,
+                nv_state.as_mut_ptr().cast::<c_void>(),
+            ) != 0
+        };
+        if !ok {
+            log::error!("VTPM: failed to read NV state");
+            return Err(SvsmReqError::incomplete());
+        }
+        Ok(nv_state)
+    }
+
+    fn restore(&mut self, nv_state: &[u8]) -> Result<(), SvsmReqError> {
+        if self.is_powered_on {
+            // SAFETY: FFI call. No parameters or return value.
+            unsafe { _plat__Signal_PowerOff() };
+            self.is_powered_on = false;
+        }
+        self.ekpub = None;
+
+        self.restore_nv(nv_state)?;
+        self.signal_poweron(false)?;
+        self.signal_nvon()?;
+
+        log::info!("VTPM: TPM 2.0 Reference Implementation restored from NV state");
+        Ok(())
+    }
+
+    fn save_state(&self) -> Result<bool, SvsmReqError> {
+        match tss::shutdown_state(self) {
+            Ok(()) => Ok(true),
+            // The guest did not start the TPM, there is no volatile state
+            Err(SvsmVTpmError::CommandError(tss::TPM_RC_INITIALIZE)) => Ok(false),
+            Err(e) => {
+                log::error!("VTPM: failed to save the volatile state: {e:?}");
+                Err(SvsmReqError::incomplete())
+            }
+        }
+    }
+
+    fn resume(&mut self) -> Result<(), SvsmReqError> {
+        tss::startup_state(self).map_err(|e| {
+            log::error!("VTPM: failed to resume from the saved state: {e:?}");
+            SvsmReqError::incomplete()
+        })
+    }
+
+    fn init(&mut self, nv_state: Option<&[u8]>) -> Result<(), SvsmReqError> {
+        // Initialize the TPM TCG following the same steps done in the Simulator.
+        // When a saved NV state is available, it is restored and the TPM is
+        // only powered on. Otherwise:
         //
         // 1. Manufacture it for the first time
         // 2. Make sure it does not fail if it is re-manufactured
@@ -193,6 +274,10 @@ impl VtpmInterface for TcgTpm {
             return Err(SvsmReqError::incomplete());
         }
 
+        if let Some(nv_state) = nv_state {
+            return self.restore(nv_state);
+        }
+
         rc = self.manufacture(1)?;
         if rc != 0 {
             // SAFETY: FFI call. Parameter checked, no return value.
@@ -17,6 +17,12 @@ use crate::vtpm::{
 use alloc::vec::Vec;
 
 pub const TPM_RC_SUCCESS: u32 = 0;
+/// The TPM is not initialized, i.e. TPM2_Startup was not run yet
+pub const TPM_RC_INITIALIZE: u32 = 0x100;
+
+const TPM_CC_STARTUP: u32 = 0x144;
+const TPM_CC_SHUTDOWN: u32 = 0x145;
+const TPM_SU_STATE: u16 = 0x0001;
 
 // PREREQUISITE: CMD must be at least 10 bytes long.
 // A TPM command result contains
@@ -119,6 +125,41 @@ pub fn checked_send<T: TcgTpmSimulatorInterface>(
     Ok(response)
 }
 
+fn su_state_cmd(command_code: u32) -> Vec<u8> {
+    let mut cmd = Vec::<u8>::with_capacity(12);
+
+    // TPM Command header
+    cmd.extend_from_slice(&[
+        0x80, 0x01, // TPM_ST_NO_SESSIONS
+        0x00, 0x00, 0x00, 0x00, // Placeholder for command size
+    ]);
+    cmd.extend_from_slice(&command_code.to_be_bytes());
+    cmd.extend_from_slice(&TPM_SU_STATE.to_be_bytes());
+    cmd
+}
+
+/// Saves the volatile state of `vtpm`, e.g. the PCRs, into its NV memory
+/// with TPM2_Shutdown(TPM_SU_STATE).
+pub fn shutdown_state<T: TcgTpmSimulatorInterface>(vtpm: &T) -> Result<(), SvsmVTpmError> {
+    checked_send(
+        vtpm,
+        &mut su_state_cmd(TPM_CC_SHUTDOWN),
+        /*set_len=*/ true,
+    )
+    .map(|_| ())
+}
+
+/// Resumes `vtpm` from the volatile state saved by [`shutdown_state()`] with
+/// TPM2_Startup(TPM_SU_STATE).
+pub fn startup_state<T: TcgTpmSimulatorInterface>(vtpm: &T) -> Result<(), SvsmVTpmError> {
+    checked_send(
+        vtpm,
+        &mut su_state_cmd(TPM_CC_STARTUP),
+        /*set_len=*/ true,
+    )
+    .map(|_| ())
+}
+
 /// Uses `vtpm` to create an a primary key on the endorsement hierarchy.
 ///
 /// The key has no authorization policy.
@@ -13,9 +13,13 @@ void _plat__RunCommand(
 void _plat__LocalitySet(unsigned char locality);
 void _plat__SetNvAvail(void);
 int  _plat__Signal_PowerOn(void);
+void _plat__Signal_PowerOff(void);
 int  _plat__Signal_Reset(void);
 void _plat__NVDisable(void *platParameter, size_t paramSize);
 int  _plat__NVEnable(void *platParameter, size_t paramSize);
+int  _plat__NvMemoryRead(unsigned int startOffset, unsigned int size, void *data);
+int  _plat__NvMemoryWrite(unsigned int startOffset, unsigned int size, void *data);
+int  _plat__NvCommit(void);
 
 int  TPM_Manufacture(int firstTime);
 int  TPM_TearDown(void);
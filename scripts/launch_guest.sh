@@ -35,6 +35,7 @@ IGVM_OBJ=""
 
 STATE_DEVICE=""
 STATE_ENABLE=""
+VSOCK_DEVICE=""
 
 while [[ $# -gt 0 ]]; do
   case $1 in
@@ -61,6 +62,13 @@ while [[ $# -gt 0 ]]; do
       shift
       shift
       ;;
+    --vsock)
+      STATE_ENABLE="x-svsm-virtio-mmio=on"
+      VSOCK_DEVICE+="-global virtio-mmio.force-legacy=false "
+      VSOCK_DEVICE+="-device vhost-vsock-device,guest-cid=$2 "
+      shift
+      shift
+      ;;
     -d|--debugserial)
       COM2_SERIAL="-serial pty"
       shift
@@ -187,4 +195,5 @@ $SUDO_CMD \
     $QEMU_EXIT_DEVICE \
     $QEMU_TEST_IO_DEVICE \
     $STATE_DEVICE \
+    $VSOCK_DEVICE \
     "$@"
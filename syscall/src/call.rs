@@ -79,6 +79,7 @@ pub enum SysCallError {
     EEXIST = -9,
     ERDONLY = -10,
     EWRONLY = -11,
+    EPIPE = -12,
     UNKNOWN = -128,
 }
 
@@ -96,6 +97,7 @@ impl From<i32> for SysCallError {
             -9 => SysCallError::EEXIST,
             -10 => SysCallError::ERDONLY,
             -11 => SysCallError::EWRONLY,
+            -12 => SysCallError::EPIPE,
             _ => SysCallError::UNKNOWN,
         }
     }
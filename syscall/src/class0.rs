@@ -4,8 +4,12 @@
 //
 // Author: Chuanxiao Dong <chuanxiao.dong@intel.com>
 
-use super::call::{syscall1, syscall3, SysCallError};
-use super::{SYS_EXEC, SYS_EXIT};
+use super::call::{syscall1, syscall2, syscall3, syscall5, SysCallError};
+use super::{
+    FsObjHandle, MMapFlags, Obj, EXEC_HANDLES_MAX, SYS_EXEC, SYS_EXIT, SYS_MMAP, SYS_MPROTECT,
+    SYS_MUNMAP, SYS_THREAD_CREATE, SYS_THREAD_DETACH, SYS_THREAD_JOIN, SYS_WAIT,
+};
+use core::arch::asm;
 use core::ffi::CStr;
 
 pub fn exit(code: u32) -> ! {
@@ -16,24 +20,205 @@ pub fn exit(code: u32) -> ! {
     unreachable!("Should never return from SYS_EXIT syscall");
 }
 
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Tid(u32);
 
+impl Tid {
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Starts a new process running the ELF binary `file` with `root` as its
+/// root directory. `flags` holds the [`ProcessCaps`](super::ProcessCaps)
+/// granted to the new process.
 pub fn exec(file: &CStr, root: &CStr, flags: u32) -> Result<Tid, SysCallError> {
+    exec_with_handles(file, root, flags, &[])
+}
+
+/// Starts a new process like [`exec`] and passes the objects referenced by
+/// `handles` to it. The new process finds them at consecutive object ids
+/// starting at [`EXEC_HANDLE_BASE`](super::EXEC_HANDLE_BASE). At most
+/// [`EXEC_HANDLES_MAX`] objects can be passed.
+pub fn exec_with_handles(
+    file: &CStr,
+    root: &CStr,
+    flags: u32,
+    handles: &[&dyn Obj],
+) -> Result<Tid, SysCallError> {
+    let mut ids = [0u32; EXEC_HANDLES_MAX];
+    if handles.len() > ids.len() {
+        return Err(SysCallError::EINVAL);
+    }
+    for (id, handle) in ids.iter_mut().zip(handles) {
+        *id = handle.id();
+    }
+
     // SAFETY:
     // 1. SYS_EXEC is a supported syscall number by the svsm kernel.
-    // 2. Parameters `file.as_ptr()` and `root.as_ptr()` are passed as raw pointers.
-    // but the function `sys_exec` which this function delegates to, performs the
-    // necessary checks.
-    // 3. Currently `flags` parameter is unused.
+    // 2. Parameters `file.as_ptr()`, `root.as_ptr()` and `ids.as_ptr()` are
+    // passed as raw pointers, but the function `sys_exec` which this function
+    // delegates to, performs the necessary checks.
+    // 3. `flags` only grants capabilities the calling process has itself.
     unsafe {
-        syscall3(
+        syscall5(
             SYS_EXEC,
             file.as_ptr() as u64,
             root.as_ptr() as u64,
             u64::from(flags),
+            ids.as_ptr() as u64,
+            handles.len() as u64,
         )
         .map(|ret| Tid(ret as u32))
     }
 }
+
+/// Waits for a child process started with [`exec`] to terminate. With
+/// `tid` set to `None` the call waits for any child process.
+///
+/// # Returns
+///
+/// The [`Tid`] and the exit code of the terminated child on success.
+pub fn wait(tid: Option<&Tid>) -> Result<(Tid, u32), SysCallError> {
+    let mut exit_code: u32 = 0;
+    let tid = tid.map_or(0, |tid| tid.0);
+    // SAFETY: SYS_WAIT is a supported syscall number by the svsm kernel. The
+    // kernel writes the exit code to `exit_code`, which is valid for writes.
+    unsafe {
+        syscall2(SYS_WAIT, u64::from(tid), &raw mut exit_code as u64)
+            .map(|ret| (Tid(ret as u32), exit_code))
+    }
+}
+
+/// Creates a new thread in the calling process which starts executing
+/// `entry` with `arg` as its parameter. The thread terminates by calling
+/// [`exit`].
+///
+/// # Safety
+///
+/// `stack` must be the 16-byte aligned top of a memory region which is used
+/// exclusively as the stack of the new thread until it has terminated, and
+/// `entry` must be safe to call with `arg`.
+pub unsafe fn thread_create(
+    entry: extern "C" fn(usize) -> !,
+    stack: usize,
+    arg: usize,
+) -> Result<Tid, SysCallError> {
+    // SAFETY: SYS_THREAD_CREATE is a supported syscall number by the svsm
+    // kernel. The caller guarantees that the stack memory is exclusively used
+    // by the new thread.
+    unsafe {
+        syscall3(
+            SYS_THREAD_CREATE,
+            entry as usize as u64,
+            stack as u64,
+            arg as u64,
+        )
+        .map(|ret| Tid(ret as u32))
+    }
+}
+
+/// Waits for the thread `tid` of the calling process to terminate and
+/// returns its exit code. Every thread can only be joined once.
+pub fn thread_join(tid: &Tid) -> Result<u32, SysCallError> {
+    // SAFETY: SYS_THREAD_JOIN is a supported syscall number by the svsm
+    // kernel. It does not change any memory of the process.
+    unsafe { syscall1(SYS_THREAD_JOIN, u64::from(tid.0)).map(|ret| ret as u32) }
+}
+
+/// Detaches the thread `tid` of the calling process. The exit code of a
+/// detached thread is discarded when it terminates and it can no longer be
+/// joined.
+pub fn thread_detach(tid: Tid) -> Result<(), SysCallError> {
+    // SAFETY: SYS_THREAD_DETACH is a supported syscall number by the svsm
+    // kernel. It does not change any memory of the process.
+    unsafe { syscall1(SYS_THREAD_DETACH, u64::from(tid.0)).map(|_| ()) }
+}
+
+/// Maps memory into the address space of the calling process.
+///
+/// With `file` set to `None` a zero-filled anonymous region is created,
+/// otherwise the pages of the file starting at `offset` are mapped. Without
+/// [`MMapFlags::FIXED`] the `addr` parameter is only a hint for where to
+/// place the mapping. Returns the start address of the new mapping.
+pub fn mmap(
+    file: Option<&FsObjHandle>,
+    offset: usize,
+    addr: usize,
+    size: usize,
+    flags: MMapFlags,
+) -> Result<usize, SysCallError> {
+    let (id, flags) = match file {
+        Some(fh) => (fh.id(), flags - MMapFlags::ANONYMOUS),
+        None => (0, flags | MMapFlags::ANONYMOUS),
+    };
+    // SAFETY: SYS_MMAP is a supported syscall number by the svsm kernel. The
+    // kernel only creates a mapping in unused parts of the address space and
+    // never modifies existing memory of the process.
+    unsafe {
+        syscall5(
+            SYS_MMAP,
+            u64::from(id),
+            offset as u64,
+            addr as u64,
+            size as u64,
+            flags.bits() as u64,
+        )
+        .map(|ret| ret as usize)
+    }
+}
+
+/// Removes the mapping starting at `addr` from the address space of the
+/// calling process.
+///
+/// # Safety
+///
+/// The caller must ensure that no references to the memory of the mapping
+/// exist anymore.
+pub unsafe fn munmap(addr: usize) -> Result<(), SysCallError> {
+    // SAFETY: SYS_MUNMAP is a supported syscall number by the svsm kernel.
+    // The caller guarantees that the unmapped memory is no longer in use.
+    unsafe { syscall1(SYS_MUNMAP, addr as u64).map(|_| ()) }
+}
+
+/// Removes the mapping starting at `addr` and terminates the calling thread
+/// with `code`. Nothing is written to or read from memory between the two
+/// system calls, so this can be used by a thread to release its own stack.
+/// The thread terminates even when the mapping could not be removed.
+///
+/// # Safety
+///
+/// The caller must ensure that no references to the memory of the mapping
+/// exist anymore, except for the stack of the calling thread.
+pub unsafe fn munmap_and_exit(addr: usize, code: u32) -> ! {
+    // SAFETY: SYS_MUNMAP and SYS_EXIT are supported syscall numbers by the
+    // svsm kernel. Only registers are used after the mapping was removed.
+    unsafe {
+        asm!(
+            "int 0x80",
+            "mov rax, {exit}",
+            "mov edi, esi",
+            "int 0x80",
+            "ud2",
+            exit = const SYS_EXIT,
+            in("rax") SYS_MUNMAP,
+            in("rdi") addr as u64,
+            in("rsi") u64::from(code),
+            options(noreturn),
+        );
+    }
+}
+
+/// Changes the access permissions of the mapping starting at `addr`. Only
+/// the [`MMapFlags::PROT_MASK`] bits of `flags` are allowed.
+///
+/// # Safety
+///
+/// The caller must ensure that no access to the memory of the mapping
+/// violates the new permissions.
+pub unsafe fn mprotect(addr: usize, flags: MMapFlags) -> Result<(), SysCallError> {
+    // SAFETY: SYS_MPROTECT is a supported syscall number by the svsm kernel.
+    // The caller guarantees that the new permissions match the use of the
+    // memory.
+    unsafe { syscall2(SYS_MPROTECT, addr as u64, flags.bits() as u64).map(|_| ()) }
+}
@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use super::call::{syscall1, syscall2, syscall3, SysCallError};
+use super::def::{
+    ProtocolFlags, SYS_CHANNEL_CREATE, SYS_CHANNEL_RECV, SYS_CHANNEL_SEND, SYS_PROTOCOL_REGISTER,
+    SYS_VSOCK_CONNECT, SYS_VSOCK_RECV, SYS_VSOCK_SEND,
+};
+use super::{Obj, ObjHandle};
+#[cfg(doc)]
+use super::{ServiceReply, ServiceRequest};
+
+/// Handle to one endpoint of a message channel.
+#[derive(Debug)]
+pub struct ChannelObjHandle(ObjHandle);
+
+impl ChannelObjHandle {
+    /// Takes ownership of a channel endpoint passed to this process by
+    /// [`exec_with_handles`](super::exec_with_handles). The first passed
+    /// object has the id [`EXEC_HANDLE_BASE`](super::EXEC_HANDLE_BASE).
+    ///
+    /// # Safety
+    ///
+    /// `id` must refer to a channel endpoint which is not owned by any other
+    /// handle in this process.
+    pub unsafe fn from_raw(id: u32) -> Self {
+        Self(ObjHandle::new(id))
+    }
+}
+
+impl Obj for ChannelObjHandle {
+    fn id(&self) -> u32 {
+        u32::from(&self.0)
+    }
+}
+
+/// Creates a new message channel and returns its two connected endpoints.
+pub fn channel_create() -> Result<(ChannelObjHandle, ChannelObjHandle), SysCallError> {
+    let mut ids = [0u32; 2];
+    // SAFETY: SYS_CHANNEL_CREATE is a supported syscall number by the svsm
+    // kernel. The kernel writes the endpoint ids to `ids`, which is valid for
+    // writes.
+    unsafe {
+        syscall1(SYS_CHANNEL_CREATE, ids.as_mut_ptr() as u64)?;
+    }
+    Ok((
+        ChannelObjHandle(ObjHandle::new(ids[0])),
+        ChannelObjHandle(ObjHandle::new(ids[1])),
+    ))
+}
+
+/// Sends `msg` to the peer of channel endpoint `ch`. The call does not
+/// block, it fails with [`SysCallError::EBUSY`] when the peer has too many
+/// pending messages and with [`SysCallError::EPIPE`] when the peer endpoint
+/// is closed. Messages can be up to [`CHANNEL_MSG_MAX`](super::CHANNEL_MSG_MAX)
+/// bytes large.
+pub fn channel_send(ch: &ChannelObjHandle, msg: &[u8]) -> Result<(), SysCallError> {
+    // SAFETY: SYS_CHANNEL_SEND is a supported syscall number by the svsm
+    // kernel. It does not change any memory of the process.
+    unsafe {
+        syscall3(
+            SYS_CHANNEL_SEND,
+            ch.id().into(),
+            msg.as_ptr() as u64,
+            msg.len() as u64,
+        )
+        .map(|_| ())
+    }
+}
+
+/// Receives the next message on channel endpoint `ch` into `buffer` and
+/// returns its size. Blocks until a message arrives, and fails with
+/// [`SysCallError::EPIPE`] when no message is pending and the peer endpoint
+/// is closed. The call fails with [`SysCallError::EINVAL`] when the message
+/// does not fit into `buffer`, the message stays queued in this case.
+pub fn channel_recv(ch: &ChannelObjHandle, buffer: &mut [u8]) -> Result<usize, SysCallError> {
+    // SAFETY: SYS_CHANNEL_RECV is a supported syscall number by the svsm
+    // kernel. All memory changes happen from kernel context.
+    unsafe {
+        syscall3(
+            SYS_CHANNEL_RECV,
+            ch.id().into(),
+            buffer.as_mut_ptr() as u64,
+            buffer.len() as u64,
+        )
+        .map(|ret| ret as usize)
+    }
+}
+
+/// Claims the SVSM protocol `protocol` for the calling process. Guest
+/// requests for the protocol are sent as [`ServiceRequest`] messages to the
+/// peer of the channel endpoint `ch`. Each request must be answered with a
+/// [`ServiceReply`] message with the `id` of the request on the peer
+/// endpoint. The protocol is released when the peer endpoint is closed.
+/// Only processes with the [`ProcessCaps::PROTOCOL_SERVICE`] capability can
+/// register protocols.
+///
+/// [`ProcessCaps::PROTOCOL_SERVICE`]: super::ProcessCaps::PROTOCOL_SERVICE
+pub fn protocol_register(
+    protocol: u32,
+    ch: &ChannelObjHandle,
+    flags: ProtocolFlags,
+) -> Result<(), SysCallError> {
+    // SAFETY: SYS_PROTOCOL_REGISTER is a supported syscall number by the
+    // svsm kernel. It does not change any memory of the process.
+    unsafe {
+        syscall3(
+            SYS_PROTOCOL_REGISTER,
+            u64::from(protocol),
+            ch.id().into(),
+            flags.bits() as u64,
+        )
+        .map(|_| ())
+    }
+}
+
+/// Handle to a connected vsock stream socket. The connection is shut down
+/// when the handle is dropped.
+#[derive(Debug)]
+pub struct VsockObjHandle(ObjHandle);
+
+impl Obj for VsockObjHandle {
+    fn id(&self) -> u32 {
+        u32::from(&self.0)
+    }
+}
+
+/// Connects a stream socket to port `port` of the VM or host with context
+/// ID `cid`, e.g. [`VSOCK_CID_HOST`](super::VSOCK_CID_HOST). Fails with
+/// [`SysCallError::ENOTSUPP`] when the SVSM has no vsock device and with
+/// [`SysCallError::ENOTFOUND`] when the peer refused the connection. Fails
+/// with [`SysCallError::EBUSY`] when the peer does not answer in time.
+pub fn vsock_connect(cid: u64, port: u32) -> Result<VsockObjHandle, SysCallError> {
+    // SAFETY: SYS_VSOCK_CONNECT is a supported syscall number by the svsm
+    // kernel. It does not change any memory of the process.
+    unsafe {
+        syscall2(SYS_VSOCK_CONNECT, cid, u64::from(port))
+            .map(|ret| VsockObjHandle(ObjHandle::new(ret as u32)))
+    }
+}
+
+/// Sends all of `buf` on the socket `sock`. Blocks while the peer has no
+/// buffer space available and fails with [`SysCallError::EPIPE`] when the
+/// connection has been closed, or with [`SysCallError::EBUSY`] when the
+/// peer does not free buffer space in time.
+pub fn vsock_send(sock: &VsockObjHandle, buf: &[u8]) -> Result<usize, SysCallError> {
+    // SAFETY: SYS_VSOCK_SEND is a supported syscall number by the svsm
+    // kernel. It does not change any memory of the process.
+    unsafe {
+        syscall3(
+            SYS_VSOCK_SEND,
+            sock.id().into(),
+            buf.as_ptr() as u64,
+            buf.len() as u64,
+        )
+        .map(|ret| ret as usize)
+    }
+}
+
+/// Receives data from the socket `sock` into `buf`. Blocks until data is
+/// available and returns the number of bytes received, which is 0 when the
+/// peer closed the connection. Fails with [`SysCallError::EBUSY`] when no
+/// data arrives in time.
+pub fn vsock_recv(sock: &VsockObjHandle, buf: &mut [u8]) -> Result<usize, SysCallError> {
+    // SAFETY: SYS_VSOCK_RECV is a supported syscall number by the svsm
+    // kernel. All memory changes happen from kernel context.
+    unsafe {
+        syscall3(
+            SYS_VSOCK_RECV,
+            sock.id().into(),
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+        )
+        .map(|ret| ret as usize)
+    }
+}
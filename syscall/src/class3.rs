@@ -4,11 +4,98 @@
 //
 // Author: Chuanxiao Dong <chuanxiao.dong@intel.com>
 
-use super::call::{syscall1, SysCallError};
-use super::SYS_CAPABILITIES;
+use super::call::{syscall1, syscall2, syscall3, syscall4, SysCallError};
+use super::{
+    MIGRATION_NONCE_SIZE, SYS_CAPABILITIES, SYS_MIGRATION_EXPORT, SYS_MIGRATION_IMPORT,
+    SYS_MIGRATION_NONCE, SYS_RTMR_EXTEND,
+};
 
 pub fn capabilities(index: u32) -> Result<u64, SysCallError> {
     // SAFETY: Invokes a system call and does not directly change any memory of
     // the process.
     unsafe { syscall1(SYS_CAPABILITIES, index.into()) }
 }
+
+/// Measures `event_data` into runtime measurement register `index` and
+/// records the event with type `event_type` in the event log of the SVSM.
+/// The event data can be up to [`RTMR_EVENT_DATA_MAX`](super::RTMR_EVENT_DATA_MAX)
+/// bytes large. `EV_NO_ACTION` events are not measured and are rejected with
+/// [`SysCallError::EINVAL`]. Fails with [`SysCallError::ENOTSUPP`] on
+/// platforms without RTMRs, with [`SysCallError::EPERM`] when the process lacks the
+/// [`ProcessCaps::RTMR_EXTEND`](super::ProcessCaps::RTMR_EXTEND) capability
+/// and with [`SysCallError::ENOMEM`] when the event log is full.
+pub fn rtmr_extend(index: u32, event_type: u32, event_data: &[u8]) -> Result<(), SysCallError> {
+    // SAFETY: SYS_RTMR_EXTEND is a supported syscall number by the svsm
+    // kernel. The kernel only reads from `event_data`.
+    unsafe {
+        syscall4(
+            SYS_RTMR_EXTEND,
+            index.into(),
+            event_type.into(),
+            event_data.as_ptr() as u64,
+            event_data.len() as u64,
+        )
+        .map(|_| ())
+    }
+}
+
+/// Starts the import of a migration image on the destination of a live
+/// migration and returns the nonce of the migration session. The nonce is
+/// generated by the SVSM and has to be passed to [`migration_export()`] on
+/// the source. It is valid for a single call to [`migration_import()`].
+/// Requires the [`ProcessCaps::MIGRATION`](super::ProcessCaps::MIGRATION)
+/// capability.
+pub fn migration_nonce() -> Result<[u8; MIGRATION_NONCE_SIZE], SysCallError> {
+    let mut nonce = [0u8; MIGRATION_NONCE_SIZE];
+    // SAFETY: SYS_MIGRATION_NONCE is a supported syscall number by the svsm
+    // kernel. The kernel only writes the nonce to `nonce`.
+    unsafe {
+        syscall1(SYS_MIGRATION_NONCE, nonce.as_mut_ptr() as u64)?;
+    }
+    Ok(nonce)
+}
+
+/// Exports the SVSM state into a migration image for the destination of a
+/// live migration with the session `nonce` obtained from
+/// [`migration_nonce()`] on the destination. Requires the
+/// [`ProcessCaps::MIGRATION`](super::ProcessCaps::MIGRATION) capability.
+///
+/// # Returns
+///
+/// The size of the migration image. Nothing is written to `image` when it
+/// is too small, so the call can be repeated with a large enough buffer.
+pub fn migration_export(
+    nonce: &[u8; MIGRATION_NONCE_SIZE],
+    image: &mut [u8],
+) -> Result<usize, SysCallError> {
+    // SAFETY: SYS_MIGRATION_EXPORT is a supported syscall number by the svsm
+    // kernel. The kernel only reads from `nonce` and writes up to
+    // `image.len()` bytes to `image`.
+    unsafe {
+        syscall3(
+            SYS_MIGRATION_EXPORT,
+            nonce.as_ptr() as u64,
+            image.as_mut_ptr() as u64,
+            image.len() as u64,
+        )
+        .map(|size| size as usize)
+    }
+}
+
+/// Restores the SVSM state from a migration image exported on the source
+/// of a live migration. The image must have been exported with the nonce
+/// returned by the last call to [`migration_nonce()`], and all guest vCPUs
+/// must have been deleted. Requires the
+/// [`ProcessCaps::MIGRATION`](super::ProcessCaps::MIGRATION) capability.
+pub fn migration_import(image: &[u8]) -> Result<(), SysCallError> {
+    // SAFETY: SYS_MIGRATION_IMPORT is a supported syscall number by the svsm
+    // kernel. The kernel only reads from `image`.
+    unsafe {
+        syscall2(
+            SYS_MIGRATION_IMPORT,
+            image.as_ptr() as u64,
+            image.len() as u64,
+        )
+        .map(|_| ())
+    }
+}
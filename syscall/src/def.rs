@@ -5,16 +5,25 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use bitflags::bitflags;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
 // Syscall classes
 const CLASS0: u64 = 0;
 const CLASS1: u64 = 1 << 32;
+const CLASS2: u64 = 2 << 32;
 const CLASS3: u64 = 3 << 32;
 
 // Syscall number in class0
 pub const SYS_EXIT: u64 = CLASS0;
+pub const SYS_THREAD_CREATE: u64 = CLASS0 + 1;
+pub const SYS_THREAD_JOIN: u64 = CLASS0 + 2;
+pub const SYS_WAIT: u64 = CLASS0 + 3;
 pub const SYS_EXEC: u64 = CLASS0 + 4;
+pub const SYS_THREAD_DETACH: u64 = CLASS0 + 5;
 pub const SYS_CLOSE: u64 = CLASS0 + 10;
+pub const SYS_MMAP: u64 = CLASS0 + 11;
+pub const SYS_MUNMAP: u64 = CLASS0 + 12;
+pub const SYS_MPROTECT: u64 = CLASS0 + 13;
 
 // Syscall number in class1
 pub const SYS_OPEN: u64 = CLASS1;
@@ -28,8 +37,21 @@ pub const SYS_READDIR: u64 = CLASS1 + 7;
 pub const SYS_MKDIR: u64 = CLASS1 + 8;
 pub const SYS_RMDIR: u64 = CLASS1 + 9;
 
+// Syscall number in class2
+pub const SYS_CHANNEL_CREATE: u64 = CLASS2;
+pub const SYS_CHANNEL_SEND: u64 = CLASS2 + 1;
+pub const SYS_CHANNEL_RECV: u64 = CLASS2 + 2;
+pub const SYS_PROTOCOL_REGISTER: u64 = CLASS2 + 3;
+pub const SYS_VSOCK_CONNECT: u64 = CLASS2 + 4;
+pub const SYS_VSOCK_SEND: u64 = CLASS2 + 5;
+pub const SYS_VSOCK_RECV: u64 = CLASS2 + 6;
+
 // Syscall number in class3
 pub const SYS_CAPABILITIES: u64 = CLASS3;
+pub const SYS_RTMR_EXTEND: u64 = CLASS3 + 1;
+pub const SYS_MIGRATION_NONCE: u64 = CLASS3 + 2;
+pub const SYS_MIGRATION_EXPORT: u64 = CLASS3 + 3;
+pub const SYS_MIGRATION_IMPORT: u64 = CLASS3 + 4;
 
 ///Maximum length of path name including null character in bytes
 pub const PATH_MAX: usize = 4096;
@@ -37,6 +59,34 @@ pub const PATH_MAX: usize = 4096;
 /// Maximum length of file name in bytes
 pub const F_NAME_SIZE: usize = 256;
 
+/// Maximum size of a channel message in bytes
+pub const CHANNEL_MSG_MAX: usize = 4096;
+
+/// Context ID of the host for vsock connections
+pub const VSOCK_CID_HOST: u64 = 2;
+
+/// Maximum number of object handles passed to a new process by the Exec
+/// system call
+pub const EXEC_HANDLES_MAX: usize = 8;
+
+/// Number of runtime measurement registers which can be extended with the
+/// RtmrExtend system call
+pub const RTMR_COUNT: usize = 4;
+
+/// Maximum size of the event data passed to the RtmrExtend system call
+pub const RTMR_EVENT_DATA_MAX: usize = 4096;
+
+/// Size of the nonce of a migration session
+pub const MIGRATION_NONCE_SIZE: usize = 32;
+
+/// Maximum size of a migration image accepted by the MigrationImport system
+/// call
+pub const MIGRATION_IMAGE_MAX: usize = 4 * 1024 * 1024;
+
+/// Object handle of the first object passed to a new process by the Exec
+/// system call. Handle 0 is the console.
+pub const EXEC_HANDLE_BASE: u32 = 1;
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FileType {
@@ -72,6 +122,32 @@ bitflags! {
     }
 }
 
+//
+// Flags for MMap and MProtect system calls
+//
+bitflags! {
+    #[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+    pub struct MMapFlags: usize {
+        /// Pages may be read
+        const READ = 1 << 0;
+        /// Pages may be written
+        const WRITE = 1 << 1;
+        /// Pages may be executed
+        const EXEC = 1 << 2;
+        /// Map private copies of file pages
+        const PRIVATE = 1 << 3;
+        /// Map at exactly the given address
+        const FIXED = 1 << 4;
+        /// Map zero-filled memory not backed by a file
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+impl MMapFlags {
+    /// Flags describing the access permissions of a mapping
+    pub const PROT_MASK: Self = Self::READ.union(Self::WRITE).union(Self::EXEC);
+}
+
 //
 // Modes for Seek system call
 //
@@ -128,6 +204,82 @@ impl Default for DirEnt {
     }
 }
 
+//
+// Flags for the ProtocolRegister system call
+//
+bitflags! {
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct ProtocolFlags: usize {
+        /// RCX of every request contains the guest physical address and RDX
+        /// the size of a guest buffer, which is forwarded with the request
+        const GUEST_BUFFER = 1 << 0;
+    }
+}
+
+//
+// Capabilities of processes
+//
+bitflags! {
+    /// Privileged operations a process is allowed to perform. The
+    /// capabilities of a new process are passed in the `flags` of
+    /// [`exec`](crate::exec) and are limited to the capabilities of the
+    /// calling process. The initial process started by the kernel has all
+    /// capabilities.
+    #[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+    pub struct ProcessCaps: u32 {
+        /// Register a service for an SVSM protocol
+        const PROTOCOL_SERVICE = 1 << 0;
+        /// Extend runtime measurement registers
+        const RTMR_EXTEND = 1 << 1;
+        /// Export and import the SVSM state for a live migration
+        const MIGRATION = 1 << 2;
+    }
+}
+
+/// Header of a message sent to a protocol service. The message continues
+/// with `buffer_size` bytes of the guest buffer of the request.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct ServiceRequest {
+    /// Identifier of the request, which the service returns in its reply
+    pub id: u64,
+    /// SVSM protocol number
+    pub protocol: u32,
+    /// Call identifier within the protocol
+    pub request: u32,
+    /// Guest RCX register
+    pub rcx: u64,
+    /// Guest RDX register
+    pub rdx: u64,
+    /// Guest R8 register
+    pub r8: u64,
+    /// Number of guest buffer bytes following the header
+    pub buffer_size: u64,
+}
+
+/// Header of the reply of a protocol service to a [`ServiceRequest`]. The
+/// message continues with `buffer_size` bytes which are written back to the
+/// guest buffer of the request.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct ServiceReply {
+    /// Identifier of the request this is the reply to
+    pub id: u64,
+    /// SVSM result code returned to the guest in RAX
+    pub result: u64,
+    /// New value of the guest RCX register
+    pub rcx: u64,
+    /// New value of the guest RDX register
+    pub rdx: u64,
+    /// New value of the guest R8 register
+    pub r8: u64,
+    /// Number of bytes following the header
+    pub buffer_size: u64,
+}
+
+/// Maximum size of a guest buffer forwarded to a protocol service
+pub const SERVICE_BUFFER_MAX: usize = CHANNEL_MSG_MAX - size_of::<ServiceRequest>();
+
 bitflags! {
     #[derive(Clone, Copy, Debug)]
     pub struct GlobalFeatureFlags: u64 {
@@ -8,6 +8,7 @@
 mod call;
 mod class0;
 mod class1;
+mod class2;
 mod class3;
 mod console;
 mod def;
@@ -16,6 +17,7 @@ mod obj;
 pub use call::SysCallError;
 pub use class0::*;
 pub use class1::*;
+pub use class2::*;
 pub use class3::*;
 pub use console::*;
 pub use def::*;
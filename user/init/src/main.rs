@@ -1,8 +1,11 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
 use userlib::*;
 
+use alloc::vec::Vec;
 use core::ptr::{addr_of, addr_of_mut};
 
 static mut SOME_BSS_DATA: [u64; 128] = [0; 128];
@@ -37,5 +40,11 @@ fn main() -> u32 {
         check(&*addr_of!(SOME_RO_DATA), 0xeeu64);
         check(&*addr_of!(SOME_BSS_DATA), 0xaa);
     }
+
+    let heap_data: Vec<u64> = (0..1024).collect();
+    let worker = thread::spawn(move || heap_data.iter().sum::<u64>());
+    if worker.join() != Ok(1023 * 1024 / 2) {
+        panic!("Unexpected result from worker thread");
+    }
     0
 }
@@ -0,0 +1,515 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+use crate::{println, SpinLock};
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::mem::size_of;
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use syscall::{mmap, munmap, MMapFlags, SysCallError};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Size of the chunks of memory requested from the kernel when the heap
+/// needs to grow.
+const HEAP_CHUNK_SIZE: usize = 16 * PAGE_SIZE;
+
+/// Allocations of at least this size are not served from the heap chunks
+/// but get a memory mapping of their own, which is returned to the kernel
+/// when the allocation is freed.
+const LARGE_ALLOC_SIZE: usize = HEAP_CHUNK_SIZE / 4;
+
+const fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// Header stored at the beginning of every free block of heap memory.
+#[repr(C)]
+#[derive(Debug)]
+struct FreeBlock {
+    size: usize,
+    next: Option<NonNull<FreeBlock>>,
+}
+
+/// Granularity of heap allocations. Every block handed out or tracked by a
+/// [`FreeList`] is aligned to and a multiple of this size, so that any
+/// remainder of a free block can hold a [`FreeBlock`] header.
+const BLOCK_SIZE: usize = size_of::<FreeBlock>();
+
+/// Address-ordered list of free memory blocks. Adjacent blocks are merged
+/// when memory is returned to the list.
+#[derive(Debug)]
+pub struct FreeList {
+    head: Option<NonNull<FreeBlock>>,
+    free: usize,
+}
+
+// SAFETY: The FreeList exclusively owns the memory of the blocks it tracks,
+// so it can be moved between threads.
+unsafe impl Send for FreeList {}
+
+impl FreeList {
+    /// Create an empty `FreeList`.
+    pub const fn new() -> Self {
+        Self {
+            head: None,
+            free: 0,
+        }
+    }
+
+    /// Returns the number of bytes available in the list.
+    pub fn free_bytes(&self) -> usize {
+        self.free
+    }
+
+    /// Returns the size of the largest free block in the list.
+    pub fn largest_block(&self) -> usize {
+        let mut largest = 0;
+        let mut cur = self.head;
+        while let Some(block) = cur {
+            // SAFETY: All blocks on the list point to valid headers.
+            let block = unsafe { block.as_ref() };
+            largest = largest.max(block.size);
+            cur = block.next;
+        }
+        largest
+    }
+
+    /// Computes the size and alignment of the block backing an allocation
+    /// with `layout`.
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let size = align_up(layout.size().max(1), BLOCK_SIZE);
+        let align = layout.align().max(BLOCK_SIZE);
+        (size, align)
+    }
+
+    /// Adds a region of memory to the list.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the region `[start, start + size)` is
+    /// valid, writable, unused memory that is not tracked by the list yet.
+    /// `start` and `size` must both be aligned to the size of a free block
+    /// header.
+    pub unsafe fn add_region(&mut self, start: usize, size: usize) {
+        debug_assert_eq!(start % BLOCK_SIZE, 0);
+        debug_assert_eq!(size % BLOCK_SIZE, 0);
+
+        if size == 0 {
+            return;
+        }
+
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut cur = self.head;
+        while let Some(block) = cur {
+            if block.as_ptr() as usize > start {
+                break;
+            }
+            prev = cur;
+            // SAFETY: All blocks on the list point to valid headers.
+            cur = unsafe { block.as_ref().next };
+        }
+
+        // Merge with the following block if the region ends where it begins.
+        let mut new_size = size;
+        let mut next = cur;
+        if let Some(block) = cur {
+            if start + size == block.as_ptr() as usize {
+                // SAFETY: All blocks on the list point to valid headers.
+                let block = unsafe { block.as_ref() };
+                new_size += block.size;
+                next = block.next;
+            }
+        }
+
+        self.free += size;
+
+        // Merge with the preceding block if it ends where the region begins.
+        if let Some(mut block) = prev {
+            let block_start = block.as_ptr() as usize;
+            // SAFETY: All blocks on the list point to valid headers and the
+            // list is exclusively borrowed.
+            let block = unsafe { block.as_mut() };
+            if block_start + block.size == start {
+                block.size += new_size;
+                block.next = next;
+                return;
+            }
+        }
+
+        let node = start as *mut FreeBlock;
+        // SAFETY: The caller guarantees that the region is valid, unused and
+        // suitably aligned for a FreeBlock header.
+        unsafe {
+            node.write(FreeBlock {
+                size: new_size,
+                next,
+            });
+        }
+        let node = NonNull::new(node);
+        match prev {
+            // SAFETY: All blocks on the list point to valid headers.
+            Some(mut block) => unsafe { block.as_mut().next = node },
+            None => self.head = node,
+        }
+    }
+
+    /// Allocates a block of memory satisfying `layout` from the list.
+    ///
+    /// # Returns
+    ///
+    /// Pointer to the allocated memory, or `None` if no free block is large
+    /// enough.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = Self::block_layout(layout);
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut cur = self.head;
+
+        while let Some(block) = cur {
+            let block_start = block.as_ptr() as usize;
+            // SAFETY: All blocks on the list point to valid headers.
+            let (block_size, next) = unsafe { (block.as_ref().size, block.as_ref().next) };
+            let block_end = block_start + block_size;
+            let start = align_up(block_start, align);
+
+            if start.checked_add(size).is_some_and(|end| end <= block_end) {
+                match prev {
+                    // SAFETY: All blocks on the list point to valid headers.
+                    Some(mut p) => unsafe { p.as_mut().next = next },
+                    None => self.head = next,
+                }
+                self.free -= block_size;
+
+                // SAFETY: The padding in front of and the remainder behind
+                // the allocation were part of a free block and are aligned
+                // to BLOCK_SIZE.
+                unsafe {
+                    self.add_region(block_start, start - block_start);
+                    self.add_region(start + size, block_end - (start + size));
+                }
+                return NonNull::new(start as *mut u8);
+            }
+
+            prev = cur;
+            cur = next;
+        }
+
+        None
+    }
+
+    /// Returns a block of memory to the list.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that `ptr` was returned by a previous call to
+    /// [`FreeList::allocate`] on this list with the same `layout`, and that
+    /// the memory is no longer in use.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+        // SAFETY: The caller guarantees that the block belongs to this list
+        // and is not in use anymore.
+        unsafe { self.add_region(ptr.as_ptr() as usize, size) }
+    }
+}
+
+impl Default for FreeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Snapshot of the memory usage of a [`Heap`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Bytes mapped for heap chunks
+    pub chunk_bytes: usize,
+    /// Bytes free in the heap chunks
+    pub free_bytes: usize,
+    /// Size of the largest free block in the heap chunks
+    pub largest_free_block: usize,
+    /// Bytes mapped for large allocations with a mapping of their own
+    pub large_bytes: usize,
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes in heap chunks, {} bytes free, largest free block {} bytes, {} bytes in large allocations",
+            self.chunk_bytes, self.free_bytes, self.largest_free_block, self.large_bytes
+        )
+    }
+}
+
+/// Part of the heap which failed to provide memory for an allocation.
+#[derive(Clone, Copy, Debug)]
+enum AllocFailure {
+    /// Mapping a new heap chunk failed.
+    Chunk(SysCallError),
+    /// Mapping the memory of a large allocation failed.
+    Large(SysCallError),
+    /// No free block fits the allocation even after growing the heap.
+    NoFit,
+}
+
+impl fmt::Display for AllocFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chunk(err) => write!(f, "mapping a new heap chunk failed ({err:?})"),
+            Self::Large(err) => write!(f, "mapping a large allocation failed ({err:?})"),
+            Self::NoFit => write!(f, "no free block large enough"),
+        }
+    }
+}
+
+/// Heap allocator for user-mode modules. Small allocations are served from
+/// chunks of anonymous memory mapped with the `mmap` system call, while
+/// large allocations get a mapping of their own. Heap chunks are kept for
+/// later allocations once they are freed.
+#[derive(Debug)]
+pub struct Heap {
+    free_list: SpinLock<FreeList>,
+    chunk_bytes: AtomicUsize,
+    large_bytes: AtomicUsize,
+}
+
+impl Heap {
+    /// Create a new, empty `Heap`.
+    pub const fn new() -> Self {
+        Self {
+            free_list: SpinLock::new(FreeList::new()),
+            chunk_bytes: AtomicUsize::new(0),
+            large_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes currently free in the heap chunks.
+    pub fn free_bytes(&self) -> usize {
+        self.free_list.lock().free_bytes()
+    }
+
+    /// Returns a snapshot of the memory usage of the heap.
+    pub fn stats(&self) -> HeapStats {
+        self.stats_locked(&self.free_list.lock())
+    }
+
+    fn stats_locked(&self, free_list: &FreeList) -> HeapStats {
+        HeapStats {
+            chunk_bytes: self.chunk_bytes.load(Ordering::Relaxed),
+            free_bytes: free_list.free_bytes(),
+            largest_free_block: free_list.largest_block(),
+            large_bytes: self.large_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reports a failed allocation on the console before the allocation
+    /// error handler terminates the module.
+    fn report_failure(&self, free_list: &FreeList, layout: Layout, failure: AllocFailure) {
+        println!(
+            "Heap: allocation of {} bytes (alignment {}) failed: {}",
+            layout.size(),
+            layout.align(),
+            failure
+        );
+        println!("Heap: {}", self.stats_locked(free_list));
+    }
+
+    fn is_large(layout: Layout) -> bool {
+        layout.size() >= LARGE_ALLOC_SIZE || layout.align() > PAGE_SIZE
+    }
+
+    fn large_size(layout: Layout) -> usize {
+        // Mappings without a fixed address are aligned to the next power of
+        // two of their size, which also takes care of large alignments.
+        align_up(layout.size().max(layout.align()), PAGE_SIZE)
+    }
+
+    fn map_pages(size: usize) -> Result<usize, SysCallError> {
+        mmap(None, 0, 0, size, MMapFlags::READ | MMapFlags::WRITE)
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: The allocator hands out memory which is either exclusively owned by
+// the free list or freshly mapped. Memory is only returned to the free list or
+// unmapped when the caller hands it back.
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if Self::is_large(layout) {
+            let size = Self::large_size(layout);
+            return match Self::map_pages(size) {
+                Ok(addr) => {
+                    self.large_bytes.fetch_add(size, Ordering::Relaxed);
+                    addr as *mut u8
+                }
+                Err(err) => {
+                    let free_list = self.free_list.lock();
+                    self.report_failure(&free_list, layout, AllocFailure::Large(err));
+                    null_mut()
+                }
+            };
+        }
+
+        let mut free_list = self.free_list.lock();
+        if let Some(ptr) = free_list.allocate(layout) {
+            return ptr.as_ptr();
+        }
+
+        let chunk = match Self::map_pages(HEAP_CHUNK_SIZE) {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                self.report_failure(&free_list, layout, AllocFailure::Chunk(err));
+                return null_mut();
+            }
+        };
+        // SAFETY: The chunk was freshly mapped and is page-aligned.
+        unsafe { free_list.add_region(chunk, HEAP_CHUNK_SIZE) };
+        self.chunk_bytes
+            .fetch_add(HEAP_CHUNK_SIZE, Ordering::Relaxed);
+
+        match free_list.allocate(layout) {
+            Some(ptr) => ptr.as_ptr(),
+            None => {
+                self.report_failure(&free_list, layout, AllocFailure::NoFit);
+                null_mut()
+            }
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if Self::is_large(layout) {
+            // Anonymous mappings are zero-filled by the kernel.
+            // SAFETY: Forwarding the caller's guarantees.
+            return unsafe { self.alloc(layout) };
+        }
+
+        // SAFETY: Forwarding the caller's guarantees.
+        let ptr = unsafe { self.alloc(layout) };
+        if !ptr.is_null() {
+            // SAFETY: The allocation is at least `layout.size()` bytes large.
+            unsafe { ptr.write_bytes(0, layout.size()) };
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if Self::is_large(layout) {
+            // SAFETY: Large allocations are the only users of their mapping,
+            // and the caller guarantees the memory is no longer used.
+            unsafe {
+                munmap(ptr as usize).expect("Failed to unmap heap allocation");
+            }
+            self.large_bytes
+                .fetch_sub(Self::large_size(layout), Ordering::Relaxed);
+            return;
+        }
+
+        let Some(ptr) = NonNull::new(ptr) else {
+            return;
+        };
+        // SAFETY: The caller guarantees that `ptr` was allocated by this
+        // allocator with `layout`, so it was served from the free list.
+        unsafe { self.free_list.lock().deallocate(ptr, layout) }
+    }
+}
+
+#[cfg(all(not(test), target_os = "none"))]
+#[global_allocator]
+static ALLOCATOR: Heap = Heap::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C, align(4096))]
+    struct Arena([u8; 4 * PAGE_SIZE]);
+
+    fn test_list(arena: &mut Arena) -> (FreeList, usize) {
+        let mut list = FreeList::new();
+        let start = arena.0.as_mut_ptr() as usize;
+        // SAFETY: The arena is valid, unused and page-aligned.
+        unsafe { list.add_region(start, arena.0.len()) };
+        (list, start)
+    }
+
+    #[test]
+    fn test_alloc_free() {
+        let mut arena = Arena([0; 4 * PAGE_SIZE]);
+        let (mut list, start) = test_list(&mut arena);
+        let layout = Layout::from_size_align(24, 8).unwrap();
+
+        let a = list.allocate(layout).unwrap();
+        let b = list.allocate(layout).unwrap();
+        assert_eq!(a.as_ptr() as usize, start);
+        assert_eq!(b.as_ptr() as usize, start + 32);
+        assert_eq!(list.free_bytes(), 4 * PAGE_SIZE - 64);
+
+        // SAFETY: Both blocks were allocated from the list with `layout`.
+        unsafe {
+            list.deallocate(a, layout);
+            list.deallocate(b, layout);
+        }
+        assert_eq!(list.free_bytes(), 4 * PAGE_SIZE);
+
+        // Freed blocks must be merged back into a single region
+        let all = Layout::from_size_align(4 * PAGE_SIZE, 8).unwrap();
+        assert_eq!(list.allocate(all).unwrap().as_ptr() as usize, start);
+        assert_eq!(list.free_bytes(), 0);
+    }
+
+    #[test]
+    fn test_alloc_aligned() {
+        let mut arena = Arena([0; 4 * PAGE_SIZE]);
+        let (mut list, start) = test_list(&mut arena);
+        let small = Layout::from_size_align(16, 16).unwrap();
+        let aligned = Layout::from_size_align(64, PAGE_SIZE).unwrap();
+
+        let a = list.allocate(small).unwrap();
+        let b = list.allocate(aligned).unwrap();
+        assert_eq!(a.as_ptr() as usize, start);
+        assert_eq!(b.as_ptr() as usize, start + PAGE_SIZE);
+
+        // The padding between both allocations can be used again
+        let c = list.allocate(small).unwrap();
+        assert_eq!(c.as_ptr() as usize, start + 16);
+    }
+
+    #[test]
+    fn test_alloc_exhausted() {
+        let mut arena = Arena([0; 4 * PAGE_SIZE]);
+        let (mut list, _) = test_list(&mut arena);
+        let layout = Layout::from_size_align(3 * PAGE_SIZE, 8).unwrap();
+
+        let a = list.allocate(layout).unwrap();
+        assert!(list.allocate(layout).is_none());
+        // SAFETY: The block was allocated from the list with `layout`.
+        unsafe { list.deallocate(a, layout) };
+        assert!(list.allocate(layout).is_some());
+    }
+
+    #[test]
+    fn test_largest_block() {
+        let mut arena = Arena([0; 4 * PAGE_SIZE]);
+        let (mut list, start) = test_list(&mut arena);
+        assert_eq!(list.largest_block(), 4 * PAGE_SIZE);
+
+        // Split the free memory into blocks of one and two pages
+        let page = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
+        let a = list.allocate(page).unwrap();
+        let b = list.allocate(page).unwrap();
+        assert_eq!(b.as_ptr() as usize, start + PAGE_SIZE);
+        // SAFETY: The block was allocated from the list with `page`.
+        unsafe { list.deallocate(a, page) };
+        assert_eq!(list.free_bytes(), 3 * PAGE_SIZE);
+        assert_eq!(list.largest_block(), 2 * PAGE_SIZE);
+    }
+}
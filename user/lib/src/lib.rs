@@ -7,9 +7,12 @@
 #![cfg_attr(all(not(test), target_os = "none"), no_std)]
 
 pub mod console;
+pub mod heap;
 pub mod locking;
+pub mod thread;
 
 pub use console::*;
+pub use heap::*;
 pub use locking::*;
 pub use syscall::*;
 
@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2026 agent
+//
+// Author: agent <agent@local>
+
+extern crate alloc;
+
+use crate::SpinLock;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU8, Ordering};
+use syscall::{
+    exit, mmap, munmap, munmap_and_exit, thread_create, thread_detach, thread_join, MMapFlags,
+    SysCallError, Tid,
+};
+
+/// Default size of the stack of a new thread
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Boxed thread main function. Returns the stack of the thread when the
+/// thread has been detached and has to release its stack itself.
+type ThreadMain = Box<dyn FnOnce() -> Option<usize> + Send + 'static>;
+
+/// The thread main function has not returned and the thread is not detached
+const THREAD_RUNNING: u8 = 0;
+/// The thread main function has returned, the thread is about to exit
+const THREAD_FINISHED: u8 = 1;
+/// The [`JoinHandle`] of the thread has been dropped
+const THREAD_DETACHED: u8 = 2;
+
+/// State shared between a thread and its [`JoinHandle`]
+#[derive(Debug)]
+struct Packet<T> {
+    /// One of `THREAD_RUNNING`, `THREAD_FINISHED` or `THREAD_DETACHED`
+    state: AtomicU8,
+    /// Value returned by the thread main function
+    result: SpinLock<Option<T>>,
+}
+
+/// Entry point of every thread created by [`Builder::spawn`]. Takes
+/// ownership of the boxed thread main function passed in `arg`.
+extern "C" fn thread_start(arg: usize) -> ! {
+    // SAFETY: `arg` was created by `Box::into_raw()` in `Builder::spawn()`
+    // and ownership was passed to this thread.
+    let main = unsafe { Box::from_raw(arg as *mut ThreadMain) };
+    match main() {
+        // SAFETY: The thread is detached, so nothing else references its
+        // stack anymore.
+        Some(stack) => unsafe { munmap_and_exit(stack, 0) },
+        None => exit(0),
+    }
+}
+
+/// Thread factory which can be used to configure the properties of a new
+/// thread.
+#[derive(Debug)]
+pub struct Builder {
+    stack_size: usize,
+}
+
+impl Builder {
+    /// Creates a builder with the default thread configuration.
+    pub const fn new() -> Self {
+        Self {
+            stack_size: DEFAULT_STACK_SIZE,
+        }
+    }
+
+    /// Sets the size of the stack of the new thread in bytes. The size is
+    /// rounded up to full pages.
+    pub const fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Spawns a new thread running `f` and returns a [`JoinHandle`] for it.
+    ///
+    /// # Returns
+    ///
+    /// The [`JoinHandle`] of the new thread on success, or the error
+    /// returned by the kernel when the stack could not be allocated or the
+    /// thread could not be created.
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, SysCallError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let stack_size = self.stack_size.max(PAGE_SIZE).next_multiple_of(PAGE_SIZE);
+        let stack = mmap(None, 0, 0, stack_size, MMapFlags::READ | MMapFlags::WRITE)?;
+
+        let packet = Arc::new(Packet {
+            state: AtomicU8::new(THREAD_RUNNING),
+            result: SpinLock::new(None),
+        });
+        let thread_packet = packet.clone();
+        let main: ThreadMain = Box::new(move || {
+            let ret = f();
+            *thread_packet.result.lock() = Some(ret);
+            thread_packet
+                .state
+                .compare_exchange(
+                    THREAD_RUNNING,
+                    THREAD_FINISHED,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .err()
+                .map(|_| stack)
+        });
+        let arg = Box::into_raw(Box::new(main)) as usize;
+
+        // SAFETY: The stack was freshly mapped and is only used by the new
+        // thread. It is unmapped after the thread has terminated, either by
+        // the `JoinHandle` or by the thread itself when it was detached.
+        // `thread_start` takes ownership of `arg`.
+        match unsafe { thread_create(thread_start, stack + stack_size, arg) } {
+            Ok(tid) => Ok(JoinHandle {
+                tid: Some(tid),
+                stack,
+                packet,
+            }),
+            Err(e) => {
+                // SAFETY: The thread was not created, so ownership of `arg`
+                // and the stack remains with this function.
+                unsafe {
+                    drop(Box::from_raw(arg as *mut ThreadMain));
+                    let _ = munmap(stack);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle to a spawned thread which allows to wait for its termination.
+/// Dropping the handle detaches the thread, which then releases its stack
+/// when its main function returns.
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    /// Thread ID, `None` once the thread has been joined
+    tid: Option<Tid>,
+    stack: usize,
+    packet: Arc<Packet<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns the thread ID of the thread.
+    pub fn tid(&self) -> u32 {
+        self.tid.as_ref().map_or(0, Tid::id)
+    }
+
+    /// Waits for the thread to terminate.
+    ///
+    /// # Returns
+    ///
+    /// The value returned by the thread main function, or the exit code of
+    /// the thread if it terminated without returning, e.g. because it
+    /// panicked or called [`exit`].
+    pub fn join(mut self) -> Result<T, u32> {
+        let tid = self.tid.take().expect("Thread already joined");
+        let exit_code = thread_join(&tid).expect("Failed to join thread");
+
+        // SAFETY: The thread has terminated, so its stack is no longer used.
+        unsafe {
+            munmap(self.stack).expect("Failed to unmap thread stack");
+        }
+
+        self.packet.result.lock().take().ok_or(exit_code)
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let Some(tid) = self.tid.take() else {
+            return;
+        };
+
+        // A thread that already returned from its main function will not
+        // release its stack, so reap it and release the stack here. Otherwise
+        // detach the thread so that the kernel releases its exit status when
+        // it terminates. Nothing can be done about errors here.
+        if self.packet.state.swap(THREAD_DETACHED, Ordering::AcqRel) == THREAD_FINISHED {
+            if thread_join(&tid).is_ok() {
+                // SAFETY: The thread has terminated, so its stack is no
+                // longer used.
+                unsafe {
+                    let _ = munmap(self.stack);
+                }
+            }
+        } else {
+            let _ = thread_detach(tid);
+        }
+    }
+}
+
+/// Spawns a new thread with the default configuration and returns a
+/// [`JoinHandle`] for it.
+///
+/// # Panics
+///
+/// Panics if the thread could not be created. Use [`Builder::spawn`] to
+/// handle this case.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f).expect("Failed to spawn thread")
+}
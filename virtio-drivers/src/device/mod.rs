@@ -4,3 +4,5 @@
 
 pub mod blk;
 pub(crate) mod common;
+#[cfg(feature = "alloc")]
+pub mod socket;
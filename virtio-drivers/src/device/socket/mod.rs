@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT
+
+//! Driver for VirtIO socket devices.
+//!
+//! To use the driver, you should first create a [`VirtIOSocket`] instance with your VirtIO
+//! transport, and then create a [`VsockConnectionManager`] wrapping it to keep track of
+//! connections. Only stream sockets are supported.
+
+mod connectionmanager;
+mod error;
+mod protocol;
+mod vsock;
+
+pub use connectionmanager::VsockConnectionManager;
+pub use error::SocketError;
+#[cfg(feature = "fake")]
+pub use protocol::{SocketType, VirtioVsockConfig, VirtioVsockHdr, VirtioVsockOp};
+pub use protocol::{StreamShutdown, VsockAddr, VMADDR_CID_HOST};
+pub use vsock::{
+    ConnectionInfo, DisconnectReason, VirtIOSocket, VsockBufferStatus, VsockEvent, VsockEventType,
+    DEFAULT_RX_BUFFER_SIZE,
+};
+#[cfg(feature = "fake")]
+pub use vsock::{QUEUE_SIZE, RX_QUEUE_IDX, TX_QUEUE_IDX};
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MIT
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 pub mod fake;
 
 use zerocopy::{FromBytes, Immutable, IntoBytes};
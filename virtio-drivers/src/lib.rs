@@ -12,7 +12,7 @@
 //! then construct the appropriate transport for the VirtIO device, e.g. for an MMIO device (perhaps
 //! discovered from the device tree):
 //! EXAMPLES REMOVED TEMPORARILY
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "fake")), no_std)]
 #![deny(unused_must_use, missing_docs)]
 #![allow(clippy::identity_op)]
 #![allow(dead_code)]
@@ -32,6 +32,8 @@ use core::{
 };
 
 pub use self::hal::{BufferDirection, Hal, PhysAddr};
+#[cfg(feature = "fake")]
+pub use self::{hal::fake::FakeHal, volatile::ReadOnly};
 
 /// The page size in bytes supported by the library (4 KiB).
 pub const PAGE_SIZE: usize = 0x1000;
@@ -62,6 +64,16 @@ pub enum Error {
     ConfigSpaceTooSmall,
     /// The device doesn't have any config space, but the driver expects some.
     ConfigSpaceMissing,
+    /// Error from the socket device.
+    #[cfg(feature = "alloc")]
+    SocketDeviceError(device::socket::SocketError),
+}
+
+#[cfg(feature = "alloc")]
+impl From<device::socket::SocketError> for Error {
+    fn from(e: device::socket::SocketError) -> Self {
+        Self::SocketDeviceError(e)
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -95,6 +107,8 @@ impl Display for Error {
                     "The device doesn't have any config space, but the driver expects some"
                 )
             }
+            #[cfg(feature = "alloc")]
+            Self::SocketDeviceError(e) => write!(f, "Error from the socket device: {e:?}"),
         }
     }
 }
@@ -11,12 +11,12 @@ use crate::{align_up, nonnull_slice_from_raw_parts, pages, Error, Result, PAGE_S
 #[cfg(feature = "alloc")]
 use alloc::boxed::Box;
 use bitflags::bitflags;
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 use core::cmp::min;
 use core::convert::TryInto;
 use core::hint::spin_loop;
 use core::mem::{size_of, take};
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 use core::ptr;
 use core::ptr::NonNull;
 use core::sync::atomic::{fence, AtomicU16, Ordering};
@@ -851,7 +851,7 @@ fn take_first_mut<'a, T>(slice: &mut &'a mut [T]) -> Option<&'a mut T> {
 ///
 /// Returns true if a descriptor chain was available and processed, or false if no descriptors were
 /// available.
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 pub(crate) fn fake_read_write_queue<const QUEUE_SIZE: usize>(
     descriptors: *const [Descriptor; QUEUE_SIZE],
     queue_driver_area: *const u8,
@@ -26,6 +26,10 @@ pub struct FakeTransport<C: 'static> {
     pub state: Arc<Mutex<State>>,
 }
 
+// SAFETY: The config space is only accessed through the transport, and the
+// device state is protected by a mutex.
+unsafe impl<C> Send for FakeTransport<C> {}
+
 impl<C> Transport for FakeTransport<C> {
     fn device_type(&self) -> DeviceType {
         self.device_type
@@ -2,14 +2,17 @@
 
 //! VirtIO transports.
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 pub mod fake;
 pub mod mmio;
+pub mod pci;
 
-use crate::{PhysAddr, Result, PAGE_SIZE};
+use crate::{Hal, PhysAddr, Result, PAGE_SIZE};
 use bitflags::{bitflags, Flags};
 use core::{fmt::Debug, ops::BitAnd, ptr::NonNull};
 use log::debug;
+use mmio::MmioTransport;
+use pci::PciTransport;
 use zerocopy::{FromBytes, Immutable, IntoBytes};
 
 /// A VirtIO transport layer.
@@ -104,6 +107,133 @@ pub trait Transport {
     fn config_space<T: 'static>(&self) -> Result<NonNull<T>>;
 }
 
+/// A wrapper for an arbitrary VirtIO transport, either MMIO or PCI.
+pub enum SomeTransport<H: Hal> {
+    /// An MMIO transport.
+    Mmio(MmioTransport<H>),
+    /// A PCI transport.
+    Pci(PciTransport<H>),
+}
+
+impl<H: Hal> From<MmioTransport<H>> for SomeTransport<H> {
+    fn from(mmio: MmioTransport<H>) -> Self {
+        Self::Mmio(mmio)
+    }
+}
+
+impl<H: Hal> From<PciTransport<H>> for SomeTransport<H> {
+    fn from(pci: PciTransport<H>) -> Self {
+        Self::Pci(pci)
+    }
+}
+
+impl<H: Hal> Transport for SomeTransport<H> {
+    fn device_type(&self) -> DeviceType {
+        match self {
+            Self::Mmio(mmio) => mmio.device_type(),
+            Self::Pci(pci) => pci.device_type(),
+        }
+    }
+
+    fn read_device_features(&mut self) -> u64 {
+        match self {
+            Self::Mmio(mmio) => mmio.read_device_features(),
+            Self::Pci(pci) => pci.read_device_features(),
+        }
+    }
+
+    fn write_driver_features(&mut self, driver_features: u64) {
+        match self {
+            Self::Mmio(mmio) => mmio.write_driver_features(driver_features),
+            Self::Pci(pci) => pci.write_driver_features(driver_features),
+        }
+    }
+
+    fn max_queue_size(&mut self, queue: u16) -> u32 {
+        match self {
+            Self::Mmio(mmio) => mmio.max_queue_size(queue),
+            Self::Pci(pci) => pci.max_queue_size(queue),
+        }
+    }
+
+    fn notify(&mut self, queue: u16) {
+        match self {
+            Self::Mmio(mmio) => mmio.notify(queue),
+            Self::Pci(pci) => pci.notify(queue),
+        }
+    }
+
+    fn get_status(&self) -> DeviceStatus {
+        match self {
+            Self::Mmio(mmio) => mmio.get_status(),
+            Self::Pci(pci) => pci.get_status(),
+        }
+    }
+
+    fn set_status(&mut self, status: DeviceStatus) {
+        match self {
+            Self::Mmio(mmio) => mmio.set_status(status),
+            Self::Pci(pci) => pci.set_status(status),
+        }
+    }
+
+    fn set_guest_page_size(&mut self, guest_page_size: u32) {
+        match self {
+            Self::Mmio(mmio) => mmio.set_guest_page_size(guest_page_size),
+            Self::Pci(pci) => pci.set_guest_page_size(guest_page_size),
+        }
+    }
+
+    fn requires_legacy_layout(&self) -> bool {
+        match self {
+            Self::Mmio(mmio) => mmio.requires_legacy_layout(),
+            Self::Pci(pci) => pci.requires_legacy_layout(),
+        }
+    }
+
+    fn queue_set(
+        &mut self,
+        queue: u16,
+        size: u32,
+        descriptors: PhysAddr,
+        driver_area: PhysAddr,
+        device_area: PhysAddr,
+    ) {
+        match self {
+            Self::Mmio(mmio) => mmio.queue_set(queue, size, descriptors, driver_area, device_area),
+            Self::Pci(pci) => pci.queue_set(queue, size, descriptors, driver_area, device_area),
+        }
+    }
+
+    fn queue_unset(&mut self, queue: u16) {
+        match self {
+            Self::Mmio(mmio) => mmio.queue_unset(queue),
+            Self::Pci(pci) => pci.queue_unset(queue),
+        }
+    }
+
+    fn queue_used(&mut self, queue: u16) -> bool {
+        match self {
+            Self::Mmio(mmio) => mmio.queue_used(queue),
+            Self::Pci(pci) => pci.queue_used(queue),
+        }
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        match self {
+            Self::Mmio(mmio) => mmio.ack_interrupt(),
+            Self::Pci(pci) => pci.ack_interrupt(),
+        }
+    }
+
+    fn config_space<T: 'static>(&self) -> Result<NonNull<T>> {
+        match self {
+            Self::Mmio(mmio) => mmio.config_space(),
+            Self::Pci(pci) => pci.config_space(),
+        }
+    }
+}
+
 /// DeviceStatus
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, IntoBytes, FromBytes, Immutable)]
 pub struct DeviceStatus(u32);
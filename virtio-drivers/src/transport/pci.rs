@@ -0,0 +1,703 @@
+// SPDX-License-Identifier: MIT
+
+//! PCI transport for VirtIO.
+
+pub mod bus;
+
+use self::bus::{
+    ConfigurationAccess, DeviceFunction, DeviceFunctionInfo, PciError, PciRoot, PCI_CAP_ID_VNDR,
+};
+use super::{DeviceStatus, DeviceType, Transport};
+use crate::{
+    volatile::{
+        volread, volwrite, ReadOnly, Volatile, VolatileReadable, VolatileWritable, WriteOnly,
+    },
+    Error, Hal, PhysAddr,
+};
+use core::{
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+    mem::{align_of, size_of},
+    ptr::NonNull,
+};
+
+/// The PCI vendor ID for VirtIO devices.
+pub const VIRTIO_VENDOR_ID: u16 = 0x1af4;
+
+/// The offset to add to a VirtIO device ID to get the corresponding PCI device ID.
+const PCI_DEVICE_ID_OFFSET: u16 = 0x1040;
+
+const TRANSITIONAL_NETWORK: u16 = 0x1000;
+const TRANSITIONAL_BLOCK: u16 = 0x1001;
+const TRANSITIONAL_MEMORY_BALLOONING: u16 = 0x1002;
+const TRANSITIONAL_CONSOLE: u16 = 0x1003;
+const TRANSITIONAL_SCSI_HOST: u16 = 0x1004;
+const TRANSITIONAL_ENTROPY_SOURCE: u16 = 0x1005;
+const TRANSITIONAL_9P_TRANSPORT: u16 = 0x1009;
+
+/// Common configuration.
+const VIRTIO_PCI_CAP_COMMON_CFG: u8 = 1;
+/// Notifications.
+const VIRTIO_PCI_CAP_NOTIFY_CFG: u8 = 2;
+/// ISR Status.
+const VIRTIO_PCI_CAP_ISR_CFG: u8 = 3;
+/// Device specific configuration.
+const VIRTIO_PCI_CAP_DEVICE_CFG: u8 = 4;
+
+/// Minimum length of a VirtIO PCI capability.
+const VIRTIO_PCI_CAP_LEN: u8 = 16;
+/// Minimum length of the notification capability, which has an additional
+/// `notify_off_multiplier` field.
+const VIRTIO_PCI_NOTIFY_CAP_LEN: u8 = 20;
+
+/// Bits of the ISR status which indicate a queue or configuration interrupt.
+const ISR_STATUS_MASK: u8 = 0x3;
+
+fn device_type(pci_device_id: u16) -> DeviceType {
+    match pci_device_id {
+        TRANSITIONAL_NETWORK => DeviceType::Network,
+        TRANSITIONAL_BLOCK => DeviceType::Block,
+        TRANSITIONAL_MEMORY_BALLOONING => DeviceType::MemoryBalloon,
+        TRANSITIONAL_CONSOLE => DeviceType::Console,
+        TRANSITIONAL_SCSI_HOST => DeviceType::ScsiHost,
+        TRANSITIONAL_ENTROPY_SOURCE => DeviceType::EntropySource,
+        TRANSITIONAL_9P_TRANSPORT => DeviceType::_9P,
+        id if id >= PCI_DEVICE_ID_OFFSET => DeviceType::from(id - PCI_DEVICE_ID_OFFSET),
+        _ => DeviceType::Invalid,
+    }
+}
+
+/// Returns the type of VirtIO device to which the given PCI vendor and device ID corresponds, or
+/// `None` if it is not a recognised VirtIO device.
+pub fn virtio_device_type(device_function_info: &DeviceFunctionInfo) -> Option<DeviceType> {
+    if device_function_info.vendor_id != VIRTIO_VENDOR_ID {
+        return None;
+    }
+    match device_type(device_function_info.device_id) {
+        DeviceType::Invalid => None,
+        device_type => Some(device_type),
+    }
+}
+
+/// PCI transport for VirtIO.
+///
+/// Ref: 4.1 Virtio Over PCI Bus
+#[derive(Debug)]
+pub struct PciTransport<H: Hal> {
+    device_type: DeviceType,
+    /// The bus, device and function identifier for the VirtIO device.
+    device_function: DeviceFunction,
+    /// The common configuration structure within some BAR.
+    common_cfg: NonNull<CommonCfg>,
+    /// The start of the queue notification region within some BAR.
+    notify_region: NonNull<[WriteOnly<u16>]>,
+    notify_off_multiplier: u32,
+    /// The ISR status register within some BAR.
+    isr_status: NonNull<ReadOnly<u8>>,
+    /// The VirtIO device-specific configuration within some BAR.
+    config_space: Option<NonNull<[u32]>>,
+    _phantom: PhantomData<H>,
+}
+
+impl<H: Hal> PciTransport<H> {
+    /// Constructs a new PCI VirtIO transport for the given device function on the given PCI root
+    /// controller.
+    ///
+    /// The memory BARs referenced by the VirtIO capabilities must already be assigned and are
+    /// mapped through [`Hal::mmio_phys_to_virt`].
+    pub fn new<C: ConfigurationAccess>(
+        root: &mut PciRoot<C>,
+        device_function: DeviceFunction,
+    ) -> Result<Self, VirtioPciError> {
+        let info = root
+            .device_function_info(device_function)
+            .ok_or(VirtioPciError::NoDevice)?;
+        if info.vendor_id != VIRTIO_VENDOR_ID {
+            return Err(VirtioPciError::InvalidVendorId(info.vendor_id));
+        }
+        let device_type = device_type(info.device_id);
+
+        // Find the PCI capabilities we need.
+        let mut common_cfg = None;
+        let mut notify_cfg = None;
+        let mut notify_off_multiplier = 0;
+        let mut isr_cfg = None;
+        let mut device_cfg = None;
+        for capability in root.capabilities(device_function) {
+            if capability.id != PCI_CAP_ID_VNDR {
+                continue;
+            }
+            let cap_len = capability.private_header as u8;
+            let cfg_type = (capability.private_header >> 8) as u8;
+            if cap_len < VIRTIO_PCI_CAP_LEN {
+                continue;
+            }
+            let struct_info = VirtioCapabilityInfo {
+                bar: root.config_read_word(device_function, capability.offset + 4) as u8,
+                offset: root.config_read_word(device_function, capability.offset + 8),
+                length: root.config_read_word(device_function, capability.offset + 12),
+            };
+
+            match cfg_type {
+                VIRTIO_PCI_CAP_COMMON_CFG if common_cfg.is_none() => {
+                    common_cfg = Some(struct_info);
+                }
+                VIRTIO_PCI_CAP_NOTIFY_CFG
+                    if cap_len >= VIRTIO_PCI_NOTIFY_CAP_LEN && notify_cfg.is_none() =>
+                {
+                    notify_cfg = Some(struct_info);
+                    notify_off_multiplier =
+                        root.config_read_word(device_function, capability.offset + 16);
+                }
+                VIRTIO_PCI_CAP_ISR_CFG if isr_cfg.is_none() => {
+                    isr_cfg = Some(struct_info);
+                }
+                VIRTIO_PCI_CAP_DEVICE_CFG if device_cfg.is_none() => {
+                    device_cfg = Some(struct_info);
+                }
+                _ => {}
+            }
+        }
+
+        let common_cfg = get_bar_region::<H, _, _>(
+            root,
+            device_function,
+            &common_cfg.ok_or(VirtioPciError::MissingCommonConfig)?,
+        )?;
+
+        let notify_cfg = notify_cfg.ok_or(VirtioPciError::MissingNotifyConfig)?;
+        if notify_off_multiplier % 2 != 0 {
+            return Err(VirtioPciError::InvalidNotifyOffMultiplier(
+                notify_off_multiplier,
+            ));
+        }
+        let notify_region = get_bar_region_slice::<H, _, _>(root, device_function, &notify_cfg)?;
+
+        let isr_status = get_bar_region::<H, _, _>(
+            root,
+            device_function,
+            &isr_cfg.ok_or(VirtioPciError::MissingIsrConfig)?,
+        )?;
+
+        let config_space = if let Some(device_cfg) = device_cfg {
+            Some(get_bar_region_slice::<H, _, _>(
+                root,
+                device_function,
+                &device_cfg,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            device_type,
+            device_function,
+            common_cfg,
+            notify_region,
+            notify_off_multiplier,
+            isr_status,
+            config_space,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the bus, device and function of the VirtIO device.
+    pub fn device_function(&self) -> DeviceFunction {
+        self.device_function
+    }
+}
+
+// SAFETY: The pointers are only used for MMIO, which can happen from any thread or CPU core.
+unsafe impl<H: Hal> Send for PciTransport<H> {}
+
+// SAFETY: `&PciTransport` only allows MMIO reads or getting the config space, both of which are
+// fine to happen concurrently on different CPU cores.
+unsafe impl<H: Hal> Sync for PciTransport<H> {}
+
+impl<H: Hal> Transport for PciTransport<H> {
+    fn device_type(&self) -> DeviceType {
+        self.device_type
+    }
+
+    fn read_device_features(&mut self) -> u64 {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, device_feature_select, 0);
+            let mut device_features_bits = volread!(H, self.common_cfg, device_feature) as u64;
+            volwrite!(H, self.common_cfg, device_feature_select, 1);
+            device_features_bits |= (volread!(H, self.common_cfg, device_feature) as u64) << 32;
+            device_features_bits
+        }
+    }
+
+    fn write_driver_features(&mut self, driver_features: u64) {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, driver_feature_select, 0);
+            volwrite!(H, self.common_cfg, driver_feature, driver_features as u32);
+            volwrite!(H, self.common_cfg, driver_feature_select, 1);
+            volwrite!(
+                H,
+                self.common_cfg,
+                driver_feature,
+                (driver_features >> 32) as u32
+            );
+        }
+    }
+
+    fn max_queue_size(&mut self, queue: u16) -> u32 {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, queue_select, queue);
+            volread!(H, self.common_cfg, queue_size).into()
+        }
+    }
+
+    fn notify(&mut self, queue: u16) {
+        // SAFETY: Safe because the common config and notify region pointers are valid and we
+        // checked in get_bar_region that they were aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, queue_select, queue);
+            // TODO: Consider caching this somewhere (per queue).
+            let queue_notify_off = volread!(H, self.common_cfg, queue_notify_off);
+
+            let offset_bytes = usize::from(queue_notify_off) * self.notify_off_multiplier as usize;
+            let index = offset_bytes / size_of::<u16>();
+            assert!(
+                index < self.notify_region.len(),
+                "Notify offset {offset_bytes:#x} for queue {queue} is outside the notification region"
+            );
+            let notify_ptr = self.notify_region.as_ptr().cast::<WriteOnly<u16>>();
+            notify_ptr.add(index).vwrite_hal::<H>(queue);
+        }
+    }
+
+    fn get_status(&self) -> DeviceStatus {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        let status = unsafe { volread!(H, self.common_cfg, device_status) };
+        DeviceStatus::from_bits_truncate(status.into())
+    }
+
+    fn set_status(&mut self, status: DeviceStatus) {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, device_status, status.bits() as u8);
+        }
+    }
+
+    fn set_guest_page_size(&mut self, _guest_page_size: u32) {
+        // No-op, the PCI transport doesn't care.
+    }
+
+    fn requires_legacy_layout(&self) -> bool {
+        false
+    }
+
+    fn queue_set(
+        &mut self,
+        queue: u16,
+        size: u32,
+        descriptors: PhysAddr,
+        driver_area: PhysAddr,
+        device_area: PhysAddr,
+    ) {
+        // The 64-bit queue addresses are written as two 32-bit halves, which
+        // every device has to support (4.1.3.1).
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, queue_select, queue);
+            volwrite!(H, self.common_cfg, queue_size, size as u16);
+            volwrite!(H, self.common_cfg, queue_desc_low, descriptors as u32);
+            volwrite!(
+                H,
+                self.common_cfg,
+                queue_desc_high,
+                (descriptors as u64 >> 32) as u32
+            );
+            volwrite!(H, self.common_cfg, queue_driver_low, driver_area as u32);
+            volwrite!(
+                H,
+                self.common_cfg,
+                queue_driver_high,
+                (driver_area as u64 >> 32) as u32
+            );
+            volwrite!(H, self.common_cfg, queue_device_low, device_area as u32);
+            volwrite!(
+                H,
+                self.common_cfg,
+                queue_device_high,
+                (device_area as u64 >> 32) as u32
+            );
+            volwrite!(H, self.common_cfg, queue_enable, 1);
+        }
+    }
+
+    fn queue_unset(&mut self, _queue: u16) {
+        // The VirtIO spec doesn't allow queues to be unset once they have been set up for the PCI
+        // transport, so this is a no-op. The queues are disabled when the device is reset.
+    }
+
+    fn queue_used(&mut self, queue: u16) -> bool {
+        // SAFETY: Safe because the common config pointer is valid and we checked in
+        // get_bar_region that it was aligned.
+        unsafe {
+            volwrite!(H, self.common_cfg, queue_select, queue);
+            volread!(H, self.common_cfg, queue_enable) == 1
+        }
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        // Reading the ISR status resets it to 0 and causes the device to de-assert the interrupt.
+        // SAFETY: Safe because the ISR status pointer is valid and we checked in get_bar_region
+        // that it was aligned.
+        let isr_status = unsafe { self.isr_status.as_ptr().cast_const().vread_hal::<H>() };
+        isr_status & ISR_STATUS_MASK != 0
+    }
+
+    fn config_space<T>(&self) -> Result<NonNull<T>, Error> {
+        if let Some(config_space) = self.config_space {
+            if size_of::<T>() > config_space.len() * size_of::<u32>() {
+                Err(Error::ConfigSpaceTooSmall)
+            } else if align_of::<T>() > 4 {
+                // Panic as this should only happen if the driver is written incorrectly.
+                panic!(
+                    "Driver expected config space alignment of {} bytes, but VirtIO only guarantees 4 byte alignment.",
+                    align_of::<T>()
+                );
+            } else {
+                // TODO: Use NonNull::as_non_null_ptr once it is stable.
+                let config_space_ptr = NonNull::new(config_space.as_ptr() as *mut u32).unwrap();
+                Ok(config_space_ptr.cast())
+            }
+        } else {
+            Err(Error::ConfigSpaceMissing)
+        }
+    }
+}
+
+impl<H: Hal> Drop for PciTransport<H> {
+    fn drop(&mut self) {
+        // Reset the device when the transport is dropped.
+        self.set_status(DeviceStatus::empty());
+        // The reset is complete once the device reads back zero (4.1.4.3.2).
+        while self.get_status() != DeviceStatus::empty() {}
+    }
+}
+
+/// `virtio_pci_common_cfg`, see 4.1.4.3 "Common configuration structure layout".
+#[repr(C)]
+struct CommonCfg {
+    device_feature_select: Volatile<u32>,
+    device_feature: ReadOnly<u32>,
+    driver_feature_select: Volatile<u32>,
+    driver_feature: Volatile<u32>,
+    msix_config: Volatile<u16>,
+    num_queues: ReadOnly<u16>,
+    device_status: Volatile<u8>,
+    config_generation: ReadOnly<u8>,
+    queue_select: Volatile<u16>,
+    queue_size: Volatile<u16>,
+    queue_msix_vector: Volatile<u16>,
+    queue_enable: Volatile<u16>,
+    queue_notify_off: Volatile<u16>,
+    queue_desc_low: Volatile<u32>,
+    queue_desc_high: Volatile<u32>,
+    queue_driver_low: Volatile<u32>,
+    queue_driver_high: Volatile<u32>,
+    queue_device_low: Volatile<u32>,
+    queue_device_high: Volatile<u32>,
+}
+
+/// Information about a VirtIO structure within some BAR, as provided by a `virtio_pci_cap`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct VirtioCapabilityInfo {
+    /// The bar in which the structure can be found.
+    bar: u8,
+    /// The offset within the bar.
+    offset: u32,
+    /// The length in bytes of the structure within the bar.
+    length: u32,
+}
+
+fn get_bar_region<H: Hal, T, C: ConfigurationAccess>(
+    root: &mut PciRoot<C>,
+    device_function: DeviceFunction,
+    struct_info: &VirtioCapabilityInfo,
+) -> Result<NonNull<T>, VirtioPciError> {
+    let bar_info = root
+        .bar_info(device_function, struct_info.bar)?
+        .ok_or(VirtioPciError::BarNotAllocated(struct_info.bar))?;
+    let (bar_address, bar_size) = bar_info
+        .memory_address_size()
+        .ok_or(VirtioPciError::UnexpectedIoBar)?;
+    if bar_address == 0 {
+        return Err(VirtioPciError::BarNotAllocated(struct_info.bar));
+    }
+    if u64::from(struct_info.offset) + u64::from(struct_info.length) > bar_size
+        || size_of::<T>() > struct_info.length as usize
+    {
+        return Err(VirtioPciError::BarOffsetOutOfRange);
+    }
+    let paddr = bar_address as PhysAddr + struct_info.offset as PhysAddr;
+    // SAFETY: The paddr and size describe a valid MMIO region, at least according to the PCI bus.
+    let vaddr = unsafe { H::mmio_phys_to_virt(paddr, struct_info.length as usize) };
+    if vaddr.as_ptr() as usize % align_of::<T>() != 0 {
+        return Err(VirtioPciError::Misaligned {
+            vaddr: vaddr.as_ptr() as usize,
+            alignment: align_of::<T>(),
+        });
+    }
+    Ok(vaddr.cast())
+}
+
+fn get_bar_region_slice<H: Hal, T, C: ConfigurationAccess>(
+    root: &mut PciRoot<C>,
+    device_function: DeviceFunction,
+    struct_info: &VirtioCapabilityInfo,
+) -> Result<NonNull<[T]>, VirtioPciError> {
+    let ptr = get_bar_region::<H, T, C>(root, device_function, struct_info)?;
+    Ok(crate::nonnull_slice_from_raw_parts(
+        ptr,
+        struct_info.length as usize / size_of::<T>(),
+    ))
+}
+
+/// An error encountered initialising a VirtIO PCI transport.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VirtioPciError {
+    /// There is no PCI function at the given location.
+    NoDevice,
+    /// PCI device vender ID was not the VirtIO vendor ID.
+    InvalidVendorId(u16),
+    /// No valid `VIRTIO_PCI_CAP_COMMON_CFG` capability was found.
+    MissingCommonConfig,
+    /// No valid `VIRTIO_PCI_CAP_NOTIFY_CFG` capability was found.
+    MissingNotifyConfig,
+    /// `VIRTIO_PCI_CAP_NOTIFY_CFG` capability has a `notify_off_multiplier` that is not a multiple
+    /// of 2.
+    InvalidNotifyOffMultiplier(u32),
+    /// No valid `VIRTIO_PCI_CAP_ISR_CFG` capability was found.
+    MissingIsrConfig,
+    /// An IO BAR was provided rather than a memory BAR.
+    UnexpectedIoBar,
+    /// A BAR which we need was not allocated an address.
+    BarNotAllocated(u8),
+    /// The offset for some capability was greater than the length of the BAR.
+    BarOffsetOutOfRange,
+    /// The virtual address was not aligned as expected.
+    Misaligned {
+        /// The virtual address in question.
+        vaddr: usize,
+        /// The expected alignment in bytes.
+        alignment: usize,
+    },
+    /// A generic PCI error,
+    Pci(PciError),
+}
+
+impl Display for VirtioPciError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::NoDevice => write!(f, "No PCI function found."),
+            Self::InvalidVendorId(vendor_id) => write!(
+                f,
+                "PCI device vender ID {:#06x} was not the VirtIO vendor ID {:#06x}.",
+                vendor_id, VIRTIO_VENDOR_ID
+            ),
+            Self::MissingCommonConfig => write!(
+                f,
+                "No valid `VIRTIO_PCI_CAP_COMMON_CFG` capability was found."
+            ),
+            Self::MissingNotifyConfig => write!(
+                f,
+                "No valid `VIRTIO_PCI_CAP_NOTIFY_CFG` capability was found."
+            ),
+            Self::InvalidNotifyOffMultiplier(notify_off_multiplier) => {
+                write!(
+                    f,
+                    "`VIRTIO_PCI_CAP_NOTIFY_CFG` capability has a `notify_off_multiplier` that is not a multiple of 2: {}",
+                    notify_off_multiplier
+                )
+            }
+            Self::MissingIsrConfig => {
+                write!(f, "No valid `VIRTIO_PCI_CAP_ISR_CFG` capability was found.")
+            }
+            Self::UnexpectedIoBar => write!(f, "Unexpected IO BAR (expected memory BAR)."),
+            Self::BarNotAllocated(bar_index) => write!(f, "Bar {} not allocated.", bar_index),
+            Self::BarOffsetOutOfRange => write!(f, "Capability offset greater than BAR length."),
+            Self::Misaligned { vaddr, alignment } => write!(
+                f,
+                "Virtual address {:#018x} was not aligned to a {} byte boundary as expected.",
+                vaddr, alignment
+            ),
+            Self::Pci(pci_error) => pci_error.fmt(f),
+        }
+    }
+}
+
+impl From<PciError> for VirtioPciError {
+    fn from(error: PciError) -> Self {
+        Self::Pci(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bus::tests::FakeConfigurationAccess;
+    use super::bus::{HeaderType, Status};
+    use super::*;
+    use crate::hal::fake::FakeHal;
+
+    const DEVICE_FUNCTION: DeviceFunction = DeviceFunction {
+        bus: 0,
+        device: 4,
+        function: 0,
+    };
+
+    fn info(vendor_id: u16, device_id: u16) -> DeviceFunctionInfo {
+        DeviceFunctionInfo {
+            vendor_id,
+            device_id,
+            class: 0,
+            subclass: 0,
+            prog_if: 0,
+            revision: 0,
+            header_type: HeaderType::Standard,
+            multi_function: false,
+        }
+    }
+
+    #[test]
+    fn transitional_device_ids() {
+        assert_eq!(device_type(0x1000), DeviceType::Network);
+        assert_eq!(device_type(0x1002), DeviceType::MemoryBalloon);
+        assert_eq!(device_type(0x1009), DeviceType::_9P);
+    }
+
+    #[test]
+    fn offset_device_ids() {
+        assert_eq!(device_type(0x1040), DeviceType::Invalid);
+        assert_eq!(device_type(0x1042), DeviceType::Block);
+        assert_eq!(device_type(0x1053), DeviceType::Socket);
+        assert_eq!(device_type(0x1059), DeviceType::Sound);
+        assert_eq!(device_type(0x105a), DeviceType::Invalid);
+    }
+
+    #[test]
+    fn virtio_device_type_valid() {
+        assert_eq!(
+            virtio_device_type(&info(VIRTIO_VENDOR_ID, TRANSITIONAL_BLOCK)),
+            Some(DeviceType::Block)
+        );
+    }
+
+    #[test]
+    fn virtio_device_type_invalid() {
+        // Non-VirtIO vendor ID.
+        assert_eq!(virtio_device_type(&info(0x1234, TRANSITIONAL_BLOCK)), None);
+
+        // Invalid device IDs.
+        assert_eq!(virtio_device_type(&info(VIRTIO_VENDOR_ID, 0)), None);
+        assert_eq!(virtio_device_type(&info(VIRTIO_VENDOR_ID, 0x1040)), None);
+    }
+
+    /// A memory BAR backed by normal memory, for use with [`FakeHal`] which
+    /// maps physical addresses one to one. BARs are naturally aligned.
+    #[repr(C, align(16384))]
+    struct FakeBar([u8; 0x4000]);
+
+    /// Builds the configuration space of a virtio-vsock device with its
+    /// VirtIO structures in BAR4, which is at `bar_address`.
+    fn vsock_config_space(bar_address: u64) -> FakeConfigurationAccess {
+        let mut words = [0u32; 64];
+        words[0] = u32::from(VIRTIO_VENDOR_ID) | (0x1053 << 16);
+        words[1] = u32::from(Status::CAPABILITIES_LIST.bits()) << 16;
+        words[0x10 / 4 + 4] = bar_address as u32 | 0xc;
+        words[0x10 / 4 + 5] = (bar_address >> 32) as u32;
+        words[0x34 / 4] = 0x40;
+
+        // (offset, cfg_type, cap_len, bar offset, length, next)
+        let caps: [(usize, u32, u32, u32, u32, u32); 4] = [
+            (0x40, 1, 16, 0x0000, 0x38, 0x50),
+            (0x50, 3, 16, 0x1000, 0x4, 0x60),
+            (0x60, 4, 16, 0x2000, 0x8, 0x70),
+            (0x70, 2, 20, 0x3000, 0x1000, 0),
+        ];
+        for (offset, cfg_type, cap_len, bar_offset, length, next) in caps {
+            words[offset / 4] =
+                u32::from(PCI_CAP_ID_VNDR) | (next << 8) | (cap_len << 16) | (cfg_type << 24);
+            words[offset / 4 + 1] = 4;
+            words[offset / 4 + 2] = bar_offset;
+            words[offset / 4 + 3] = length;
+        }
+        // notify_off_multiplier
+        words[0x70 / 4 + 4] = 4;
+
+        let mut access = FakeConfigurationAccess::default();
+        access.add_function(DEVICE_FUNCTION, words);
+        access.add_bar(DEVICE_FUNCTION, 4, 0xffff_c000);
+        access.add_bar(DEVICE_FUNCTION, 5, 0xffff_ffff);
+        access
+    }
+
+    #[test]
+    fn transport() {
+        let mut bar = Box::new(FakeBar([0; 0x4000]));
+        let bar_address = bar.0.as_mut_ptr() as u64;
+        // guest_cid_low of the vsock configuration.
+        bar.0[0x2000..0x2004].copy_from_slice(&66u32.to_le_bytes());
+        // queue_notify_off of the selected queue.
+        bar.0[0x1e..0x20].copy_from_slice(&3u16.to_le_bytes());
+
+        let mut root = PciRoot::new(vsock_config_space(bar_address));
+        let mut transport = PciTransport::<FakeHal>::new(&mut root, DEVICE_FUNCTION).unwrap();
+        assert_eq!(transport.device_type(), DeviceType::Socket);
+
+        let config = transport.config_space::<[u32; 2]>().unwrap();
+        // SAFETY: The config space points into `bar`, which is still alive.
+        assert_eq!(unsafe { config.as_ptr().read_volatile() }, [66, 0]);
+        assert_eq!(
+            transport.config_space::<[u32; 3]>(),
+            Err(Error::ConfigSpaceTooSmall)
+        );
+
+        transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+        assert_eq!(bar.0[0x14], 3);
+
+        transport.write_driver_features(0x1_0000_0001);
+        assert_eq!(bar.0[0x0c..0x10], 1u32.to_le_bytes());
+        assert_eq!(bar.0[0x08..0x0c], 1u32.to_le_bytes());
+
+        transport.queue_set(2, 8, 0x1_2345_6000, 0x1_2345_6080, 0x1_2345_7000);
+        assert_eq!(bar.0[0x16..0x18], 2u16.to_le_bytes());
+        assert_eq!(bar.0[0x20..0x24], 0x2345_6000u32.to_le_bytes());
+        assert_eq!(bar.0[0x24..0x28], 1u32.to_le_bytes());
+        assert!(transport.queue_used(2));
+
+        // Queue 2 uses notification offset 3 with a multiplier of 4 bytes.
+        transport.notify(2);
+        assert_eq!(bar.0[0x300c..0x300e], 2u16.to_le_bytes());
+
+        bar.0[0x1000] = 1;
+        assert!(transport.ack_interrupt());
+
+        // Dropping the transport resets the device.
+        drop(transport);
+        assert_eq!(bar.0[0x14], 0);
+    }
+
+    #[test]
+    fn unallocated_bar() {
+        let mut root = PciRoot::new(vsock_config_space(0));
+        assert_eq!(
+            PciTransport::<FakeHal>::new(&mut root, DEVICE_FUNCTION).unwrap_err(),
+            VirtioPciError::BarNotAllocated(4)
+        );
+    }
+}
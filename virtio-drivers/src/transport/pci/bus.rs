@@ -0,0 +1,734 @@
+// SPDX-License-Identifier: MIT
+
+//! Module for dealing with a PCI bus in general, without anything specific to VirtIO.
+
+use bitflags::bitflags;
+use core::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+
+/// The maximum number of devices on a bus.
+const MAX_DEVICES: u8 = 32;
+/// The maximum number of functions on a device.
+const MAX_FUNCTIONS: u8 = 8;
+
+/// The offset in bytes to the status and command fields within PCI configuration space.
+const STATUS_COMMAND_OFFSET: u8 = 0x04;
+/// The offset in bytes to BAR0 within PCI configuration space.
+const BAR0_OFFSET: u8 = 0x10;
+/// The number of BARs of a standard (type 0) header.
+const MAX_BARS: u8 = 6;
+
+/// ID for vendor-specific PCI capabilities.
+pub const PCI_CAP_ID_VNDR: u8 = 0x09;
+
+bitflags! {
+    /// The status register in PCI configuration space.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct Status: u16 {
+        // Bits 0-2 are reserved.
+        /// The state of the device's INTx# signal.
+        const INTERRUPT_STATUS = 1 << 3;
+        /// The device has a linked list of capabilities.
+        const CAPABILITIES_LIST = 1 << 4;
+        /// The device is capabile of running at 66 MHz rather than 33 MHz.
+        const MHZ_66_CAPABLE = 1 << 5;
+        // Bit 6 is reserved.
+        /// The device can accept fast back-to-back transactions not from the same agent.
+        const FAST_BACK_TO_BACK_CAPABLE = 1 << 7;
+        /// The bus agent observed a parity error (if parity error handling is enabled).
+        const MASTER_DATA_PARITY_ERROR = 1 << 8;
+        // Bits 9-10 are DEVSEL timing.
+        /// A target device terminated a transaction with target-abort.
+        const SIGNALED_TARGET_ABORT = 1 << 11;
+        /// A master device transaction was terminated with target-abort.
+        const RECEIVED_TARGET_ABORT = 1 << 12;
+        /// A master device transaction was terminated with master-abort.
+        const RECEIVED_MASTER_ABORT = 1 << 13;
+        /// A device asserts SERR#.
+        const SIGNALED_SYSTEM_ERROR = 1 << 14;
+        /// The device detects a parity error, even if parity error handling is disabled.
+        const DETECTED_PARITY_ERROR = 1 << 15;
+    }
+}
+
+bitflags! {
+    /// The command register in PCI configuration space.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct Command: u16 {
+        /// The device can respond to I/O Space accesses.
+        const IO_SPACE = 1 << 0;
+        /// The device can respond to Memory Space accesses.
+        const MEMORY_SPACE = 1 << 1;
+        /// The device can behave as a bus master.
+        const BUS_MASTER = 1 << 2;
+        /// The device can monitor Special Cycle operations.
+        const SPECIAL_CYCLES = 1 << 3;
+        /// The device can generate the Memory Write and Invalidate command.
+        const MEMORY_WRITE_AND_INVALIDATE_ENABLE = 1 << 4;
+        /// The device will snoop palette register data.
+        const VGA_PALETTE_SNOOP = 1 << 5;
+        /// The device should take its normal action when a parity error is detected.
+        const PARITY_ERROR_RESPONSE = 1 << 6;
+        // Bit 7 is reserved.
+        /// The SERR# driver is enabled.
+        const SERR_ENABLE = 1 << 8;
+        /// The device is allowed to generate fast back-to-back transactions.
+        const FAST_BACK_TO_BACK_ENABLE = 1 << 9;
+        /// The INTx# signal is disabled.
+        const INTERRUPT_DISABLE = 1 << 10;
+    }
+}
+
+/// Errors accessing a PCI device.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PciError {
+    /// The device reported an invalid BAR type.
+    InvalidBarType,
+    /// The BAR index is out of range for the header type.
+    InvalidBarIndex(u8),
+}
+
+impl Display for PciError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidBarType => write!(f, "Invalid PCI BAR type."),
+            Self::InvalidBarIndex(index) => write!(f, "Invalid PCI BAR index {}.", index),
+        }
+    }
+}
+
+/// Access to the configuration space of PCI devices, e.g. through the legacy
+/// I/O-port mechanism or a memory-mapped ECAM region.
+pub trait ConfigurationAccess {
+    /// Reads 4 bytes from the configuration space of the given function.
+    ///
+    /// `register_offset` is a multiple of 4.
+    fn read_word(&self, device_function: DeviceFunction, register_offset: u8) -> u32;
+
+    /// Writes 4 bytes to the configuration space of the given function.
+    ///
+    /// `register_offset` is a multiple of 4.
+    fn write_word(&mut self, device_function: DeviceFunction, register_offset: u8, data: u32);
+}
+
+/// The root complex of a PCI bus.
+#[derive(Debug)]
+pub struct PciRoot<C: ConfigurationAccess> {
+    configuration_access: C,
+}
+
+impl<C: ConfigurationAccess> PciRoot<C> {
+    /// Creates a new PCI root complex using the given configuration space
+    /// access mechanism.
+    pub fn new(configuration_access: C) -> Self {
+        Self {
+            configuration_access,
+        }
+    }
+
+    /// Reads 4 bytes from configuration space of the given function.
+    pub fn config_read_word(&self, device_function: DeviceFunction, register_offset: u8) -> u32 {
+        self.configuration_access
+            .read_word(device_function, register_offset)
+    }
+
+    /// Writes 4 bytes to configuration space of the given function.
+    pub fn config_write_word(
+        &mut self,
+        device_function: DeviceFunction,
+        register_offset: u8,
+        data: u32,
+    ) {
+        self.configuration_access
+            .write_word(device_function, register_offset, data)
+    }
+
+    /// Enumerates PCI devices on the given bus.
+    pub fn enumerate_bus(&self, bus: u8) -> BusDeviceIterator<'_, C> {
+        BusDeviceIterator {
+            root: self,
+            next: DeviceFunction {
+                bus,
+                device: 0,
+                function: 0,
+            },
+        }
+    }
+
+    /// Reads the identification fields of the given function.
+    ///
+    /// # Returns
+    ///
+    /// The information, or `None` when there is no such function.
+    pub fn device_function_info(
+        &self,
+        device_function: DeviceFunction,
+    ) -> Option<DeviceFunctionInfo> {
+        let device_vendor = self.config_read_word(device_function, 0);
+        let vendor_id = device_vendor as u16;
+        if vendor_id == 0xffff {
+            return None;
+        }
+        let class_revision = self.config_read_word(device_function, 8);
+        let bist_type_latency_cache = self.config_read_word(device_function, 12);
+        Some(DeviceFunctionInfo {
+            vendor_id,
+            device_id: (device_vendor >> 16) as u16,
+            class: (class_revision >> 24) as u8,
+            subclass: (class_revision >> 16) as u8,
+            prog_if: (class_revision >> 8) as u8,
+            revision: class_revision as u8,
+            header_type: HeaderType::from((bist_type_latency_cache >> 16) as u8 & 0x7f),
+            multi_function: bist_type_latency_cache & (0x80 << 16) != 0,
+        })
+    }
+
+    /// Reads the status and command registers of the given function.
+    pub fn get_status_command(&self, device_function: DeviceFunction) -> (Status, Command) {
+        let status_command = self.config_read_word(device_function, STATUS_COMMAND_OFFSET);
+        let status = Status::from_bits_truncate((status_command >> 16) as u16);
+        let command = Command::from_bits_truncate(status_command as u16);
+        (status, command)
+    }
+
+    /// Sets the command register of the given function.
+    pub fn set_command(&mut self, device_function: DeviceFunction, command: Command) {
+        self.config_write_word(
+            device_function,
+            STATUS_COMMAND_OFFSET,
+            command.bits().into(),
+        );
+    }
+
+    /// Returns an iterator over the capabilities of the given function.
+    pub fn capabilities(&self, device_function: DeviceFunction) -> CapabilityIterator<'_, C> {
+        let (status, _) = self.get_status_command(device_function);
+        let next_capability_offset = if status.contains(Status::CAPABILITIES_LIST) {
+            Some((self.config_read_word(device_function, 0x34) & 0xfc) as u8)
+        } else {
+            None
+        };
+        CapabilityIterator {
+            root: self,
+            device_function,
+            next_capability_offset,
+        }
+    }
+
+    /// Gets information about the given BAR of the given function. The BAR
+    /// is sized by writing all ones to it, so memory decoding should be
+    /// disabled while this is called.
+    ///
+    /// # Returns
+    ///
+    /// The BAR information, or `None` when the BAR is not implemented.
+    pub fn bar_info(
+        &mut self,
+        device_function: DeviceFunction,
+        bar_index: u8,
+    ) -> Result<Option<BarInfo>, PciError> {
+        if bar_index >= MAX_BARS {
+            return Err(PciError::InvalidBarIndex(bar_index));
+        }
+        let bar_offset = BAR0_OFFSET + 4 * bar_index;
+        let bar_orig = self.config_read_word(device_function, bar_offset);
+
+        // Get the size of the BAR.
+        self.config_write_word(device_function, bar_offset, 0xffffffff);
+        let size_mask = self.config_read_word(device_function, bar_offset);
+        self.config_write_word(device_function, bar_offset, bar_orig);
+
+        if size_mask == 0 {
+            return Ok(None);
+        }
+
+        if bar_orig & 0x00000001 == 0x00000001 {
+            // I/O space
+            let address = bar_orig & 0xfffffffc;
+            let size = (!(size_mask & 0xfffffffc)).wrapping_add(1);
+            return Ok(Some(BarInfo::IO { address, size }));
+        }
+
+        // Memory space
+        let prefetchable = bar_orig & 0x00000008 != 0;
+        let address_type = MemoryBarType::try_from(((bar_orig & 0x00000006) >> 1) as u8)?;
+        let mut address = u64::from(bar_orig & 0xfffffff0);
+        let mut size = u64::from(size_mask & 0xfffffff0) | 0xffffffff_00000000;
+        if address_type == MemoryBarType::Width64 {
+            if bar_index + 1 >= MAX_BARS {
+                return Err(PciError::InvalidBarIndex(bar_index + 1));
+            }
+            let high_offset = bar_offset + 4;
+            let address_high = self.config_read_word(device_function, high_offset);
+            self.config_write_word(device_function, high_offset, 0xffffffff);
+            let size_mask_high = self.config_read_word(device_function, high_offset);
+            self.config_write_word(device_function, high_offset, address_high);
+            address |= u64::from(address_high) << 32;
+            size = (size & 0xffffffff) | (u64::from(size_mask_high) << 32);
+        }
+        let size = (!size).wrapping_add(1);
+
+        Ok(Some(BarInfo::Memory {
+            address_type,
+            prefetchable,
+            address,
+            size,
+        }))
+    }
+
+    /// Sets the address of the given 32-bit memory or I/O BAR.
+    pub fn set_bar_32(&mut self, device_function: DeviceFunction, bar_index: u8, address: u32) {
+        self.config_write_word(device_function, BAR0_OFFSET + 4 * bar_index, address);
+    }
+
+    /// Sets the address of the given pair of BARs, which form a 64-bit
+    /// memory BAR.
+    pub fn set_bar_64(&mut self, device_function: DeviceFunction, bar_index: u8, address: u64) {
+        self.config_write_word(device_function, BAR0_OFFSET + 4 * bar_index, address as u32);
+        self.config_write_word(
+            device_function,
+            BAR0_OFFSET + 4 * (bar_index + 1),
+            (address >> 32) as u32,
+        );
+    }
+}
+
+/// Information about a PCI Base Address Register.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BarInfo {
+    /// The BAR is for a memory region.
+    Memory {
+        /// The size of the BAR address and where it can be located.
+        address_type: MemoryBarType,
+        /// If true, then reading from the region doesn't have side effects. The CPU may cache reads
+        /// and merge repeated stores.
+        prefetchable: bool,
+        /// The memory address, always 16-byte aligned.
+        address: u64,
+        /// The size of the BAR in bytes.
+        size: u64,
+    },
+    /// The BAR is for an I/O region.
+    IO {
+        /// The I/O address, always 4-byte aligned.
+        address: u32,
+        /// The size of the BAR in bytes.
+        size: u32,
+    },
+}
+
+impl BarInfo {
+    /// Returns whether this BAR is a 64-bit memory region, and so takes two entries in the table in
+    /// configuration space.
+    pub fn takes_two_entries(&self) -> bool {
+        matches!(
+            self,
+            BarInfo::Memory {
+                address_type: MemoryBarType::Width64,
+                ..
+            }
+        )
+    }
+
+    /// Returns the address and size of this BAR if it is a memory bar, or `None` if it is an IO
+    /// BAR.
+    pub fn memory_address_size(&self) -> Option<(u64, u64)> {
+        if let Self::Memory { address, size, .. } = self {
+            Some((*address, *size))
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for BarInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory {
+                address_type,
+                prefetchable,
+                address,
+                size,
+            } => write!(
+                f,
+                "Memory space at {:#010x}, size {}, type {:?}, prefetchable {}",
+                address, size, address_type, prefetchable
+            ),
+            Self::IO { address, size } => {
+                write!(f, "I/O space at {:#010x}, size {}", address, size)
+            }
+        }
+    }
+}
+
+/// The location allowed for a memory BAR.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemoryBarType {
+    /// The BAR has a 32-bit address and can be mapped anywhere in 32-bit address space.
+    Width32,
+    /// The BAR must be mapped below 1MiB.
+    Below1MiB,
+    /// The BAR has a 64-bit address and can be mapped anywhere in 64-bit address space.
+    Width64,
+}
+
+impl From<MemoryBarType> for u8 {
+    fn from(bar_type: MemoryBarType) -> Self {
+        match bar_type {
+            MemoryBarType::Width32 => 0,
+            MemoryBarType::Below1MiB => 1,
+            MemoryBarType::Width64 => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for MemoryBarType {
+    type Error = PciError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Width32),
+            1 => Ok(Self::Below1MiB),
+            2 => Ok(Self::Width64),
+            _ => Err(PciError::InvalidBarType),
+        }
+    }
+}
+
+/// Iterator over capabilities for a device.
+#[derive(Debug)]
+pub struct CapabilityIterator<'a, C: ConfigurationAccess> {
+    root: &'a PciRoot<C>,
+    device_function: DeviceFunction,
+    next_capability_offset: Option<u8>,
+}
+
+impl<C: ConfigurationAccess> Iterator for CapabilityIterator<'_, C> {
+    type Item = CapabilityInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next_capability_offset?;
+
+        // Read the first 4 bytes of the capability.
+        let capability_header = self.root.config_read_word(self.device_function, offset);
+        let id = capability_header as u8;
+        let next_offset = (capability_header >> 8) as u8 & 0xfc;
+        let private_header = (capability_header >> 16) as u16;
+
+        // Offsets below the standard header are invalid and terminate the
+        // list, so that a broken device can't make the iterator loop forever.
+        self.next_capability_offset = if next_offset < 0x40 {
+            None
+        } else {
+            Some(next_offset)
+        };
+
+        Some(CapabilityInfo {
+            offset,
+            id,
+            private_header,
+        })
+    }
+}
+
+/// Information about a PCI device capability.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CapabilityInfo {
+    /// The offset of the capability in the PCI configuration space of the device function.
+    pub offset: u8,
+    /// The ID of the capability.
+    pub id: u8,
+    /// The third and fourth bytes of the capability, to save reading them again.
+    pub private_header: u16,
+}
+
+/// An iterator which enumerates PCI devices and functions on a given bus.
+#[derive(Debug)]
+pub struct BusDeviceIterator<'a, C: ConfigurationAccess> {
+    root: &'a PciRoot<C>,
+    next: DeviceFunction,
+}
+
+impl<C: ConfigurationAccess> Iterator for BusDeviceIterator<'_, C> {
+    type Item = (DeviceFunction, DeviceFunctionInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next.device < MAX_DEVICES {
+            // Read the header for the current device and function.
+            let current = self.next;
+            let info = self.root.device_function_info(current);
+
+            // Advance to the next device or function.
+            self.next.function += 1;
+            let single_function =
+                current.function == 0 && !info.as_ref().is_some_and(|i| i.multi_function);
+            if single_function || self.next.function >= MAX_FUNCTIONS {
+                self.next.function = 0;
+                self.next.device += 1;
+            }
+
+            if let Some(info) = info {
+                return Some((current, info));
+            }
+        }
+        None
+    }
+}
+
+/// An identifier for a PCI bus, device and function.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DeviceFunction {
+    /// The PCI bus number, between 0 and 255.
+    pub bus: u8,
+    /// The device number on the bus, between 0 and 31.
+    pub device: u8,
+    /// The function number of the device, between 0 and 7.
+    pub function: u8,
+}
+
+impl DeviceFunction {
+    /// Returns whether the device and function numbers are valid, i.e. the device is between 0 and
+    /// 31, and the function is between 0 and 7.
+    pub fn valid(&self) -> bool {
+        self.device < MAX_DEVICES && self.function < MAX_FUNCTIONS
+    }
+}
+
+impl Display for DeviceFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+    }
+}
+
+/// Information about a PCI device function.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceFunctionInfo {
+    /// The PCI vendor ID.
+    pub vendor_id: u16,
+    /// The PCI device ID.
+    pub device_id: u16,
+    /// The PCI class.
+    pub class: u8,
+    /// The PCI subclass.
+    pub subclass: u8,
+    /// The PCI programming interface byte.
+    pub prog_if: u8,
+    /// The PCI revision ID.
+    pub revision: u8,
+    /// The type of PCI device.
+    pub header_type: HeaderType,
+    /// Whether the device implements more than one function.
+    pub multi_function: bool,
+}
+
+impl Display for DeviceFunctionInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:04x} (class {:02x}.{:02x}, rev {:02x}) {:?}",
+            self.vendor_id,
+            self.device_id,
+            self.class,
+            self.subclass,
+            self.revision,
+            self.header_type,
+        )
+    }
+}
+
+/// The type of a PCI device function header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HeaderType {
+    /// A normal PCI device.
+    Standard,
+    /// A PCI to PCI bridge.
+    PciPciBridge,
+    /// A PCI to CardBus bridge.
+    PciCardbusBridge,
+    /// Unrecognised header type.
+    Unrecognised(u8),
+}
+
+impl From<u8> for HeaderType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Self::Standard,
+            0x01 => Self::PciPciBridge,
+            0x02 => Self::PciCardbusBridge,
+            _ => Self::Unrecognised(value),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// An in-memory configuration space for unit tests. BARs report their
+    /// size like real hardware when all ones are written to them.
+    #[derive(Debug, Default)]
+    pub(crate) struct FakeConfigurationAccess {
+        pub(crate) functions: HashMap<(u8, u8, u8), [u32; 64]>,
+        /// Size masks of the BARs, indexed by function and BAR index.
+        pub(crate) bar_masks: HashMap<(u8, u8, u8, u8), u32>,
+    }
+
+    impl FakeConfigurationAccess {
+        fn key(device_function: DeviceFunction) -> (u8, u8, u8) {
+            (
+                device_function.bus,
+                device_function.device,
+                device_function.function,
+            )
+        }
+
+        pub(crate) fn add_function(&mut self, device_function: DeviceFunction, words: [u32; 64]) {
+            self.functions.insert(Self::key(device_function), words);
+        }
+
+        pub(crate) fn add_bar(&mut self, device_function: DeviceFunction, bar: u8, mask: u32) {
+            let (bus, device, function) = Self::key(device_function);
+            self.bar_masks.insert((bus, device, function, bar), mask);
+        }
+    }
+
+    impl ConfigurationAccess for FakeConfigurationAccess {
+        fn read_word(&self, device_function: DeviceFunction, register_offset: u8) -> u32 {
+            self.functions
+                .get(&Self::key(device_function))
+                .map_or(0xffffffff, |words| words[usize::from(register_offset / 4)])
+        }
+
+        fn write_word(&mut self, device_function: DeviceFunction, register_offset: u8, data: u32) {
+            let (bus, device, function) = Self::key(device_function);
+            let mask = if (BAR0_OFFSET..BAR0_OFFSET + 4 * MAX_BARS).contains(&register_offset) {
+                let bar = (register_offset - BAR0_OFFSET) / 4;
+                Some(
+                    self.bar_masks
+                        .get(&(bus, device, function, bar))
+                        .copied()
+                        .unwrap_or(0),
+                )
+            } else {
+                None
+            };
+            if let Some(words) = self.functions.get_mut(&(bus, device, function)) {
+                let word = &mut words[usize::from(register_offset / 4)];
+                *word = match mask {
+                    // Only the address bits of a BAR are writable.
+                    Some(mask) => (data & mask) | (*word & !mask & 0xf),
+                    None => data,
+                };
+            }
+        }
+    }
+
+    fn df(device: u8, function: u8) -> DeviceFunction {
+        DeviceFunction {
+            bus: 0,
+            device,
+            function,
+        }
+    }
+
+    fn header(vendor_id: u16, device_id: u16, multi_function: bool) -> [u32; 64] {
+        let mut words = [0; 64];
+        words[0] = u32::from(vendor_id) | (u32::from(device_id) << 16);
+        words[2] = 0x0100_0001;
+        if multi_function {
+            words[3] = 0x80 << 16;
+        }
+        words
+    }
+
+    #[test]
+    fn enumerate_bus() {
+        let mut access = FakeConfigurationAccess::default();
+        access.add_function(df(0, 0), header(0x8086, 0x29c0, false));
+        access.add_function(df(3, 0), header(0x1af4, 0x1042, true));
+        access.add_function(df(3, 2), header(0x1af4, 0x1053, false));
+        // Not reported, because function 0 of the device is single-function.
+        access.add_function(df(0, 1), header(0x8086, 0x2918, false));
+        let root = PciRoot::new(access);
+
+        let found: Vec<_> = root
+            .enumerate_bus(0)
+            .map(|(device_function, info)| (device_function, info.device_id))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(df(0, 0), 0x29c0), (df(3, 0), 0x1042), (df(3, 2), 0x1053)]
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        let mut words = header(0x1af4, 0x1053, false);
+        words[1] = u32::from(Status::CAPABILITIES_LIST.bits()) << 16;
+        words[0x34 / 4] = 0x40;
+        words[0x40 / 4] = 0x0100_5009;
+        words[0x50 / 4] = 0x0000_0011;
+        let mut access = FakeConfigurationAccess::default();
+        access.add_function(df(1, 0), words);
+        let root = PciRoot::new(access);
+
+        let caps: Vec<_> = root.capabilities(df(1, 0)).collect();
+        assert_eq!(
+            caps,
+            vec![
+                CapabilityInfo {
+                    offset: 0x40,
+                    id: PCI_CAP_ID_VNDR,
+                    private_header: 0x0100,
+                },
+                CapabilityInfo {
+                    offset: 0x50,
+                    id: 0x11,
+                    private_header: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bar_info() {
+        let mut words = header(0x1af4, 0x1053, false);
+        // BAR1: 32-bit memory at 0xfe000000, 4 KiB.
+        words[5] = 0xfe00_0000;
+        // BAR4/5: 64-bit prefetchable memory, unassigned, 16 KiB.
+        words[8] = 0x0000_000c;
+        let mut access = FakeConfigurationAccess::default();
+        access.add_function(df(1, 0), words);
+        access.add_bar(df(1, 0), 1, 0xffff_f000);
+        access.add_bar(df(1, 0), 4, 0xffff_c000);
+        access.add_bar(df(1, 0), 5, 0xffff_ffff);
+        let mut root = PciRoot::new(access);
+
+        assert_eq!(root.bar_info(df(1, 0), 0), Ok(None));
+        assert_eq!(
+            root.bar_info(df(1, 0), 1),
+            Ok(Some(BarInfo::Memory {
+                address_type: MemoryBarType::Width32,
+                prefetchable: false,
+                address: 0xfe00_0000,
+                size: 0x1000,
+            }))
+        );
+
+        let bar = root.bar_info(df(1, 0), 4).unwrap().unwrap();
+        assert!(bar.takes_two_entries());
+        assert_eq!(bar.memory_address_size(), Some((0, 0x4000)));
+
+        root.set_bar_64(df(1, 0), 4, 0x80_0000_4000);
+        let bar = root.bar_info(df(1, 0), 4).unwrap().unwrap();
+        assert_eq!(bar.memory_address_size(), Some((0x80_0000_4000, 0x4000)));
+
+        assert_eq!(
+            root.bar_info(df(1, 0), 6),
+            Err(PciError::InvalidBarIndex(6))
+        );
+    }
+}
@@ -5,15 +5,61 @@
 use crate::{
     helpers::HELPERS, run_cmd_checked, Args, BuildResult, BuildTarget, Component, ComponentConfig,
 };
+use bootlib::fs_archive::{
+    FsSignatureHeader, FsSignedData, FS_SIGNATURE_HEADER_SIZE, FS_SIGNATURE_MAGIC,
+    P384_SIGNATURE_SIZE,
+};
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey};
+use p384::pkcs8::{EncodePublicKey, LineEnding};
+use p384::SecretKey;
+use packit::PackItArchiveDecoder;
 use serde::Deserialize;
+use sha2::{Digest, Sha384};
 use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use zerocopy::IntoBytes;
+
+/// Page size of the SVSM, which the file contents in the archive are
+/// aligned to.
+const PAGE_SIZE: usize = 4096;
+
+/// Number of times the archive is packed while aligning its files.
+const PACK_ATTEMPTS: usize = 4;
 
 /// Components for the filesystem image.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct FsConfig {
     modules: HashMap<String, ComponentConfig>,
+    /// Optional PEM file with the NIST P-384 private key to sign the
+    /// filesystem image with, e.g. generated with
+    /// `openssl ecparam -name secp384r1 -genkey -noout -out key.pem`.
+    signing_key: Option<PathBuf>,
+    /// See help for `igvmbuilder --filesystem-size`.
+    size: Option<u64>,
+    /// Version of the signed filesystem image, which must increase with
+    /// every release of the image.
+    #[serde(default)]
+    version: u64,
+    /// See help for `igvmbuilder --fs-min-version`.
+    min_version: Option<u64>,
+}
+
+/// A built filesystem image.
+#[derive(Debug, Clone)]
+pub struct FsImage {
+    /// Path to the filesystem image
+    pub path: PathBuf,
+    /// Path to the PEM file with the public key if the image is signed
+    pub public_key: Option<PathBuf>,
+    /// Size to reserve for the image in the IGVM file
+    pub size: Option<u64>,
+    /// Minimum version of the image the SVSM accepts
+    pub min_version: Option<u64>,
 }
 
 impl FsConfig {
@@ -24,9 +70,8 @@ impl FsConfig {
     }
 
     /// Builds the filesystem image based on the config's components,
-    /// and returns the path to the built image if there were any
-    /// files to pack.
-    pub fn build(&self, args: &Args, mut dst: PathBuf) -> BuildResult<Option<PathBuf>> {
+    /// and returns the built image if there were any files to pack.
+    pub fn build(&self, args: &Args, mut dst: PathBuf) -> BuildResult<Option<FsImage>> {
         if dst.try_exists()? {
             std::fs::remove_dir_all(&dst)?;
         }
@@ -56,14 +101,159 @@ impl FsConfig {
 
         // Now build filesystem image from all components
         let fs = PathBuf::from("bin/svsm-fs.bin");
+        let (archive, page_offset) = Self::pack_aligned(args, &dst, &fs)?;
+
+        let (signature, public_key) = match self.signing_key.as_ref() {
+            Some(key) => {
+                let (signature, public_key) = Self::sign(key, &archive, self.version, &fs)?;
+                (signature, Some(public_key))
+            }
+            None => ([0; P384_SIGNATURE_SIZE], None),
+        };
+
+        // Place the archive behind the header such that the file contents
+        // start on a page boundary.
+        let archive_offset = FS_SIGNATURE_HEADER_SIZE + (PAGE_SIZE - page_offset) % PAGE_SIZE;
+        let header = FsSignatureHeader {
+            magic: FS_SIGNATURE_MAGIC,
+            version: self.version,
+            archive_size: archive.len() as u64,
+            archive_offset: archive_offset as u64,
+            signature,
+        };
+
+        let mut image = header.as_bytes().to_vec();
+        image.resize(archive_offset, 0);
+        image.extend_from_slice(&archive);
+        std::fs::write(&fs, image)?;
+
+        Ok(Some(FsImage {
+            path: fs,
+            public_key,
+            size: self.size,
+            min_version: self.min_version,
+        }))
+    }
+
+    /// Packs the contents of `src` into the archive `dst` and returns the
+    /// archive.
+    fn pack(args: &Args, src: &Path, dst: &Path) -> BuildResult<Vec<u8>> {
         let mut cmd = Command::new(HELPERS.packit(args));
         cmd.arg("pack")
             .arg("--input")
-            .arg(&dst)
+            .arg(src)
             .arg("--output")
-            .arg(&fs);
+            .arg(dst);
         run_cmd_checked(cmd, args)?;
+        Ok(std::fs::read(dst)?)
+    }
+
+    /// Returns the name and the offset of the contents of each file in the
+    /// archive, in archive order.
+    fn file_offsets(archive: &[u8]) -> BuildResult<Vec<(String, usize)>> {
+        PackItArchiveDecoder::load(archive)
+            .map_err(|e| format!("invalid filesystem archive: {e:?}"))?
+            .into_iter()
+            .map(|file| {
+                let file = file.map_err(|e| format!("invalid filesystem archive: {e:?}"))?;
+                let offset = file.data().as_ptr() as usize - archive.as_ptr() as usize;
+                Ok((file.name().to_string(), offset))
+            })
+            .collect()
+    }
+
+    /// Returns the number of zero bytes to append to each file so that the
+    /// contents of all files start at the same offset within a page as the
+    /// contents of the first file.
+    fn alignment_padding(files: &[(String, usize)]) -> Vec<(&str, usize)> {
+        let Some((_, first)) = files.first() else {
+            return Vec::new();
+        };
+
+        let mut shift = 0;
+        files
+            .windows(2)
+            .filter_map(|pair| {
+                let offset = pair[1].1 + shift;
+                let padding = (first + PAGE_SIZE - offset % PAGE_SIZE) % PAGE_SIZE;
+                shift += padding;
+                (padding != 0).then_some((pair[0].0.as_str(), padding))
+            })
+            .collect()
+    }
+
+    /// Packs the contents of `src` into the archive `dst` such that the
+    /// contents of all files start at the same offset within a page, so
+    /// that the SVSM can map the files directly from the archive. Files
+    /// are padded with trailing zeros to move the files behind them, which
+    /// does not change the ELF binaries in the image. Returns the archive
+    /// and the offset of the file contents within a page.
+    fn pack_aligned(args: &Args, src: &Path, dst: &Path) -> BuildResult<(Vec<u8>, usize)> {
+        for _ in 0..PACK_ATTEMPTS {
+            let archive = Self::pack(args, src, dst)?;
+            let files = Self::file_offsets(&archive)?;
+            let padding = Self::alignment_padding(&files);
+            if padding.is_empty() {
+                let page_offset = files.first().map_or(0, |(_, offset)| offset % PAGE_SIZE);
+                return Ok((archive, page_offset));
+            }
+
+            for (name, size) in padding {
+                let mut file = OpenOptions::new().append(true).open(src.join(name))?;
+                file.write_all(&vec![0; size])?;
+            }
+        }
+
+        Err("failed to page-align the files in the filesystem archive".into())
+    }
+
+    /// Signs the version and the digest of the filesystem archive and
+    /// writes the public key next to the image. Returns the signature and
+    /// the path to the public key.
+    fn sign(
+        key_file: &Path,
+        archive: &[u8],
+        version: u64,
+        fs: &Path,
+    ) -> BuildResult<([u8; P384_SIGNATURE_SIZE], PathBuf)> {
+        let pem = std::fs::read_to_string(key_file)?;
+        let signing_key = SigningKey::from(SecretKey::from_sec1_pem(&pem)?);
+        let signed = FsSignedData {
+            version,
+            digest: Sha384::digest(archive).as_slice().try_into()?,
+        };
+        let signature: Signature = signing_key.sign(signed.as_bytes());
+
+        let public_key = fs.with_extension("pub.pem");
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)?;
+        std::fs::write(&public_key, pem)?;
+        Ok((signature.to_bytes().as_slice().try_into()?, public_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alignment_padding() {
+        let files = [
+            ("a".to_string(), 0x20),
+            ("b".to_string(), 0x1030),
+            ("c".to_string(), 0x3010),
+            ("d".to_string(), 0x4050),
+        ];
+        // Padding "a" moves "b" to 0x2020, "c" to 0x4000 and "d" to 0x5040.
+        // Padding "b" then moves "c" to 0x4020 and "d" to 0x5060.
+        assert_eq!(
+            FsConfig::alignment_padding(&files),
+            [("a", 0xff0), ("b", 0x20), ("c", 0xfc0)]
+        );
 
-        Ok(Some(fs))
+        let aligned = [("a".to_string(), 0x20), ("b".to_string(), 0x3020)];
+        assert!(FsConfig::alignment_padding(&aligned).is_empty());
+        assert!(FsConfig::alignment_padding(&[]).is_empty());
     }
 }
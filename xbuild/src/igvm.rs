@@ -80,6 +80,10 @@ struct IgvmTargetConfig {
     policy: String,
     /// See help for `igvmbuilder --comport`.
     comport: Option<String>,
+    /// See help for `igvmbuilder --vtpm-nv-min-generation`.
+    vtpm_nv_min_generation: Option<u64>,
+    /// See help for `igvmbuilder --svsm-pci-ecam`.
+    svsm_pci_ecam: Option<u64>,
     /// Platform flags for igvmbuilder
     #[serde(default = "IgvmTargetConfig::default_platforms")]
     platforms: Vec<IgvmPlatform>,
@@ -130,11 +134,27 @@ impl IgvmTargetConfig {
             cmd.arg("--firmware").arg(fw);
         }
         if let Some(fs) = parts.fs.as_ref() {
-            cmd.arg("--filesystem").arg(fs);
+            cmd.arg("--filesystem").arg(&fs.path);
+            if let Some(key) = fs.public_key.as_ref() {
+                cmd.arg("--fs-public-key").arg(key);
+            }
+            if let Some(size) = fs.size {
+                cmd.arg("--filesystem-size").arg(size.to_string());
+            }
+            if let Some(version) = fs.min_version {
+                cmd.arg("--fs-min-version").arg(version.to_string());
+            }
         }
         if let Some(comport) = self.comport.as_ref() {
             cmd.arg("--comport").arg(comport);
         }
+        if let Some(generation) = self.vtpm_nv_min_generation {
+            cmd.arg("--vtpm-nv-min-generation")
+                .arg(generation.to_string());
+        }
+        if let Some(address) = self.svsm_pci_ecam {
+            cmd.arg("--svsm-pci-ecam").arg(format!("{address:#x}"));
+        }
         if args.verbose {
             cmd.arg("--verbose");
         }
@@ -9,7 +9,11 @@ mod igvm;
 mod kernel;
 
 use crate::{
-    fs::FsConfig, fw::FirmwareConfig, helpers::HELPERS, igvm::IgvmConfig, kernel::KernelConfig,
+    fs::{FsConfig, FsImage},
+    fw::FirmwareConfig,
+    helpers::HELPERS,
+    igvm::IgvmConfig,
+    kernel::KernelConfig,
 };
 use clap::Parser;
 use serde::Deserialize;
@@ -259,7 +263,7 @@ struct RecipePartsBuilder {
     stage2: Option<PathBuf>,
     kernel: Option<PathBuf>,
     firmware: Option<PathBuf>,
-    fs: Option<PathBuf>,
+    fs: Option<FsImage>,
 }
 
 impl RecipePartsBuilder {
@@ -283,7 +287,7 @@ impl RecipePartsBuilder {
         self.firmware = Some(v);
     }
 
-    fn set_fs(&mut self, v: PathBuf) {
+    fn set_fs(&mut self, v: FsImage) {
         self.fs = Some(v);
     }
 
@@ -308,7 +312,7 @@ struct RecipeParts {
     stage2: PathBuf,
     kernel: PathBuf,
     firmware: Option<PathBuf>,
-    fs: Option<PathBuf>,
+    fs: Option<FsImage>,
 }
 
 #[derive(clap::Parser, Debug)]